@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        system::{CommandQueue, Commands},
+        world::World,
+    },
+    math::IVec2,
+};
+use bevy_entitiles::tilemap::{map::TilemapStorage, tile::TileBuilder};
+
+const SIZE: i32 = 100;
+const ROUNDS: u32 = 20;
+
+fn bench(name: &str, mut f: impl FnMut(&mut TilemapStorage, &mut Commands)) {
+    let mut total = Duration::ZERO;
+
+    for _ in 0..ROUNDS {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut storage = TilemapStorage::new(32, Entity::PLACEHOLDER);
+
+        let start = Instant::now();
+        let mut commands = Commands::new(&mut queue, &world);
+        f(&mut storage, &mut commands);
+        queue.apply(&mut world);
+        total += start.elapsed();
+    }
+
+    println!("{}: {:?} per round", name, total / ROUNDS);
+}
+
+fn main() {
+    bench("per tile set", |storage, commands| {
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                storage.set(commands, IVec2 { x, y }, TileBuilder::new());
+            }
+        }
+    });
+
+    bench("fill_rect_clipped", |storage, commands| {
+        storage.fill_rect_clipped(
+            commands,
+            IVec2::ZERO,
+            IVec2::splat(SIZE - 1),
+            None,
+            TileBuilder::new(),
+        );
+    });
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{CommandQueue, Commands},
+        world::World,
+    },
+    math::Vec2,
+    utils::HashMap,
+};
+use bevy_entitiles::ldtk::components::{EntityIid, LdtkFields, LdtkTempTransform};
+
+const ENTITIES: u32 = 5000;
+const ROUNDS: u32 = 20;
+
+/// Stands for whatever `LdtkEntity::initialize` inserts.
+#[derive(Component)]
+struct Custom(#[allow(dead_code)] u32);
+
+fn packed(i: u32) -> (LdtkTempTransform, EntityIid, LdtkFields) {
+    (
+        LdtkTempTransform {
+            level_translation: Vec2::new(i as f32, 0.),
+            y_up: true,
+            z_index: 0.,
+        },
+        EntityIid(format!("entity-{}", i)),
+        LdtkFields(HashMap::new()),
+    )
+}
+
+fn bench(name: &str, mut f: impl FnMut(&World, &mut Commands)) {
+    let mut total = Duration::ZERO;
+
+    for _ in 0..ROUNDS {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+
+        let start = Instant::now();
+        let mut commands = Commands::new(&mut queue, &world);
+        f(&world, &mut commands);
+        queue.apply(&mut world);
+        total += start.elapsed();
+
+        assert_eq!(world.entities().len(), ENTITIES);
+    }
+
+    println!("{}: {:?} per round", name, total / ROUNDS);
+}
+
+fn main() {
+    // How the entities were spawned before: one spawn and two inserts each.
+    bench("spawn per entity", |_, commands| {
+        for i in 0..ENTITIES {
+            let (transform, iid, fields) = packed(i);
+            let mut entity = commands.spawn((transform, iid));
+            entity.insert(Custom(i));
+            entity.insert(fields);
+        }
+    });
+
+    // How `LdtkLayers::apply_all` spawns them now.
+    bench("reserve and batch", |world, commands| {
+        let reserved = world
+            .entities()
+            .reserve_entities(ENTITIES)
+            .collect::<Vec<Entity>>();
+        let mut packed = (0..ENTITIES).map(packed).collect::<Vec<_>>();
+
+        commands.insert_or_spawn_batch(
+            reserved
+                .iter()
+                .zip(packed.iter())
+                .map(|(e, (transform, iid, _))| (*e, (transform.clone(), iid.clone())))
+                .collect::<Vec<_>>(),
+        );
+
+        let mut fields = Vec::with_capacity(reserved.len());
+        reserved
+            .into_iter()
+            .zip(packed.drain(..))
+            .enumerate()
+            .for_each(|(i, (e, (_, _, f)))| {
+                commands.entity(e).insert(Custom(i as u32));
+                fields.push((e, f));
+            });
+        commands.insert_or_spawn_batch(fields);
+    });
+}
@@ -137,6 +137,7 @@ fn setup(
         storage: TilemapStorage::new(32, entity),
         material: materials.add(StandardTilemapMaterial {
             tint: Color::TOMATO,
+            ..Default::default()
         }),
         textures: textures.add(TilemapTextures::single(
             TilemapTexture::new(
@@ -0,0 +1,129 @@
+use bevy::{
+    app::{App, Startup, Update},
+    asset::{AssetServer, Assets, Handle},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::system::{Commands, Res, ResMut, Resource},
+    input::{keyboard::KeyCode, ButtonInput},
+    log::info,
+    math::{IVec2, UVec2, Vec2},
+    render::{color::Color, render_resource::FilterMode, view::Msaa},
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::{StandardTilemapMaterial, TilemapBlendMode},
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapTransform, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+/// The materials of the translucent tilemaps.
+#[derive(Resource)]
+struct Layers(Vec<Handle<StandardTilemapMaterial>>);
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        // `AlphaToCoverage` needs MSAA.
+        .insert_resource(Msaa::Sample4)
+        .add_systems(Startup, setup)
+        .add_systems(Update, switch_blend_mode)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let texture = textures.add(TilemapTextures::single(
+        TilemapTexture::new(
+            asset_server.load("test_square.png"),
+            TilemapTextureDescriptor::new(UVec2 { x: 32, y: 32 }, UVec2 { x: 16, y: 16 }),
+        ),
+        FilterMode::Nearest,
+    ));
+
+    // Three overlapping translucent tilemaps, like layers of glass.
+    let mut layers = Vec::new();
+    for (i, tint) in [
+        Color::rgba(1., 0.3, 0.3, 0.5),
+        Color::rgba(0.3, 1., 0.3, 0.5),
+        Color::rgba(0.3, 0.3, 1., 0.5),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let material = materials.add(StandardTilemapMaterial {
+            tint,
+            ..Default::default()
+        });
+        layers.push(material.clone());
+
+        let entity = commands.spawn_empty().id();
+        let mut tilemap = StandardTilemapBundle {
+            tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+            slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+            ty: TilemapType::Square,
+            storage: TilemapStorage::new(16, entity),
+            material,
+            textures: texture.clone(),
+            transform: TilemapTransform {
+                translation: Vec2::splat(i as f32 * 48.),
+                z_index: i as f32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        tilemap.storage.fill_rect(
+            &mut commands,
+            TileArea::new(IVec2::ZERO, UVec2 { x: 10, y: 10 }),
+            TileBuilder::new().with_layer(0, TileLayer::no_flip(i as i32)),
+        );
+
+        commands.entity(entity).insert(tilemap);
+    }
+    commands.insert_resource(Layers(layers));
+}
+
+/// Press space to switch between the blend modes.
+///
+/// `Alpha` is the usual straight alpha blending, `PremultipliedAlpha` blends
+/// premultiplied colors which stacks translucent tilemaps correctly, and
+/// `AlphaToCoverage` doesn't depend on the drawing order but has visible dithering.
+fn switch_blend_mode(
+    layers: Res<Layers>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    input: Res<ButtonInput<KeyCode>>,
+) {
+    if !input.just_pressed(KeyCode::Space) {
+        return;
+    }
+
+    let blend_mode = match materials.get(&layers.0[0]).unwrap().blend_mode {
+        TilemapBlendMode::Alpha => TilemapBlendMode::PremultipliedAlpha,
+        TilemapBlendMode::PremultipliedAlpha => TilemapBlendMode::AlphaToCoverage,
+        TilemapBlendMode::AlphaToCoverage => TilemapBlendMode::Alpha,
+    };
+    layers.0.iter().for_each(|handle| {
+        materials.get_mut(handle).unwrap().blend_mode = blend_mode;
+    });
+    info!("Blend mode: {:?}", blend_mode);
+}
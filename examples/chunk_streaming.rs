@@ -0,0 +1,136 @@
+use bevy::{
+    app::{App, Startup, Update},
+    asset::{AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::{
+        event::EventReader,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{IVec2, UVec2, Vec2},
+    render::render_resource::FilterMode,
+    time::Time,
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    serializing::{
+        chunk::{
+            load::ChunkLoadConfig,
+            save::ChunkSaveConfig,
+            streaming::{ChunkGenerationRequest, ChunkStreaming, ChunkStreamingConfig},
+        },
+        map::TilemapLayer,
+    },
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::{camera_movement::CameraControl, EntiTilesHelpersPlugin};
+
+mod helpers;
+
+const CHUNK_SIZE: u32 = 16;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin { inspector: false },
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (fly, generate))
+        .insert_resource(ChunkSaveConfig {
+            path: "generated/chunk_streaming".to_string(),
+            chunks_per_frame: 4,
+        })
+        .insert_resource(ChunkLoadConfig {
+            path: "generated/chunk_streaming".to_string(),
+            chunks_per_frame: 4,
+        })
+        // Keep the chunks around the camera loaded.
+        // The unload radius is larger so the chunks won't be unloaded
+        // and loaded again when the camera moves back and forth on the boundary.
+        .insert_resource(ChunkStreamingConfig::new(2, 3))
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let entity = commands.spawn_empty().id();
+    commands.entity(entity).insert((
+        StandardTilemapBundle {
+            name: TilemapName("streaming_map".to_string()),
+            tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+            slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+            ty: TilemapType::Square,
+            storage: TilemapStorage::new(CHUNK_SIZE, entity),
+            material: materials.add(StandardTilemapMaterial::default()),
+            textures: textures.add(TilemapTextures::single(
+                TilemapTexture::new(
+                    asset_server.load("test_square.png"),
+                    TilemapTextureDescriptor::new(UVec2 { x: 32, y: 32 }, UVec2 { x: 16, y: 16 }),
+                ),
+                FilterMode::Nearest,
+            )),
+            ..Default::default()
+        },
+        // Nothing is spawned here, the chunks around the camera will be
+        // loaded from the disk, or generated if they are never saved.
+        ChunkStreaming::new(TilemapLayer::COLOR),
+    ));
+}
+
+/// Fly the camera across the world.
+/// You can still drag the camera around using the mouse.
+fn fly(mut control: ResMut<CameraControl>, time: Res<Time>) {
+    control.target_pos +=
+        Vec2::new(300., 80. * time.elapsed_seconds().sin()) * time.delta_seconds();
+}
+
+/// A cheap hash so the same tile is always generated at the same position.
+fn noise(index: IVec2) -> u32 {
+    let mut h =
+        (index.x as u32).wrapping_mul(0x27d4eb2d) ^ (index.y as u32).wrapping_mul(0x165667b1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^ (h >> 13)
+}
+
+fn generate(
+    mut commands: Commands,
+    mut tilemaps_query: Query<&mut TilemapStorage>,
+    mut requests: EventReader<ChunkGenerationRequest>,
+) {
+    requests.read().for_each(|request| {
+        let Ok(mut storage) = tilemaps_query.get_mut(request.tilemap) else {
+            return;
+        };
+
+        // Every generated chunk will be saved when the camera leaves,
+        // and loaded from the disk next time instead of being generated again.
+        storage.fill_rect_custom(
+            &mut commands,
+            TileArea::new(request.index * CHUNK_SIZE as i32, UVec2::splat(CHUNK_SIZE)),
+            |index| {
+                (noise(index) % 5 != 0).then(|| {
+                    TileBuilder::new().with_layer(0, TileLayer::no_flip((noise(index) % 4) as i32))
+                })
+            },
+            false,
+        );
+    });
+}
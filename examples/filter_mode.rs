@@ -0,0 +1,82 @@
+use bevy::{
+    app::{App, Startup},
+    asset::{AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::system::{Commands, Res, ResMut},
+    math::{IVec2, UVec2, Vec2},
+    render::render_resource::FilterMode,
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapTransform, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    // The same texture is sampled differently by the two tilemaps.
+    // Zoom in to see the difference: the left one keeps the pixels crisp,
+    // while the right one blurs them.
+    for (filter_mode, x) in [(FilterMode::Nearest, -340.), (FilterMode::Linear, 20.)] {
+        let entity = commands.spawn_empty().id();
+        let mut tilemap = StandardTilemapBundle {
+            tile_render_size: TileRenderSize(Vec2::new(64., 64.)),
+            slot_size: TilemapSlotSize(Vec2::new(64., 64.)),
+            ty: TilemapType::Square,
+            storage: TilemapStorage::new(16, entity),
+            material: materials.add(StandardTilemapMaterial::default()),
+            textures: textures.add(TilemapTextures::single(
+                TilemapTexture::new(
+                    asset_server.load("test_square.png"),
+                    TilemapTextureDescriptor::new(UVec2 { x: 32, y: 32 }, UVec2 { x: 16, y: 16 }),
+                ),
+                filter_mode,
+            )),
+            transform: TilemapTransform::from_translation(Vec2::new(x, -160.)),
+            ..Default::default()
+        };
+
+        tilemap.storage.fill_rect_custom(
+            &mut commands,
+            TileArea::new(IVec2::ZERO, UVec2 { x: 5, y: 5 }),
+            |index| {
+                Some(
+                    TileBuilder::new()
+                        .with_layer(0, TileLayer::no_flip((index.x + index.y).rem_euclid(4))),
+                )
+            },
+            false,
+        );
+
+        commands.entity(entity).insert(tilemap);
+    }
+}
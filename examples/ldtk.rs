@@ -29,10 +29,13 @@ use bevy_entitiles::tilemap::{physics::PhysicsTileSpawn, tile::RawTileAnimation}
 use bevy_entitiles::{
     ldtk::{
         app_ext::LdtkApp,
-        events::LdtkEvent,
+        events::{LdtkEvent, WorldSpawnedEvent},
         json::{field::FieldInstance, level::EntityInstance, EntityRef},
         layer::physics::LdtkPhysicsLayer,
-        resources::{LdtkAdditionalLayers, LdtkAssets, LdtkLevelManager, LdtkLoadConfig},
+        resources::{
+            LdtkAdditionalLayers, LdtkAssets, LdtkHotReload, LdtkLevelManager, LdtkLoadConfig,
+            UnknownEntityPolicy,
+        },
         sprite::LdtkEntityMaterial,
     },
     tilemap::physics::PhysicsTile,
@@ -63,6 +66,7 @@ fn main() {
             (
                 load,
                 level_events,
+                world_events,
                 hot_reload,
                 player_control,
                 physics_tile_events,
@@ -81,7 +85,7 @@ fn main() {
             file_path: "assets/ldtk/ignore grid_vania.ldtk".to_string(),
             asset_path_prefix: "ldtk/".to_string(),
             filter_mode: FilterMode::Nearest,
-            ignore_unregistered_entities: true,
+            unknown_entity_policy: UnknownEntityPolicy::Skip,
             animation_mapper: HashMap::from([(
                 470,
                 RawTileAnimation {
@@ -91,6 +95,8 @@ fn main() {
             )]),
             ..Default::default()
         })
+        // respawn the loaded levels when the file is saved in LDtk
+        .init_resource::<LdtkHotReload>()
         .insert_resource(LdtkAdditionalLayers {
             physics_layer: Some(LdtkPhysicsLayer {
                 identifier: "PhysicsColliders".to_string(),
@@ -112,6 +118,7 @@ fn main() {
                         },
                     ),
                 ])),
+                slopes: None,
             }),
             ..Default::default()
         })
@@ -198,10 +205,22 @@ fn level_events(mut ldtk_events: EventReader<LdtkEvent>) {
             LdtkEvent::LevelUnloaded(level) => {
                 println!("Level unloaded: {}", level.identifier);
             }
+            LdtkEvent::UnknownEntities(unknown) => {
+                println!(
+                    "Unknown entities in {}: {:?}",
+                    unknown.level.identifier, unknown.identifiers
+                );
+            }
         }
     }
 }
 
+fn world_events(mut world_events: EventReader<WorldSpawnedEvent>) {
+    for event in world_events.read() {
+        println!("{} levels spawned", event.levels.len());
+    }
+}
+
 fn player_control(
     mut query: Query<&mut LinearVelocity, With<Player>>,
     input: Res<ButtonInput<KeyCode>>,
@@ -288,6 +307,7 @@ pub enum ItemType {
 
 #[derive(Component, LdtkEntity, Default, Reflect)]
 // this means the entity will be spawned with a sprite
+// use #[spawn_sprite_sheet] instead if you prefer a SpriteSheetBundle
 #[spawn_sprite]
 // this means the entity will not disappear when the level is unloaded
 #[global_entity]
@@ -304,6 +324,7 @@ pub struct Player {
     // You can impl the LdtkEntity trait yourself so these wrappers
     // can be avoided.
     pub inventory: ItemTypeVec,
+    // or #[ldtk_field("HP")]
     #[ldtk_name = "HP"]
     pub hp: i32,
     // this will be deafult as it not exists in the ldtk file
@@ -76,6 +76,7 @@ fn main() {
                         },
                     ),
                 ])),
+                slopes: None,
             }),
             ..Default::default()
         })
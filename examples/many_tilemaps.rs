@@ -0,0 +1,99 @@
+use bevy::{
+    app::{App, PluginGroup, Startup},
+    asset::{AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    diagnostic::LogDiagnosticsPlugin,
+    ecs::system::{Commands, Res, ResMut},
+    math::{IVec2, UVec2, Vec2},
+    render::render_resource::FilterMode,
+    window::{PresentMode, Window, WindowPlugin},
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapTransform, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+/// The number of tilemaps is `COLUMNS * ROWS`.
+const COLUMNS: i32 = 25;
+const ROWS: i32 = 20;
+const TILEMAP_SIZE: u32 = 4;
+
+/// Spawns a lot of small tilemaps, so the per tilemap render overhead
+/// dominates the frame time, which is logged to the console.
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    present_mode: PresentMode::Immediate,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin { inspector: false },
+            LogDiagnosticsPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let material = materials.add(StandardTilemapMaterial::default());
+    let texture = textures.add(TilemapTextures::single(
+        TilemapTexture::new(
+            asset_server.load("test_square.png"),
+            TilemapTextureDescriptor::new(UVec2::splat(32), UVec2::splat(16)),
+        ),
+        FilterMode::Nearest,
+    ));
+    let stride = (TILEMAP_SIZE * 16 + 8) as f32;
+
+    for y in 0..ROWS {
+        for x in 0..COLUMNS {
+            let entity = commands.spawn_empty().id();
+            let mut tilemap = StandardTilemapBundle {
+                name: TilemapName(format!("tilemap_{}_{}", x, y)),
+                tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+                slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+                ty: TilemapType::Square,
+                storage: TilemapStorage::new(TILEMAP_SIZE, entity),
+                material: material.clone(),
+                textures: texture.clone(),
+                transform: TilemapTransform::from_translation(Vec2::new(
+                    (x - COLUMNS / 2) as f32 * stride,
+                    (y - ROWS / 2) as f32 * stride,
+                )),
+                ..Default::default()
+            };
+
+            tilemap.storage.fill_rect(
+                &mut commands,
+                TileArea::new(IVec2::ZERO, UVec2::splat(TILEMAP_SIZE)),
+                TileBuilder::new().with_layer(0, TileLayer::no_flip((x + y) % 4)),
+            );
+
+            commands.entity(entity).insert(tilemap);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use bevy::{
+    app::{App, Startup, Update},
+    asset::{AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::system::{Commands, Local, Query, Res, ResMut},
+    math::{IVec2, UVec2, Vec2},
+    render::{render_resource::FilterMode, texture::Image},
+    time::Time,
+    ui::{node_bundles::ImageBundle, PositionType, Style, UiImage, Val},
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapType,
+        },
+        minimap::MinimapBuilder,
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+const MAP_SIZE: UVec2 = UVec2 { x: 40, y: 30 };
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, paint)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let entity = commands.spawn_empty().id();
+    let mut tilemap = StandardTilemapBundle {
+        tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+        slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+        ty: TilemapType::Square,
+        storage: TilemapStorage::new(16, entity),
+        material: materials.add(StandardTilemapMaterial::default()),
+        textures: textures.add(TilemapTextures::single(
+            TilemapTexture::new(
+                asset_server.load("test_square.png"),
+                TilemapTextureDescriptor::new(UVec2 { x: 32, y: 32 }, UVec2 { x: 16, y: 16 }),
+            ),
+            FilterMode::Nearest,
+        )),
+        ..Default::default()
+    };
+
+    tilemap.storage.fill_rect(
+        &mut commands,
+        TileArea::new(IVec2::ZERO, MAP_SIZE),
+        TileBuilder::new().with_layer(0, TileLayer::no_flip(0)),
+    );
+
+    // 4x4 pixels per tile.
+    let minimap = MinimapBuilder::new(TileArea::new(IVec2::ZERO, MAP_SIZE))
+        .with_scale(4)
+        .build(&mut images);
+
+    commands.spawn(ImageBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            right: Val::Px(10.),
+            width: Val::Px((MAP_SIZE.x * 4) as f32),
+            height: Val::Px((MAP_SIZE.y * 4) as f32),
+            ..Default::default()
+        },
+        image: UiImage::new(minimap.image.clone()),
+        ..Default::default()
+    });
+
+    commands.entity(entity).insert((tilemap, minimap));
+}
+
+/// Paint a random tile every few frames, the minimap only redraws these tiles.
+fn paint(
+    mut commands: Commands,
+    mut tilemaps_query: Query<&mut TilemapStorage>,
+    time: Res<Time>,
+    mut elapsed: Local<f32>,
+) {
+    *elapsed += time.delta_seconds();
+    if *elapsed < 0.05 {
+        return;
+    }
+    *elapsed = 0.;
+
+    let Ok(mut storage) = tilemaps_query.get_single_mut() else {
+        return;
+    };
+
+    let index = IVec2::new(
+        rand::random::<i32>().rem_euclid(MAP_SIZE.x as i32),
+        rand::random::<i32>().rem_euclid(MAP_SIZE.y as i32),
+    );
+    let atlas_index = rand::random::<i32>().rem_euclid(5) - 1;
+    if atlas_index < 0 {
+        storage.remove(&mut commands, index);
+    } else {
+        storage.set(
+            &mut commands,
+            index,
+            TileBuilder::new().with_layer(0, TileLayer::no_flip(atlas_index)),
+        );
+    }
+}
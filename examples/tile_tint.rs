@@ -0,0 +1,103 @@
+use bevy::{
+    app::{App, Startup, Update},
+    asset::{AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::system::{Commands, Local, Query, Res, ResMut},
+    math::{IVec2, UVec2, Vec2},
+    render::{color::Color, render_resource::FilterMode},
+    time::Time,
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, flash)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let entity = commands.spawn_empty().id();
+    let mut tilemap = StandardTilemapBundle {
+        tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+        slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+        ty: TilemapType::Square,
+        storage: TilemapStorage::new(16, entity),
+        material: materials.add(StandardTilemapMaterial::default()),
+        textures: textures.add(TilemapTextures::single(
+            TilemapTexture::new(
+                asset_server.load("test_square.png"),
+                TilemapTextureDescriptor::new(UVec2 { x: 32, y: 32 }, UVec2 { x: 16, y: 16 }),
+            ),
+            FilterMode::Nearest,
+        )),
+        ..Default::default()
+    };
+
+    tilemap.storage.fill_rect(
+        &mut commands,
+        TileArea::new(IVec2::ZERO, UVec2 { x: 10, y: 10 }),
+        TileBuilder::new().with_layer(0, TileLayer::no_flip(0)),
+    );
+
+    commands.entity(entity).insert(tilemap);
+}
+
+/// Flash a random tile red like it's taking damage, and fade back to white.
+fn flash(
+    mut commands: Commands,
+    mut tilemaps_query: Query<&mut TilemapStorage>,
+    time: Res<Time>,
+    mut flashing: Local<Option<(IVec2, f32)>>,
+) {
+    let Ok(mut storage) = tilemaps_query.get_single_mut() else {
+        return;
+    };
+
+    let (index, elapsed) = flashing.get_or_insert_with(|| {
+        (
+            IVec2::new(
+                rand::random::<i32>().rem_euclid(10),
+                rand::random::<i32>().rem_euclid(10),
+            ),
+            0.,
+        )
+    });
+    *elapsed += time.delta_seconds();
+
+    // Red at the beginning, and back to white after half a second.
+    let t = (*elapsed / 0.5).min(1.);
+    storage.set_tile_color(&mut commands, *index, Color::rgb(1., t, t));
+
+    if t >= 1. {
+        *flashing = None;
+    }
+}
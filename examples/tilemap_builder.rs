@@ -0,0 +1,61 @@
+use bevy::{
+    app::{App, Startup},
+    asset::{AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::system::{Commands, Res, ResMut},
+    math::{IVec2, UVec2, Vec2},
+    render::render_resource::FilterMode,
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        builder::TilemapBuilder,
+        map::{TilemapTexture, TilemapTextureDescriptor, TilemapTextures, TilemapType},
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let texture = textures.add(TilemapTextures::single(
+        TilemapTexture::new(
+            asset_server.load("test_square.png"),
+            TilemapTextureDescriptor::new(UVec2 { x: 32, y: 32 }, UVec2 { x: 16, y: 16 }),
+        ),
+        FilterMode::Nearest,
+    ));
+
+    // A 64x64 tilemap, and the errors tell you what's wrong with the settings.
+    TilemapBuilder::new(TilemapType::Square, Vec2::splat(16.))
+        .with_material(materials.add(StandardTilemapMaterial::default()))
+        .with_textures(texture)
+        .with_fill(
+            TileArea::new(IVec2::ZERO, UVec2::splat(64)),
+            TileBuilder::new().with_layer(0, TileLayer::no_flip(1)),
+        )
+        .build(&mut commands)
+        .unwrap();
+}
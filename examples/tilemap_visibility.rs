@@ -0,0 +1,116 @@
+use bevy::{
+    app::{App, Startup, Update},
+    asset::{AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::{
+        component::Component,
+        query::With,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    math::{IVec2, UVec2, Vec2},
+    render::render_resource::FilterMode,
+    time::Time,
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapTransform, TilemapType,
+            TilemapVisibility,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+/// The tilemap fading in and out.
+#[derive(Component)]
+struct Night;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (crossfade, toggle))
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut textures: ResMut<Assets<TilemapTextures>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let material = materials.add(StandardTilemapMaterial::default());
+    let texture = textures.add(TilemapTextures::single(
+        TilemapTexture::new(
+            asset_server.load("test_square.png"),
+            TilemapTextureDescriptor::new(UVec2 { x: 32, y: 32 }, UVec2 { x: 16, y: 16 }),
+        ),
+        FilterMode::Nearest,
+    ));
+
+    // Two tilemaps on top of each other, using different tiles.
+    for (atlas_index, z_index, night) in [(0, 0., false), (3, 1., true)] {
+        let entity = commands.spawn_empty().id();
+        let mut tilemap = StandardTilemapBundle {
+            tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+            slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+            ty: TilemapType::Square,
+            storage: TilemapStorage::new(16, entity),
+            material: material.clone(),
+            textures: texture.clone(),
+            transform: TilemapTransform {
+                z_index,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        tilemap.storage.fill_rect(
+            &mut commands,
+            TileArea::new(IVec2::ZERO, UVec2 { x: 10, y: 10 }),
+            TileBuilder::new().with_layer(0, TileLayer::no_flip(atlas_index)),
+        );
+
+        commands.entity(entity).insert(tilemap);
+        if night {
+            commands
+                .entity(entity)
+                .insert((Night, TilemapVisibility::default()));
+        }
+    }
+}
+
+/// Fade the night tilemap in and out, revealing the day tilemap below.
+fn crossfade(mut tilemaps_query: Query<&mut TilemapVisibility, With<Night>>, time: Res<Time>) {
+    tilemaps_query.iter_mut().for_each(|mut visibility| {
+        visibility.alpha = time.elapsed_seconds().sin() * 0.5 + 0.5;
+    });
+}
+
+/// Press space to hide or show the night tilemap.
+fn toggle(
+    mut tilemaps_query: Query<&mut TilemapVisibility, With<Night>>,
+    input: Res<ButtonInput<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        tilemaps_query.iter_mut().for_each(|mut visibility| {
+            visibility.visible = !visibility.visible;
+        });
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::{
+    asset::Assets,
+    ecs::system::ResMut,
+    math::IVec2,
+    prelude::{App, Camera2dBundle, Commands, Startup, UVec2, Vec2},
+    render::color::Color,
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    algorithm::wfc::{WfcRules, WfcRunner, WfcSource},
+    math::TileArea,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardPureColorTilemapBundle,
+        map::{TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapType},
+        tile::TileBuilder,
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+const VOID: u8 = 0;
+const WALL: u8 = 1;
+const FLOOR: u8 = 2;
+const DOOR: u8 = 3;
+
+// up, right, left, down
+const UP: usize = 0;
+const RIGHT: usize = 1;
+const LEFT: usize = 2;
+const DOWN: usize = 3;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut materials: ResMut<Assets<StandardTilemapMaterial>>) {
+    commands.spawn(Camera2dBundle::default());
+
+    // Walls separate the void from the floors,
+    // and doors connect the floors vertically with walls on both sides.
+    let mut adjacencies = Vec::new();
+    for (a, b) in [
+        (VOID, VOID),
+        (VOID, WALL),
+        (WALL, WALL),
+        (WALL, FLOOR),
+        (FLOOR, FLOOR),
+    ] {
+        for dir in [UP, RIGHT, LEFT, DOWN] {
+            adjacencies.push((a, dir, b));
+        }
+    }
+    adjacencies.extend([
+        (DOOR, UP, FLOOR),
+        (DOOR, DOWN, FLOOR),
+        (DOOR, LEFT, WALL),
+        (DOOR, RIGHT, WALL),
+    ]);
+
+    let rules = WfcRules::from_adjacencies(TilemapType::Square, 4, &adjacencies).unwrap();
+    let entity = commands.spawn_empty().id();
+
+    commands.entity(entity).insert((
+        WfcSource::SingleTile(vec![
+            TileBuilder::new().with_tint(Color::rgb(0.05, 0.05, 0.08)),
+            TileBuilder::new().with_tint(Color::rgb(0.45, 0.45, 0.5)),
+            TileBuilder::new().with_tint(Color::rgb(0.8, 0.7, 0.5)),
+            TileBuilder::new().with_tint(Color::rgb(0.55, 0.3, 0.1)),
+        ]),
+        WfcRunner::new(
+            TilemapType::Square,
+            rules,
+            TileArea::new(IVec2::ZERO, UVec2 { x: 32, y: 32 }),
+            // Use the same seed to get the same dungeon.
+            Some(0),
+        ),
+        StandardPureColorTilemapBundle {
+            tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+            slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+            ty: TilemapType::Square,
+            storage: TilemapStorage::new(16, entity),
+            material: materials.add(StandardTilemapMaterial::default()),
+            ..Default::default()
+        },
+    ));
+}
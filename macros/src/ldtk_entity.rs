@@ -1,6 +1,8 @@
 static LDTK_DEFAULT_ATTR: &str = "ldtk_default";
 static LDTK_NAME_ATTR: &str = "ldtk_name";
+static LDTK_FIELD_ATTR: &str = "ldtk_field";
 static SPAWN_SPRITE_ATTR: &str = "spawn_sprite";
+static SPAWN_SPRITE_SHEET_ATTR: &str = "spawn_sprite_sheet";
 static GLOBAL_ENTITY_ATTR: &str = "global_entity";
 static CALLBACK_ATTR: &str = "callback";
 
@@ -12,6 +14,10 @@ pub fn expand_ldtk_entity_derive(input: syn::DeriveInput) -> proc_macro::TokenSt
         .iter()
         .find(|attr| attr.path().get_ident().unwrap() == SPAWN_SPRITE_ATTR);
 
+    let spawn_sprite_sheet_attr = attrs
+        .iter()
+        .find(|attr| attr.path().get_ident().unwrap() == SPAWN_SPRITE_SHEET_ATTR);
+
     let global_entity_attr = attrs
         .iter()
         .find(|attr| attr.path().get_ident().unwrap() == GLOBAL_ENTITY_ATTR);
@@ -30,6 +36,18 @@ pub fn expand_ldtk_entity_derive(input: syn::DeriveInput) -> proc_macro::TokenSt
         }
     };
 
+    let spawn_sprite_sheet = {
+        if spawn_sprite_sheet_attr.is_some() {
+            quote::quote!(if let Some(sprite) = sprite {
+                commands.insert(sprite);
+            })
+        } else {
+            quote::quote!(
+                let _ = sprite;
+            )
+        }
+    };
+
     let global_entity = {
         if global_entity_attr.is_some() {
             quote::quote!(
@@ -70,8 +88,6 @@ pub fn expand_ldtk_entity_derive(input: syn::DeriveInput) -> proc_macro::TokenSt
         let mut fields_cton = Vec::new();
 
         for field in fields.iter() {
-            let field_name = field.ident.as_ref().unwrap();
-
             let default = field
                 .attrs
                 .iter()
@@ -80,16 +96,16 @@ pub fn expand_ldtk_entity_derive(input: syn::DeriveInput) -> proc_macro::TokenSt
                 continue;
             }
 
-            let name = field
-                .attrs
-                .iter()
-                .find(|attr| attr.path().get_ident().unwrap() == LDTK_NAME_ATTR);
+            let name = field.attrs.iter().find(|attr| {
+                let ident = attr.path().get_ident().unwrap();
+                ident == LDTK_NAME_ATTR || ident == LDTK_FIELD_ATTR
+            });
             if let Some(attr) = name {
-                fields_cton.push(expand_entity_fields_rename(field_name, &attr.meta));
+                fields_cton.push(expand_entity_fields_rename(field, &attr.meta));
                 continue;
             }
 
-            fields_cton.push(expand_entity_fields(field_name));
+            fields_cton.push(expand_entity_fields(field));
         }
 
         if fields_cton.len() < fields.len() {
@@ -113,9 +129,11 @@ pub fn expand_ldtk_entity_derive(input: syn::DeriveInput) -> proc_macro::TokenSt
                 fields: &bevy::utils::HashMap<String, bevy_entitiles::ldtk::json::field::FieldInstance>,
                 asset_server: &bevy::prelude::AssetServer,
                 ldtk_assets: &bevy_entitiles::ldtk::resources::LdtkAssets,
+                sprite: Option<bevy::sprite::SpriteSheetBundle>,
             ) {
                 #callback
                 #spawn_sprite
+                #spawn_sprite_sheet
                 #global_entity
 
                 commands.insert(#ctor);
@@ -125,22 +143,50 @@ pub fn expand_ldtk_entity_derive(input: syn::DeriveInput) -> proc_macro::TokenSt
     .into()
 }
 
-pub fn expand_entity_fields(field_name: &syn::Ident) -> proc_macro2::TokenStream {
-    quote::quote!(
-        #field_name: fields[&stringify!(#field_name).to_string()].clone().into(),
-    )
+pub fn expand_entity_fields(field: &syn::Field) -> proc_macro2::TokenStream {
+    let field_name = field.ident.as_ref().unwrap();
+    let name = quote::quote!(stringify!(#field_name));
+    expand_field_value(field, name)
 }
 
 pub fn expand_entity_fields_rename(
-    field_name: &syn::Ident,
+    field: &syn::Field,
     ldtk_name: &syn::Meta,
 ) -> proc_macro2::TokenStream {
     let name = match ldtk_name {
-        syn::Meta::NameValue(value) => &value.value,
-        _ => panic!("LdtkEnum attribute must be a name value!"),
+        syn::Meta::NameValue(value) => {
+            let value = &value.value;
+            quote::quote!(#value)
+        }
+        syn::Meta::List(list) => list.tokens.clone(),
+        _ => panic!("Field name attribute must be a name value or a list!"),
     };
 
+    expand_field_value(field, name)
+}
+
+fn expand_field_value(
+    field: &syn::Field,
+    name: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let field_name = field.ident.as_ref().unwrap();
+    let field_ty = &field.ty;
+
+    // Spanned to the type, so unsupported types are reported on the field itself.
+    let value = quote::quote_spanned!(syn::spanned::Spanned::span(field_ty) =>
+        <bevy_entitiles::ldtk::json::field::FieldInstance as Into<#field_ty>>::into(
+            fields
+                .get(#name)
+                .unwrap_or_else(|| panic!(
+                    "Entity {} doesn't have the field {}!",
+                    entity_instance.identifier,
+                    #name
+                ))
+                .clone(),
+        )
+    );
+
     quote::quote!(
-        #field_name: fields[#name].clone().into(),
+        #field_name: #value,
     )
 }
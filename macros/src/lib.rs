@@ -7,7 +7,15 @@ mod tiled_object;
 
 #[proc_macro_derive(
     LdtkEntity,
-    attributes(ldtk_default, ldtk_name, spawn_sprite, global_entity, callback)
+    attributes(
+        ldtk_default,
+        ldtk_name,
+        ldtk_field,
+        spawn_sprite,
+        spawn_sprite_sheet,
+        global_entity,
+        callback
+    )
 )]
 pub fn derive_ldtk_entities(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     ldtk_entity::expand_ldtk_entity_derive(syn::parse(input).unwrap())
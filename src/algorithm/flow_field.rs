@@ -0,0 +1,296 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Query, Res},
+    },
+    math::{IVec2, Vec2},
+    reflect::Reflect,
+    utils::HashMap,
+};
+
+use crate::{
+    math::extension::TileIndex,
+    tilemap::{
+        algorithm::path::{PathTile, PathTilemap},
+        map::TilemapType,
+    },
+};
+
+use super::{nav_grid::CostProvider, pathfinding::PathTilemaps};
+
+/// A flow field that leads every reachable path tile to the same goal.
+///
+/// Many agents can share one flow field instead of finding paths on their own.
+/// Add this to a tilemap that has a `PathTilemap`, and it will be recomputed
+/// whenever the goal or the path tilemap changes.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct FlowField {
+    pub goal: IVec2,
+    pub allow_diagonal: bool,
+    pub(crate) costs: HashMap<IVec2, u32>,
+    pub(crate) next: HashMap<IVec2, IVec2>,
+    /// The goal and the `PathTilemap::version()` this field was computed with.
+    pub(crate) computed: Option<(IVec2, u32)>,
+}
+
+impl FlowField {
+    pub fn new(goal: IVec2, allow_diagonal: bool) -> Self {
+        Self {
+            goal,
+            allow_diagonal,
+            costs: HashMap::new(),
+            next: HashMap::new(),
+            computed: None,
+        }
+    }
+
+    /// Compute a flow field to `goal` with the costs from `grid`, like a `NavGrid`.
+    ///
+    /// Unlike the ones computed from a `PathTilemap`, this is never outdated by
+    /// itself, so compute it again after the costs change.
+    pub fn compute(
+        grid: &impl CostProvider,
+        tilemap_ty: TilemapType,
+        goal: IVec2,
+        allow_diagonal: bool,
+    ) -> Self {
+        let mut field = Self::new(goal, allow_diagonal);
+        field.fill(tilemap_ty, |index| grid.cost(index));
+        field
+    }
+
+    /// Get the direction an agent at `index` should move towards.
+    ///
+    /// This is in tile index space, and is `Vec2::ZERO` at the goal.
+    /// Returns `None` if the goal can't be reached from `index`.
+    #[inline]
+    pub fn direction_at(&self, index: IVec2) -> Option<Vec2> {
+        self.next
+            .get(&index)
+            .map(|next| (*next - index).as_vec2().normalize_or_zero())
+    }
+
+    /// Get the next tile an agent at `index` should step onto.
+    #[inline]
+    pub fn next_step(&self, index: IVec2) -> Option<IVec2> {
+        self.next.get(&index).cloned()
+    }
+
+    /// Get the total cost to reach the goal from `index`.
+    #[inline]
+    pub fn cost_at(&self, index: IVec2) -> Option<u32> {
+        self.costs.get(&index).cloned()
+    }
+
+    /// Returns true if the goal or the path tilemap has changed since the last computation.
+    #[inline]
+    pub fn is_outdated(&self, path_tilemap: &PathTilemap) -> bool {
+        self.computed != Some((self.goal, path_tilemap.version()))
+    }
+
+    /// Recompute the flow field if it's outdated.
+    ///
+    /// Returns true if the field is recomputed.
+    pub fn update(
+        &mut self,
+        path_tilemap: &PathTilemap,
+        tilemap_ty: TilemapType,
+        cost: impl Fn(IVec2, &PathTile) -> u32,
+    ) -> bool {
+        if !self.is_outdated(path_tilemap) {
+            return false;
+        }
+
+        *self = compute_flow_field(
+            path_tilemap,
+            self.goal,
+            tilemap_ty,
+            self.allow_diagonal,
+            cost,
+        );
+        true
+    }
+
+    /// Search from the goal to every reachable tile, where `cost` returns
+    /// the cost to step onto a tile, or `None` if it's not walkable.
+    fn fill(&mut self, tilemap_ty: TilemapType, cost: impl Fn(IVec2) -> Option<u32>) {
+        let goal = self.goal;
+        if cost(goal).is_none() {
+            return;
+        }
+
+        let mut queue = BinaryHeap::new();
+        self.costs.insert(goal, 0);
+        self.next.insert(goal, goal);
+        queue.push(Reverse((0, goal.x, goal.y)));
+
+        while let Some(Reverse((cur_cost, x, y))) = queue.pop() {
+            let index = IVec2 { x, y };
+            if cur_cost > self.costs[&index] {
+                continue;
+            }
+
+            // Agents on the neighbours pay for stepping onto this tile.
+            let new_cost = cur_cost.saturating_add(cost(index).unwrap());
+
+            for neighbour in index
+                .neighbours(tilemap_ty, self.allow_diagonal)
+                .into_iter()
+                .flatten()
+            {
+                if cost(neighbour).is_none() {
+                    continue;
+                }
+
+                if self.costs.get(&neighbour).is_none_or(|c| new_cost < *c) {
+                    self.costs.insert(neighbour, new_cost);
+                    self.next.insert(neighbour, index);
+                    queue.push(Reverse((new_cost, neighbour.x, neighbour.y)));
+                }
+            }
+        }
+    }
+}
+
+/// Compute a flow field by searching from the goal to every reachable path tile.
+///
+/// `cost` returns the cost to step onto a tile, which is `PathTile::cost` in most cases.
+/// The field will be empty if there's no path tile at the goal.
+pub fn compute_flow_field(
+    path_tilemap: &PathTilemap,
+    goal: IVec2,
+    tilemap_ty: TilemapType,
+    allow_diagonal: bool,
+    cost: impl Fn(IVec2, &PathTile) -> u32,
+) -> FlowField {
+    let mut field = FlowField::new(goal, allow_diagonal);
+    field.computed = Some((goal, path_tilemap.version()));
+    field.fill(tilemap_ty, |index| {
+        path_tilemap.get(index).map(|tile| cost(index, tile))
+    });
+    field
+}
+
+pub fn flow_field_updater(
+    mut fields_query: Query<(Entity, &TilemapType, &mut FlowField)>,
+    path_tilemaps: Res<PathTilemaps>,
+) {
+    fields_query
+        .iter_mut()
+        .for_each(|(entity, ty, mut flow_field)| {
+            #[cfg(feature = "multi-threaded")]
+            let Some(path_tilemap) = path_tilemaps.lock(entity) else {
+                return;
+            };
+            #[cfg(not(feature = "multi-threaded"))]
+            let Some(path_tilemap) = path_tilemaps.get(entity) else {
+                return;
+            };
+
+            if flow_field.is_outdated(&path_tilemap) {
+                flow_field.update(&path_tilemap, *ty, |_, tile| tile.cost);
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        algorithm::nav_grid::NavGrid,
+        math::{extension::ManhattanDistance, TileArea},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_flow_field() {
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            TileArea::new(IVec2::ZERO, bevy::math::UVec2::new(8, 8)),
+            PathTile { cost: 1 },
+        );
+
+        let goal = IVec2::new(3, 4);
+        let mut field = compute_flow_field(
+            &path_tilemap,
+            goal,
+            TilemapType::Square,
+            false,
+            |_, tile| tile.cost,
+        );
+
+        assert_eq!(field.direction_at(goal), Some(Vec2::ZERO));
+        assert_eq!(field.direction_at(IVec2::new(0, 4)), Some(Vec2::X));
+        assert_eq!(field.direction_at(IVec2::new(7, 4)), Some(Vec2::NEG_X));
+        assert_eq!(field.direction_at(IVec2::new(3, 0)), Some(Vec2::Y));
+        assert_eq!(field.direction_at(IVec2::new(3, 7)), Some(Vec2::NEG_Y));
+        assert_eq!(field.direction_at(IVec2::new(8, 8)), None);
+
+        // Following the field from any tile leads to the goal.
+        for start in [
+            IVec2::ZERO,
+            IVec2::new(7, 0),
+            IVec2::new(0, 7),
+            IVec2::splat(7),
+        ] {
+            let mut cur = start;
+            let dist = (goal - start).abs();
+            assert_eq!(field.cost_at(start), Some((dist.x + dist.y) as u32));
+            for _ in 0..(dist.x + dist.y) {
+                let next = field.next_step(cur).unwrap();
+                assert!(goal.manhattan_distance(next) < goal.manhattan_distance(cur));
+                cur = next;
+            }
+            assert_eq!(cur, goal);
+        }
+
+        assert!(!field.update(&path_tilemap, TilemapType::Square, |_, tile| tile.cost));
+        field.goal = IVec2::new(7, 7);
+        assert!(field.update(&path_tilemap, TilemapType::Square, |_, tile| tile.cost));
+        assert_eq!(field.direction_at(IVec2::new(7, 0)), Some(Vec2::Y));
+
+        // A wall below the goal with a gap on the left.
+        for x in 1..8 {
+            path_tilemap.remove(IVec2::new(x, 6));
+        }
+        assert!(field.is_outdated(&path_tilemap));
+        assert!(field.update(&path_tilemap, TilemapType::Square, |_, tile| tile.cost));
+        assert_eq!(field.direction_at(IVec2::new(7, 5)), Some(Vec2::NEG_X));
+        assert_eq!(field.direction_at(IVec2::new(0, 5)), Some(Vec2::Y));
+        assert_eq!(field.cost_at(IVec2::new(7, 5)), Some(16));
+    }
+
+    #[test]
+    fn test_compute_from_grid() {
+        // A wall in the middle with a gap at the bottom.
+        #[rustfmt::skip]
+        let csv = vec![
+            0, 0, 1, 0, 0,
+            0, 0, 1, 0, 0,
+            0, 0, 0, 0, 0,
+        ];
+        let grid = NavGrid::from_int_grid_csv(bevy::math::UVec2::new(5, 3), &csv, &[1]).unwrap();
+        let goal = IVec2::new(4, 0);
+        let field = FlowField::compute(&grid, TilemapType::Square, goal, false);
+
+        assert_eq!(field.direction_at(goal), Some(Vec2::ZERO));
+        assert_eq!(field.direction_at(IVec2::new(3, 0)), Some(Vec2::X));
+        assert_eq!(field.direction_at(IVec2::new(2, 0)), None);
+        assert_eq!(field.direction_at(IVec2::new(1, 0)), Some(Vec2::Y));
+        assert_eq!(field.direction_at(IVec2::new(1, 2)), Some(Vec2::X));
+        assert_eq!(field.cost_at(IVec2::new(0, 0)), Some(8));
+
+        // Following the field goes around the wall along the shortest route.
+        let mut cur = IVec2::new(0, 0);
+        let mut steps = 0;
+        while cur != goal {
+            cur = field.next_step(cur).unwrap();
+            assert!(grid.get(cur).is_some());
+            steps += 1;
+        }
+        assert_eq!(steps, 8);
+    }
+}
@@ -1,10 +1,18 @@
-use bevy::prelude::{Plugin, Update};
+use bevy::{
+    ecs::system::Resource,
+    prelude::{Plugin, Update},
+};
+use rand::{rngs::StdRng, SeedableRng};
 
 use self::{
+    flow_field::FlowField,
+    nav_grid::NavGrid,
     pathfinding::{Path, PathTilemaps},
     wfc::{WfcData, WfcElement, WfcHistory, WfcSource},
 };
 
+pub mod flow_field;
+pub mod nav_grid;
 pub mod pathfinding;
 pub mod wfc;
 
@@ -12,14 +20,17 @@ pub struct EntiTilesAlgorithmPlugin;
 
 impl Plugin for EntiTilesAlgorithmPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.register_type::<Path>();
+        app.register_type::<Path>()
+            .register_type::<FlowField>()
+            .register_type::<NavGrid>();
 
         app.register_type::<WfcElement>()
             .register_type::<WfcHistory>()
             .register_type::<WfcData>()
             .register_type::<WfcSource>();
 
-        app.init_resource::<PathTilemaps>();
+        app.init_resource::<PathTilemaps>()
+            .init_resource::<ProcGenRng>();
 
         app.add_systems(
             Update,
@@ -29,6 +40,8 @@ impl Plugin for EntiTilesAlgorithmPlugin {
                 pathfinding::path_assigner,
                 #[cfg(not(feature = "multi-threaded"))]
                 pathfinding::path_finding_single_threaded,
+                flow_field::flow_field_updater,
+                nav_grid::nav_grid_updater,
                 #[cfg(feature = "multi-threaded")]
                 wfc::wave_function_collapse,
                 #[cfg(feature = "multi-threaded")]
@@ -42,3 +55,28 @@ impl Plugin for EntiTilesAlgorithmPlugin {
         );
     }
 }
+
+/// The rng used by the procedural features which aren't given a seed or an rng.
+///
+/// Insert one created by `from_seed()` to make the generated levels reproducible.
+#[derive(Resource)]
+pub struct ProcGenRng(pub StdRng);
+
+impl Default for ProcGenRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+impl ProcGenRng {
+    #[inline]
+    pub fn from_seed(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+
+    /// Create a new rng seeded by this one, for tasks that need their own rng.
+    #[inline]
+    pub fn fork(&mut self) -> StdRng {
+        StdRng::from_rng(&mut self.0).unwrap()
+    }
+}
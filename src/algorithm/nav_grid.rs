@@ -0,0 +1,559 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Query, Res},
+    },
+    math::{IVec2, UVec2},
+    reflect::Reflect,
+};
+
+use crate::tilemap::{algorithm::path::PathTilemap, map::TilemapType};
+
+#[cfg(feature = "ldtk")]
+use crate::ldtk::{
+    json::{definitions::LayerType, level::LayerInstance},
+    resources::LdtkCoordConfig,
+};
+
+use super::pathfinding::{self, PathTilemaps};
+
+/// Where the pathfinding reads the cost to step onto a tile from.
+///
+/// Implement this to take costs from anything else, like dynamic hazards
+/// or terrain modifiers for a specific unit, and pass it to `pathfinding::find_path`.
+pub trait CostProvider {
+    /// The cost to step onto the tile at `index`, or `None` if it's not walkable.
+    fn cost(&self, index: IVec2) -> Option<u32>;
+}
+
+impl CostProvider for PathTilemap {
+    #[inline]
+    fn cost(&self, index: IVec2) -> Option<u32> {
+        self.get(index).map(|t| t.cost)
+    }
+}
+
+impl CostProvider for NavGrid {
+    #[inline]
+    fn cost(&self, index: IVec2) -> Option<u32> {
+        self.get(index)
+    }
+}
+
+impl<F: Fn(IVec2) -> Option<u32>> CostProvider for F {
+    #[inline]
+    fn cost(&self, index: IVec2) -> Option<u32> {
+        self(index)
+    }
+}
+
+/// A dense copy of a rect in a `PathTilemap`, cached for repeated path queries.
+///
+/// Queries don't need to look up the chunked storage (or lock it) for every tile.
+/// It will be rebuilt whenever the path tilemap is modified.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct NavGrid {
+    /// The tilemap the `PathTilemap` belongs to.
+    pub tilemap: Entity,
+    pub tilemap_ty: TilemapType,
+    pub origin: IVec2,
+    pub size: UVec2,
+    pub(crate) costs: Vec<Option<u32>>,
+    /// The `PathTilemap::version()` this grid was built with.
+    pub(crate) computed: Option<u32>,
+}
+
+impl NavGrid {
+    /// Create an empty grid, which will be built by the next `nav_grid_updater`.
+    pub fn new(tilemap: Entity, tilemap_ty: TilemapType, origin: IVec2, size: UVec2) -> Self {
+        Self {
+            tilemap,
+            tilemap_ty,
+            origin,
+            size,
+            costs: vec![None; (size.x * size.y) as usize],
+            computed: None,
+        }
+    }
+
+    /// Returns true if the path tilemap has changed since the grid was built.
+    #[inline]
+    pub fn is_outdated(&self, path_tilemap: &PathTilemap) -> bool {
+        self.computed != Some(path_tilemap.version())
+    }
+
+    /// Force the grid to be rebuilt next time.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.computed = None;
+    }
+
+    /// Copy the costs from the path tilemap.
+    pub fn rebuild(&mut self, path_tilemap: &PathTilemap) {
+        for y in 0..self.size.y as i32 {
+            for x in 0..self.size.x as i32 {
+                let index = IVec2 { x, y };
+                let tile = path_tilemap.get(index + self.origin);
+                self.costs[(y * self.size.x as i32 + x) as usize] = tile.map(|t| t.cost);
+            }
+        }
+        self.computed = Some(path_tilemap.version());
+    }
+
+    /// Create a grid from IntGrid values, which are listed row by row from the top
+    /// like `LayerInstance::int_grid_csv`. So the cell `(x, y)` is `csv[y * size.x + x]`.
+    ///
+    /// Cells with `solid_values` are not walkable, and the others cost 1 to step onto.
+    /// The grid is not linked to any `PathTilemap`, so it won't be rebuilt by `nav_grid_updater`.
+    ///
+    /// Returns `NavGridError::SizeMismatch` if there're not `size.x * size.y` values.
+    pub fn from_int_grid_csv(
+        size: UVec2,
+        csv: &[i32],
+        solid_values: &[i32],
+    ) -> Result<Self, NavGridError> {
+        let mut nav_grid = Self::new(Entity::PLACEHOLDER, TilemapType::Square, IVec2::ZERO, size);
+        nav_grid.rebuild_from_int_grid_csv(size, csv, solid_values)?;
+        Ok(nav_grid)
+    }
+
+    /// Copy the costs from IntGrid values again, for example after the layer is modified.
+    ///
+    /// The grid is left unchanged if it returns an error, see `from_int_grid_csv`.
+    pub fn rebuild_from_int_grid_csv(
+        &mut self,
+        size: UVec2,
+        csv: &[i32],
+        solid_values: &[i32],
+    ) -> Result<(), NavGridError> {
+        self.rebuild_from_cells(size, csv, solid_values, |cell| cell)
+    }
+
+    /// Copy the costs from IntGrid values, putting the cell `(x, y)` on `cell_index((x, y))`.
+    ///
+    /// The origin is moved to the smallest index, so `cell_index` may flip the axes.
+    fn rebuild_from_cells(
+        &mut self,
+        size: UVec2,
+        csv: &[i32],
+        solid_values: &[i32],
+        cell_index: impl Fn(IVec2) -> IVec2,
+    ) -> Result<(), NavGridError> {
+        if csv.len() != (size.x * size.y) as usize {
+            return Err(NavGridError::SizeMismatch {
+                len: csv.len(),
+                size,
+            });
+        }
+
+        let corner = cell_index(size.as_ivec2() - 1);
+        self.origin = cell_index(IVec2::ZERO).min(corner);
+        self.size = size;
+        self.costs = vec![None; csv.len()];
+        csv.iter().enumerate().for_each(|(i, value)| {
+            let cell = IVec2::new(i as i32 % size.x as i32, i as i32 / size.x as i32);
+            let local = cell_index(cell) - self.origin;
+            self.costs[(local.y * size.x as i32 + local.x) as usize] =
+                (!solid_values.contains(value)).then_some(1);
+        });
+        self.computed = None;
+        Ok(())
+    }
+
+    /// Get the cost to step onto the tile at `index`, or `None` if it's not walkable.
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<u32> {
+        let local = index - self.origin;
+        if local.x < 0
+            || local.y < 0
+            || local.x >= self.size.x as i32
+            || local.y >= self.size.y as i32
+        {
+            return None;
+        }
+        self.costs[(local.y * self.size.x as i32 + local.x) as usize]
+    }
+
+    /// Find the path with the lowest cost from `origin` to `dest`.
+    ///
+    /// Returns the tiles to step onto in order, which ends with `dest`,
+    /// or `None` if `dest` can't be reached.
+    #[inline]
+    pub fn find_path(
+        &self,
+        origin: IVec2,
+        dest: IVec2,
+        allow_diagonal: bool,
+    ) -> Option<Vec<IVec2>> {
+        pathfinding::find_path(
+            self,
+            self.tilemap_ty,
+            origin,
+            dest,
+            allow_diagonal,
+            u32::MAX,
+        )
+    }
+
+    /// Find the path with the lowest cost from `origin` to the nearest of `goals`.
+    ///
+    /// See `pathfinding::find_path_multi`.
+    #[inline]
+    pub fn find_path_multi(
+        &self,
+        origin: IVec2,
+        goals: &[IVec2],
+        allow_diagonal: bool,
+    ) -> Option<(Vec<IVec2>, IVec2)> {
+        pathfinding::find_path_multi(
+            self,
+            self.tilemap_ty,
+            origin,
+            goals,
+            allow_diagonal,
+            u32::MAX,
+        )
+    }
+}
+
+#[cfg(feature = "ldtk")]
+impl NavGrid {
+    /// Create a grid from an LDtk IntGrid layer, see `from_int_grid_csv`.
+    ///
+    /// The cells are placed with `LdtkCoordConfig::cell_index()`,
+    /// so the indices are the same as the `PathTilemap` generated by `LdtkPathLayer`.
+    ///
+    /// Returns `NavGridError::NotIntGrid` if the layer is not an IntGrid layer.
+    pub fn from_int_grid(
+        layer: &LayerInstance,
+        solid_values: &[i32],
+        coords: &LdtkCoordConfig,
+    ) -> Result<Self, NavGridError> {
+        let size = int_grid_size(layer)?;
+        let mut nav_grid = Self::new(Entity::PLACEHOLDER, TilemapType::Square, IVec2::ZERO, size);
+        nav_grid.rebuild_from_int_grid(layer, solid_values, coords)?;
+        Ok(nav_grid)
+    }
+
+    /// Copy the costs from the layer again, for example after it's modified at runtime.
+    ///
+    /// The grid is left unchanged if it returns an error, see `from_int_grid`.
+    pub fn rebuild_from_int_grid(
+        &mut self,
+        layer: &LayerInstance,
+        solid_values: &[i32],
+        coords: &LdtkCoordConfig,
+    ) -> Result<(), NavGridError> {
+        self.rebuild_from_cells(
+            int_grid_size(layer)?,
+            &layer.int_grid_csv,
+            solid_values,
+            |cell| coords.cell_index(cell),
+        )
+    }
+}
+
+#[cfg(feature = "ldtk")]
+fn int_grid_size(layer: &LayerInstance) -> Result<UVec2, NavGridError> {
+    if layer.ty != LayerType::IntGrid {
+        return Err(NavGridError::NotIntGrid {
+            identifier: layer.identifier.clone(),
+        });
+    }
+    Ok(UVec2::new(layer.c_wid as u32, layer.c_hei as u32))
+}
+
+/// The reason why a `NavGrid` couldn't be built from IntGrid values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NavGridError {
+    /// The number of values is not `size.x * size.y`.
+    SizeMismatch { len: usize, size: UVec2 },
+    /// The LDtk layer is not an IntGrid layer.
+    NotIntGrid { identifier: String },
+}
+
+impl std::fmt::Display for NavGridError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavGridError::SizeMismatch { len, size } => write!(
+                f,
+                "The IntGrid has {} values, but the size is {}!",
+                len, size
+            ),
+            NavGridError::NotIntGrid { identifier } => {
+                write!(f, "The layer {:?} is not an IntGrid layer!", identifier)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NavGridError {}
+
+pub fn nav_grid_updater(mut grids_query: Query<&mut NavGrid>, path_tilemaps: Res<PathTilemaps>) {
+    grids_query.iter_mut().for_each(|mut nav_grid| {
+        #[cfg(feature = "multi-threaded")]
+        let Some(path_tilemap) = path_tilemaps.lock(nav_grid.tilemap) else {
+            return;
+        };
+        #[cfg(feature = "multi-threaded")]
+        let path_tilemap = &*path_tilemap;
+        #[cfg(not(feature = "multi-threaded"))]
+        let Some(path_tilemap) = path_tilemaps.get(nav_grid.tilemap) else {
+            return;
+        };
+
+        if nav_grid.is_outdated(path_tilemap) {
+            nav_grid.rebuild(path_tilemap);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::app::{App, Update};
+
+    use crate::{math::TileArea, tilemap::algorithm::path::PathTile};
+
+    use super::*;
+
+    #[test]
+    fn test_nav_grid() {
+        let mut app = App::new();
+        app.init_resource::<PathTilemaps>()
+            .add_systems(Update, nav_grid_updater);
+
+        let tilemap = app.world.spawn_empty().id();
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            TileArea::new(IVec2::ZERO, UVec2::new(5, 3)),
+            PathTile { cost: 1 },
+        );
+        // A wall in the middle with a gap at the top.
+        path_tilemap.remove(IVec2::new(2, 0));
+        path_tilemap.remove(IVec2::new(2, 1));
+        app.world
+            .resource_mut::<PathTilemaps>()
+            .insert(tilemap, path_tilemap);
+
+        let level = app
+            .world
+            .spawn(NavGrid::new(
+                tilemap,
+                TilemapType::Square,
+                IVec2::ZERO,
+                UVec2::new(5, 3),
+            ))
+            .id();
+        app.update();
+
+        let nav_grid = app.world.get::<NavGrid>(level).unwrap();
+        assert_eq!(nav_grid.get(IVec2::new(1, 1)), Some(1));
+        assert_eq!(nav_grid.get(IVec2::new(2, 1)), None);
+        assert_eq!(nav_grid.get(IVec2::new(5, 1)), None);
+        let path = nav_grid
+            .find_path(IVec2::new(0, 0), IVec2::new(4, 0), false)
+            .unwrap();
+        assert_eq!(path.len(), 8);
+        assert_eq!(path.last(), Some(&IVec2::new(4, 0)));
+        assert!(path.contains(&IVec2::new(2, 2)));
+
+        // Close the gap, so the cached grid is outdated.
+        #[cfg(feature = "multi-threaded")]
+        app.world
+            .resource::<PathTilemaps>()
+            .lock(tilemap)
+            .unwrap()
+            .remove(IVec2::new(2, 2));
+        #[cfg(not(feature = "multi-threaded"))]
+        app.world
+            .resource_mut::<PathTilemaps>()
+            .get_mut(tilemap)
+            .unwrap()
+            .remove(IVec2::new(2, 2));
+        {
+            let path_tilemaps = app.world.resource::<PathTilemaps>();
+            #[cfg(feature = "multi-threaded")]
+            let path_tilemap = path_tilemaps.lock(tilemap).unwrap();
+            #[cfg(not(feature = "multi-threaded"))]
+            let path_tilemap = path_tilemaps.get(tilemap).unwrap();
+            assert!(app
+                .world
+                .get::<NavGrid>(level)
+                .unwrap()
+                .is_outdated(&path_tilemap));
+        }
+
+        app.update();
+        let nav_grid = app.world.get::<NavGrid>(level).unwrap();
+        assert_eq!(nav_grid.get(IVec2::new(2, 2)), None);
+        assert!(nav_grid
+            .find_path(IVec2::new(0, 0), IVec2::new(4, 0), false)
+            .is_none());
+    }
+
+    #[test]
+    fn test_int_grid() {
+        // A wall row with a gap on the right.
+        #[rustfmt::skip]
+        let mut csv = vec![
+            0, 0, 0, 0, 0,
+            1, 1, 2, 1, 0,
+            0, 0, 0, 0, 0,
+        ];
+        let size = UVec2::new(5, 3);
+        let mut nav_grid = NavGrid::from_int_grid_csv(size, &csv, &[1, 2]).unwrap();
+        assert_eq!(nav_grid.get(IVec2::new(2, 1)), None);
+        assert_eq!(nav_grid.get(IVec2::new(4, 1)), Some(1));
+
+        let path = nav_grid
+            .find_path(IVec2::new(0, 0), IVec2::new(0, 2), false)
+            .unwrap();
+        assert_eq!(path.len(), 10);
+        assert!(path.contains(&IVec2::new(4, 1)));
+        assert!(path.iter().all(|index| nav_grid.get(*index).is_some()));
+
+        // Close the gap.
+        csv[9] = 1;
+        nav_grid
+            .rebuild_from_int_grid_csv(size, &csv, &[1, 2])
+            .unwrap();
+        assert!(nav_grid
+            .find_path(IVec2::new(0, 0), IVec2::new(0, 2), false)
+            .is_none());
+    }
+
+    #[test]
+    fn test_int_grid_size_mismatch() {
+        let size = UVec2::new(5, 3);
+        assert_eq!(
+            NavGrid::from_int_grid_csv(size, &[0; 14], &[]).unwrap_err(),
+            NavGridError::SizeMismatch { len: 14, size }
+        );
+
+        // A failed rebuild keeps the old costs.
+        let mut nav_grid = NavGrid::from_int_grid_csv(size, &[1; 15], &[]).unwrap();
+        assert!(nav_grid
+            .rebuild_from_int_grid_csv(UVec2::new(4, 4), &[1; 15], &[1])
+            .is_err());
+        assert_eq!(nav_grid.size, size);
+        assert_eq!(nav_grid.get(IVec2::ZERO), Some(1));
+    }
+
+    #[test]
+    fn test_cost_provider() {
+        let size = UVec2::new(5, 5);
+        let nav_grid = NavGrid::from_int_grid_csv(size, &[0; 25], &[]).unwrap();
+        // Stepping onto the column x = 2 is expensive, except at the top.
+        let provider = |index: IVec2| {
+            nav_grid.cost(index).map(|cost| {
+                if index.x == 2 && index.y != 4 {
+                    10
+                } else {
+                    cost
+                }
+            })
+        };
+
+        let origin = IVec2::new(0, 0);
+        let dest = IVec2::new(4, 0);
+        let path = pathfinding::find_path(&provider, TilemapType::Square, origin, dest, false, 100)
+            .unwrap();
+        // Crossing at the bottom costs 13, going around through the top costs 12.
+        assert_eq!(path.len(), 12);
+        assert!(path.contains(&IVec2::new(2, 4)));
+        assert!(path.iter().all(|index| index.x != 2 || index.y == 4));
+
+        // The same search with the IntGrid costs goes straight.
+        let path = pathfinding::find_path(&nav_grid, TilemapType::Square, origin, dest, false, 100)
+            .unwrap();
+        assert_eq!(path, nav_grid.find_path(origin, dest, false).unwrap());
+        assert_eq!(path.len(), 4);
+
+        // Detouring is not possible within the budget.
+        assert!(
+            pathfinding::find_path(&provider, TilemapType::Square, origin, dest, false, 11)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_find_path_multi() {
+        // A wall row with a gap on the right.
+        #[rustfmt::skip]
+        let csv = vec![
+            0, 0, 0, 0, 0,
+            1, 1, 1, 1, 0,
+            0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0,
+        ];
+        let nav_grid = NavGrid::from_int_grid_csv(UVec2::new(5, 4), &csv, &[1]).unwrap();
+        let origin = IVec2::new(0, 0);
+        // Blocked, closest by distance but behind the wall, and the nearest reachable one.
+        let goals = [IVec2::new(1, 1), IVec2::new(0, 2), IVec2::new(3, 0)];
+
+        let (path, goal) = nav_grid.find_path_multi(origin, &goals, false).unwrap();
+        assert_eq!(goal, IVec2::new(3, 0));
+        assert_eq!(path.len(), 3);
+        assert_eq!(path.last(), Some(&goal));
+
+        // Without it, the one behind the wall is the nearest reachable.
+        let (path, goal) = nav_grid
+            .find_path_multi(origin, &goals[..2], false)
+            .unwrap();
+        assert_eq!(goal, IVec2::new(0, 2));
+        assert_eq!(path.len(), 10);
+        assert_eq!(
+            Some(path),
+            nav_grid.find_path(origin, IVec2::new(0, 2), false)
+        );
+
+        assert!(nav_grid
+            .find_path_multi(origin, &goals[..1], false)
+            .is_none());
+    }
+
+    #[cfg(feature = "ldtk")]
+    #[test]
+    fn test_ldtk_int_grid() {
+        use crate::ldtk::{
+            layer::path::{analyze_path_layer, path_tilemap, LdtkPathLayer},
+            load_grid_vania,
+        };
+
+        let json = load_grid_vania();
+        let layer = json.levels[0]
+            .layer_instances
+            .iter()
+            .find(|layer| layer.ty == LayerType::IntGrid && layer.int_grid_csv.contains(&1))
+            .unwrap();
+        let path_layer = LdtkPathLayer {
+            identifier: layer.identifier.clone(),
+            parent: String::new(),
+            cost_mapper: None,
+            nav_grid: true,
+        };
+        let path_tiles = analyze_path_layer(layer, &path_layer);
+
+        for y_up in [true, false] {
+            let coords = LdtkCoordConfig {
+                y_up,
+                ..Default::default()
+            };
+            let nav_grid = NavGrid::from_int_grid(layer, &[1], &coords).unwrap();
+            let path_tilemap = path_tilemap(&path_tiles, &coords);
+
+            // The same csv entry is on the same index in both.
+            layer
+                .int_grid_csv
+                .iter()
+                .enumerate()
+                .for_each(|(i, value)| {
+                    let cell = IVec2::new(i as i32 % layer.c_wid, i as i32 / layer.c_wid);
+                    let index = coords.cell_index(cell);
+                    assert_eq!(nav_grid.get(index).is_none(), *value == 1);
+                    assert_eq!(path_tilemap.get(index).unwrap().cost, *value as u32);
+                });
+        }
+    }
+}
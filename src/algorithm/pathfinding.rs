@@ -8,12 +8,19 @@ use bevy::{
     math::IVec2,
     prelude::{Component, Entity},
     reflect::Reflect,
-    utils::{Entry, HashMap, HashSet},
+    utils::{HashMap, HashSet},
 };
 
+pub use crate::math::grid::line_of_sight;
+
+use super::nav_grid::CostProvider;
+
 use crate::{
     math::extension::{ManhattanDistance, TileIndex},
-    tilemap::{algorithm::path::PathTilemap, map::TilemapType},
+    tilemap::{
+        algorithm::path::{PathTile, PathTilemap},
+        map::TilemapType,
+    },
 };
 
 #[cfg(feature = "multi-threaded")]
@@ -154,6 +161,53 @@ impl Path {
     pub fn iter(&self) -> std::slice::Iter<IVec2> {
         self.path.iter()
     }
+
+    /// Remove the waypoints that can be skipped without crossing a solid tile.
+    ///
+    /// See `smooth_path` for more details.
+    pub fn smooth(
+        &mut self,
+        tilemap: &PathTilemap,
+        is_solid: impl Fn(IVec2, Option<&PathTile>) -> bool,
+    ) {
+        self.path = smooth_path(&self.path, tilemap, is_solid);
+        self.current_step = self.current_step.min(self.path.len());
+    }
+}
+
+/// Simplify a path using line-of-sight checks.
+///
+/// Waypoints that can be skipped without crossing a solid tile are removed.
+/// The first and the last waypoint are always preserved.
+///
+/// `is_solid` takes the tile index and the path tile at that index (if any).
+pub fn smooth_path(
+    path: &[IVec2],
+    tilemap: &PathTilemap,
+    is_solid: impl Fn(IVec2, Option<&PathTile>) -> bool,
+) -> Vec<IVec2> {
+    if path.len() <= 2 {
+        return path.to_vec();
+    }
+
+    let mut result = vec![path[0]];
+    let mut anchor = 0;
+
+    while anchor < path.len() - 1 {
+        let mut next = anchor + 1;
+        for candidate in (anchor + 2..path.len()).rev() {
+            if line_of_sight(path[anchor], path[candidate], |index| {
+                is_solid(index, tilemap.get(index))
+            }) {
+                next = candidate;
+                break;
+            }
+        }
+        result.push(path[next]);
+        anchor = next;
+    }
+
+    result
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -204,17 +258,19 @@ pub struct PathGrid {
     pub allow_diagonal: bool,
     pub tilemap_ty: TilemapType,
     pub origin: IVec2,
-    pub dest: IVec2,
+    /// The search stops at whichever of these is reached first.
+    pub goals: HashSet<IVec2>,
     pub to_explore: BinaryHeap<PathNode>,
     pub explored: HashSet<IVec2>,
     pub all_nodes: HashMap<IVec2, PathNode>,
     pub steps: u32,
     pub max_steps: Option<u32>,
-    #[cfg(feature = "multi-threaded")]
-    pub path_tilemap: Arc<Mutex<PathTilemap>>,
+    /// Tiles whose total cost from `origin` exceeds this are not explored.
+    pub max_cost: u32,
+    /// The goal the search ended at, if any.
+    pub reached: Option<IVec2>,
     #[cfg(not(feature = "multi-threaded"))]
     pub max_steps_per_frame: u32,
-    #[cfg(not(feature = "multi-threaded"))]
     pub is_done: bool,
 }
 
@@ -224,86 +280,110 @@ impl PathGrid {
         requester: Entity,
         tilemap: Entity,
         tilemap_ty: TilemapType,
-        #[cfg(feature = "multi-threaded")] path_tilemap: Arc<Mutex<PathTilemap>>,
     ) -> Self {
         PathGrid {
+            max_steps: finder.max_steps,
+            #[cfg(not(feature = "multi-threaded"))]
+            max_steps_per_frame: finder.max_steps_per_frame,
             requester,
             tilemap,
-            allow_diagonal: finder.allow_diagonal,
+            ..Self::with_goals(
+                tilemap_ty,
+                finder.origin,
+                &[finder.dest],
+                finder.allow_diagonal,
+                u32::MAX,
+            )
+        }
+    }
+
+    /// Create a search which is not bound to any requester or tilemap entity.
+    fn with_goals(
+        tilemap_ty: TilemapType,
+        origin: IVec2,
+        goals: &[IVec2],
+        allow_diagonal: bool,
+        max_cost: u32,
+    ) -> Self {
+        PathGrid {
+            requester: Entity::PLACEHOLDER,
+            tilemap: Entity::PLACEHOLDER,
+            allow_diagonal,
             tilemap_ty,
-            origin: finder.origin,
-            dest: finder.dest,
+            origin,
+            goals: goals.iter().copied().collect(),
             to_explore: BinaryHeap::new(),
             explored: HashSet::new(),
             all_nodes: HashMap::new(),
             steps: 0,
-            max_steps: finder.max_steps,
-            #[cfg(feature = "multi-threaded")]
-            path_tilemap,
-            #[cfg(not(feature = "multi-threaded"))]
-            max_steps_per_frame: finder.max_steps_per_frame,
+            max_steps: None,
+            max_cost,
+            reached: None,
             #[cfg(not(feature = "multi-threaded"))]
+            max_steps_per_frame: u32::MAX,
             is_done: false,
         }
     }
 
-    #[cfg(feature = "multi-threaded")]
-    pub fn get_or_register(&mut self, index: IVec2) -> Option<PathNode> {
-        if let Some(node) = self.all_nodes.get(&index) {
-            Some(node.clone())
-        } else {
-            self.path_tilemap.lock().unwrap().get(index).map(|tile| {
-                let new = PathNode::new(index, u32::MAX, self.dest, tile.cost);
-                self.all_nodes.insert(index, new);
-                new
-            })
-        }
+    /// The manhattan distance to the nearest goal.
+    fn heuristic(&self, index: IVec2) -> u32 {
+        self.goals
+            .iter()
+            .map(|goal| goal.manhattan_distance(index))
+            .min()
+            .unwrap_or_default()
     }
 
-    #[cfg(not(feature = "multi-threaded"))]
     pub fn get_or_register(
         &mut self,
         index: IVec2,
-        path_tilemaps: &PathTilemaps,
+        provider: &impl CostProvider,
     ) -> Option<PathNode> {
         if let Some(node) = self.all_nodes.get(&index) {
             Some(*node)
         } else {
-            path_tilemaps
-                .get(self.tilemap)
-                .unwrap()
-                .get(index)
-                .map(|tile| {
-                    let new = PathNode::new(index, u32::MAX, self.dest, tile.cost);
-                    self.all_nodes.insert(index, new);
-                    new
-                })
+            provider.cost(index).map(|cost_to_pass| {
+                let new = PathNode {
+                    index,
+                    parent: None,
+                    g_cost: u32::MAX,
+                    h_cost: self.heuristic(index),
+                    cost_to_pass,
+                };
+                self.all_nodes.insert(index, new);
+                new
+            })
         }
     }
 
-    #[cfg(feature = "multi-threaded")]
-    pub fn neighbours(&mut self, index: IVec2) -> Vec<PathNode> {
+    pub fn neighbours(&mut self, index: IVec2, provider: &impl CostProvider) -> Vec<PathNode> {
         index
             .neighbours(self.tilemap_ty, self.allow_diagonal)
             .into_iter()
-            .filter_map(|p| p.and_then(|p| self.get_or_register(p)))
+            .filter_map(|p| p.and_then(|p| self.get_or_register(p, provider)))
             .collect()
     }
 
-    #[cfg(not(feature = "multi-threaded"))]
-    pub fn neighbours(&mut self, index: IVec2, path_tilemaps: &PathTilemaps) -> Vec<PathNode> {
-        index
-            .neighbours(self.tilemap_ty, self.allow_diagonal)
-            .into_iter()
-            .filter_map(|p| p.and_then(|p| self.get_or_register(p, path_tilemaps)))
-            .collect()
-    }
+    /// Search the path, reading the costs from `provider`.
+    ///
+    /// In single-threaded mode, this returns after `max_steps_per_frame` steps
+    /// and continues from there next time, until `is_done`.
+    pub fn find_path(&mut self, provider: &impl CostProvider) {
+        if self.is_done {
+            return;
+        }
 
-    #[allow(unused)]
-    pub fn find_path(&mut self, path_tilemaps: Option<&PathTilemaps>) {
-        let origin = PathNode::new(self.origin, 0, self.dest, 0);
-        self.to_explore.push(origin.clone());
-        self.all_nodes.insert(self.origin, origin);
+        if self.all_nodes.is_empty() {
+            let origin = PathNode {
+                index: self.origin,
+                parent: None,
+                g_cost: 0,
+                h_cost: self.heuristic(self.origin),
+                cost_to_pass: 0,
+            };
+            self.to_explore.push(origin);
+            self.all_nodes.insert(self.origin, origin);
+        }
 
         #[cfg(not(feature = "multi-threaded"))]
         let mut steps_cur_frame = 0;
@@ -311,10 +391,9 @@ impl PathGrid {
         while !self.to_explore.is_empty() {
             if let Some(max_steps) = self.max_steps {
                 if self.steps > max_steps {
-                    return;
+                    break;
                 }
             }
-            self.steps += 1;
 
             #[cfg(not(feature = "multi-threaded"))]
             {
@@ -323,60 +402,106 @@ impl PathGrid {
                 }
                 steps_cur_frame += 1;
             }
+            self.steps += 1;
 
             let current = self.to_explore.pop().unwrap();
-            if current.index == self.dest {
-                break;
-            }
             if current.g_cost > self.all_nodes[&current.index].g_cost {
                 continue;
             }
+            if self.goals.contains(&current.index) {
+                self.reached = Some(current.index);
+                break;
+            }
 
-            #[cfg(feature = "multi-threaded")]
-            let neighbours = self.neighbours(current.index);
-            #[cfg(not(feature = "multi-threaded"))]
-            let neighbours = self.neighbours(current.index, path_tilemaps.unwrap());
+            for mut neighbour in self.neighbours(current.index, provider) {
+                let g_cost = current.g_cost.saturating_add(neighbour.cost_to_pass);
+                if g_cost > self.max_cost || g_cost >= neighbour.g_cost {
+                    continue;
+                }
 
-            for mut neighbour in neighbours {
-                neighbour.g_cost = current.g_cost + neighbour.cost_to_pass;
+                neighbour.g_cost = g_cost;
                 neighbour.parent = Some(current.index);
-
-                match self.all_nodes.entry(neighbour.index) {
-                    Entry::Occupied(mut e) => {
-                        if e.get().g_cost > neighbour.g_cost {
-                            e.insert(neighbour.clone());
-                            self.to_explore.push(neighbour);
-                        }
-                    }
-                    Entry::Vacant(e) => {
-                        e.insert(neighbour.clone());
-                        self.to_explore.push(neighbour);
-                    }
-                };
+                self.all_nodes.insert(neighbour.index, neighbour);
+                self.to_explore.push(neighbour);
             }
         }
 
-        #[cfg(not(feature = "multi-threaded"))]
-        {
-            self.is_done = true;
+        self.is_done = true;
+    }
+
+    /// Returns the tiles to step onto in order, which ends with the goal reached,
+    /// along with that goal. Or `None` if no goal is reached.
+    pub fn steps_to_goal(&self) -> Option<(Vec<IVec2>, IVec2)> {
+        let goal = self.reached?;
+        let mut path = vec![];
+        let mut current = goal;
+        while current != self.origin {
+            path.push(current);
+            current = self.all_nodes[&current].parent.unwrap();
         }
+        path.reverse();
+        Some((path, goal))
     }
 
     pub fn collect_path(&self) -> Path {
-        let mut path = Path {
-            path: vec![],
+        let mut path = self
+            .steps_to_goal()
+            .map(|(path, _)| path)
+            .unwrap_or_default();
+        path.reverse();
+        Path {
+            path,
             current_step: 0,
             tilemap: self.tilemap,
-        };
-        let mut current = self.all_nodes.get(&self.dest).unwrap();
-        while current.index != self.origin {
-            path.path.push(current.index);
-            current = self.all_nodes.get(&current.parent.unwrap()).unwrap();
         }
-        path
     }
 }
 
+/// Find the path with the lowest cost from `origin` to `dest`, reading the costs from `provider`.
+///
+/// Tiles whose total cost from `origin` exceeds `max_cost` are not explored, which
+/// keeps the search finite for providers that are walkable everywhere.
+///
+/// Returns the tiles to step onto in order, which ends with `dest`,
+/// or `None` if `dest` can't be reached.
+pub fn find_path(
+    provider: &impl CostProvider,
+    tilemap_ty: TilemapType,
+    origin: IVec2,
+    dest: IVec2,
+    allow_diagonal: bool,
+    max_cost: u32,
+) -> Option<Vec<IVec2>> {
+    find_path_multi(
+        provider,
+        tilemap_ty,
+        origin,
+        &[dest],
+        allow_diagonal,
+        max_cost,
+    )
+    .map(|(path, _)| path)
+}
+
+/// Find the path with the lowest cost from `origin` to the nearest of `goals`,
+/// in a single search which stops at whichever goal is reached first.
+///
+/// Returns the path like `find_path` along with the goal it ends with,
+/// or `None` if none of the goals can be reached within `max_cost`.
+pub fn find_path_multi(
+    provider: &impl CostProvider,
+    tilemap_ty: TilemapType,
+    origin: IVec2,
+    goals: &[IVec2],
+    allow_diagonal: bool,
+    max_cost: u32,
+) -> Option<(Vec<IVec2>, IVec2)> {
+    provider.cost(origin)?;
+    let mut grid = PathGrid::with_goals(tilemap_ty, origin, goals, allow_diagonal, max_cost);
+    grid.find_path(provider);
+    grid.steps_to_goal()
+}
+
 #[cfg(feature = "multi-threaded")]
 pub fn pathfinding_scheduler(
     mut queues_query: Query<(Entity, &TilemapType, &mut PathFindingQueue)>,
@@ -392,8 +517,8 @@ pub fn pathfinding_scheduler(
                 let ty = *ty;
                 let path_tilemap = path_tilemap.clone();
                 let task = thread_pool.spawn(async move {
-                    let mut grid = PathGrid::new(finder, requester, tilemap, ty, path_tilemap);
-                    grid.find_path(None);
+                    let mut grid = PathGrid::new(finder, requester, tilemap, ty);
+                    grid.find_path(&|index| path_tilemap.lock().unwrap().cost(index));
                     grid.collect_path()
                 });
                 tasks.push((requester, task));
@@ -428,7 +553,8 @@ pub fn path_finding_single_threaded(
         return;
     };
 
-    cur_task.find_path(Some(&path_tilemaps));
+    let path_tilemap = path_tilemaps.get(cur_task.tilemap).unwrap();
+    cur_task.find_path(path_tilemap);
     if cur_task.is_done {
         commands.entity(requester).insert(cur_task.collect_path());
         commands.entity(requester).remove::<PathGrid>();
@@ -450,3 +576,99 @@ pub fn path_assigner(mut commands: Commands, mut queues_query: Query<&mut PathFi
         });
     });
 }
+
+#[cfg(test)]
+mod test {
+    use crate::math::TileArea;
+
+    use super::*;
+
+    #[test]
+    fn test_smooth_path() {
+        let mut tilemap = PathTilemap::new();
+        tilemap.fill_path_rect(
+            TileArea::new(IVec2::ZERO, bevy::math::UVec2::splat(10)),
+            PathTile { cost: 1 },
+        );
+
+        let path = (0..5)
+            .flat_map(|i| [IVec2::new(i, i), IVec2::new(i + 1, i)])
+            .chain([IVec2::new(5, 5)])
+            .collect::<Vec<_>>();
+        let smoothed = smooth_path(&path, &tilemap, |_, tile| tile.is_none());
+        assert_eq!(smoothed, vec![IVec2::new(0, 0), IVec2::new(5, 5)]);
+
+        tilemap.remove(IVec2::new(1, 2));
+        let smoothed = smooth_path(&path, &tilemap, |_, tile| tile.is_none());
+        assert_eq!(smoothed.first(), Some(&IVec2::new(0, 0)));
+        assert_eq!(smoothed.last(), Some(&IVec2::new(5, 5)));
+        smoothed.windows(2).for_each(|seg| {
+            assert!(line_of_sight(seg[0], seg[1], |i| tilemap.get(i).is_none()));
+        });
+    }
+
+    #[test]
+    fn test_path_grid() {
+        let mut tilemap = PathTilemap::new();
+        tilemap.fill_path_rect(
+            TileArea::new(IVec2::ZERO, bevy::math::UVec2::new(5, 3)),
+            PathTile { cost: 1 },
+        );
+        // A wall in the middle with a gap at the top.
+        tilemap.remove(IVec2::new(2, 0));
+        tilemap.remove(IVec2::new(2, 1));
+
+        let origin = IVec2::new(0, 0);
+        let dest = IVec2::new(4, 0);
+        let new_grid = |max_steps| {
+            let finder = PathFinder {
+                origin,
+                dest,
+                allow_diagonal: false,
+                max_steps,
+                #[cfg(not(feature = "multi-threaded"))]
+                max_steps_per_frame: 2,
+                #[cfg(not(feature = "multi-threaded"))]
+                tilemap_ty: TilemapType::Square,
+            };
+            PathGrid::new(
+                finder,
+                Entity::PLACEHOLDER,
+                Entity::PLACEHOLDER,
+                TilemapType::Square,
+            )
+        };
+
+        let mut grid = new_grid(None);
+        let mut calls = 0;
+        while !grid.is_done {
+            grid.find_path(&tilemap);
+            calls += 1;
+        }
+        #[cfg(not(feature = "multi-threaded"))]
+        assert!(calls > 1);
+        #[cfg(feature = "multi-threaded")]
+        assert_eq!(calls, 1);
+
+        let expected =
+            find_path(&tilemap, TilemapType::Square, origin, dest, false, u32::MAX).unwrap();
+        assert_eq!(expected.len(), 8);
+        assert_eq!(grid.steps_to_goal(), Some((expected.clone(), dest)));
+        assert_eq!(
+            grid.collect_path()
+                .iter()
+                .rev()
+                .copied()
+                .collect::<Vec<_>>(),
+            expected
+        );
+
+        // Gives up once it runs out of steps.
+        let mut grid = new_grid(Some(3));
+        while !grid.is_done {
+            grid.find_path(&tilemap);
+        }
+        assert_eq!(grid.steps_to_goal(), None);
+        assert!(grid.collect_path().is_arrived());
+    }
+}
@@ -4,7 +4,7 @@ use std::{collections::VecDeque, path::Path};
 use bevy::{
     asset::Assets,
     ecs::{entity::Entity, system::ResMut},
-    log::warn,
+    log::{error, warn},
     math::IVec2,
     prelude::{Commands, Component, Query, UVec2},
     reflect::Reflect,
@@ -32,7 +32,7 @@ use crate::{
     DEFAULT_CHUNK_SIZE,
 };
 
-use super::pathfinding::PathTilemaps;
+use super::{pathfinding::PathTilemaps, ProcGenRng};
 
 #[cfg(feature = "physics")]
 use crate::tilemap::physics::{PhysicsTilemap, SerializablePhysicsSource};
@@ -103,6 +103,94 @@ impl WfcRules {
         res
     }
 
+    /// Create rules from hand-authored adjacencies.
+    ///
+    /// Each `(a, dir, b)` means element `b` can be placed at the `dir` side of element `a`.
+    /// The order of the directions is the same as `from_file`: up, right, left, down
+    /// (or up_right, right, down_right, up_left, left, down_left for hexagonal tilemaps).
+    /// The opposite adjacency will be added automatically.
+    ///
+    /// Returns `WfcError::TooManyElements` if there're more than 128 elements,
+    /// `WfcError::InvalidDirection` if a direction is not one of them
+    /// and `WfcError::InvalidElement` if an element is not less than `element_count`.
+    pub fn from_adjacencies(
+        ty: TilemapType,
+        element_count: usize,
+        adjacencies: &[(u8, usize, u8)],
+    ) -> Result<Self, WfcError> {
+        if element_count > 128 {
+            return Err(WfcError::TooManyElements { element_count });
+        }
+
+        let total_dirs = Self::total_dirs(ty);
+        let mut rule = vec![vec![0; total_dirs]; element_count];
+        for &(a, dir, b) in adjacencies {
+            if dir >= total_dirs {
+                return Err(WfcError::InvalidDirection { dir, total_dirs });
+            }
+            if let Some(element) = [a, b].into_iter().find(|e| *e as usize >= element_count) {
+                return Err(WfcError::InvalidElement {
+                    element,
+                    element_count,
+                });
+            }
+            rule[a as usize][dir] |= 1 << b;
+            rule[b as usize][total_dirs - dir - 1] |= 1 << a;
+        }
+
+        let res = Self(rule);
+        res.check_rules(ty);
+        Ok(res)
+    }
+
+    /// Derive the rules from a sample.
+    ///
+    /// The `sample` is a grid of element indices whose size is `size`, stored row by row
+    /// from the bottom (`y = 0`) to the top. Every two elements that are adjacent in the sample
+    /// will be allowed to be adjacent in the result. So make sure every element appears
+    /// in the sample at least once.
+    ///
+    /// Panics if the sample has more than 128 elements.
+    pub fn from_sample(ty: TilemapType, sample: &[u8], size: UVec2) -> Self {
+        assert_eq!(
+            sample.len(),
+            (size.x * size.y) as usize,
+            "Sample size mismatch!"
+        );
+
+        let element_count = sample.iter().max().map(|m| *m as usize + 1).unwrap_or(0);
+        let mut adjacencies = Vec::new();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let cur = sample[(y * size.x + x) as usize];
+                UVec2 { x, y }
+                    .neighbours(ty, false)
+                    .into_iter()
+                    .enumerate()
+                    .for_each(|(dir, nei)| {
+                        let Some(nei) = nei else {
+                            return;
+                        };
+                        if nei.x < size.x && nei.y < size.y {
+                            adjacencies.push((cur, dir, sample[(nei.y * size.x + nei.x) as usize]));
+                        }
+                    });
+            }
+        }
+
+        // The directions come from `neighbours` and the elements from the sample,
+        // so this only fails if there're more than 128 elements.
+        Self::from_adjacencies(ty, element_count, &adjacencies).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[inline]
+    fn total_dirs(ty: TilemapType) -> usize {
+        match ty {
+            TilemapType::Hexagonal(_) => 6,
+            _ => 4,
+        }
+    }
+
     /// Check if there are conflicts in the rules.
     pub fn check_rules(&self, ty: TilemapType) {
         let (total_dirs, dir_names) = match ty {
@@ -246,6 +334,45 @@ impl WfcSource {
     }
 }
 
+/// What to do when the retry budget runs out.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum OnFail {
+    /// Give up and return `WfcError::Contradiction`.
+    #[default]
+    Error,
+    /// Start over from an empty grid. This will happen at most `max_restarts` times.
+    Restart,
+    /// Stop and keep the elements that are already collapsed.
+    /// The rest of them will be `WfcData::EMPTY`.
+    LeaveEmpty,
+}
+
+/// Settings of the backtracking solver.
+///
+/// Every collapse is a decision point. When a contradiction occurs, the solver rolls back
+/// to the last decision point and bans the possibility that was picked, then tries again.
+#[derive(Debug, Clone, Copy, Reflect)]
+pub struct WfcConfig {
+    /// The max number of rollbacks before `fallback` is triggered.
+    pub max_retries: u32,
+    /// The max number of times to start over when `fallback` is `OnFail::Restart`.
+    pub max_restarts: u32,
+    /// Overrides the seed passed to `WfcRunner::new` if it's `Some`.
+    pub seed: Option<u64>,
+    pub fallback: OnFail,
+}
+
+impl Default for WfcConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 64,
+            max_restarts: 8,
+            seed: None,
+            fallback: OnFail::Error,
+        }
+    }
+}
+
 /// The order of the directions in config should be: up, right, left, down.
 #[derive(Component, Reflect)]
 pub struct WfcRunner {
@@ -254,10 +381,13 @@ pub struct WfcRunner {
     ty: TilemapType,
     sampler: Option<Box<dyn Fn(&WfcElement, &mut StdRng) -> u8 + Send + Sync>>,
     seed: Option<u64>,
+    #[reflect(ignore)]
+    rng: Option<StdRng>,
     area: TileArea,
     max_retrace_factor: u32,
     max_retrace_time: u32,
     max_history: usize,
+    config: Option<WfcConfig>,
 }
 
 impl WfcRunner {
@@ -270,9 +400,11 @@ impl WfcRunner {
             sampler: None,
             area,
             seed,
+            rng: None,
             max_retrace_factor: size.ilog10().clamp(2, 16),
             max_retrace_time: size.ilog10().clamp(2, 16) * 100,
             max_history: (size.ilog10().clamp(1, 8) * 20) as usize,
+            config: None,
         }
     }
 
@@ -345,12 +477,92 @@ impl WfcRunner {
         self
     }
 
+    /// Use the backtracking solver instead of the default retracing one.
+    ///
+    /// The retrace settings will be ignored, but `max_history` still limits
+    /// how far the solver can roll back.
+    pub fn with_config(mut self, config: WfcConfig) -> Self {
+        if config.seed.is_some() {
+            self.seed = config.seed;
+        }
+        self.config = Some(config);
+        self
+    }
+
+    /// Use the rng instead of the seed.
+    ///
+    /// If neither is given, the runner forks `ProcGenRng` when it's inserted as a component,
+    /// or uses a random seed when it's run immediately.
+    pub fn with_rng(mut self, rng: StdRng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
     /// Get the rule for wfc.
     pub fn get_rule(&self) -> &Vec<Vec<u128>> {
         &self.conn_rules
     }
+
+    fn fork_rng_if_unseeded(&mut self, rng: &mut ProcGenRng) {
+        if self.seed.is_none() && self.rng.is_none() {
+            self.rng = Some(rng.fork());
+        }
+    }
+
+    /// Run the algorithm immediately on the current thread
+    /// instead of inserting the runner as a component.
+    pub fn run(mut self) -> Result<WfcData, WfcError> {
+        WfcGrid::from_runner(&mut self).run()
+    }
+}
+
+/// The reason why the wave function collapse failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WfcError {
+    /// Some elements ran out of possibilities and retracing couldn't resolve it.
+    Contradiction,
+    /// An adjacency passed to `WfcRules::from_adjacencies` has a direction
+    /// that is not less than the number of directions of the tilemap type.
+    InvalidDirection { dir: usize, total_dirs: usize },
+    /// `WfcRules::from_adjacencies` supports up to 128 elements.
+    TooManyElements { element_count: usize },
+    /// An adjacency passed to `WfcRules::from_adjacencies` has an element
+    /// that is not less than the number of elements.
+    InvalidElement { element: u8, element_count: usize },
+}
+
+impl std::fmt::Display for WfcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WfcError::Contradiction => write!(
+                f,
+                "Contradiction occurred and couldn't be resolved! \
+                Try increasing the retrace settings/retries or check your rules."
+            ),
+            WfcError::InvalidDirection { dir, total_dirs } => write!(
+                f,
+                "Invalid direction {} in the adjacencies! There're only {} directions.",
+                dir, total_dirs
+            ),
+            WfcError::TooManyElements { element_count } => write!(
+                f,
+                "Too many elements! Only 128 elements are supported but got {}.",
+                element_count
+            ),
+            WfcError::InvalidElement {
+                element,
+                element_count,
+            } => write!(
+                f,
+                "Invalid element {} in the adjacencies! There're only {} elements.",
+                element, element_count
+            ),
+        }
+    }
 }
 
+impl std::error::Error for WfcError {}
+
 #[derive(Component, Debug, Clone, Reflect)]
 pub struct WfcData {
     pub(crate) data: Vec<u8>,
@@ -358,17 +570,22 @@ pub struct WfcData {
 }
 
 impl WfcData {
+    /// The element that is left uncollapsed when using `OnFail::LeaveEmpty`.
+    pub const EMPTY: u8 = u8::MAX;
+
     pub(crate) fn new(area: TileArea) -> Self {
         Self {
-            data: vec![0; area.size()],
+            data: vec![Self::EMPTY; area.size()],
             area,
         }
     }
 
+    /// Get the element at the index. Returns `None` if it's out of bounds or empty.
     pub fn get(&self, index: UVec2) -> Option<u8> {
         self.data
             .get((index.y * self.area.extent.x + index.x) as usize)
             .cloned()
+            .filter(|e| *e != Self::EMPTY)
     }
 
     pub(crate) fn set(&mut self, index: UVec2, value: u8) {
@@ -389,14 +606,14 @@ impl WfcData {
         if flip {
             for y in (0..self.area.extent.y).rev() {
                 for x in 0..self.area.extent.x {
-                    print!("{:3} ", self.get(UVec2 { x, y }).unwrap());
+                    print!("{:3} ", self.get(UVec2 { x, y }).map_or(-1, |e| e as i32));
                 }
                 println!();
             }
         } else {
             for y in 0..self.area.extent.y {
                 for x in 0..self.area.extent.x {
-                    print!("{:3} ", self.get(UVec2 { x, y }).unwrap());
+                    print!("{:3} ", self.get(UVec2 { x, y }).map_or(-1, |e| e as i32));
                 }
                 println!();
             }
@@ -429,6 +646,8 @@ pub struct WfcHistory {
     uncollapsed: HashSet<(u8, UVec2)>,
     elements: HashMap<UVec2, WfcElement>,
     remaining: usize,
+    /// The element collapsed right after this snapshot and the possibility picked.
+    decision: Option<(UVec2, u8)>,
 }
 
 #[derive(Component)]
@@ -448,29 +667,17 @@ pub struct WfcGrid {
     max_retrace_time: u32,
     retraced_time: u32,
     sampler: Option<Box<dyn Fn(&WfcElement, &mut StdRng) -> u8 + Send + Sync>>,
+    config: Option<WfcConfig>,
+    retries: u32,
+    restarts: u32,
+    failed: bool,
+    stopped: bool,
 }
 
 impl WfcGrid {
     pub fn from_runner(runner: &mut WfcRunner) -> Self {
-        let mut uncollapsed = HashSet::new();
-        let mut elements = HashMap::new();
-        let max_psbs = runner.conn_rules.len() as u8;
-
-        for y in 0..runner.area.extent.y {
-            for x in 0..runner.area.extent.x {
-                elements.insert(
-                    UVec2 { x, y },
-                    WfcElement {
-                        index: UVec2 { x, y },
-                        element_index: None,
-                        collapsed: false,
-                        psbs: (!0) >> (128 - max_psbs),
-                    },
-                );
-
-                uncollapsed.insert((max_psbs, UVec2 { x, y }));
-            }
-        }
+        let (uncollapsed, elements) =
+            Self::init_elements(runner.area, runner.conn_rules.len() as u8);
 
         WfcGrid {
             mode: runner.mode.clone(),
@@ -481,9 +688,10 @@ impl WfcGrid {
             history: vec![None; runner.max_history],
             cur_hist: 0,
             ty: runner.ty,
-            rng: match runner.seed {
-                Some(seed) => StdRng::seed_from_u64(seed),
-                None => StdRng::from_entropy(),
+            rng: match (runner.rng.take(), runner.seed) {
+                (Some(rng), _) => rng,
+                (None, Some(seed)) => StdRng::seed_from_u64(seed),
+                (None, None) => StdRng::from_entropy(),
             },
             remaining: runner.area.size(),
             retrace_strength: 1,
@@ -491,14 +699,61 @@ impl WfcGrid {
             max_retrace_time: runner.max_retrace_time,
             retraced_time: 0,
             sampler: runner.sampler.take(),
+            config: runner.config,
+            retries: 0,
+            restarts: 0,
+            failed: false,
+            stopped: false,
         }
     }
 
+    fn init_elements(
+        area: TileArea,
+        max_psbs: u8,
+    ) -> (HashSet<(u8, UVec2)>, HashMap<UVec2, WfcElement>) {
+        let mut uncollapsed = HashSet::new();
+        let mut elements = HashMap::new();
+
+        for y in 0..area.extent.y {
+            for x in 0..area.extent.x {
+                elements.insert(
+                    UVec2 { x, y },
+                    WfcElement {
+                        index: UVec2 { x, y },
+                        element_index: None,
+                        collapsed: false,
+                        psbs: (!0) >> (128 - max_psbs),
+                    },
+                );
+
+                uncollapsed.insert((max_psbs, UVec2 { x, y }));
+            }
+        }
+
+        (uncollapsed, elements)
+    }
+
+    /// Clear the grid and start over. The rng won't be reset
+    /// so the result will be different.
+    pub fn reset(&mut self) {
+        let (uncollapsed, elements) = Self::init_elements(self.area, self.conn_rules.len() as u8);
+        self.uncollapsed = uncollapsed;
+        self.elements = elements;
+        self.remaining = self.area.size();
+        self.history.iter_mut().for_each(|h| *h = None);
+        self.cur_hist = 0;
+        self.retrace_strength = 1;
+        self.retraced_time = 0;
+        self.retries = 0;
+    }
+
     pub fn collapse(&mut self) {
-        self.history[self.cur_hist] = Some(WfcHistory {
+        let hist_index = self.cur_hist;
+        self.history[hist_index] = Some(WfcHistory {
             uncollapsed: self.uncollapsed.clone(),
             elements: self.elements.clone(),
             remaining: self.remaining,
+            decision: None,
         });
         self.cur_hist = (self.cur_hist + 1) % self.history.len();
 
@@ -533,10 +788,25 @@ impl WfcGrid {
         self.retrace_strength *= self.max_retrace_factor;
 
         let index = elem.index;
+        if let Some(hist) = &mut self.history[hist_index] {
+            hist.decision = Some((index, psb));
+        }
         self.constrain(index);
     }
 
     pub fn constrain(&mut self, center: UVec2) {
+        if self.propagate(center) {
+            self.retrace_strength = 1;
+        } else if self.config.is_some() {
+            self.backtrack();
+        } else {
+            self.retrace();
+        }
+    }
+
+    /// Spread the possibilities of the center to its neighbours.
+    /// Returns `false` if a contradiction occurred.
+    fn propagate(&mut self, center: UVec2) -> bool {
         let mut queue = VecDeque::from([center]);
         let mut spreaded = HashSet::from([center]);
 
@@ -567,8 +837,7 @@ impl WfcGrid {
                 nei_elem.psbs &= psb;
 
                 if nei_elem.psbs.count_ones() == 0 {
-                    self.retrace();
-                    return;
+                    return false;
                 }
 
                 if nei_elem.psbs != psb_rec {
@@ -579,7 +848,7 @@ impl WfcGrid {
             }
         }
 
-        self.retrace_strength = 1;
+        true
     }
 
     pub fn update_entropy(&mut self, old: u8, new: u8, target: UVec2) {
@@ -625,6 +894,70 @@ impl WfcGrid {
         self.retraced_time += self.retrace_strength;
     }
 
+    /// Roll back to the last decision point and ban the possibility picked there.
+    /// If there's no possibility left, keep rolling back.
+    pub fn backtrack(&mut self) {
+        let Some(config) = self.config else {
+            return;
+        };
+        let hist_len = self.history.len();
+
+        loop {
+            if self.retries >= config.max_retries {
+                self.fail(config);
+                return;
+            }
+            self.retries += 1;
+
+            let last = (self.cur_hist + hist_len - 1) % hist_len;
+            let Some(hist) = self.history[last].take() else {
+                // No more history to roll back to.
+                self.fail(config);
+                return;
+            };
+            self.cur_hist = last;
+            self.remaining = hist.remaining;
+            self.uncollapsed = hist.uncollapsed;
+            self.elements = hist.elements;
+
+            let Some((index, psb)) = hist.decision else {
+                continue;
+            };
+            let elem = self.elements.get_mut(&index).unwrap();
+            let old = elem.psbs.count_ones() as u8;
+            elem.psbs &= !(1 << psb);
+            let new = elem.psbs.count_ones() as u8;
+            if new == 0 {
+                continue;
+            }
+
+            self.update_entropy(old, new, index);
+            if self.propagate(index) {
+                return;
+            }
+        }
+    }
+
+    fn fail(&mut self, config: WfcConfig) {
+        match config.fallback {
+            OnFail::Error => self.failed = true,
+            OnFail::Restart => {
+                if self.restarts < config.max_restarts {
+                    self.restarts += 1;
+                    self.reset();
+                } else {
+                    self.failed = true;
+                }
+            }
+            OnFail::LeaveEmpty => self.stopped = true,
+        }
+    }
+
+    #[inline]
+    fn is_failed(&self) -> bool {
+        self.failed || (self.config.is_none() && self.retraced_time >= self.max_retrace_time)
+    }
+
     pub fn get_min(&mut self) -> UVec2 {
         let mut min_entropy = u8::MAX;
         let mut candidates = Vec::with_capacity(self.remaining);
@@ -640,37 +973,44 @@ impl WfcGrid {
         candidates[self.rng.sample(Uniform::new(0, candidates.len()))]
     }
 
-    pub fn generate_data(&mut self) -> Option<WfcData> {
-        if self.retraced_time >= self.max_retrace_time {
-            return None;
+    /// Collapse all the elements.
+    pub fn run(&mut self) -> Result<WfcData, WfcError> {
+        while self.remaining > 0 && !self.stopped && !self.is_failed() {
+            self.collapse();
+        }
+        self.generate_data()
+    }
+
+    pub fn generate_data(&mut self) -> Result<WfcData, WfcError> {
+        if self.is_failed() {
+            return Err(WfcError::Contradiction);
         }
 
         let mut data = WfcData::new(self.area);
         self.elements.drain().for_each(|(i, e)| {
-            data.set(i, e.element_index.unwrap());
+            if e.collapsed {
+                data.set(i, e.element_index.unwrap());
+            }
         });
-        Some(data)
+        Ok(data)
     }
 }
 
 #[cfg(feature = "multi-threaded")]
 #[derive(Component)]
-pub struct WfcTask(Task<Option<WfcData>>);
+pub struct WfcTask(Task<Result<WfcData, WfcError>>);
 
 #[cfg(feature = "multi-threaded")]
 pub fn wave_function_collapse(
     mut commands: Commands,
     mut runner_query: Query<(Entity, &mut WfcRunner), Without<WfcTask>>,
+    mut rng: ResMut<ProcGenRng>,
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
     runner_query.iter_mut().for_each(|(entity, mut runner)| {
+        runner.fork_rng_if_unseeded(&mut rng);
         let mut wfc_grid = WfcGrid::from_runner(&mut runner);
-        let task = thread_pool.spawn(async move {
-            while wfc_grid.remaining > 0 && wfc_grid.retraced_time < wfc_grid.max_retrace_time {
-                wfc_grid.collapse();
-            }
-            wfc_grid.generate_data()
-        });
+        let task = thread_pool.spawn(async move { wfc_grid.run() });
 
         commands
             .entity(entity)
@@ -683,18 +1023,18 @@ pub fn wave_function_collapse(
 pub fn wave_function_collapse_single_threaded(
     mut commands: Commands,
     mut runner_query: Query<(Entity, &mut WfcRunner)>,
+    mut rng: ResMut<ProcGenRng>,
 ) {
     runner_query.iter_mut().for_each(|(entity, mut runner)| {
-        let mut wfc_grid = WfcGrid::from_runner(&mut runner);
-
-        while wfc_grid.remaining > 0 && wfc_grid.retraced_time < wfc_grid.max_retrace_time {
-            wfc_grid.collapse();
-        }
-        let data = wfc_grid.generate_data();
+        runner.fork_rng_if_unseeded(&mut rng);
+        let data = WfcGrid::from_runner(&mut runner).run();
 
         commands.entity(entity).remove::<WfcRunner>();
-        if let Some(data) = data {
-            commands.entity(entity).insert(data);
+        match data {
+            Ok(data) => {
+                commands.entity(entity).insert(data);
+            }
+            Err(err) => error!("Failed to collapse {:?}: {}", entity, err),
         }
     });
 }
@@ -705,8 +1045,11 @@ pub fn wfc_data_assigner(mut commands: Commands, mut tasks_query: Query<(Entity,
         if let Some(data) = bevy::tasks::block_on(futures_lite::future::poll_once(&mut task.0)) {
             let mut entity = commands.entity(entity);
             entity.remove::<WfcTask>();
-            if let Some(data) = data {
-                entity.insert(data);
+            match data {
+                Ok(data) => {
+                    entity.insert(data);
+                }
+                Err(err) => error!("Failed to collapse {:?}: {}", entity.id(), err),
             }
         }
     });
@@ -736,6 +1079,9 @@ pub fn wfc_applier(
                 });
 
                 for (i, e) in wfc_data.data.iter().enumerate() {
+                    if *e == WfcData::EMPTY {
+                        continue;
+                    }
                     let ser_tile = tiles.get(*e as usize).unwrap();
                     tilemap.set(
                         &mut commands,
@@ -755,6 +1101,9 @@ pub fn wfc_applier(
                 });
 
                 wfc_data.data.iter().enumerate().for_each(|(i, e)| {
+                    if *e == WfcData::EMPTY {
+                        return;
+                    }
                     let p = &patterns.get(*e as usize);
                     let origin =
                         (wfc_data.elem_idx_to_grid(i) + wfc_data.area.origin) * p.tiles.aabb.size();
@@ -795,6 +1144,9 @@ pub fn wfc_applier(
                 }
 
                 wfc_data.data.iter().enumerate().for_each(|(i, e)| {
+                    if *e == WfcData::EMPTY {
+                        return;
+                    }
                     let slice = layered_patterns.get_element(*e as usize);
 
                     slice.element.iter().for_each(|(layer, texture)| {
@@ -898,6 +1250,9 @@ pub fn ldtk_wfc_helper(
                 match mode {
                     LdtkWfcMode::SingleMap => {
                         data.data.iter().enumerate().for_each(|(i, e)| {
+                            if *e == WfcData::EMPTY {
+                                return;
+                            }
                             let mut bg = patterns.backgrounds[*e as usize].clone().unwrap();
                             let ptn_idx = data.elem_idx_to_grid(i);
                             let ptn_render_size = bg.sprite.custom_size.unwrap();
@@ -925,3 +1280,184 @@ pub fn ldtk_wfc_helper(
             _ => {}
         });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wfc_from_sample() {
+        // 0: wall, 1: floor, 2: void
+        #[rustfmt::skip]
+        let sample = [
+            2, 2, 2, 2, 2, 2,
+            2, 0, 0, 0, 0, 2,
+            2, 0, 1, 1, 0, 2,
+            2, 0, 1, 1, 0, 2,
+            2, 0, 0, 0, 0, 2,
+            2, 2, 2, 2, 2, 2,
+        ];
+        let rules = WfcRules::from_sample(TilemapType::Square, &sample, UVec2::splat(6));
+        assert_eq!(rules.0.len(), 3);
+        // Floors are never next to the void.
+        rules.0[1].iter().for_each(|r| assert_eq!(r & (1 << 2), 0));
+        rules.0[2].iter().for_each(|r| assert_eq!(r & (1 << 1), 0));
+
+        let area = TileArea::new(IVec2::ZERO, UVec2::splat(12));
+        let run = |seed| {
+            WfcRunner::new(
+                TilemapType::Square,
+                WfcRules::from_sample(TilemapType::Square, &sample, UVec2::splat(6)),
+                area,
+                Some(seed),
+            )
+            .run()
+            .unwrap()
+        };
+
+        let data = run(42);
+        assert_eq!(data.data, run(42).data);
+
+        for y in 0..area.extent.y {
+            for x in 0..area.extent.x {
+                let cur = data.get(UVec2 { x, y }).unwrap();
+                for (dir, nei) in UVec2::new(x, y)
+                    .neighbours(TilemapType::Square, false)
+                    .into_iter()
+                    .enumerate()
+                {
+                    let Some(nei) = nei.and_then(|n| {
+                        (n.x < area.extent.x && n.y < area.extent.y).then(|| data.get(n).unwrap())
+                    }) else {
+                        continue;
+                    };
+                    assert_ne!(rules.0[cur as usize][dir] & (1 << nei), 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wfc_contradiction() {
+        // An element that can't be next to anything.
+        let rules = WfcRules::from_adjacencies(TilemapType::Square, 1, &[]).unwrap();
+        let result = WfcRunner::new(
+            TilemapType::Square,
+            rules,
+            TileArea::new(IVec2::ZERO, UVec2::new(2, 1)),
+            Some(0),
+        )
+        .run();
+        assert_eq!(result.unwrap_err(), WfcError::Contradiction);
+    }
+
+    #[test]
+    fn test_wfc_backtracking() {
+        // Each row can only be 0, 1, 2 from left to right.
+        let adjacencies = [(0, 1, 1), (1, 1, 2), (0, 0, 0), (1, 0, 1), (2, 0, 2)];
+        let area = TileArea::new(IVec2::ZERO, UVec2::new(3, 2));
+
+        for seed in 0..8 {
+            let data = WfcRunner::new(
+                TilemapType::Square,
+                WfcRules::from_adjacencies(TilemapType::Square, 3, &adjacencies).unwrap(),
+                area,
+                None,
+            )
+            .with_config(WfcConfig {
+                max_retries: 64,
+                seed: Some(seed),
+                fallback: OnFail::Error,
+                ..Default::default()
+            })
+            .run()
+            .unwrap();
+            assert_eq!(data.data, vec![0, 1, 2, 0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn test_wfc_proc_gen_rng() {
+        // Anything goes, so the result only depends on the rng.
+        let adjacencies = [
+            (0, 0, 0),
+            (0, 0, 1),
+            (1, 0, 0),
+            (1, 0, 1),
+            (0, 1, 0),
+            (0, 1, 1),
+            (1, 1, 0),
+            (1, 1, 1),
+        ];
+        let run = |rng: &mut ProcGenRng| {
+            WfcRunner::new(
+                TilemapType::Square,
+                WfcRules::from_adjacencies(TilemapType::Square, 2, &adjacencies).unwrap(),
+                TileArea::new(IVec2::ZERO, UVec2::splat(8)),
+                None,
+            )
+            .with_rng(rng.fork())
+            .run()
+            .unwrap()
+            .data
+        };
+
+        let mut a = ProcGenRng::from_seed(7);
+        let mut b = ProcGenRng::from_seed(7);
+        for _ in 0..3 {
+            assert_eq!(run(&mut a), run(&mut b));
+        }
+    }
+
+    #[test]
+    fn test_wfc_over_constrained() {
+        let run = |fallback| {
+            WfcRunner::new(
+                TilemapType::Square,
+                WfcRules::from_adjacencies(TilemapType::Square, 1, &[]).unwrap(),
+                TileArea::new(IVec2::ZERO, UVec2::new(2, 1)),
+                None,
+            )
+            .with_config(WfcConfig {
+                max_retries: 4,
+                max_restarts: 2,
+                seed: Some(0),
+                fallback,
+            })
+            .run()
+        };
+
+        assert_eq!(run(OnFail::Error).unwrap_err(), WfcError::Contradiction);
+        assert_eq!(run(OnFail::Restart).unwrap_err(), WfcError::Contradiction);
+
+        let data = run(OnFail::LeaveEmpty).unwrap();
+        assert_eq!(data.get(UVec2::ZERO), None);
+        assert_eq!(data.get(UVec2::X), None);
+    }
+
+    #[test]
+    fn test_invalid_direction() {
+        assert_eq!(
+            WfcRules::from_adjacencies(TilemapType::Square, 2, &[(0, 1, 1), (0, 4, 1)]).err(),
+            Some(WfcError::InvalidDirection {
+                dir: 4,
+                total_dirs: 4
+            })
+        );
+        assert!(
+            WfcRules::from_adjacencies(TilemapType::Hexagonal(4), 2, &[(0, 4, 1), (1, 5, 0)])
+                .is_ok()
+        );
+        assert_eq!(
+            WfcRules::from_adjacencies(TilemapType::Square, 2, &[(0, 1, 2)]).err(),
+            Some(WfcError::InvalidElement {
+                element: 2,
+                element_count: 2
+            })
+        );
+        assert_eq!(
+            WfcRules::from_adjacencies(TilemapType::Square, 129, &[]).err(),
+            Some(WfcError::TooManyElements { element_count: 129 })
+        );
+    }
+}
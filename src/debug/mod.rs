@@ -4,7 +4,10 @@ use bevy::{
     math::Vec2,
 };
 
+use self::scrubber::AnimationTimeScrubber;
+
 pub mod drawing;
+pub mod scrubber;
 
 pub struct EntiTilesDebugPlugin;
 
@@ -26,6 +29,9 @@ impl Plugin for EntiTilesDebugPlugin {
 
         #[cfg(feature = "debug")]
         app.init_resource::<CameraAabbScale>();
+
+        app.init_resource::<AnimationTimeScrubber>()
+            .register_type::<AnimationTimeScrubber>();
     }
 }
 
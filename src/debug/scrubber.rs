@@ -0,0 +1,123 @@
+use bevy::{
+    ecs::system::{Res, Resource, SystemParam},
+    reflect::Reflect,
+    time::Time,
+};
+
+/// Overrides the time used by tile animations, so animations can be paused
+/// and inspected frame by frame.
+///
+/// The animations use the real elapsed time unless this is paused.
+#[derive(Resource, Default, Debug, Clone, Copy, Reflect)]
+pub struct AnimationTimeScrubber {
+    pub(crate) time: Option<f32>,
+}
+
+impl AnimationTimeScrubber {
+    /// Pause the animations at `now`, which is usually `Time::elapsed_seconds()`.
+    /// Does nothing if already paused.
+    #[inline]
+    pub fn pause(&mut self, now: f32) {
+        self.time.get_or_insert(now);
+    }
+
+    /// Continue using the real elapsed time.
+    #[inline]
+    pub fn resume(&mut self) {
+        self.time = None;
+    }
+
+    /// Pause the animations at the given time.
+    #[inline]
+    pub fn set(&mut self, time: f32) {
+        self.time = Some(time);
+    }
+
+    /// Move the paused time forward, or backward if `delta` is negative.
+    /// Does nothing if not paused.
+    #[inline]
+    pub fn step(&mut self, delta: f32) {
+        if let Some(time) = &mut self.time {
+            *time = (*time + delta).max(0.);
+        }
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.time.is_some()
+    }
+
+    /// The time used by animations given the real elapsed time.
+    #[inline]
+    pub fn time(&self, elapsed: f32) -> f32 {
+        self.time.unwrap_or(elapsed)
+    }
+}
+
+/// The time tile animations are rendered at, which is the scrubbed time
+/// if an [`AnimationTimeScrubber`] is present.
+#[derive(SystemParam)]
+pub struct AnimationTime<'w> {
+    time: Res<'w, Time>,
+    scrubber: Option<Res<'w, AnimationTimeScrubber>>,
+}
+
+impl<'w> AnimationTime<'w> {
+    #[inline]
+    pub fn elapsed_seconds(&self) -> f32 {
+        let elapsed = self.time.elapsed_seconds();
+        self.scrubber.as_ref().map_or(elapsed, |s| s.time(elapsed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::ecs::{system::RunSystemOnce, world::World};
+
+    use super::*;
+
+    #[test]
+    fn test_scrubber() {
+        let mut scrubber = AnimationTimeScrubber::default();
+        assert_eq!(scrubber.time(3.5), 3.5);
+
+        scrubber.set(1.25);
+        assert_eq!(scrubber.time(3.5), 1.25);
+        assert_eq!(scrubber.time(100.), 1.25);
+
+        scrubber.step(0.5);
+        assert_eq!(scrubber.time(100.), 1.75);
+        scrubber.pause(10.);
+        assert_eq!(scrubber.time(100.), 1.75);
+
+        scrubber.resume();
+        assert!(!scrubber.is_paused());
+        scrubber.step(0.5);
+        assert_eq!(scrubber.time(100.), 100.);
+        scrubber.pause(10.);
+        assert_eq!(scrubber.time(100.), 10.);
+    }
+
+    #[test]
+    fn test_animation_time() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(3.5));
+        world.insert_resource(time);
+        let elapsed =
+            |world: &mut World| world.run_system_once(|time: AnimationTime| time.elapsed_seconds());
+
+        assert_eq!(elapsed(&mut world), 3.5);
+
+        world.insert_resource(AnimationTimeScrubber::default());
+        assert_eq!(elapsed(&mut world), 3.5);
+
+        world.resource_mut::<AnimationTimeScrubber>().set(1.25);
+        assert_eq!(elapsed(&mut world), 1.25);
+
+        world.remove_resource::<AnimationTimeScrubber>();
+        assert_eq!(elapsed(&mut world), 3.5);
+    }
+}
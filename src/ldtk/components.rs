@@ -1,11 +1,18 @@
 use bevy::{
     ecs::{component::Component, entity::Entity, system::Commands},
-    math::Vec2,
+    math::{IVec2, Vec2},
     reflect::Reflect,
     utils::HashMap,
 };
+use serde::{Deserialize, Serialize};
 
-use super::resources::LdtkGlobalEntityRegistry;
+use super::{
+    json::{
+        field::{FieldInstance, FieldValue},
+        EntityRef, LdtkColor,
+    },
+    resources::LdtkGlobalEntityRegistry,
+};
 
 #[derive(Reflect, Default, Clone, Copy, PartialEq, Eq)]
 pub enum LdtkLoaderMode {
@@ -53,20 +60,199 @@ impl LdtkLoadedLevel {
 #[derive(Component, Debug, Clone)]
 pub struct LdtkTempTransform {
     pub level_translation: Vec2,
+    /// See `LdtkCoordConfig::y_up`.
+    pub y_up: bool,
     pub z_index: f32,
 }
 
 #[derive(Component, Reflect)]
 pub struct GlobalEntity;
 
-#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone)]
+/// Added to entities whose identifier is not registered
+/// when using `UnknownEntityPolicy::SpawnPlaceholder`.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct LdtkUnknownEntity {
+    pub identifier: String,
+}
+
+/// All the raw field instances of an entity spawned from LDtk.
+///
+/// Useful for debugging and generic systems that don't know the `LdtkEntity` types.
+/// The getters return `None` if the field doesn't exist, is null or has another type.
+#[derive(Component, Debug, Default, Clone, Reflect, Serialize, Deserialize)]
+pub struct LdtkFields(pub HashMap<String, FieldInstance>);
+
+impl LdtkFields {
+    #[inline]
+    pub fn get(&self, identifier: &str) -> Option<&FieldInstance> {
+        self.0.get(identifier)
+    }
+
+    #[inline]
+    pub fn get_value(&self, identifier: &str) -> Option<&FieldValue> {
+        self.0.get(identifier).and_then(|f| f.value.as_ref())
+    }
+
+    pub fn get_int(&self, identifier: &str) -> Option<i32> {
+        match self.get_value(identifier)? {
+            FieldValue::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_float(&self, identifier: &str) -> Option<f32> {
+        match self.get_value(identifier)? {
+            FieldValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, identifier: &str) -> Option<bool> {
+        match self.get_value(identifier)? {
+            FieldValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_str(&self, identifier: &str) -> Option<&str> {
+        match self.get_value(identifier)? {
+            FieldValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a local or extern enum field.
+    pub fn get_enum(&self, identifier: &str) -> Option<&str> {
+        match self.get_value(identifier)? {
+            FieldValue::LocalEnum((_, v)) | FieldValue::ExternEnum((_, v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn get_color(&self, identifier: &str) -> Option<LdtkColor> {
+        match self.get_value(identifier)? {
+            FieldValue::Color(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_point(&self, identifier: &str) -> Option<IVec2> {
+        match self.get_value(identifier)? {
+            FieldValue::Point(p) => Some(IVec2 { x: p.cx, y: p.cy }),
+            _ => None,
+        }
+    }
+
+    pub fn get_entity_ref(&self, identifier: &str) -> Option<&EntityRef> {
+        match self.get_value(identifier)? {
+            FieldValue::EntityRef(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// The stable iid of an entity spawned from LDtk.
+///
+/// Save this along with the entity to keep the `EntityRef`s pointing to it working:
+/// the entities spawned with it again can be found by `LdtkIidMap::resolve()`,
+/// and are registered in `LdtkGlobalEntityRegistry` as well if they are `GlobalEntity`.
+#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct EntityIid(pub String);
 
-#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone)]
+#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct LayerIid(pub String);
 
-#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone)]
+#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct LevelIid(pub String);
 
-#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone)]
+#[derive(Component, Debug, Reflect, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct WorldIid(pub String);
+
+#[cfg(test)]
+mod test {
+    use crate::ldtk::json::GridPoint;
+
+    use super::*;
+
+    fn field(identifier: &str, value: Option<FieldValue>) -> (String, FieldInstance) {
+        (
+            identifier.to_string(),
+            FieldInstance {
+                def_uid: 0,
+                identifier: identifier.to_string(),
+                tile: None,
+                ty: String::new(),
+                value,
+            },
+        )
+    }
+
+    #[test]
+    fn test_ldtk_fields() {
+        let fields = LdtkFields(HashMap::from_iter([
+            field("HP", Some(FieldValue::Integer(10))),
+            field("Speed", Some(FieldValue::Float(1.5))),
+            field("Hostile", Some(FieldValue::Bool(true))),
+            field("Name", Some(FieldValue::String("Bob".to_string()))),
+            field(
+                "Kind",
+                Some(FieldValue::LocalEnum((
+                    "Kind".to_string(),
+                    "Slime".to_string(),
+                ))),
+            ),
+            field(
+                "Tint",
+                Some(FieldValue::Color(LdtkColor {
+                    r: 1.,
+                    g: 0.,
+                    b: 0.,
+                    a: 1.,
+                })),
+            ),
+            field(
+                "Target",
+                Some(FieldValue::Point(GridPoint { cx: 3, cy: 4 })),
+            ),
+            field(
+                "Friend",
+                Some(FieldValue::EntityRef(EntityRef {
+                    entity_iid: "a".to_string(),
+                    layer_iid: "b".to_string(),
+                    level_iid: "c".to_string(),
+                    world_iid: "d".to_string(),
+                })),
+            ),
+            field("Empty", None),
+        ]));
+
+        assert_eq!(fields.get_int("HP"), Some(10));
+        assert_eq!(fields.get_float("Speed"), Some(1.5));
+        assert_eq!(fields.get_bool("Hostile"), Some(true));
+        assert_eq!(fields.get_str("Name"), Some("Bob"));
+        assert_eq!(fields.get_enum("Kind"), Some("Slime"));
+        assert_eq!(fields.get_color("Tint").map(|c| c.r), Some(1.));
+        assert_eq!(fields.get_point("Target"), Some(IVec2::new(3, 4)));
+        assert_eq!(
+            fields
+                .get_entity_ref("Friend")
+                .map(|r| r.entity_iid.as_str()),
+            Some("a")
+        );
+
+        // Type mismatches.
+        assert_eq!(fields.get_int("Speed"), None);
+        assert_eq!(fields.get_float("HP"), None);
+        assert_eq!(fields.get_bool("Name"), None);
+        assert_eq!(fields.get_str("Kind"), None);
+        assert_eq!(fields.get_enum("Name"), None);
+        assert!(fields.get_color("HP").is_none());
+        assert_eq!(fields.get_point("Friend"), None);
+        assert!(fields.get_entity_ref("Target").is_none());
+
+        // Null and missing fields.
+        assert!(fields.get("Empty").is_some());
+        assert_eq!(fields.get_int("Empty"), None);
+        assert_eq!(fields.get_int("Missing"), None);
+    }
+}
@@ -1,9 +1,15 @@
-use bevy::{ecs::event::Event, reflect::Reflect};
+use bevy::{
+    ecs::{entity::Entity, event::Event},
+    reflect::Reflect,
+};
 
 #[derive(Event)]
 pub enum LdtkEvent {
     LevelLoaded(LevelEvent),
     LevelUnloaded(LevelEvent),
+    /// Sent with `UnknownEntityPolicy::Error` before loading a level
+    /// that has unregistered entities, which are not spawned.
+    UnknownEntities(UnknownEntitiesEvent),
 }
 
 #[derive(Reflect, Debug, Clone)]
@@ -11,3 +17,32 @@ pub struct LevelEvent {
     pub identifier: String,
     pub iid: String,
 }
+
+#[derive(Reflect, Debug, Clone)]
+pub struct UnknownEntitiesEvent {
+    pub level: LevelEvent,
+    /// The sorted identifiers of the unregistered entities.
+    pub identifiers: Vec<String>,
+}
+
+/// Sent once all the layers, tiles and entities of a level are spawned
+/// and the entities are moved into place.
+///
+/// Unlike `LdtkEvent::LevelLoaded`, this is sent in `PostUpdate`,
+/// so the spawn commands are always applied when you receive it.
+#[derive(Event, Reflect, Debug, Clone)]
+pub struct LevelSpawnedEvent {
+    pub level_iid: String,
+    /// The level entity, which has the `LdtkLoadedLevel`.
+    pub entity: Entity,
+}
+
+/// Sent after the `LevelSpawnedEvent`s once all the levels
+/// requested from `LdtkLevelManager` are spawned.
+#[derive(Event, Reflect, Debug, Clone)]
+pub struct WorldSpawnedEvent {
+    /// The iid of the LDtk project.
+    pub world_iid: String,
+    /// The level entities spawned since the last `WorldSpawnedEvent`.
+    pub levels: Vec<Entity>,
+}
@@ -0,0 +1,240 @@
+use std::{io, path::Path};
+
+use bevy::math::IVec2;
+use serde::Serialize;
+
+use crate::tilemap::tile::{Tile, TileTexture};
+
+use super::{
+    json::{
+        definitions::TilesetDef,
+        level::{EntityInstance, LayerInstance, Level, TileInstance},
+        LdtkJson,
+    },
+    resources::LdtkCoordConfig,
+};
+
+fn save_json<T: Serialize>(path: &Path, object: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(object)?)
+}
+
+impl LdtkJson {
+    /// Write the project to a `.ldtk` file so it can be opened in the LDtk editor.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        save_json(path.as_ref(), self)
+    }
+}
+
+impl Level {
+    /// Write the level to a `.ldtkl` file.
+    /// This is used when the project has `external_levels` enabled.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        save_json(path.as_ref(), self)
+    }
+}
+
+impl TileInstance {
+    /// Create a tile instance at the given cell of the layer.
+    /// `px` and `src` are computed from the layer and the tileset.
+    ///
+    /// The cell is in LDtk coordinates, which means the origin is at the top left corner.
+    pub fn new(
+        layer: &LayerInstance,
+        tileset: &TilesetDef,
+        cell: IVec2,
+        tile_id: i32,
+        flip: i32,
+        alpha: f32,
+    ) -> Self {
+        let step = tileset.tile_grid_size + tileset.spacing;
+        Self {
+            alpha,
+            flip,
+            px: [cell.x * layer.grid_size, cell.y * layer.grid_size],
+            src: [
+                tileset.padding + tile_id % tileset.c_wid * step,
+                tileset.padding + tile_id / tileset.c_wid * step,
+            ],
+            tile_id,
+        }
+    }
+}
+
+impl LayerInstance {
+    /// Regenerate `int_grid_csv` using the given function.
+    ///
+    /// The function receives the cell in LDtk coordinates (origin at the top left corner)
+    /// and returns the IntGrid value, `0` for empty cells.
+    pub fn regenerate_int_grid(&mut self, value: impl Fn(IVec2) -> i32) {
+        self.int_grid_csv = (0..self.c_hei)
+            .flat_map(|y| (0..self.c_wid).map(move |x| IVec2::new(x, y)))
+            .map(value)
+            .collect();
+    }
+
+    /// Regenerate `grid_tiles` from the tiles spawned in the tilemap.
+    ///
+    /// Tiles are expected to be spawned using `LdtkLoaderMode::Tilemap` with `coords`.
+    /// Every static layer of a tile becomes a stacked tile instance,
+    /// animated tiles are skipped as LDtk doesn't support them.
+    pub fn regenerate_tiles<'a>(
+        &mut self,
+        tileset: &TilesetDef,
+        tiles: impl IntoIterator<Item = &'a Tile>,
+        coords: &LdtkCoordConfig,
+    ) {
+        let mut tiles = tiles
+            .into_iter()
+            .filter_map(|tile| match &tile.texture {
+                TileTexture::Static(layers) => Some((coords.cell_index(tile.index), tile, layers)),
+                TileTexture::Animated(_) => None,
+            })
+            .collect::<Vec<_>>();
+        // LDtk stores tiles from left to right, top to bottom.
+        tiles.sort_by_key(|(cell, ..)| (cell.y, cell.x));
+
+        self.grid_tiles.clear();
+        for (cell, tile, layers) in tiles {
+            for layer in layers.iter().filter(|l| l.atlas_index >= 0) {
+                let instance = TileInstance::new(
+                    self,
+                    tileset,
+                    cell,
+                    layer.atlas_index,
+                    layer.flip.to_ldtk(),
+                    tile.tint.a(),
+                );
+                self.grid_tiles.push(instance);
+            }
+        }
+    }
+}
+
+impl EntityInstance {
+    /// Move the entity to the given pixel position relative to the level.
+    /// This updates `px`, `__grid` and `__worldX`/`__worldY` together.
+    pub fn set_local_pos(&mut self, px: IVec2, level: &Level, grid_size: i32) {
+        self.local_pos = [px.x, px.y];
+        self.grid = [px.x / grid_size, px.y / grid_size];
+        if self.world_x.is_some() {
+            self.world_x = Some(level.world_x + px.x);
+        }
+        if self.world_y.is_some() {
+            self.world_y = Some(level.world_y + px.y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{ecs::entity::Entity, math::UVec2, render::color::Color, utils::HashMap};
+
+    use crate::{
+        ldtk::{load_grid_vania, resources::LdtkLoadConfig},
+        tilemap::tile::{TileFlip, TileLayer},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_level_round_trip() {
+        let json = load_grid_vania();
+
+        let mut level = json.levels[0].clone();
+        for layer in level.layer_instances.iter_mut() {
+            let csv = layer.int_grid_csv.clone();
+            let c_wid = layer.c_wid;
+            if csv.is_empty() {
+                continue;
+            }
+            layer.regenerate_int_grid(|cell| csv[(cell.y * c_wid + cell.x) as usize]);
+            assert_eq!(layer.int_grid_csv, csv);
+        }
+
+        let serialized = serde_json::to_string(&level).unwrap();
+        let deserialized = serde_json::from_str::<Level>(&serialized).unwrap();
+        assert_eq!(
+            serde_json::to_value(&deserialized).unwrap(),
+            serde_json::to_value(&level).unwrap()
+        );
+
+        let serialized = serde_json::to_string(&json).unwrap();
+        let deserialized = serde_json::from_str::<LdtkJson>(&serialized).unwrap();
+        assert_eq!(
+            serde_json::to_value(&deserialized).unwrap(),
+            serde_json::to_value(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_regenerate_tiles() {
+        let coords = LdtkLoadConfig::default().coords;
+        regenerate_tiles(coords);
+        regenerate_tiles(LdtkCoordConfig {
+            y_up: false,
+            ..coords
+        });
+    }
+
+    fn regenerate_tiles(coords: LdtkCoordConfig) {
+        let json = load_grid_vania();
+        let mut layer = json
+            .levels
+            .iter()
+            .flat_map(|level| level.layer_instances.iter())
+            .find(|layer| !layer.grid_tiles.is_empty())
+            .unwrap()
+            .clone();
+        let tileset = json
+            .defs
+            .tilesets
+            .iter()
+            .find(|t| Some(t.uid) == layer.tileset_def_uid)
+            .unwrap();
+
+        // Spawn the tiles like `LdtkLoaderMode::Tilemap` does, with all kinds of flips.
+        let mut source = layer.grid_tiles.clone();
+        source
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, tile)| tile.flip = i as i32 % 4);
+        let mut tiles = HashMap::<IVec2, Tile>::default();
+        for instance in &source {
+            let index = coords.tile_index(
+                IVec2::new(instance.px[0], instance.px[1]),
+                UVec2::splat(layer.grid_size as u32),
+            );
+            let tile = tiles.entry(index).or_insert_with(|| Tile {
+                tilemap_id: Entity::PLACEHOLDER,
+                chunk_index: IVec2::ZERO,
+                in_chunk_index: 0,
+                index,
+                texture: TileTexture::Static(Vec::new()),
+                tint: Color::rgba_linear(1., 1., 1., instance.alpha),
+            });
+            if let TileTexture::Static(layers) = &mut tile.texture {
+                layers.push(TileLayer {
+                    atlas_index: instance.tile_id,
+                    flip: TileFlip::from_ldtk(instance.flip),
+                    ..Default::default()
+                });
+            }
+        }
+
+        layer.grid_tiles.clear();
+        layer.regenerate_tiles(tileset, tiles.values(), &coords);
+
+        let sorted = |tiles: &[TileInstance]| {
+            let mut tiles = tiles
+                .iter()
+                .map(|t| (t.px, t.src, t.tile_id, t.flip, t.alpha))
+                .collect::<Vec<_>>();
+            tiles.sort_by_key(|(px, ..)| (px[1], px[0]));
+            tiles
+        };
+        assert_eq!(sorted(&layer.grid_tiles), sorted(&source));
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::{
+    math::{IVec2, UVec2, Vec2},
+    reflect::Reflect,
+    sprite::Anchor,
+};
+
+/// LDtk puts the origin of levels at the top left corner with the y axis pointing down.
+/// This controls how levels, tiles and entities are converted into world space.
+///
+/// Path and physics layers, `GridPoint::to_world` and `LayerInstance::regenerate_tiles`
+/// follow it too. Patterns loaded by `LdtkLoaderMode::MapPattern` always have the y axis up.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct LdtkCoordConfig {
+    /// Whether the y axis points up like Bevy, or down like LDtk.
+    pub y_up: bool,
+    /// Which point of a level is placed at the translation of the level.
+    pub origin: Anchor,
+}
+
+impl Default for LdtkCoordConfig {
+    fn default() -> Self {
+        Self {
+            y_up: true,
+            origin: Anchor::TopLeft,
+        }
+    }
+}
+
+impl LdtkCoordConfig {
+    /// Convert pixels relative to the top left corner of a level
+    /// into world space, still relative to the corner.
+    #[inline]
+    pub fn px_to_local(&self, px: Vec2) -> Vec2 {
+        if self.y_up {
+            Vec2::new(px.x, -px.y)
+        } else {
+            px
+        }
+    }
+
+    /// The position of the top left corner of a level relative to its origin.
+    #[inline]
+    pub fn level_corner(&self, level_size: Vec2) -> Vec2 {
+        let anchor = self.origin.as_vec();
+        -self.px_to_local(Vec2::new(anchor.x + 0.5, 0.5 - anchor.y) * level_size)
+    }
+
+    /// The translation of a level whose top left corner is at `world_px` in LDtk.
+    #[inline]
+    pub fn level_translation(&self, world_px: Vec2, level_size: Vec2) -> Vec2 {
+        self.px_to_local(world_px) - self.level_corner(level_size)
+    }
+
+    /// Convert pixels in a level into world space.
+    #[inline]
+    pub fn px_to_world(&self, px: Vec2, level_translation: Vec2, level_size: Vec2) -> Vec2 {
+        level_translation + self.level_corner(level_size) + self.px_to_local(px)
+    }
+
+    /// The index of the tile at `px` in a tilemap placed at the top left corner of the level.
+    #[inline]
+    pub fn tile_index(&self, px: IVec2, tile_size: UVec2) -> IVec2 {
+        self.cell_index(px / tile_size.as_ivec2())
+    }
+
+    /// The index of a grid cell of a layer in a tilemap placed at the top left corner of the level.
+    ///
+    /// This is its own inverse, so it also turns the index back into the cell.
+    #[inline]
+    pub fn cell_index(&self, cell: IVec2) -> IVec2 {
+        if self.y_up {
+            IVec2::new(cell.x, -cell.y - 1)
+        } else {
+            cell
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_coord_config() {
+        let level_size = Vec2::new(64., 32.);
+        let tile_size = UVec2::splat(16);
+        // The center of the tile at grid (0, 0), with the level origin at `translation`.
+        let tile_center = |coords: LdtkCoordConfig, translation: Vec2| {
+            let center = translation
+                + coords.level_corner(level_size)
+                + (coords.tile_index(IVec2::ZERO, tile_size).as_vec2() + 0.5) * 16.;
+            assert_eq!(
+                coords.px_to_world(Vec2::splat(8.), translation, level_size),
+                center
+            );
+            center
+        };
+
+        let coords = LdtkCoordConfig::default();
+        assert_eq!(tile_center(coords, Vec2::ZERO), Vec2::new(8., -8.));
+
+        let coords = LdtkCoordConfig {
+            y_up: true,
+            origin: Anchor::Center,
+        };
+        assert_eq!(tile_center(coords, Vec2::ZERO), Vec2::new(-24., 8.));
+        // Levels are still placed at their world position in LDtk.
+        let translation = coords.level_translation(Vec2::new(64., 32.), level_size);
+        assert_eq!(translation, Vec2::new(96., -48.));
+        assert_eq!(tile_center(coords, translation), Vec2::new(72., -40.));
+
+        let coords = LdtkCoordConfig {
+            y_up: false,
+            origin: Anchor::TopLeft,
+        };
+        assert_eq!(tile_center(coords, Vec2::ZERO), Vec2::new(8., 8.));
+
+        let coords = LdtkCoordConfig {
+            y_up: false,
+            origin: Anchor::BottomLeft,
+        };
+        assert_eq!(tile_center(coords, Vec2::ZERO), Vec2::new(8., -24.));
+        let translation = coords.level_translation(Vec2::new(64., 32.), level_size);
+        assert_eq!(tile_center(coords, translation), Vec2::new(72., 40.));
+    }
+}
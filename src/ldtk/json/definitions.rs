@@ -1,7 +1,12 @@
-use bevy::reflect::Reflect;
-use serde::{de::Visitor, Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 
-use crate::ldtk::sprite::{NineSliceBorders, TileRenderMode};
+use bevy::{reflect::Reflect, utils::HashMap};
+use serde::{de::Visitor, ser::SerializeSeq, Deserialize, Serialize, Serializer};
+
+use super::{
+    field::{FieldInstance, FieldValue},
+    GridPoint, LdtkColor,
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
 #[serde(rename_all = "camelCase")]
@@ -20,9 +25,29 @@ pub struct Definitions {
     pub layers: Vec<LayerDef>,
 
     /// All custom fields available to all levels.
+    #[serde(default)]
+    pub level_fields: Vec<FieldDef>,
+
+    /// All tilesets
     pub tilesets: Vec<TilesetDef>,
 }
 
+impl Definitions {
+    /// Check the fields of a level or an entity against their definitions.
+    pub fn validate_fields(
+        &self,
+        field_defs: &[FieldDef],
+        fields: &HashMap<String, FieldInstance>,
+    ) -> Result<(), LdtkFieldError> {
+        field_defs.iter().try_for_each(|def| {
+            def.validate(
+                fields.get(&def.identifier),
+                self.enums.iter().chain(self.external_enums.iter()),
+            )
+        })
+    }
+}
+
 /*
  * Layer Definition
  */
@@ -136,6 +161,7 @@ pub struct EntityDef {
     /// when using 9-slice mode for tileRenderMode.
     /// If the tileRenderMode is not NineSlice, then this array is empty.
     /// See: https://en.wikipedia.org/wiki/9-slice_scaling
+    #[serde(default)]
     pub nine_slice_borders: NineSliceBorders,
 
     /// Pivot X coordinate (from 0 to 1.0)
@@ -148,6 +174,8 @@ pub struct EntityDef {
     pub tile_rect: Option<TilesetRect>,
 
     /// An enum describing how the the Entity tile is rendered inside the Entity bounds.
+    /// Defaults to `Stretch` if missing.
+    #[serde(default)]
     pub tile_render_mode: TileRenderMode,
 
     /// Tileset ID used for optional tile display
@@ -164,6 +192,31 @@ pub struct EntityDef {
 
     /// Pixel height
     pub height: i32,
+
+    /// Array of field definitions
+    #[serde(default)]
+    pub field_defs: Vec<FieldDef>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Reflect)]
+pub enum TileRenderMode {
+    Cover,
+    FitInside,
+    Repeat,
+    #[default]
+    Stretch,
+    FullSizeCropped,
+    FullSizeUncropped,
+    NineSlice,
+}
+
+#[derive(Debug, Default, Clone, Copy, Reflect)]
+pub struct NineSliceBorders {
+    pub is_valid: bool,
+    pub up: i32,
+    pub right: i32,
+    pub down: i32,
+    pub left: i32,
 }
 
 impl<'de> Deserialize<'de> for NineSliceBorders {
@@ -175,6 +228,24 @@ impl<'de> Deserialize<'de> for NineSliceBorders {
     }
 }
 
+impl Serialize for NineSliceBorders {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if !self.is_valid {
+            return serializer.serialize_seq(Some(0))?.end();
+        }
+
+        let mut seq = serializer.serialize_seq(Some(4))?;
+        seq.serialize_element(&self.up)?;
+        seq.serialize_element(&self.right)?;
+        seq.serialize_element(&self.down)?;
+        seq.serialize_element(&self.left)?;
+        seq.end()
+    }
+}
+
 pub struct NineSliceBordersVisitor;
 
 impl<'de> Visitor<'de> for NineSliceBordersVisitor {
@@ -269,6 +340,16 @@ pub struct TilesetDef {
     pub uid: i32,
 }
 
+impl TilesetDef {
+    /// Get the custom data of the tile.
+    pub fn get_custom_data(&self, tile_id: i32) -> Option<&str> {
+        self.custom_data
+            .iter()
+            .find(|d| d.tile_id == tile_id)
+            .map(|d| d.data.as_str())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
 #[serde(rename_all = "camelCase")]
 pub struct CustomData {
@@ -313,6 +394,286 @@ pub struct EnumTagValue {
     pub enum_value_id: String,
 }
 
+/*
+ * Field Definition
+ */
+
+#[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDef {
+    /// User defined unique identifier
+    pub identifier: String,
+
+    /// Human readable value type. Possible values: Int, Float, String,
+    /// Bool, Color, ExternEnum.XXX, LocalEnum.XXX, Point, FilePath.
+    /// If the field is an array, this field will look like Array<...>
+    /// (eg. Array<Int>, Array<Point> etc.)
+    #[serde(rename = "__type")]
+    pub ty: String,
+
+    /// Unique Int identifier
+    pub uid: i32,
+
+    /// TRUE if the value is an array of multiple values
+    pub is_array: bool,
+
+    /// TRUE if the value can be null. For arrays, TRUE means it can contain null values
+    /// (exception: array of Points can't have null values).
+    pub can_be_null: bool,
+
+    /// Array min length
+    pub array_min_length: Option<i32>,
+
+    /// Array max length
+    pub array_max_length: Option<i32>,
+
+    /// Min limit for value, if applicable
+    pub min: Option<f32>,
+
+    /// Max limit for value, if applicable
+    pub max: Option<f32>,
+
+    /// Optional regular expression that needs to be matched to accept values.
+    /// Expected format: /some_reg_ex/g, with optional "i" flag.
+    pub regex: Option<String>,
+
+    /// Default value if selected value is null or invalid.
+    pub default_override: Option<FieldDefault>,
+}
+
+impl FieldDef {
+    /// The value LDtk gives to this field if it's not set.
+    ///
+    /// Returns `None` if there's no `default_override` and the field can be null,
+    /// or if there's no sensible default for the type, like enums and entity refs.
+    pub fn default_value(&self) -> Option<FieldValue> {
+        if self.is_array {
+            let item_ty = self.ty.trim_start_matches("Array<").trim_end_matches('>');
+            return Some(match item_ty {
+                "Int" => FieldValue::IntegerArray(Vec::new()),
+                "Float" => FieldValue::FloatArray(Vec::new()),
+                "Bool" => FieldValue::BoolArray(Vec::new()),
+                "Color" => FieldValue::ColorArray(Vec::new()),
+                "Point" => FieldValue::PointArray(Vec::new()),
+                "EntityRef" => FieldValue::EntityRefArray(Vec::new()),
+                ty => match ty.split_once('.') {
+                    Some(("LocalEnum", name)) => {
+                        FieldValue::LocalEnumArray((name.to_string(), Vec::new()))
+                    }
+                    Some(("ExternEnum", name)) => {
+                        FieldValue::ExternEnumArray((name.to_string(), Vec::new()))
+                    }
+                    _ => FieldValue::StringArray(Vec::new()),
+                },
+            });
+        }
+
+        let enum_value = |value: &str| match self.ty.split_once('.') {
+            Some(("LocalEnum", name)) => {
+                Some(FieldValue::LocalEnum((name.to_string(), value.to_string())))
+            }
+            Some(("ExternEnum", name)) => Some(FieldValue::ExternEnum((
+                name.to_string(),
+                value.to_string(),
+            ))),
+            _ => None,
+        };
+
+        match (&self.default_override, self.ty.as_str()) {
+            (Some(FieldDefault::Int([v])), "Color") => Some(FieldValue::Color(LdtkColor {
+                r: ((v >> 16) & 0xFF) as f32 / 255.,
+                g: ((v >> 8) & 0xFF) as f32 / 255.,
+                b: (v & 0xFF) as f32 / 255.,
+                a: 1.,
+            })),
+            (Some(FieldDefault::Int([v])), _) => Some(FieldValue::Integer(*v)),
+            (Some(FieldDefault::Float([v])), _) => Some(FieldValue::Float(*v)),
+            (Some(FieldDefault::Bool([v])), _) => Some(FieldValue::Bool(*v)),
+            (Some(FieldDefault::String([v])), _) => {
+                enum_value(v).or_else(|| Some(FieldValue::String(v.clone())))
+            }
+            (_, "Bool") => Some(FieldValue::Bool(false)),
+            _ if self.can_be_null => None,
+            (_, "Int") => Some(FieldValue::Integer(self.clamp(0.) as i32)),
+            (_, "Float") => Some(FieldValue::Float(self.clamp(0.))),
+            (_, "String" | "Multilines" | "FilePath") => Some(FieldValue::String(String::new())),
+            (_, "Color") => Some(FieldValue::Color(LdtkColor {
+                r: 0.,
+                g: 0.,
+                b: 0.,
+                a: 1.,
+            })),
+            (_, "Point") => Some(FieldValue::Point(GridPoint { cx: 0, cy: 0 })),
+            _ => None,
+        }
+    }
+
+    /// Clamp the value into `[min, max]`. The bounds that are not set are ignored.
+    fn clamp(&self, value: f32) -> f32 {
+        let value = self.min.map_or(value, |min| value.max(min));
+        self.max.map_or(value, |max| value.min(max))
+    }
+
+    /// Insert the default value if the field is missing, or null but not nullable.
+    pub fn fill_default(&self, fields: &mut HashMap<String, FieldInstance>) {
+        if fields
+            .get(&self.identifier)
+            .is_some_and(|f| f.value.is_some() || self.can_be_null)
+        {
+            return;
+        }
+
+        fields.insert(
+            self.identifier.clone(),
+            FieldInstance {
+                def_uid: self.uid,
+                identifier: self.identifier.clone(),
+                tile: None,
+                ty: self.ty.clone(),
+                value: self.default_value(),
+            },
+        );
+    }
+
+    /// Check whether the field matches this definition.
+    pub fn validate<'a>(
+        &self,
+        field: Option<&FieldInstance>,
+        mut enums: impl Iterator<Item = &'a EnumDef>,
+    ) -> Result<(), LdtkFieldError> {
+        let Some(field) = field else {
+            return Err(LdtkFieldError::Missing(self.identifier.clone()));
+        };
+        let Some(value) = &field.value else {
+            return if self.can_be_null {
+                Ok(())
+            } else {
+                Err(LdtkFieldError::UnexpectedNull(self.identifier.clone()))
+            };
+        };
+
+        // `Multilines` and `FilePath` values are strings if the type is not kept.
+        let found = if field.ty.is_empty() {
+            value.ty()
+        } else {
+            field.ty.clone()
+        };
+        let expected = if field.ty.is_empty() {
+            self.ty
+                .replace("Multilines", "String")
+                .replace("FilePath", "String")
+        } else {
+            self.ty.clone()
+        };
+        if found != expected {
+            return Err(LdtkFieldError::TypeMismatch {
+                identifier: self.identifier.clone(),
+                expected,
+                found,
+            });
+        }
+
+        let out_of_range =
+            |v: f32| self.min.is_some_and(|min| v < min) || self.max.is_some_and(|max| v > max);
+        match value {
+            FieldValue::Integer(v) if out_of_range(*v as f32) => {
+                return Err(LdtkFieldError::OutOfRange {
+                    identifier: self.identifier.clone(),
+                    value: *v as f32,
+                })
+            }
+            FieldValue::Float(v) if out_of_range(*v) => {
+                return Err(LdtkFieldError::OutOfRange {
+                    identifier: self.identifier.clone(),
+                    value: *v,
+                })
+            }
+            FieldValue::LocalEnum((name, v)) | FieldValue::ExternEnum((name, v)) => {
+                let is_declared = enums
+                    .find(|e| &e.identifier == name)
+                    .is_some_and(|e| e.values.iter().any(|ev| &ev.id == v));
+                if !is_declared {
+                    return Err(LdtkFieldError::UnknownEnumValue {
+                        identifier: self.identifier.clone(),
+                        value: v.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// The `defaultOverride` of a field definition.
+#[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
+#[serde(tag = "id", content = "params")]
+pub enum FieldDefault {
+    #[serde(rename = "V_Int")]
+    Int([i32; 1]),
+    #[serde(rename = "V_Float")]
+    Float([f32; 1]),
+    #[serde(rename = "V_Bool")]
+    Bool([bool; 1]),
+    #[serde(rename = "V_String")]
+    String([String; 1]),
+    #[serde(other)]
+    Other,
+}
+
+/// Why a field doesn't match its definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LdtkFieldError {
+    Missing(String),
+    UnexpectedNull(String),
+    TypeMismatch {
+        identifier: String,
+        expected: String,
+        found: String,
+    },
+    OutOfRange {
+        identifier: String,
+        value: f32,
+    },
+    UnknownEnumValue {
+        identifier: String,
+        value: String,
+    },
+}
+
+impl Display for LdtkFieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LdtkFieldError::Missing(identifier) => write!(f, "field {} is missing", identifier),
+            LdtkFieldError::UnexpectedNull(identifier) => {
+                write!(f, "field {} is null but not nullable", identifier)
+            }
+            LdtkFieldError::TypeMismatch {
+                identifier,
+                expected,
+                found,
+            } => write!(
+                f,
+                "field {} is {}, expected {}",
+                identifier, found, expected
+            ),
+            LdtkFieldError::OutOfRange { identifier, value } => {
+                write!(f, "field {} is out of range: {}", identifier, value)
+            }
+            LdtkFieldError::UnknownEnumValue { identifier, value } => {
+                write!(
+                    f,
+                    "field {} has an undeclared enum value {}",
+                    identifier, value
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LdtkFieldError {}
+
 /*
  * Enum Definition
  */
@@ -351,3 +712,117 @@ pub struct EnumValue {
     /// Optional tileset rectangle to represents this value
     pub tile_rect: Option<TilesetRect>,
 }
+
+#[cfg(test)]
+mod test {
+    use crate::ldtk::load_grid_vania;
+
+    use super::*;
+
+    #[test]
+    fn test_field_defaults() {
+        let project = load_grid_vania();
+        let item_def = project
+            .defs
+            .entities
+            .iter()
+            .find(|def| def.identifier == "Item")
+            .unwrap();
+        let item = project
+            .iter_levels()
+            .flat_map(|level| level.layer_instances.iter())
+            .flat_map(|layer| layer.entity_instances.iter())
+            .find(|entity| entity.def_uid == item_def.uid)
+            .unwrap();
+
+        let mut fields = item
+            .field_instances
+            .iter()
+            .filter(|field| field.identifier != "count")
+            .map(|field| (field.identifier.clone(), field.clone()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            project.defs.validate_fields(&item_def.field_defs, &fields),
+            Err(LdtkFieldError::Missing("count".to_string()))
+        );
+
+        item_def
+            .field_defs
+            .iter()
+            .for_each(|def| def.fill_default(&mut fields));
+        assert!(matches!(
+            fields["count"].value,
+            Some(FieldValue::Integer(1))
+        ));
+        assert_eq!(
+            project.defs.validate_fields(&item_def.field_defs, &fields),
+            Ok(())
+        );
+
+        fields.get_mut("count").unwrap().value = Some(FieldValue::Integer(0));
+        assert_eq!(
+            project.defs.validate_fields(&item_def.field_defs, &fields),
+            Err(LdtkFieldError::OutOfRange {
+                identifier: "count".to_string(),
+                value: 0.
+            })
+        );
+        fields.get_mut("type").unwrap().value = Some(FieldValue::LocalEnum((
+            "ItemType".to_string(),
+            "Nothing".to_string(),
+        )));
+        assert!(matches!(
+            project.defs.validate_fields(&item_def.field_defs, &fields),
+            Err(LdtkFieldError::UnknownEnumValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_non_null_defaults() {
+        let project = load_grid_vania();
+        let mut def = project
+            .defs
+            .entities
+            .iter()
+            .flat_map(|def| def.field_defs.iter())
+            .find(|def| def.ty == "Int" && !def.is_array)
+            .unwrap()
+            .clone();
+        def.can_be_null = false;
+        def.default_override = None;
+
+        let int_default = |def: &mut FieldDef, min, max| {
+            def.min = min;
+            def.max = max;
+            match def.default_value() {
+                Some(FieldValue::Integer(v)) => v,
+                v => panic!("{:?}", v),
+            }
+        };
+        assert_eq!(int_default(&mut def, None, None), 0);
+        assert_eq!(int_default(&mut def, Some(-5.), Some(5.)), 0);
+        assert_eq!(int_default(&mut def, Some(2.), None), 2);
+        assert_eq!(int_default(&mut def, None, Some(-3.)), -3);
+
+        for ty in ["String", "Multilines", "FilePath", "Color", "Point"] {
+            def.ty = ty.to_string();
+            let mut fields = HashMap::default();
+            def.fill_default(&mut fields);
+            assert!(fields[&def.identifier].value.is_some(), "{}", ty);
+            assert_eq!(
+                def.validate(fields.get(&def.identifier), std::iter::empty()),
+                Ok(())
+            );
+        }
+        def.ty = "Color".to_string();
+        assert!(matches!(
+            def.default_value(),
+            Some(FieldValue::Color(LdtkColor {
+                r: 0.,
+                g: 0.,
+                b: 0.,
+                a: 1.
+            }))
+        ));
+    }
+}
@@ -1,35 +1,63 @@
-use bevy::{math::IVec2, reflect::Reflect};
+use std::path::Path;
+
+use bevy::{asset::AssetPath, math::IVec2, reflect::Reflect};
 use serde::{
     de::{Error, IgnoredAny, Visitor},
-    Deserialize, Deserializer, Serialize,
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use crate::{ldtk::json::LdtkColor, match_field, match_field_enum, transfer_field, unwrap_field};
+use crate::{
+    ldtk::json::LdtkColor, match_field, match_field_enum, transfer_field, unwrap_field,
+    utils::asset::to_asset_path,
+};
 
 use super::{definitions::TilesetRect, EntityRef, GridPoint};
 
-#[derive(Serialize, Debug, Clone, Reflect)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Reflect)]
 pub struct FieldInstance {
     /// Reference of the Field definition UID
     pub def_uid: i32,
 
     /// Field definition identifier
-    #[serde(rename = "__identifier")]
     pub identifier: String,
 
     /// Optional TilesetRect used to display this field
     /// (this can be the field own Tile,
     /// or some other Tile guessed from the value, like an Enum).
-    #[serde(rename = "__tile")]
     pub tile: Option<TilesetRect>,
 
+    /// Type of the field, such as Int, Float, String, Enum(my_enum_name), Bool, etc.
+    /// Kept so the field can be serialized back to LDtk exactly.
+    ///
+    /// If this is empty, `__type` will be derived from the value when serializing.
+    pub ty: String,
+
     /// Actual value of the field instance. The value type varies, depending on `__type`
     /// If the field is an array, then this `__value` will also be a JSON array.
-    #[serde(rename = "__value")]
     pub value: Option<FieldValue>,
 }
 
+impl FieldInstance {
+    /// Resolve a `FilePath` field against the directory of the LDtk file,
+    /// so it can be loaded using the `AssetServer`.
+    ///
+    /// `asset_path_prefix` is the same as `LdtkLoadConfig::asset_path_prefix`.
+    /// Returns `None` if this is not a `FilePath` field or the value is null.
+    pub fn resolve_file_path(&self, asset_path_prefix: &str) -> Option<AssetPath<'static>> {
+        if self.ty != "FilePath" {
+            return None;
+        }
+
+        match &self.value {
+            Some(FieldValue::String(path)) => Some(AssetPath::from(to_asset_path(
+                Path::new(asset_path_prefix).join(path),
+            ))),
+            _ => None,
+        }
+    }
+}
+
 const FIELDS: &[&str] = &["defUid", "__identifier", "__tile", "__type", "__value"];
 
 impl<'de> Deserialize<'de> for FieldInstance {
@@ -52,18 +80,18 @@ impl<'de> Deserialize<'de> for FieldInstance {
                 let mut def_uid = None;
                 let mut identifier = None;
                 let mut tile = None;
-                let mut ty = None;
+                let mut ty: Option<String> = None;
                 let mut value = None;
 
-                while let Some(key) = map.next_key()? {
-                    match key {
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
                         "defUid" => transfer_field!(def_uid, "defUid", map),
                         "__identifier" => {
                             transfer_field!(identifier, "__identifier", map)
                         }
                         "__tile" => transfer_field!(tile, "__tile", map),
                         "__type" => transfer_field!(ty, "__type", map),
-                        "__value" => match ty.unwrap() {
+                        "__value" => match ty.as_deref().unwrap() {
                             "Int" => match_field!(value, Integer, i32, map),
                             "Float" => match_field!(value, Float, f32, map),
                             "Bool" => match_field!(value, Bool, bool, map),
@@ -74,7 +102,7 @@ impl<'de> Deserialize<'de> for FieldInstance {
                             "Point" => match_field!(value, Point, GridPoint, map),
                             "EntityRef" => match_field!(value, EntityRef, EntityRef, map),
                             _ => {
-                                let ty = ty.unwrap();
+                                let ty = ty.as_deref().unwrap();
                                 if ty.starts_with("LocalEnum") {
                                     match_field_enum!(
                                         value,
@@ -164,11 +192,13 @@ impl<'de> Deserialize<'de> for FieldInstance {
                 let def_uid = unwrap_field!(def_uid, "defUid");
                 let identifier = unwrap_field!(identifier, "__identifier");
                 let tile = unwrap_field!(tile, "__tile");
+                let ty = unwrap_field!(ty, "__type");
 
                 Ok(FieldInstance {
                     def_uid,
                     identifier,
                     tile,
+                    ty,
                     value,
                 })
             }
@@ -178,13 +208,34 @@ impl<'de> Deserialize<'de> for FieldInstance {
     }
 }
 
+impl Serialize for FieldInstance {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let ty = if self.ty.is_empty() {
+            self.value.as_ref().map(|v| v.ty()).unwrap_or_default()
+        } else {
+            self.ty.clone()
+        };
+
+        let mut state = serializer.serialize_struct("FieldInstance", FIELDS.len())?;
+        state.serialize_field("defUid", &self.def_uid)?;
+        state.serialize_field("__identifier", &self.identifier)?;
+        state.serialize_field("__tile", &self.tile)?;
+        state.serialize_field("__type", &ty)?;
+        state.serialize_field("__value", &self.value)?;
+        state.end()
+    }
+}
+
 /// - For classic types (ie. Integer, Float, Boolean, String, Text and FilePath), you just get the actual value with the expected type.
 /// - For Color, the value is an hexadecimal string using "#rrggbb" format.
 /// - For Enum, the value is a String representing the selected enum value.
 /// - For Point, the value is a GridPoint object.
 /// - For Tile, the value is a TilesetRect object.
 /// - For EntityRef, the value is an EntityReferenceInfos object.
-#[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
+#[derive(Deserialize, Debug, Clone, Reflect)]
 #[serde(untagged)]
 pub enum FieldValue {
     Integer(i32),
@@ -208,6 +259,127 @@ pub enum FieldValue {
     EntityRefArray(Vec<EntityRef>),
 }
 
+impl FieldValue {
+    /// The LDtk `__type` of this value, like `Int`, `LocalEnum.MyEnum` or `Array<Float>`.
+    ///
+    /// `Multilines` and `FilePath` values can't be told apart from `String` ones,
+    /// so they are all `String` here.
+    pub fn ty(&self) -> String {
+        match self {
+            FieldValue::Integer(_) => "Int".to_string(),
+            FieldValue::Float(_) => "Float".to_string(),
+            FieldValue::Bool(_) => "Bool".to_string(),
+            FieldValue::String(_) => "String".to_string(),
+            FieldValue::LocalEnum((name, _)) => format!("LocalEnum.{}", name),
+            FieldValue::ExternEnum((name, _)) => format!("ExternEnum.{}", name),
+            FieldValue::Color(_) => "Color".to_string(),
+            FieldValue::Point(_) => "Point".to_string(),
+            FieldValue::EntityRef(_) => "EntityRef".to_string(),
+            FieldValue::IntegerArray(_) => "Array<Int>".to_string(),
+            FieldValue::FloatArray(_) => "Array<Float>".to_string(),
+            FieldValue::BoolArray(_) => "Array<Bool>".to_string(),
+            FieldValue::StringArray(_) => "Array<String>".to_string(),
+            FieldValue::LocalEnumArray((name, _)) => format!("Array<LocalEnum.{}>", name),
+            FieldValue::ExternEnumArray((name, _)) => format!("Array<ExternEnum.{}>", name),
+            FieldValue::ColorArray(_) => "Array<Color>".to_string(),
+            FieldValue::PointArray(_) => "Array<Point>".to_string(),
+            FieldValue::EntityRefArray(_) => "Array<EntityRef>".to_string(),
+        }
+    }
+
+    /// Returns `None` if this is not an `Array<Int>`. The same goes for other `as_*_array`s.
+    pub fn as_int_array(&self) -> Option<Vec<i32>> {
+        match self {
+            FieldValue::IntegerArray(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn as_float_array(&self) -> Option<Vec<f32>> {
+        match self {
+            FieldValue::FloatArray(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool_array(&self) -> Option<Vec<bool>> {
+        match self {
+            FieldValue::BoolArray(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn as_string_array(&self) -> Option<Vec<String>> {
+        match self {
+            FieldValue::StringArray(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    /// The values of an array of local or extern enums.
+    pub fn as_enum_array(&self) -> Option<Vec<String>> {
+        match self {
+            FieldValue::LocalEnumArray((_, v)) | FieldValue::ExternEnumArray((_, v)) => {
+                Some(v.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_color_array(&self) -> Option<Vec<LdtkColor>> {
+        match self {
+            FieldValue::ColorArray(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    /// The grid coordinates of an array of points, like a patrol route.
+    pub fn as_point_array(&self) -> Option<Vec<IVec2>> {
+        match self {
+            FieldValue::PointArray(v) => Some(v.iter().map(|p| IVec2::new(p.cx, p.cy)).collect()),
+            _ => None,
+        }
+    }
+
+    pub fn as_entity_ref_array(&self) -> Option<Vec<EntityRef>> {
+        match self {
+            FieldValue::EntityRefArray(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for FieldValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            FieldValue::Integer(v) => v.serialize(serializer),
+            FieldValue::Float(v) => v.serialize(serializer),
+            FieldValue::Bool(v) => v.serialize(serializer),
+            FieldValue::String(v) => v.serialize(serializer),
+            // The enum name lives in `__type`, only the value is written here.
+            FieldValue::LocalEnum((_, v)) | FieldValue::ExternEnum((_, v)) => {
+                v.serialize(serializer)
+            }
+            FieldValue::Color(v) => v.serialize(serializer),
+            FieldValue::Point(v) => v.serialize(serializer),
+            FieldValue::EntityRef(v) => v.serialize(serializer),
+            FieldValue::IntegerArray(v) => v.serialize(serializer),
+            FieldValue::FloatArray(v) => v.serialize(serializer),
+            FieldValue::BoolArray(v) => v.serialize(serializer),
+            FieldValue::StringArray(v) => v.serialize(serializer),
+            FieldValue::LocalEnumArray((_, v)) | FieldValue::ExternEnumArray((_, v)) => {
+                v.serialize(serializer)
+            }
+            FieldValue::ColorArray(v) => v.serialize(serializer),
+            FieldValue::PointArray(v) => v.serialize(serializer),
+            FieldValue::EntityRefArray(v) => v.serialize(serializer),
+        }
+    }
+}
+
 macro_rules! impl_into {
     ($ty:ty, $variant:ident) => {
         impl Into<$ty> for FieldInstance {
@@ -215,9 +387,14 @@ macro_rules! impl_into {
                 match self.value {
                     Some(v) => match v {
                         FieldValue::$variant(x) => x,
-                        _ => panic!("Expected {} value!", stringify!($variant)),
+                        _ => panic!(
+                            "Field {} is {}, expected {} value!",
+                            self.identifier,
+                            self.ty,
+                            stringify!($variant)
+                        ),
                     },
-                    None => panic!("Expected value!"),
+                    None => panic!("Field {} is null, expected value!", self.identifier),
                 }
             }
         }
@@ -231,7 +408,12 @@ macro_rules! impl_into_optional {
                 match self.value {
                     Some(v) => match v {
                         FieldValue::$variant(x) => Some(x),
-                        _ => panic!("Expected {} value!", stringify!($variant)),
+                        _ => panic!(
+                            "Field {} is {}, expected {} value!",
+                            self.identifier,
+                            self.ty,
+                            stringify!($variant)
+                        ),
                     },
                     None => None,
                 }
@@ -277,9 +459,12 @@ impl Into<IVec2> for FieldInstance {
         match self.value {
             Some(v) => match v {
                 FieldValue::Point(p) => IVec2 { x: p.cx, y: p.cy },
-                _ => panic!("Expected Point value!"),
+                _ => panic!(
+                    "Field {} is {}, expected Point value!",
+                    self.identifier, self.ty
+                ),
             },
-            None => panic!("Expected value!"),
+            None => panic!("Field {} is null, expected value!", self.identifier),
         }
     }
 }
@@ -289,7 +474,10 @@ impl Into<Option<IVec2>> for FieldInstance {
         match self.value {
             Some(v) => match v {
                 FieldValue::Point(p) => Some(IVec2 { x: p.cx, y: p.cy }),
-                _ => panic!("Expected Point value!"),
+                _ => panic!(
+                    "Field {} is {}, expected Point value!",
+                    self.identifier, self.ty
+                ),
             },
             None => None,
         }
@@ -298,6 +486,8 @@ impl Into<Option<IVec2>> for FieldInstance {
 
 #[cfg(test)]
 mod test {
+    use bevy::render::color::Color;
+
     use super::*;
 
     #[test]
@@ -314,4 +504,155 @@ mod test {
 
         dbg!(field_instance);
     }
+
+    #[test]
+    fn test_ser_type() {
+        let json = r#"{
+            "defUid": 1,
+            "__identifier": "test",
+            "__tile": null,
+            "__type": "LocalEnum.MyEnum",
+            "__value": "A"
+        }"#;
+
+        let mut field_instance: FieldInstance = serde_json::from_str(json).unwrap();
+        let value = serde_json::to_value(&field_instance).unwrap();
+        assert_eq!(value["__type"], "LocalEnum.MyEnum");
+        assert_eq!(value["__value"], "A");
+        assert_eq!(
+            value,
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+
+        // Derived from the value when the original type is missing.
+        field_instance.ty.clear();
+        let value = serde_json::to_value(&field_instance).unwrap();
+        assert_eq!(value["__type"], "LocalEnum.MyEnum");
+
+        field_instance.value = Some(FieldValue::ExternEnumArray((
+            "Items".to_string(),
+            vec!["Sword".to_string()],
+        )));
+        let value = serde_json::to_value(&field_instance).unwrap();
+        assert_eq!(value["__type"], "Array<ExternEnum.Items>");
+        assert_eq!(value["__value"][0], "Sword");
+    }
+
+    #[test]
+    fn test_color_alpha() {
+        let field = |value: &str| {
+            serde_json::from_str::<FieldInstance>(&format!(
+                r#"{{
+                    "defUid": 1,
+                    "__identifier": "Tint",
+                    "__tile": null,
+                    "__type": "Color",
+                    "__value": "{}"
+                }}"#,
+                value
+            ))
+            .unwrap()
+        };
+
+        let field_instance = field("#ff000080");
+        let value = serde_json::to_value(&field_instance).unwrap();
+        assert_eq!(value["__value"], "#ff000080");
+        let color: LdtkColor = field_instance.into();
+        assert_eq!((color.r, color.g, color.b), (1., 0., 0.));
+        assert!((color.a - 0.5).abs() < 0.01);
+        let tint: Color = color.into();
+        assert_eq!(tint.a(), color.a);
+
+        let field_instance = field("#ff0000");
+        let value = serde_json::to_value(&field_instance).unwrap();
+        assert_eq!(value["__value"], "#ff0000");
+        let color: LdtkColor = field_instance.into();
+        assert_eq!(color.a, 1.);
+    }
+
+    #[test]
+    #[should_panic(expected = "Field HP is Float, expected Integer value!")]
+    fn test_into_mismatch() {
+        let field_instance = FieldInstance {
+            def_uid: 1,
+            identifier: "HP".to_string(),
+            tile: None,
+            ty: "Float".to_string(),
+            value: Some(FieldValue::Float(1.)),
+        };
+
+        let _: i32 = field_instance.into();
+    }
+
+    #[test]
+    fn test_typed_arrays() {
+        let json = r#"{
+            "defUid": 1,
+            "__identifier": "Patrol",
+            "__tile": null,
+            "__type": "Array<Point>",
+            "__value": [{ "cx": 1, "cy": 2 }, { "cx": 4, "cy": 2 }, { "cx": 4, "cy": 6 }]
+        }"#;
+
+        let field_instance: FieldInstance = serde_json::from_str(json).unwrap();
+        let value = field_instance.value.unwrap();
+        assert_eq!(
+            value.as_point_array(),
+            Some(vec![IVec2::new(1, 2), IVec2::new(4, 2), IVec2::new(4, 6)])
+        );
+        assert_eq!(value.as_int_array(), None);
+        assert_eq!(value.as_enum_array(), None);
+
+        let value = FieldValue::LocalEnumArray((
+            "Item".to_string(),
+            vec!["Sword".to_string(), "Shield".to_string()],
+        ));
+        assert_eq!(
+            value.as_enum_array(),
+            Some(vec!["Sword".to_string(), "Shield".to_string()])
+        );
+        assert_eq!(value.as_string_array(), None);
+        assert_eq!(FieldValue::Integer(1).as_int_array(), None);
+    }
+
+    #[test]
+    fn test_file_path() {
+        let json = r#"{
+            "defUid": 1,
+            "__identifier": "Sound",
+            "__tile": null,
+            "__type": "FilePath",
+            "__value": "../audio/door.ogg"
+        }"#;
+
+        let mut field_instance: FieldInstance = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            field_instance.resolve_file_path("ldtk/"),
+            Some(AssetPath::from("audio/door.ogg"))
+        );
+
+        field_instance.value = Some(FieldValue::String("data/door.json".to_string()));
+        assert_eq!(
+            field_instance.resolve_file_path("ldtk/"),
+            Some(AssetPath::from("ldtk/data/door.json"))
+        );
+
+        field_instance.ty = "String".to_string();
+        assert_eq!(field_instance.resolve_file_path("ldtk/"), None);
+    }
+
+    #[test]
+    fn test_multilines() {
+        let json = r#"{
+            "defUid": 1,
+            "__identifier": "Dialogue",
+            "__tile": null,
+            "__type": "Multilines",
+            "__value": "Hello!\n\n  How are you?\r\nBye."
+        }"#;
+
+        let field_instance: FieldInstance = serde_json::from_str(json).unwrap();
+        let value: String = field_instance.into();
+        assert_eq!(value, "Hello!\n\n  How are you?\r\nBye.");
+    }
 }
@@ -1,14 +1,15 @@
 use bevy::{
-    ecs::system::EntityCommands, reflect::Reflect, sprite::MaterialMesh2dBundle,
-    transform::components::Transform,
+    log::warn,
+    math::{IVec2, Vec2},
+    reflect::Reflect,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::ldtk::resources::LdtkAssets;
+use crate::math::aabb::Aabb2d;
 
 use super::{
     definitions::{LayerType, TilesetRect},
-    field::FieldInstance,
+    field::{FieldInstance, FieldValue},
     LdtkColor,
 };
 
@@ -62,6 +63,7 @@ pub struct Level {
     ///
     /// This array is **sorted in display order**: the 1st layer is
     /// the top-most and the last is behind.
+    #[serde(deserialize_with = "null_as_empty")]
     pub layer_instances: Vec<LayerInstance>,
 
     /// Height of the level in pixels
@@ -95,6 +97,68 @@ pub struct Level {
     pub world_y: i32,
 }
 
+impl Level {
+    /// Get the area the camera should stay in, relative to the top left corner of the level.
+    /// The y axis points up, so the level itself is `(0, -px_hei)` to `(px_wid, 0)`.
+    ///
+    /// The level field named `field` overrides the level rectangle. It can be:
+    /// - `Array<Point>` with 2 points: the cells at two opposite corners, both included.
+    ///   `grid_size` is used to convert them into pixels.
+    /// - `Array<Int>` or `Array<Float>` with 4 numbers: `[x, y, width, height]` in pixels,
+    ///   where `(x, y)` is the top left corner, just like the `px` of entities.
+    ///
+    /// Falls back to the level rectangle if the field is missing or null.
+    pub fn camera_bounds(&self, field: &str, grid_size: i32) -> Aabb2d {
+        let level_rect = Aabb2d::new(0., -self.px_hei as f32, self.px_wid as f32, 0.);
+        let Some(value) = self
+            .field_instances
+            .iter()
+            .find(|f| f.identifier == field)
+            .and_then(|f| f.value.as_ref())
+        else {
+            return level_rect;
+        };
+
+        // In level pixel space, where the y axis points down.
+        let (min, max) = match value {
+            FieldValue::PointArray(points) if points.len() == 2 => {
+                let a = Vec2::new(points[0].cx as f32, points[0].cy as f32);
+                let b = Vec2::new(points[1].cx as f32, points[1].cy as f32);
+                (
+                    a.min(b) * grid_size as f32,
+                    (a.max(b) + 1.) * grid_size as f32,
+                )
+            }
+            FieldValue::IntegerArray(rect) if rect.len() == 4 => {
+                let min = Vec2::new(rect[0] as f32, rect[1] as f32);
+                (min, min + Vec2::new(rect[2] as f32, rect[3] as f32))
+            }
+            FieldValue::FloatArray(rect) if rect.len() == 4 => {
+                let min = Vec2::new(rect[0], rect[1]);
+                (min, min + Vec2::new(rect[2], rect[3]))
+            }
+            _ => {
+                warn!(
+                    "Field {} of level {} is not a valid camera bounds! \
+                    Expected 2 points or [x, y, width, height].",
+                    field, self.identifier
+                );
+                return level_rect;
+            }
+        };
+
+        Aabb2d::new(min.x, -max.y, max.x, -min.y)
+    }
+}
+
+/// The external level files are spliced in when loading, see `Level::external_rel_path`.
+fn null_as_empty<'de, D>(deserializer: D) -> Result<Vec<LayerInstance>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<Vec<LayerInstance>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
 #[serde(rename_all = "camelCase")]
 pub struct ImagePosition {
@@ -270,7 +334,7 @@ pub struct TileInstance {
     ///
     /// Examples: f=0 (no flip), f=1 (X flip only), f=2 (Y flip only), f=3 (both flips)
     ///
-    /// (This is the same as the `TileFlip`)
+    /// Use `TileFlip::from_ldtk` to convert it.
     #[serde(rename = "f")]
     pub flip: i32,
 
@@ -352,16 +416,89 @@ pub struct EntityInstance {
 }
 
 impl EntityInstance {
-    pub fn generate_sprite(&self, commands: &mut EntityCommands, assets: &LdtkAssets) {
-        if self.tile.is_none() {
-            return;
-        }
-
-        commands.insert(MaterialMesh2dBundle {
-            mesh: assets.clone_mesh_handle(&self.iid),
-            material: assets.clone_material_handle(&self.iid),
-            transform: Transform::from_xyz(self.local_pos[0] as f32, -self.local_pos[1] as f32, 0.),
-            ..Default::default()
-        });
+    /// Coordinates of the cell containing the pivot.
+    #[inline]
+    pub fn grid(&self) -> IVec2 {
+        IVec2::from_array(self.grid)
+    }
+
+    /// Pixel position of the pivot relative to the level, without layer offsets.
+    #[inline]
+    pub fn local_pos(&self) -> IVec2 {
+        IVec2::from_array(self.local_pos)
+    }
+
+    /// Pixel position of the pivot in the world.
+    ///
+    /// Returns `None` in linear world layouts, where LDtk doesn't export it.
+    #[inline]
+    pub fn world_pos(&self) -> Option<IVec2> {
+        Some(IVec2::new(self.world_x?, self.world_y?))
+    }
+
+    #[inline]
+    pub fn size(&self) -> IVec2 {
+        IVec2::new(self.width, self.height)
+    }
+
+    /// Pixel position of the visual center of the entity in the world,
+    /// taking the pivot, the size and the offset of the layer into account.
+    ///
+    /// Like in LDtk, the y axis points down. Returns `None` if `world_pos()` does.
+    pub fn center_world_pos(&self, layer: &LayerInstance) -> Option<Vec2> {
+        let offset = IVec2::new(layer.px_total_offset_x, layer.px_total_offset_y);
+        let pivot = Vec2::from_array(self.pivot);
+        Some(
+            (self.world_pos()? + offset).as_vec2()
+                + (Vec2::splat(0.5) - pivot) * self.size().as_vec2(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ldtk::load_grid_vania;
+
+    use super::*;
+
+    #[test]
+    fn test_entity_positions() {
+        let ldtk_json = load_grid_vania();
+        let mut layer = ldtk_json.levels[0]
+            .layer_instances
+            .iter()
+            .find(|l| !l.entity_instances.is_empty())
+            .unwrap()
+            .clone();
+        layer.px_total_offset_x = 3;
+        layer.px_total_offset_y = -2;
+
+        // A resized entity pivoted at its bottom center.
+        let mut entity = layer.entity_instances[0].clone();
+        entity.grid = [2, 4];
+        entity.local_pos = [40, 64];
+        entity.world_x = Some(1040);
+        entity.world_y = Some(2064);
+        entity.pivot = [0.5, 1.];
+        entity.width = 32;
+        entity.height = 16;
+
+        assert_eq!(entity.grid(), IVec2::new(2, 4));
+        assert_eq!(entity.local_pos(), IVec2::new(40, 64));
+        assert_eq!(entity.world_pos(), Some(IVec2::new(1040, 2064)));
+        assert_eq!(
+            entity.center_world_pos(&layer),
+            Some(Vec2::new(1043., 2054.))
+        );
+
+        entity.pivot = [0., 0.];
+        assert_eq!(
+            entity.center_world_pos(&layer),
+            Some(Vec2::new(1059., 2070.))
+        );
+
+        entity.world_x = None;
+        assert_eq!(entity.world_pos(), None);
+        assert_eq!(entity.center_world_pos(&layer), None);
     }
 }
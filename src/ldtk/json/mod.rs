@@ -1,38 +1,151 @@
-use bevy::{math::Vec4, reflect::Reflect, render::color::Color, utils::HashMap};
-use serde::{de::Visitor, Deserialize, Serialize};
-
-use self::{definitions::Definitions, level::Level};
-
+use bevy::{
+    math::{Vec2, Vec4},
+    reflect::{Reflect, TypeRegistry},
+    utils::HashMap,
+};
+use serde::{de::Visitor, Deserialize, Serialize, Serializer};
+
+use self::{
+    coords::LdtkCoordConfig,
+    definitions::{
+        CustomData, Definitions, EntityDef, EnumDef, EnumTag, EnumTagValue, EnumValue, FieldDef,
+        FieldDefault, IntGridValue, IntGroupValueGroup, LayerDef, LayerType, NineSliceBorders,
+        TileRenderMode, TilesetDef, TilesetRect,
+    },
+    field::{FieldInstance, FieldValue},
+    level::{
+        EntityInstance, ImagePosition, LayerInstance, Level, Neighbour, NeighbourDirection,
+        TileInstance,
+    },
+};
+
+pub mod coords;
 pub mod definitions;
 pub mod field;
 pub mod level;
 pub mod macros;
+pub mod stream;
+
+/// Register all the LDtk json types for reflection,
+/// so the imported data can be viewed in inspectors.
+pub fn register_types(registry: &mut TypeRegistry) {
+    registry.register::<LdtkJson>();
+    registry.register::<LdtkColor>();
+    registry.register::<World>();
+    registry.register::<WorldLayout>();
+    registry.register::<Toc>();
+    registry.register::<TocInstance>();
+    registry.register::<TocField>();
+    registry.register::<EntityRef>();
+    registry.register::<GridPoint>();
+
+    registry.register::<Level>();
+    registry.register::<ImagePosition>();
+    registry.register::<Neighbour>();
+    registry.register::<NeighbourDirection>();
+    registry.register::<LayerInstance>();
+    registry.register::<TileInstance>();
+    registry.register::<EntityInstance>();
+    registry.register::<FieldInstance>();
+    registry.register::<FieldValue>();
+
+    registry.register::<Definitions>();
+    registry.register::<LayerDef>();
+    registry.register::<LayerType>();
+    registry.register::<IntGridValue>();
+    registry.register::<IntGroupValueGroup>();
+    registry.register::<EntityDef>();
+    registry.register::<TileRenderMode>();
+    registry.register::<NineSliceBorders>();
+    registry.register::<TilesetDef>();
+    registry.register::<TilesetRect>();
+    registry.register::<CustomData>();
+    registry.register::<EnumTag>();
+    registry.register::<EnumTagValue>();
+    registry.register::<EnumDef>();
+    registry.register::<EnumValue>();
+    registry.register::<FieldDef>();
+    registry.register::<FieldDefault>();
+}
 
-#[derive(Serialize, Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, Reflect)]
 pub struct LdtkColor {
     pub r: f32,
     pub g: f32,
     pub b: f32,
+    pub a: f32,
 }
 
-impl From<String> for LdtkColor {
-    fn from(value: String) -> Self {
-        let r = u8::from_str_radix(&value[1..3], 16).unwrap() as f32 / 255.;
-        let g = u8::from_str_radix(&value[3..5], 16).unwrap() as f32 / 255.;
-        let b = u8::from_str_radix(&value[5..7], 16).unwrap() as f32 / 255.;
-        Self { r, g, b }
+impl LdtkColor {
+    /// Parse a color in the format `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    pub fn parse(value: &str) -> Result<Self, ColorParseError> {
+        let hex = value
+            .strip_prefix('#')
+            .ok_or(ColorParseError::MissingHash)?
+            .as_bytes();
+        if !hex.iter().all(u8::is_ascii_hexdigit) {
+            return Err(ColorParseError::InvalidHex);
+        }
+
+        let digit = |i: usize| (hex[i] as char).to_digit(16).unwrap() as f32;
+        let channel = |i: usize| match hex.len() {
+            3 | 4 => digit(i) / 15.,
+            _ => (digit(i * 2) * 16. + digit(i * 2 + 1)) / 255.,
+        };
+
+        match hex.len() {
+            3 | 6 => Ok(Self {
+                r: channel(0),
+                g: channel(1),
+                b: channel(2),
+                a: 1.,
+            }),
+            4 | 8 => Ok(Self {
+                r: channel(0),
+                g: channel(1),
+                b: channel(2),
+                a: channel(3),
+            }),
+            len => Err(ColorParseError::InvalidLength(len)),
+        }
     }
 }
 
-impl Into<Color> for LdtkColor {
-    fn into(self) -> Color {
-        Color::rgb(self.r, self.g, self.b)
+/// The reason why a color string can't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorParseError {
+    MissingHash,
+    /// The number of hex digits, which should be 3, 4, 6 or 8.
+    InvalidLength(usize),
+    InvalidHex,
+}
+
+impl std::fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorParseError::MissingHash => f.write_str("color doesn't start with #"),
+            ColorParseError::InvalidLength(len) => {
+                write!(f, "color has {} hex digits, expected 3, 4, 6 or 8", len)
+            }
+            ColorParseError::InvalidHex => f.write_str("color contains non-hex characters"),
+        }
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
+impl From<String> for LdtkColor {
+    fn from(value: String) -> Self {
+        match Self::parse(&value) {
+            Ok(color) => color,
+            Err(e) => panic!("Failed to parse color {:?}: {}", value, e),
+        }
     }
 }
 
 impl Into<Vec4> for LdtkColor {
     fn into(self) -> Vec4 {
-        Vec4::new(self.r, self.g, self.b, 1.)
+        Vec4::new(self.r, self.g, self.b, self.a)
     }
 }
 
@@ -46,14 +159,14 @@ impl<'de> Deserialize<'de> for LdtkColor {
             type Value = LdtkColor;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("a color in the format #RRGGBB")
+                formatter.write_str("a color in the format #RRGGBB or #RRGGBBAA")
             }
 
             fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
             where
                 E: serde::de::Error,
             {
-                Ok(LdtkColor::from(value.to_string()))
+                LdtkColor::parse(value).map_err(E::custom)
             }
         }
 
@@ -61,12 +174,40 @@ impl<'de> Deserialize<'de> for LdtkColor {
     }
 }
 
+impl Serialize for LdtkColor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let channel = |c: f32| (c * 255.).round() as u8;
+        let rgb = format!(
+            "#{:02x}{:02x}{:02x}",
+            channel(self.r),
+            channel(self.g),
+            channel(self.b),
+        );
+        if self.a < 1. {
+            serializer.serialize_str(&format!("{}{:02x}", rgb, channel(self.a)))
+        } else {
+            serializer.serialize_str(&rgb)
+        }
+    }
+}
+
+/// An LDtk project, which can be parsed and queried without an `App`,
+/// for example to validate the levels in CI.
+pub type LdtkProject = LdtkJson;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
 #[serde(rename_all = "camelCase")]
 pub struct LdtkJson {
     /// Project background color
     pub bg_color: LdtkColor,
 
+    /// Default grid size for new layers.
+    /// This is also the grid size of the `Point` fields of levels.
+    pub default_grid_size: i32,
+
     /// A structure containing all the definitions of this project
     pub defs: Definitions,
 
@@ -128,6 +269,75 @@ pub struct LdtkJson {
     pub worlds: Vec<World>,
 }
 
+impl LdtkJson {
+    /// Parse the content of a `.ldtk` file.
+    ///
+    /// The layers of levels saved separately are not loaded.
+    #[inline]
+    pub fn parse_str(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// All the levels, including the ones in `worlds`.
+    pub fn iter_levels(&self) -> impl Iterator<Item = &Level> {
+        self.levels
+            .iter()
+            .chain(self.worlds.iter().flat_map(|w| w.levels.iter()))
+    }
+
+    #[inline]
+    pub fn find_level(&self, identifier: &str) -> Option<&Level> {
+        self.iter_levels().find(|l| l.identifier == identifier)
+    }
+
+    /// Find all the entity iids that are used by more than one entity
+    /// across all the levels (including the ones in `worlds`).
+    pub fn find_duplicate_iids(&self) -> Vec<DuplicateIid> {
+        let mut occurrences = HashMap::<&str, Vec<(String, String)>>::default();
+        self.iter_levels().for_each(|level| {
+            level
+                .layer_instances
+                .iter()
+                .flat_map(|layer| layer.entity_instances.iter())
+                .for_each(|entity| {
+                    occurrences
+                        .entry(entity.iid.as_str())
+                        .or_default()
+                        .push((level.identifier.clone(), entity.identifier.clone()));
+                });
+        });
+
+        let mut duplicates = occurrences
+            .into_iter()
+            .filter(|(_, occ)| occ.len() > 1)
+            .map(|(iid, occurrences)| DuplicateIid {
+                iid: iid.to_string(),
+                occurrences,
+            })
+            .collect::<Vec<_>>();
+        duplicates.sort_by(|a, b| a.iid.cmp(&b.iid));
+        duplicates
+    }
+}
+
+/// An entity iid that is shared by multiple entities.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateIid {
+    pub iid: String,
+    /// The `(level identifier, entity identifier)` of every entity using this iid.
+    pub occurrences: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for DuplicateIid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "iid {:?} is shared by:", self.iid)?;
+        for (level, entity) in &self.occurrences {
+            write!(f, " [{} in {}]", entity, level)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
 #[serde(rename_all = "camelCase")]
 pub struct Toc {
@@ -238,3 +448,241 @@ pub struct GridPoint {
     /// Y grid-based coordinate
     pub cy: i32,
 }
+
+/// Point fields are stored in grid coordinates relative to the **level**,
+/// not to the entity that owns them. The origin is the top left corner of the level
+/// and the y axis points down, just like the `px` of entities.
+///
+/// The world space positions below point at the center of the cell,
+/// and are converted with the `LdtkCoordConfig` the level is loaded with.
+impl GridPoint {
+    /// Get the position of the center of the cell in level pixel space (y down).
+    #[inline]
+    pub fn level_px(&self, grid_size: i32) -> Vec2 {
+        (Vec2::new(self.cx as f32, self.cy as f32) + 0.5) * grid_size as f32
+    }
+
+    /// Get the world position of the point.
+    ///
+    /// `level_translation` is the translation of the level entity,
+    /// which is placed at `LdtkCoordConfig::origin` of the level.
+    #[inline]
+    pub fn to_world(
+        &self,
+        level: &Level,
+        level_translation: Vec2,
+        grid_size: i32,
+        coords: &LdtkCoordConfig,
+    ) -> Vec2 {
+        coords.px_to_world(
+            self.level_px(grid_size),
+            level_translation,
+            Vec2::new(level.px_wid as f32, level.px_hei as f32),
+        )
+    }
+
+    /// Get the position of the point relative to the entity,
+    /// which is the translation you need when spawning a child of the entity.
+    #[inline]
+    pub fn to_entity_local(
+        &self,
+        entity: &EntityInstance,
+        grid_size: i32,
+        coords: &LdtkCoordConfig,
+    ) -> Vec2 {
+        let entity_px = Vec2::new(entity.local_pos[0] as f32, entity.local_pos[1] as f32);
+        coords.px_to_local(self.level_px(grid_size) - entity_px)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::sprite::Anchor;
+
+    use crate::ldtk::load_grid_vania;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_project() {
+        let project = LdtkProject::parse_str(
+            std::fs::read_to_string("assets/ldtk/grid_vania.ldtk")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(project.iter_levels().count(), project.levels.len());
+
+        let level = project.find_level("Entrance").unwrap();
+        let layer = level
+            .layer_instances
+            .iter()
+            .find(|l| l.identifier == "Collisions")
+            .unwrap();
+        assert_eq!(layer.auto_layer_tiles.len(), 344);
+
+        let item = level
+            .layer_instances
+            .iter()
+            .flat_map(|l| l.entity_instances.iter())
+            .find(|e| e.identifier == "Item")
+            .unwrap();
+        let count = item
+            .field_instances
+            .iter()
+            .find(|f| f.identifier == "count")
+            .unwrap();
+        assert!(matches!(count.value, Some(field::FieldValue::Integer(100))));
+        assert!(project.find_level("Nowhere").is_none());
+    }
+
+    #[test]
+    fn test_register_types() {
+        use std::any::TypeId;
+
+        use bevy::reflect::GetPath;
+
+        let mut registry = TypeRegistry::default();
+        register_types(&mut registry);
+        assert!(registry.get(TypeId::of::<LdtkJson>()).is_some());
+        assert!(registry.get(TypeId::of::<FieldInstance>()).is_some());
+        assert!(registry.get(TypeId::of::<FieldValue>()).is_some());
+        assert!(registry.get(TypeId::of::<FieldDefault>()).is_some());
+
+        let project = load_grid_vania();
+        let count = project
+            .find_level("Entrance")
+            .unwrap()
+            .layer_instances
+            .iter()
+            .flat_map(|l| l.entity_instances.iter())
+            .filter(|e| e.identifier == "Item")
+            .flat_map(|e| e.field_instances.iter())
+            .find(|f| f.identifier == "count")
+            .unwrap();
+        assert_eq!(
+            count.path::<String>("identifier").unwrap().as_str(),
+            "count"
+        );
+        assert_eq!(*count.path::<i32>("value.0.0").unwrap(), 100);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        let color = LdtkColor::parse("#fff").unwrap();
+        assert_eq!((color.r, color.g, color.b, color.a), (1., 1., 1., 1.));
+
+        let color = LdtkColor::parse("#ff000080").unwrap();
+        assert_eq!((color.r, color.g, color.b), (1., 0., 0.));
+        assert_eq!(color.a, 128. / 255.);
+
+        let color = LdtkColor::parse("#ffffffff").unwrap();
+        assert_eq!(color.a, 1.);
+        let color = LdtkColor::parse("#3399cc").unwrap();
+        assert_eq!((color.r, color.g, color.b), (0.2, 0.6, 0.8));
+
+        assert_eq!(
+            LdtkColor::parse("ffffff").unwrap_err(),
+            ColorParseError::MissingHash
+        );
+        assert_eq!(
+            LdtkColor::parse("#fffff").unwrap_err(),
+            ColorParseError::InvalidLength(5)
+        );
+        assert_eq!(
+            LdtkColor::parse("#").unwrap_err(),
+            ColorParseError::InvalidLength(0)
+        );
+        assert_eq!(
+            LdtkColor::parse("#ffgfff").unwrap_err(),
+            ColorParseError::InvalidHex
+        );
+        // A multi-byte character shouldn't be sliced.
+        assert_eq!(
+            LdtkColor::parse("#ffé").unwrap_err(),
+            ColorParseError::InvalidHex
+        );
+        assert!(serde_json::from_str::<LdtkColor>("\"#12345\"").is_err());
+    }
+
+    #[test]
+    fn test_point_field_to_world() {
+        let json = load_grid_vania();
+        let level = &json.levels[0];
+        let layer = level
+            .layer_instances
+            .iter()
+            .find(|l| !l.entity_instances.is_empty())
+            .unwrap();
+        let mut entity = layer.entity_instances[0].clone();
+        entity.local_pos = [3 * layer.grid_size, 5 * layer.grid_size];
+
+        let point = GridPoint { cx: 4, cy: 2 };
+        let grid = layer.grid_size as f32;
+        let level_translation = Vec2::new(level.world_x as f32, -level.world_y as f32);
+        let coords = LdtkCoordConfig::default();
+        let entity_world =
+            level_translation + Vec2::new(entity.local_pos[0] as f32, -entity.local_pos[1] as f32);
+
+        assert_eq!(
+            point.to_world(level, level_translation, layer.grid_size, &coords),
+            level_translation + Vec2::new(4.5 * grid, -2.5 * grid)
+        );
+        assert_eq!(
+            point.to_entity_local(&entity, layer.grid_size, &coords),
+            Vec2::new(1.5 * grid, 2.5 * grid)
+        );
+        assert_eq!(
+            entity_world + point.to_entity_local(&entity, layer.grid_size, &coords),
+            point.to_world(level, level_translation, layer.grid_size, &coords)
+        );
+
+        // With the y axis down and the level centered, the point is still
+        // in the same place relative to the cells and the entity.
+        let coords = LdtkCoordConfig {
+            y_up: false,
+            origin: Anchor::Center,
+        };
+        let level_size = Vec2::new(level.px_wid as f32, level.px_hei as f32);
+        let corner = level_translation - level_size / 2.;
+        let entity_world =
+            corner + Vec2::new(entity.local_pos[0] as f32, entity.local_pos[1] as f32);
+        assert_eq!(
+            point.to_world(level, level_translation, layer.grid_size, &coords),
+            corner + Vec2::new(4.5 * grid, 2.5 * grid)
+        );
+        assert_eq!(
+            point.to_entity_local(&entity, layer.grid_size, &coords),
+            Vec2::new(1.5 * grid, -2.5 * grid)
+        );
+        assert_eq!(
+            entity_world + point.to_entity_local(&entity, layer.grid_size, &coords),
+            point.to_world(level, level_translation, layer.grid_size, &coords)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_iids() {
+        let mut json = load_grid_vania();
+        assert!(json.find_duplicate_iids().is_empty());
+
+        let (layer_index, entity) = json.levels[0]
+            .layer_instances
+            .iter()
+            .enumerate()
+            .find_map(|(i, l)| l.entity_instances.first().map(|e| (i, e.clone())))
+            .unwrap();
+        let level_ident = json.levels[0].identifier.clone();
+        json.levels[0].layer_instances[layer_index]
+            .entity_instances
+            .push(entity.clone());
+
+        let duplicates = json.find_duplicate_iids();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].iid, entity.iid);
+        assert_eq!(
+            duplicates[0].occurrences,
+            vec![(level_ident.clone(), entity.identifier.clone()); 2]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::{fmt::Formatter, io::Read};
+
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Value};
+
+use super::{level::Level, LdtkJson};
+
+impl LdtkJson {
+    /// Parse a `.ldtk` file without keeping all the levels in memory.
+    ///
+    /// Each level is passed to `on_level` as soon as it's deserialized and dropped
+    /// afterwards, so only one level is held at a time. This is useful for huge
+    /// single-file projects, for example with embedded background images.
+    ///
+    /// The returned project contains everything else, with empty `levels`
+    /// in both the root and `worlds`.
+    ///
+    /// Wrap files in a `BufReader`, as reading byte by byte is slow.
+    pub fn stream_levels(
+        reader: impl Read,
+        mut on_level: impl FnMut(Level),
+    ) -> serde_json::Result<Self> {
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let project = StreamedObject {
+            on_level: &mut on_level,
+            with_worlds: true,
+        }
+        .deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        serde_json::from_value(project)
+    }
+}
+
+/// Deserializes the project or a world into a `Value`,
+/// except the levels which are handed out one by one.
+struct StreamedObject<'a, F: FnMut(Level)> {
+    on_level: &'a mut F,
+    /// Whether this is the project, which can contain worlds.
+    with_worlds: bool,
+}
+
+impl<'de, 'a, F: FnMut(Level)> DeserializeSeed<'de> for StreamedObject<'a, F> {
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(Level)> Visitor<'de> for StreamedObject<'a, F> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("an LDtk project or world")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut object = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = match key.as_str() {
+                "levels" => {
+                    map.next_value_seed(StreamedLevels(&mut *self.on_level))?;
+                    Value::Array(Vec::new())
+                }
+                "worlds" if self.with_worlds => {
+                    map.next_value_seed(StreamedWorlds(&mut *self.on_level))?
+                }
+                _ => map.next_value()?,
+            };
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+struct StreamedWorlds<'a, F: FnMut(Level)>(&'a mut F);
+
+impl<'de, 'a, F: FnMut(Level)> DeserializeSeed<'de> for StreamedWorlds<'a, F> {
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(Level)> Visitor<'de> for StreamedWorlds<'a, F> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of worlds")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut worlds = Vec::new();
+        while let Some(world) = seq.next_element_seed(StreamedObject {
+            on_level: &mut *self.0,
+            with_worlds: false,
+        })? {
+            worlds.push(world);
+        }
+        Ok(Value::Array(worlds))
+    }
+}
+
+struct StreamedLevels<'a, F: FnMut(Level)>(&'a mut F);
+
+impl<'de, 'a, F: FnMut(Level)> DeserializeSeed<'de> for StreamedLevels<'a, F> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(Level)> Visitor<'de> for StreamedLevels<'a, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of levels")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(level) = seq.next_element::<Level>()? {
+            (self.0)(level);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, io::BufReader};
+
+    use super::*;
+
+    #[test]
+    fn test_stream_levels() {
+        let path = "assets/ldtk/grid_vania.ldtk";
+        let full = LdtkJson::parse_str(std::fs::read_to_string(path).unwrap().as_str()).unwrap();
+
+        // Levels are moved into the closure, so nothing else can hold them.
+        // Field instances need owned keys as the reader can't lend borrowed strings.
+        let mut levels = Vec::new();
+        let project = LdtkJson::stream_levels(BufReader::new(File::open(path).unwrap()), |level| {
+            levels.push((level.identifier, level.field_instances.len()))
+        })
+        .unwrap();
+
+        assert!(project.iter_levels().next().is_none());
+        assert_eq!(project.iid, full.iid);
+        assert_eq!(project.defs.layers.len(), full.defs.layers.len());
+        assert_eq!(
+            levels,
+            full.iter_levels()
+                .map(|l| (l.identifier.clone(), l.field_instances.len()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
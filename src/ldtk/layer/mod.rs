@@ -2,10 +2,10 @@ use bevy::{
     asset::{AssetServer, Assets},
     ecs::{
         component::Component,
-        entity::Entity,
+        entity::{Entities, Entity},
         system::{Commands, EntityCommands},
     },
-    math::{IVec2, Vec2},
+    math::{IVec2, UVec2, Vec2},
     prelude::SpatialBundle,
     render::color::Color,
     sprite::SpriteBundle,
@@ -30,26 +30,25 @@ use crate::{
 };
 
 use super::{
-    components::{EntityIid, LayerIid, LdtkLoadedLevel, LdtkTempTransform, LevelIid},
+    components::{
+        EntityIid, LayerIid, LdtkFields, LdtkLoadedLevel, LdtkTempTransform, LdtkUnknownEntity,
+        LevelIid,
+    },
     json::{
         field::FieldInstance,
         level::{EntityInstance, LayerInstance, Level, TileInstance},
     },
-    resources::{LdtkAssets, LdtkLoadConfig, LdtkPatterns},
+    resources::{LdtkAssets, LdtkLoadConfig, LdtkPatterns, UnknownEntityPolicy},
+    sprite::LdtkEntityAnimation,
     traits::{LdtkEntityRegistry, LdtkEntityTagRegistry},
     LdtkLoaderMode,
 };
 
 #[cfg(feature = "algorithm")]
-use crate::{
-    algorithm::pathfinding::PathTilemaps,
-    tilemap::{algorithm::path::PathTilemap, chunking::storage::ChunkedStorage},
-};
+use crate::algorithm::{nav_grid::NavGrid, pathfinding::PathTilemaps};
 
 #[cfg(feature = "physics")]
 use crate::tilemap::physics::{DataPhysicsTilemap, SerializablePhysicsSource};
-#[cfg(feature = "physics")]
-use bevy::math::UVec2;
 
 #[cfg(feature = "algorithm")]
 pub mod path;
@@ -74,18 +73,50 @@ impl PackedLdtkEntity {
         ldtk_assets: &LdtkAssets,
         asset_server: &AssetServer,
     ) {
-        let phantom_entity = {
-            if let Some(e) = entity_registry.get(&self.instance.identifier) {
-                e
-            } else if !config.ignore_unregistered_entities {
-                panic!(
-                    "Could not find entity type with entity identifier: {}! \
-                    You need to register it using App::register_ldtk_entity::<T>() first!",
-                    self.instance.identifier
-                );
-            } else {
-                return;
+        if let Some(fields) = self.initialize(
+            commands,
+            entity_registry,
+            entity_tag_registry,
+            config,
+            ldtk_assets,
+            asset_server,
+        ) {
+            commands.insert(fields);
+        }
+    }
+
+    /// Run the custom setup of the entity, and return the fields
+    /// that still need to be inserted if it's a registered entity.
+    fn initialize(
+        self,
+        commands: &mut EntityCommands,
+        entity_registry: &LdtkEntityRegistry,
+        entity_tag_registry: &LdtkEntityTagRegistry,
+        config: &LdtkLoadConfig,
+        ldtk_assets: &LdtkAssets,
+        asset_server: &AssetServer,
+    ) -> Option<LdtkFields> {
+        let Some(phantom_entity) = entity_registry.get(&self.instance.identifier) else {
+            match config.unknown_entity_policy {
+                UnknownEntityPolicy::Skip
+                | UnknownEntityPolicy::Warn
+                | UnknownEntityPolicy::Error => {}
+                UnknownEntityPolicy::SpawnPlaceholder => {
+                    let translation = self
+                        .instance
+                        .anchored_translation(self.instance.pivot_anchor());
+                    commands.insert((
+                        LdtkUnknownEntity {
+                            identifier: self.instance.identifier,
+                        },
+                        LdtkFields(self.fields),
+                        SpatialBundle::from_transform(Transform::from_translation(
+                            translation.extend(0.),
+                        )),
+                    ));
+                }
             }
+            return None;
         };
 
         self.instance.tags.iter().for_each(|tag| {
@@ -101,18 +132,55 @@ impl PackedLdtkEntity {
             }
         });
 
+        let sprite = self.instance.sprite_sheet_bundle(ldtk_assets);
+        if let Some(animation) = config
+            .entity_animation
+            .as_ref()
+            .zip(sprite.as_ref())
+            .and_then(|(names, sprite)| {
+                LdtkEntityAnimation::from_fields(&self.fields, names, sprite.atlas.index)
+            })
+        {
+            commands.insert(animation);
+        }
+
         phantom_entity.spawn(
             commands,
             &self.instance,
             &self.fields,
             asset_server,
             ldtk_assets,
-        )
+            sprite,
+        );
+        Some(LdtkFields(self.fields))
     }
 }
 
 pub type LayerOpacity = f32;
 
+/// The physics layer along with its int grid, size and slopes.
+#[cfg(feature = "physics")]
+pub type PhysicsLayerData = (
+    physics::LdtkPhysicsLayer,
+    Vec<i32>,
+    UVec2,
+    HashMap<UVec2, crate::tilemap::physics::TileSlope>,
+);
+
+/// The resources and config `LdtkLayers::apply_all` spawns a level with.
+pub struct LdtkLayerContext<'a> {
+    pub ldtk_patterns: &'a mut LdtkPatterns,
+    pub entity_registry: &'a LdtkEntityRegistry,
+    pub entity_tag_registry: &'a LdtkEntityTagRegistry,
+    pub config: &'a LdtkLoadConfig,
+    pub ldtk_assets: &'a LdtkAssets,
+    pub asset_server: &'a AssetServer,
+    pub material_assets: &'a mut Assets<StandardTilemapMaterial>,
+    pub textures_assets: &'a mut Assets<TilemapTextures>,
+    #[cfg(feature = "algorithm")]
+    pub path_tilemaps: &'a mut PathTilemaps,
+}
+
 #[derive(Component)]
 pub struct LdtkLayers {
     pub ty: LdtkLoaderMode,
@@ -130,7 +198,7 @@ pub struct LdtkLayers {
         HashMap<IVec2, crate::tilemap::algorithm::path::PathTile>,
     )>,
     #[cfg(feature = "physics")]
-    pub physics_layer: Option<(physics::LdtkPhysicsLayer, Vec<i32>, UVec2)>,
+    pub physics_layer: Option<PhysicsLayerData>,
 }
 
 impl LdtkLayers {
@@ -170,32 +238,42 @@ impl LdtkLayers {
         patterns: &LdtkPatterns,
         mode: &LdtkLoaderMode,
     ) {
-        self.try_create_new_layer(layer_index, layer);
-
-        let (pattern, texture, _, _) = self.layers[layer_index].as_mut().unwrap();
-        let tile_size = texture.desc.tile_size;
-        let tile_index = IVec2 {
-            x: tile.px[0] / tile_size.x as i32,
-            y: match mode {
-                LdtkLoaderMode::Tilemap => -tile.px[1] / tile_size.y as i32 - 1,
-                LdtkLoaderMode::MapPattern => {
-                    patterns.pattern_size.y as i32 - tile.px[1] / tile_size.y as i32 - 1
-                }
+        let y_up = *mode == LdtkLoaderMode::MapPattern || config.coords.y_up;
+        self.try_create_new_layer(layer_index, layer, y_up);
+
+        let (pattern, _, _, _) = self.layers[layer_index].as_mut().unwrap();
+        // The tiles can be larger than the grid, so the index depends on the grid only.
+        let grid_size = layer.grid_size;
+        let px = IVec2::new(tile.px[0], tile.px[1]);
+        let tile_index = match mode {
+            LdtkLoaderMode::Tilemap => config.coords.tile_index(px, UVec2::splat(grid_size as u32)),
+            LdtkLoaderMode::MapPattern => IVec2 {
+                x: px.x / grid_size,
+                y: patterns.pattern_size.y as i32 - px.y / grid_size - 1,
             },
         };
         let atlas_index = tile.tile_id;
 
         if let Some(ser_tile) = pattern.tiles.get_mut(tile_index) {
-            let TileTexture::Static(tile_layers) = &mut ser_tile.texture else {
-                panic!(
-                    "Trying to insert multiple layers into a animated tile at {}!",
-                    tile_index
-                );
-            };
-            tile_layers.push(TileLayer {
-                atlas_index,
-                ..Default::default()
-            });
+            // A tile can only play one animation, so when animated tiles are stacked
+            // (like animated edges generated by auto layers) the upper one replaces the lower ones.
+            if let Some(anim) = config.animation_mapper.get(&(atlas_index as u32)) {
+                ser_tile.texture = TileTexture::Animated(pattern.animations.register(anim.clone()));
+            } else if let TileTexture::Static(tile_layers) = &mut ser_tile.texture {
+                tile_layers.push(TileLayer {
+                    atlas_index,
+                    flip: TileFlip::from_ldtk(tile.flip),
+                    ..Default::default()
+                });
+            } else {
+                ser_tile.texture = TileTexture::Static(vec![TileLayer {
+                    #[cfg(feature = "atlas")]
+                    texture_index: 0,
+                    atlas_index,
+                    flip: TileFlip::from_ldtk(tile.flip),
+                    ..Default::default()
+                }]);
+            }
         } else {
             let mut builder =
                 TileBuilder::new().with_tint(Color::rgba_linear(1., 1., 1., tile.alpha));
@@ -210,7 +288,8 @@ impl LdtkLayers {
                             #[cfg(feature = "atlas")]
                             texture_index: 0,
                             atlas_index,
-                            flip: TileFlip::from_bits(tile.flip as u32).unwrap(),
+                            flip: TileFlip::from_ldtk(tile.flip),
+                            ..Default::default()
                         },
                     )
                 }
@@ -224,7 +303,7 @@ impl LdtkLayers {
         self.entities.push(entity);
     }
 
-    fn try_create_new_layer(&mut self, layer_index: usize, layer: &LayerInstance) {
+    fn try_create_new_layer(&mut self, layer_index: usize, layer: &LayerInstance, y_up: bool) {
         let tileset = self
             .tilesets
             .get(&layer.tileset_def_uid.unwrap())
@@ -235,9 +314,16 @@ impl LdtkLayers {
             return;
         }
 
-        let aabb = IAabb2d {
-            min: IVec2::new(0, -layer.c_hei + 1),
-            max: IVec2::new(layer.c_wid - 1, 0),
+        let aabb = if y_up {
+            IAabb2d {
+                min: IVec2::new(0, -layer.c_hei + 1),
+                max: IVec2::new(layer.c_wid - 1, 0),
+            }
+        } else {
+            IAabb2d {
+                min: IVec2::ZERO,
+                max: IVec2::new(layer.c_wid - 1, layer.c_hei - 1),
+            }
         };
 
         self.layers[layer_index] = Some((
@@ -268,34 +354,66 @@ impl LdtkLayers {
     pub fn apply_all(
         &mut self,
         commands: &mut Commands,
-        ldtk_patterns: &mut LdtkPatterns,
-        entity_registry: &LdtkEntityRegistry,
-        entity_tag_registry: &LdtkEntityTagRegistry,
-        config: &LdtkLoadConfig,
-        ldtk_assets: &LdtkAssets,
-        asset_server: &AssetServer,
-        material_assets: &mut Assets<StandardTilemapMaterial>,
-        textures_assets: &mut Assets<TilemapTextures>,
-        #[cfg(feature = "algorithm")] path_tilemaps: &mut PathTilemaps,
+        reserved_entities: &Entities,
+        context: LdtkLayerContext,
     ) {
+        let LdtkLayerContext {
+            ldtk_patterns,
+            entity_registry,
+            entity_tag_registry,
+            config,
+            ldtk_assets,
+            asset_server,
+            material_assets,
+            textures_assets,
+            #[cfg(feature = "algorithm")]
+            path_tilemaps,
+        } = context;
+
         match self.ty {
             LdtkLoaderMode::Tilemap => {
                 let mut layers = HashMap::with_capacity(self.layers.len());
                 let mut entities = HashMap::with_capacity(self.entities.len());
 
-                self.entities.drain(..).for_each(|entity| {
-                    let mut ldtk_entity =
-                        commands.spawn((entity.transform.clone(), entity.iid.clone()));
-                    entities.insert(entity.iid.clone(), ldtk_entity.id());
-                    entity.instantiate(
-                        &mut ldtk_entity,
-                        entity_registry,
-                        entity_tag_registry,
-                        config,
-                        ldtk_assets,
-                        asset_server,
-                    );
-                });
+                // Reserve all the entities at once and insert the components they
+                // have in common in batches, so only the custom setup of each entity
+                // costs separate commands.
+                let reserved = reserved_entities
+                    .reserve_entities(self.entities.len() as u32)
+                    .collect::<Vec<_>>();
+                commands.insert_or_spawn_batch(
+                    reserved
+                        .iter()
+                        .zip(self.entities.iter())
+                        .map(|(e, entity)| {
+                            config.duplicate_iid_resolution.insert(
+                                &mut entities,
+                                entity.iid.clone(),
+                                *e,
+                                config.duplicate_iid_policy,
+                            );
+                            (*e, (entity.transform.clone(), entity.iid.clone()))
+                        })
+                        .collect::<Vec<_>>(),
+                );
+
+                let mut fields = Vec::with_capacity(reserved.len());
+                reserved
+                    .into_iter()
+                    .zip(self.entities.drain(..))
+                    .for_each(|(e, entity)| {
+                        if let Some(f) = entity.initialize(
+                            &mut commands.entity(e),
+                            entity_registry,
+                            entity_tag_registry,
+                            config,
+                            ldtk_assets,
+                            asset_server,
+                        ) {
+                            fields.push((e, f));
+                        }
+                    });
+                commands.insert_or_spawn_batch(fields);
 
                 self.layers
                     .drain(..)
@@ -303,17 +421,19 @@ impl LdtkLayers {
                     .filter_map(|(i, e)| if let Some(e) = e { Some((i, e)) } else { None })
                     .for_each(|(index, (pattern, texture, iid, opacity))| {
                         let tilemap_entity = commands.spawn_empty().id();
+                        let (tile_render_size, slot_size) =
+                            tile_sizes(&self.level.layer_instances[index], &texture);
                         let mut tilemap = StandardTilemapBundle {
                             name: TilemapName(pattern.label.clone().unwrap()),
                             ty: TilemapType::Square,
-                            tile_render_size: TileRenderSize(texture.desc.tile_size.as_vec2()),
-                            slot_size: TilemapSlotSize(texture.desc.tile_size.as_vec2()),
+                            tile_render_size,
+                            slot_size,
                             textures: textures_assets
                                 .add(TilemapTextures::single(texture.clone(), config.filter_mode)),
                             storage: TilemapStorage::new(DEFAULT_CHUNK_SIZE, tilemap_entity),
                             transform: TilemapTransform {
                                 translation: self.translation,
-                                z_index: self.base_z_index - index as f32 - 1.,
+                                z_index: self.base_z_index + config.layer_z.layer(index),
                                 ..Default::default()
                             },
                             material: material_assets.add(StandardTilemapMaterial::default()),
@@ -327,38 +447,63 @@ impl LdtkLayers {
                             .fill_with_buffer(commands, IVec2::ZERO, pattern.tiles);
 
                         #[cfg(feature = "algorithm")]
-                        if let Some((path_layer, path_tilemap)) = &self.path_layer {
+                        if let Some((path_layer, path_tiles)) = &self.path_layer {
                             if path_layer.parent == tilemap.name.0 {
-                                path_tilemaps.insert(
-                                    tilemap_entity,
-                                    PathTilemap {
-                                        storage: ChunkedStorage::from_mapper(
-                                            path_tilemap.clone(),
-                                            None,
-                                        ),
-                                    },
-                                );
+                                let path_tilemap = path::path_tilemap(path_tiles, &config.coords);
+
+                                if path_layer.nav_grid {
+                                    let aabb =
+                                        path_tilemap.storage.occupied_aabb().unwrap_or_default();
+                                    let mut nav_grid = NavGrid::new(
+                                        tilemap_entity,
+                                        TilemapType::Square,
+                                        aabb.min,
+                                        aabb.size().as_uvec2(),
+                                    );
+                                    nav_grid.rebuild(&path_tilemap);
+                                    commands.entity(self.level_entity).insert(nav_grid);
+                                }
+
+                                path_tilemaps.insert(tilemap_entity, path_tilemap);
                             }
                         }
 
                         #[cfg(feature = "physics")]
-                        if let Some((physics_layer, physics_data, size)) = &self.physics_layer {
+                        if let Some((physics_layer, physics_data, size, slopes)) =
+                            &self.physics_layer
+                        {
                             if pattern.label.clone().unwrap() == physics_layer.parent {
-                                commands
-                                    .entity(tilemap_entity)
-                                    .insert(DataPhysicsTilemap::new(
+                                let tiles = physics_layer.tiles.clone().unwrap_or_default();
+                                // The rows of the data are from top to bottom like the tiles
+                                // of the layer, so only flip them when the y axis points up.
+                                let physics_tilemap = if config.coords.y_up {
+                                    DataPhysicsTilemap::new(
                                         IVec2::new(0, -(size.y as i32)),
                                         physics_data.clone(),
                                         *size,
                                         physics_layer.air,
-                                        physics_layer.tiles.clone().unwrap_or_default(),
-                                    ));
+                                        tiles,
+                                    )
+                                } else {
+                                    DataPhysicsTilemap::new_flipped(
+                                        IVec2::ZERO,
+                                        physics_data.clone(),
+                                        *size,
+                                        physics_layer.air,
+                                        tiles,
+                                    )
+                                };
+                                commands
+                                    .entity(tilemap_entity)
+                                    .insert(physics_tilemap.with_slopes(slopes.clone()));
                             }
                         }
 
-                        commands
-                            .entity(tilemap_entity)
-                            .insert((tilemap, iid.clone()));
+                        commands.entity(tilemap_entity).insert((
+                            tilemap,
+                            iid.clone(),
+                            config.coords.tile_anchor(),
+                        ));
                         layers.insert(iid, tilemap_entity);
                     });
 
@@ -372,10 +517,24 @@ impl LdtkLayers {
                         background: bg,
                     },
                     SpatialBundle {
-                        transform: Transform::from_translation(self.translation.extend(0.)),
+                        transform: Transform::from_translation(
+                            (self.translation
+                                - config.coords.level_corner(Vec2::new(
+                                    self.level.px_wid as f32,
+                                    self.level.px_hei as f32,
+                                )))
+                            .extend(0.),
+                        ),
                         ..Default::default()
                     },
                     LevelIid(self.level.iid.clone()),
+                    LdtkFields(
+                        self.level
+                            .field_instances
+                            .iter()
+                            .map(|field| (field.identifier.clone(), field.clone()))
+                            .collect(),
+                    ),
                 ));
             }
             LdtkLoaderMode::MapPattern => {
@@ -397,17 +556,19 @@ impl LdtkLayers {
                         }
 
                         #[cfg(feature = "physics")]
-                        if let Some((physics_layer, physics_data, size)) =
+                        if let Some((physics_layer, physics_data, size, slopes)) =
                             self.physics_layer.as_ref()
                         {
-                            pattern.physics_tiles =
-                                SerializablePhysicsSource::Data(DataPhysicsTilemap::new(
+                            pattern.physics_tiles = SerializablePhysicsSource::Data(
+                                DataPhysicsTilemap::new(
                                     IVec2::ZERO,
                                     physics_data.clone(),
                                     *size,
                                     physics_layer.air,
                                     physics_layer.tiles.clone().unwrap_or_default(),
-                                ));
+                                )
+                                .with_slopes(slopes.clone()),
+                            );
                         }
 
                         ldtk_patterns.add_pattern(
@@ -427,6 +588,20 @@ impl LdtkLayers {
         }
     }
 
+    /// The sorted identifiers of the entities in this level that aren't registered.
+    pub fn unknown_entities(&self, entity_registry: &LdtkEntityRegistry) -> Vec<String> {
+        let mut unknown = self
+            .entities
+            .iter()
+            .map(|e| &e.instance.identifier)
+            .filter(|ident| !entity_registry.contains_key(*ident))
+            .cloned()
+            .collect::<Vec<_>>();
+        unknown.sort();
+        unknown.dedup();
+        unknown
+    }
+
     #[cfg(feature = "algorithm")]
     pub fn assign_path_layer(
         &mut self,
@@ -442,7 +617,408 @@ impl LdtkLayers {
         physics_layer: physics::LdtkPhysicsLayer,
         physics_data: Vec<i32>,
         size: UVec2,
+        slopes: HashMap<UVec2, crate::tilemap::physics::TileSlope>,
     ) {
-        self.physics_layer = Some((physics_layer, physics_data, size));
+        self.physics_layer = Some((physics_layer, physics_data, size, slopes));
+    }
+}
+
+/// The render size and slot size of the tilemap of a layer.
+///
+/// Tiles in LDtk can be larger than the grid of the layer, in which case
+/// they are rendered at their full size and overhang the neighbouring cells.
+pub fn tile_sizes(
+    layer: &LayerInstance,
+    texture: &TilemapTexture,
+) -> (TileRenderSize, TilemapSlotSize) {
+    (
+        TileRenderSize(texture.desc.tile_size.as_vec2()),
+        TilemapSlotSize(Vec2::splat(layer.grid_size as f32)),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::asset::Handle;
+
+    use crate::{
+        ldtk::{json::definitions::LayerType, load_grid_vania},
+        tilemap::{
+            coordinates::tile_mesh_corners, map::TilemapTextureDescriptor, tile::RawTileAnimation,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_animated_auto_tiles() {
+        let json = load_grid_vania();
+        let level = &json.levels[0];
+        let (layer_index, layer) = level
+            .layer_instances
+            .iter()
+            .enumerate()
+            .find(|(_, l)| l.ty == LayerType::AutoLayer && !l.auto_layer_tiles.is_empty())
+            .unwrap();
+
+        let mut assets = LdtkAssets::default();
+        assets.tilesets.insert(
+            layer.tileset_def_uid.unwrap(),
+            TilemapTexture {
+                texture: Handle::default(),
+                desc: TilemapTextureDescriptor {
+                    size: UVec2::splat(layer.grid_size as u32 * 8),
+                    tile_size: UVec2::splat(layer.grid_size as u32),
+                },
+            },
+        );
+        let mut config = LdtkLoadConfig::default();
+        config.animation_mapper.insert(
+            2,
+            RawTileAnimation {
+                #[cfg(not(feature = "atlas"))]
+                sequence: vec![2, 3, 4],
+                #[cfg(feature = "atlas")]
+                sequence: vec![(0, 2), (0, 3), (0, 4)],
+                fps: 2,
+            },
+        );
+        let mut layers = LdtkLayers::new(
+            Entity::PLACEHOLDER,
+            level,
+            level.layer_instances.len(),
+            &assets,
+            Vec2::ZERO,
+            0.,
+            LdtkLoaderMode::Tilemap,
+            SpriteBundle::default(),
+        );
+
+        // A static ground tile with an animated edge on top of it.
+        let ground = TileInstance {
+            tile_id: 1,
+            ..layer.auto_layer_tiles[0].clone()
+        };
+        let edge = TileInstance {
+            tile_id: 2,
+            ..ground.clone()
+        };
+        let patterns = LdtkPatterns::default();
+        for tile in [&ground, &edge] {
+            layers.set_tile(
+                layer_index,
+                layer,
+                tile,
+                &config,
+                &patterns,
+                &LdtkLoaderMode::Tilemap,
+            );
+        }
+
+        let (pattern, _, _, _) = layers.layers[layer_index].as_ref().unwrap();
+        assert_eq!(pattern.tiles.tiles.len(), 1);
+        let TileTexture::Animated(anim) = &pattern.tiles.tiles.values().next().unwrap().texture
+        else {
+            panic!("The edge tile should be animated!");
+        };
+        let frames = (0..4)
+            .map(|i| {
+                pattern
+                    .animations
+                    .frame_at(anim, i as f32 * 0.5)
+                    .atlas_index
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![2, 3, 4, 2]);
+    }
+
+    #[test]
+    fn test_oversized_tiles() {
+        let json = load_grid_vania();
+        let level = &json.levels[0];
+        let (layer_index, layer) = level
+            .layer_instances
+            .iter()
+            .enumerate()
+            .find(|(_, l)| l.ty == LayerType::AutoLayer && !l.auto_layer_tiles.is_empty())
+            .unwrap();
+        let grid = layer.grid_size;
+
+        // The tiles are twice as large as the grid.
+        let texture = TilemapTexture {
+            texture: Handle::default(),
+            desc: TilemapTextureDescriptor {
+                size: UVec2::splat(grid as u32 * 16),
+                tile_size: UVec2::splat(grid as u32 * 2),
+            },
+        };
+        let mut assets = LdtkAssets::default();
+        assets
+            .tilesets
+            .insert(layer.tileset_def_uid.unwrap(), texture.clone());
+        let config = LdtkLoadConfig::default();
+        let mut layers = LdtkLayers::new(
+            Entity::PLACEHOLDER,
+            level,
+            level.layer_instances.len(),
+            &assets,
+            Vec2::ZERO,
+            0.,
+            LdtkLoaderMode::Tilemap,
+            SpriteBundle::default(),
+        );
+
+        let patterns = LdtkPatterns::default();
+        for x in 0..2 {
+            let tile = TileInstance {
+                px: [x * grid, 0],
+                ..layer.auto_layer_tiles[0].clone()
+            };
+            layers.set_tile(
+                layer_index,
+                layer,
+                &tile,
+                &config,
+                &patterns,
+                &LdtkLoaderMode::Tilemap,
+            );
+        }
+
+        // Neighbouring cells are still different tiles.
+        let (pattern, _, _, _) = layers.layers[layer_index].as_ref().unwrap();
+        assert!(pattern.tiles.tiles.contains_key(&IVec2::new(0, -1)));
+        assert!(pattern.tiles.tiles.contains_key(&IVec2::new(1, -1)));
+
+        let (render_size, slot_size) = tile_sizes(layer, &texture);
+        assert_eq!(render_size.0, Vec2::splat(grid as f32 * 2.));
+        assert_eq!(slot_size.0, Vec2::splat(grid as f32));
+
+        // The tile at the top left corner of the level overhangs the cells below and to the right.
+        let grid = grid as f32;
+        let corners = tile_mesh_corners(
+            IVec2::new(0, -1),
+            TilemapType::Square,
+            Vec2::ZERO,
+            config.coords.tile_anchor().0,
+            render_size.0,
+            slot_size.0,
+        );
+        assert_eq!(corners[0], Vec2::new(0., -2. * grid));
+        assert_eq!(corners[2], Vec2::new(2. * grid, 0.));
+    }
+
+    #[test]
+    fn test_level_fields() {
+        use bevy::{
+            asset::{io::AssetSourceBuilders, AssetServerMode},
+            ecs::{system::CommandQueue, world::World},
+        };
+
+        use crate::ldtk::json::field::FieldValue;
+
+        let json = load_grid_vania();
+        let mut level = json.levels[0].clone();
+        level.field_instances.push(FieldInstance {
+            def_uid: 0,
+            identifier: "music_track".to_string(),
+            tile: None,
+            ty: "String".to_string(),
+            value: Some(FieldValue::String("boss.ogg".to_string())),
+        });
+
+        let mut sources = AssetSourceBuilders::default();
+        sources.init_default_source("assets", None);
+        let asset_server = AssetServer::new(
+            sources.build_sources(false, false),
+            AssetServerMode::Unprocessed,
+            false,
+        );
+
+        let mut world = World::new();
+        let level_entity = world.spawn_empty().id();
+        let mut layers = LdtkLayers::new(
+            level_entity,
+            &level,
+            level.layer_instances.len(),
+            &LdtkAssets::default(),
+            Vec2::ZERO,
+            0.,
+            LdtkLoaderMode::Tilemap,
+            SpriteBundle::default(),
+        );
+
+        let mut queue = CommandQueue::default();
+        layers.apply_all(
+            &mut Commands::new(&mut queue, &world),
+            world.entities(),
+            LdtkLayerContext {
+                ldtk_patterns: &mut LdtkPatterns::default(),
+                entity_registry: &LdtkEntityRegistry::default(),
+                entity_tag_registry: &LdtkEntityTagRegistry::default(),
+                config: &LdtkLoadConfig::default(),
+                ldtk_assets: &LdtkAssets::default(),
+                asset_server: &asset_server,
+                material_assets: &mut Assets::default(),
+                textures_assets: &mut Assets::default(),
+                #[cfg(feature = "algorithm")]
+                path_tilemaps: &mut PathTilemaps::default(),
+            },
+        );
+        queue.apply(&mut world);
+
+        let fields = world.get::<LdtkFields>(level_entity).unwrap();
+        assert_eq!(fields.get_str("music_track"), Some("boss.ogg"));
+        assert_eq!(fields.get_enum("roomType"), Some("Entrance"));
+    }
+
+    /// The physics and path data of a layer should be on the same cells as its tiles.
+    #[cfg(any(feature = "algorithm", feature = "physics"))]
+    #[test]
+    fn test_data_layers_follow_coords() {
+        use bevy::{
+            asset::{io::AssetSourceBuilders, AssetServerMode},
+            ecs::{system::CommandQueue, world::World},
+        };
+
+        let json = load_grid_vania();
+        let level = &json.levels[0];
+        let (layer_index, layer) = level
+            .layer_instances
+            .iter()
+            .enumerate()
+            .find(|(_, l)| l.ty == LayerType::AutoLayer && !l.auto_layer_tiles.is_empty())
+            .unwrap();
+        let mut assets = LdtkAssets::default();
+        assets.tilesets.insert(
+            layer.tileset_def_uid.unwrap(),
+            TilemapTexture {
+                texture: Handle::default(),
+                desc: TilemapTextureDescriptor {
+                    size: UVec2::splat(layer.grid_size as u32 * 8),
+                    tile_size: UVec2::splat(layer.grid_size as u32),
+                },
+            },
+        );
+        let mut sources = AssetSourceBuilders::default();
+        sources.init_default_source("assets", None);
+        let asset_server = AssetServer::new(
+            sources.build_sources(false, false),
+            AssetServerMode::Unprocessed,
+            false,
+        );
+
+        // A single solid cell in a 4x3 layer.
+        let cell = IVec2::new(2, 1);
+        let size = UVec2::new(4, 3);
+        let mut data = vec![0; (size.x * size.y) as usize];
+        data[(cell.y * size.x as i32 + cell.x) as usize] = 7;
+
+        for y_up in [true, false] {
+            let mut config = LdtkLoadConfig::default();
+            config.coords.y_up = y_up;
+
+            let mut world = World::new();
+            let mut layers = LdtkLayers::new(
+                world.spawn_empty().id(),
+                level,
+                level.layer_instances.len(),
+                &assets,
+                Vec2::ZERO,
+                0.,
+                LdtkLoaderMode::Tilemap,
+                SpriteBundle::default(),
+            );
+            let tile = TileInstance {
+                px: [cell.x * layer.grid_size, cell.y * layer.grid_size],
+                ..layer.auto_layer_tiles[0].clone()
+            };
+            layers.set_tile(
+                layer_index,
+                layer,
+                &tile,
+                &config,
+                &LdtkPatterns::default(),
+                &LdtkLoaderMode::Tilemap,
+            );
+
+            #[cfg(feature = "physics")]
+            layers.assign_physics_layer(
+                physics::LdtkPhysicsLayer {
+                    identifier: "Physics".to_string(),
+                    parent: layer.identifier.clone(),
+                    air: 0,
+                    tiles: None,
+                    slopes: None,
+                },
+                data.clone(),
+                size,
+                HashMap::default(),
+            );
+            #[cfg(feature = "algorithm")]
+            layers.assign_path_layer(
+                path::LdtkPathLayer {
+                    identifier: "Path".to_string(),
+                    parent: layer.identifier.clone(),
+                    cost_mapper: None,
+                    nav_grid: false,
+                },
+                (0..size.y as i32)
+                    .flat_map(|y| (0..size.x as i32).map(move |x| IVec2::new(x, y)))
+                    .map(|c| {
+                        let cost = data[(c.y * size.x as i32 + c.x) as usize] as u32;
+                        (c, crate::tilemap::algorithm::path::PathTile { cost })
+                    })
+                    .collect(),
+            );
+
+            let mut queue = CommandQueue::default();
+            #[cfg(feature = "algorithm")]
+            let mut path_tilemaps = PathTilemaps::default();
+            layers.apply_all(
+                &mut Commands::new(&mut queue, &world),
+                world.entities(),
+                LdtkLayerContext {
+                    ldtk_patterns: &mut LdtkPatterns::default(),
+                    entity_registry: &LdtkEntityRegistry::default(),
+                    entity_tag_registry: &LdtkEntityTagRegistry::default(),
+                    config: &config,
+                    ldtk_assets: &assets,
+                    asset_server: &asset_server,
+                    material_assets: &mut Assets::default(),
+                    textures_assets: &mut Assets::default(),
+                    #[cfg(feature = "algorithm")]
+                    path_tilemaps: &mut path_tilemaps,
+                },
+            );
+            queue.apply(&mut world);
+
+            let (tilemap, storage) = world.query::<(Entity, &TilemapStorage)>().single(&world);
+            let index = config
+                .coords
+                .tile_index(IVec2::from(tile.px), UVec2::splat(layer.grid_size as u32));
+            assert!(storage.get(index).is_some());
+
+            #[cfg(feature = "physics")]
+            {
+                let physics = world.get::<DataPhysicsTilemap>(tilemap).unwrap();
+                let local = (index - physics.origin).as_uvec2();
+                assert_eq!(physics.get_or_air(local), 7, "y_up: {}", y_up);
+                assert_eq!(
+                    physics.data.iter().filter(|d| **d != 0).count(),
+                    1,
+                    "y_up: {}",
+                    y_up
+                );
+            }
+
+            #[cfg(feature = "algorithm")]
+            {
+                #[cfg(feature = "multi-threaded")]
+                let path_tilemap = path_tilemaps.lock(tilemap).unwrap().clone();
+                #[cfg(not(feature = "multi-threaded"))]
+                let path_tilemap = path_tilemaps.get(tilemap).unwrap().clone();
+                assert_eq!(path_tilemap.get(index).map(|t| t.cost), Some(7));
+            }
+        }
     }
 }
@@ -1,8 +1,14 @@
 use bevy::{ecs::system::Resource, math::IVec2, reflect::Reflect, utils::HashMap};
 
 use crate::{
-    ldtk::json::{definitions::LayerType, level::LayerInstance},
-    tilemap::algorithm::path::PathTile,
+    ldtk::{
+        json::{definitions::LayerType, level::LayerInstance},
+        resources::LdtkCoordConfig,
+    },
+    tilemap::{
+        algorithm::path::{PathTile, PathTilemap},
+        chunking::storage::ChunkedStorage,
+    },
 };
 
 #[derive(Debug, Resource, Clone, Reflect)]
@@ -10,6 +16,8 @@ pub struct LdtkPathLayer {
     pub identifier: String,
     pub parent: String,
     pub cost_mapper: Option<HashMap<i32, u32>>,
+    /// Cache a `NavGrid` of this layer on the level entity when the level is spawned.
+    pub nav_grid: bool,
 }
 
 pub fn analyze_path_layer(layer: &LayerInstance, path: &LdtkPathLayer) -> HashMap<IVec2, PathTile> {
@@ -40,3 +48,17 @@ pub fn analyze_path_layer(layer: &LayerInstance, path: &LdtkPathLayer) -> HashMa
 
     tiles
 }
+
+/// Put the path tiles of the cells on the tiles of the parent layer.
+pub fn path_tilemap(tiles: &HashMap<IVec2, PathTile>, coords: &LdtkCoordConfig) -> PathTilemap {
+    PathTilemap {
+        storage: ChunkedStorage::from_mapper(
+            tiles
+                .iter()
+                .map(|(cell, tile)| (coords.cell_index(*cell), *tile))
+                .collect(),
+            None,
+        ),
+        version: 0,
+    }
+}
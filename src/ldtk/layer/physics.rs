@@ -1,6 +1,12 @@
-use bevy::{ecs::system::Resource, reflect::Reflect, utils::HashMap};
+use bevy::{ecs::system::Resource, math::UVec2, reflect::Reflect, utils::HashMap};
 
-use crate::tilemap::physics::PhysicsTile;
+use crate::{
+    ldtk::json::{definitions::TilesetDef, level::LayerInstance},
+    tilemap::{
+        physics::{PhysicsTile, TileSlope},
+        tile::TileFlip,
+    },
+};
 
 #[derive(Debug, Resource, Clone, Reflect)]
 pub struct LdtkPhysicsLayer {
@@ -8,4 +14,125 @@ pub struct LdtkPhysicsLayer {
     pub parent: String,
     pub air: i32,
     pub tiles: Option<HashMap<i32, PhysicsTile>>,
+    /// Map the custom data of tiles in the tileset to slopes.
+    ///
+    /// The tiles (or auto layer tiles) placed on the physics layer
+    /// whose custom data matches the key will be spawned as slopes.
+    pub slopes: Option<HashMap<String, TileSlope>>,
+}
+
+/// Find all the sloped tiles in the physics layer.
+///
+/// If `y_up`, the indices are flipped like `DataPhysicsTilemap::new()` flips the data,
+/// otherwise they are the cells in LDtk, like `DataPhysicsTilemap::new_flipped()` expects.
+pub fn analyze_slopes(
+    layer: &LayerInstance,
+    physics: &LdtkPhysicsLayer,
+    tilesets: &[TilesetDef],
+    y_up: bool,
+) -> HashMap<UVec2, TileSlope> {
+    let mut slopes = HashMap::default();
+    let (Some(mapper), Some(tileset_uid)) = (physics.slopes.as_ref(), layer.tileset_def_uid) else {
+        return slopes;
+    };
+    let Some(tileset) = tilesets.iter().find(|t| t.uid == tileset_uid) else {
+        return slopes;
+    };
+
+    layer
+        .grid_tiles
+        .iter()
+        .chain(layer.auto_layer_tiles.iter())
+        .for_each(|tile| {
+            let Some(mut slope) = tileset
+                .get_custom_data(tile.tile_id)
+                .and_then(|data| mapper.get(data.trim()))
+                .cloned()
+            else {
+                return;
+            };
+
+            let flip = TileFlip::from_ldtk(tile.flip);
+            if flip.contains(TileFlip::HORIZONTAL) {
+                slope = slope.flip_x();
+            }
+            if flip.contains(TileFlip::VERTICAL) {
+                slope = slope.flip_y();
+            }
+
+            let x = tile.px[0] / layer.grid_size;
+            let y = tile.px[1] / layer.grid_size;
+            if x < 0 || y < 0 || x >= layer.c_wid || y >= layer.c_hei {
+                return;
+            }
+
+            let y = if y_up { layer.c_hei - 1 - y } else { y };
+            slopes.insert(UVec2::new(x as u32, y as u32), slope);
+        });
+
+    slopes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ldtk::{
+        json::{definitions::CustomData, level::TileInstance},
+        load_grid_vania,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_analyze_slopes() {
+        let json = load_grid_vania();
+        let mut layer = json.levels[0]
+            .layer_instances
+            .iter()
+            .find(|l| l.tileset_def_uid.is_some() && !l.auto_layer_tiles.is_empty())
+            .unwrap()
+            .clone();
+        let mut tilesets = json.defs.tilesets.clone();
+        tilesets
+            .iter_mut()
+            .find(|t| t.uid == layer.tileset_def_uid.unwrap())
+            .unwrap()
+            .custom_data = vec![CustomData {
+            data: " slope\n".to_string(),
+            tile_id: 1,
+        }];
+
+        // A slope, the same slope flipped vertically and a tile without custom data.
+        let tile = layer.auto_layer_tiles[0].clone();
+        let at = |x, tile_id, flip| TileInstance {
+            px: [x * layer.grid_size, 0],
+            tile_id,
+            flip,
+            ..tile.clone()
+        };
+        layer.grid_tiles = vec![at(0, 1, 0), at(1, 1, 2)];
+        layer.auto_layer_tiles = vec![at(2, 2, 0)];
+        let physics = LdtkPhysicsLayer {
+            identifier: layer.identifier.clone(),
+            parent: layer.identifier.clone(),
+            air: 0,
+            tiles: None,
+            slopes: Some(
+                [("slope".to_string(), TileSlope::UpRight)]
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+
+        let slopes = analyze_slopes(&layer, &physics, &tilesets, true);
+        let top = (layer.c_hei - 1) as u32;
+        assert_eq!(slopes.len(), 2);
+        assert_eq!(slopes[&UVec2::new(0, top)], TileSlope::UpRight);
+        assert_eq!(slopes[&UVec2::new(1, top)], TileSlope::DownRight);
+
+        // With the y axis down the cells are kept as they are in LDtk.
+        let slopes = analyze_slopes(&layer, &physics, &tilesets, false);
+        assert_eq!(slopes.len(), 2);
+        assert_eq!(slopes[&UVec2::new(0, 0)], TileSlope::UpRight);
+        assert_eq!(slopes[&UVec2::new(1, 0)], TileSlope::DownRight);
+    }
 }
@@ -1,49 +1,62 @@
 use std::path::Path;
 
 use bevy::{
-    app::{Plugin, Startup, Update},
+    app::{Plugin, PostUpdate, Startup, Update},
     asset::{load_internal_asset, AssetServer, Assets, Handle},
     ecs::{
-        entity::Entity,
-        event::EventWriter,
-        query::{Added, With},
-        system::{Commands, NonSend, ParallelCommands, Query, Res, ResMut},
+        entity::{Entities, Entity},
+        event::{EventReader, EventWriter},
+        query::{Added, Or, With, Without},
+        reflect::AppTypeRegistry,
+        removal_detection::RemovedComponents,
+        schedule::{common_conditions::resource_exists, IntoSystemConfigs},
+        system::{Commands, Local, NonSend, ParallelCommands, Query, Res, ResMut, SystemParam},
     },
-    math::{UVec2, Vec2},
-    render::{mesh::Mesh, render_resource::Shader},
-    sprite::{Material2dPlugin, Sprite, SpriteBundle, TextureAtlasLayout},
+    log::{error, info, warn},
+    math::{UVec2, Vec2, Vec4},
+    render::{
+        camera::{Camera, ClearColorConfig, OrthographicProjection},
+        color::Color,
+        mesh::Mesh,
+        render_resource::Shader,
+    },
+    sprite::{Material2dPlugin, Sprite, SpriteBundle, TextureAtlas, TextureAtlasLayout},
+    time::Time,
     transform::components::Transform,
+    utils::HashSet,
 };
 
 use crate::{
     ldtk::{
         components::{LayerIid, LdtkLoader, LdtkLoaderMode, LdtkUnloader, WorldIid},
-        json::{
-            field::FieldInstance,
-            level::{EntityInstance, ImagePosition, Neighbour, TileInstance},
-            EntityRef, GridPoint, LdtkColor, Toc, World,
-        },
+        json::definitions::Definitions,
         resources::{
-            LdtkAdditionalLayers, LdtkAssets, LdtkGlobalEntityRegistry, LdtkPatterns, LdtkTocs,
+            LdtkAdditionalLayers, LdtkAssets, LdtkGlobalEntityRegistry, LdtkIidMap, LdtkPatterns,
+            LdtkTileMetadata, LdtkTocs,
         },
-        sprite::{AtlasRect, NineSliceBorders, SpriteMesh},
+        sprite::{AtlasRect, LdtkEntityAnimation, LdtkEntityAnimationFields, SpriteMesh},
     },
+    math::aabb::Aabb2d,
     render::material::StandardTilemapMaterial,
     tilemap::map::{TilemapStorage, TilemapTextures},
 };
 
 use self::{
     components::{
-        EntityIid, GlobalEntity, LdtkLoadedLevel, LdtkTempTransform, LdtkUnloadLayer, LevelIid,
+        EntityIid, GlobalEntity, LdtkFields, LdtkLoadedLevel, LdtkTempTransform, LdtkUnknownEntity,
+        LdtkUnloadLayer, LevelIid,
     },
-    events::{LdtkEvent, LevelEvent},
+    events::{LdtkEvent, LevelEvent, LevelSpawnedEvent, UnknownEntitiesEvent, WorldSpawnedEvent},
     json::{
         definitions::LayerType,
         level::{LayerInstance, Level},
         LdtkJson, WorldLayout,
     },
-    layer::{LdtkLayers, PackedLdtkEntity},
-    resources::{LdtkLevelManager, LdtkLoadConfig},
+    layer::{LdtkLayerContext, LdtkLayers, PackedLdtkEntity},
+    resources::{
+        LdtkCameraBounds, LdtkHotReload, LdtkLevelClearColor, LdtkLevelManager, LdtkLoadConfig,
+        UnknownEntityPolicy,
+    },
     sprite::LdtkEntityMaterial,
     traits::{LdtkEntityRegistry, LdtkEntityTagRegistry},
 };
@@ -54,6 +67,7 @@ use crate::algorithm::pathfinding::PathTilemaps;
 pub mod app_ext;
 pub mod components;
 pub mod events;
+pub mod export;
 pub mod json;
 pub mod layer;
 pub mod resources;
@@ -83,8 +97,13 @@ impl Plugin for EntiTilesLdtkPlugin {
                 unload_ldtk_level,
                 unload_ldtk_layer,
                 global_entity_registerer,
-                ldtk_temp_tranform_applier,
+                entity_iid_mapper,
+                ldtk_temp_tranform_applier.after(apply_ldtk_layers),
                 apply_ldtk_layers,
+                level_clear_color_syncer.run_if(resource_exists::<LdtkLevelClearColor>),
+                ldtk_hot_reloader.run_if(resource_exists::<LdtkHotReload>),
+                level_camera_clamper.run_if(resource_exists::<LdtkCameraBounds>),
+                ldtk_entity_animator,
             ),
         );
 
@@ -96,45 +115,49 @@ impl Plugin for EntiTilesLdtkPlugin {
             .init_resource::<LdtkAssets>()
             .init_resource::<LdtkPatterns>()
             .init_resource::<LdtkTocs>()
-            .init_resource::<LdtkGlobalEntityRegistry>();
+            .init_resource::<LdtkTileMetadata>()
+            .init_resource::<LdtkGlobalEntityRegistry>()
+            .init_resource::<LdtkIidMap>();
 
-        app.add_event::<LdtkEvent>();
+        app.add_systems(PostUpdate, ldtk_spawned_events);
+
+        app.add_event::<LdtkEvent>()
+            .add_event::<LevelSpawnedEvent>()
+            .add_event::<WorldSpawnedEvent>();
 
         app.register_type::<LdtkLoadedLevel>()
             .register_type::<GlobalEntity>()
+            .register_type::<LdtkUnknownEntity>()
+            .register_type::<LdtkFields>()
             .register_type::<EntityIid>()
             .register_type::<LayerIid>()
             .register_type::<LevelIid>()
             .register_type::<WorldIid>()
             .register_type::<LevelEvent>()
+            .register_type::<LevelSpawnedEvent>()
+            .register_type::<WorldSpawnedEvent>()
             .register_type::<LdtkLoader>()
             .register_type::<LdtkUnloader>()
             .register_type::<LdtkLoaderMode>()
             .register_type::<AtlasRect>()
             .register_type::<LdtkEntityMaterial>()
-            .register_type::<NineSliceBorders>()
-            .register_type::<SpriteMesh>();
-
-        app.register_type::<FieldInstance>()
-            .register_type::<Level>()
-            .register_type::<ImagePosition>()
-            .register_type::<Neighbour>()
-            .register_type::<LayerInstance>()
-            .register_type::<TileInstance>()
-            .register_type::<EntityInstance>()
-            .register_type::<LdtkColor>()
-            .register_type::<LdtkJson>()
-            .register_type::<Toc>()
-            .register_type::<World>()
-            .register_type::<EntityRef>()
-            .register_type::<GridPoint>();
+            .register_type::<SpriteMesh>()
+            .register_type::<LdtkEntityAnimation>()
+            .register_type::<LdtkEntityAnimationFields>();
+
+        json::register_types(&mut app.world.resource::<AppTypeRegistry>().write());
 
         app.register_type::<LdtkLevelManager>()
             .register_type::<LdtkLoadConfig>()
+            .register_type::<LdtkLevelClearColor>()
+            .register_type::<LdtkHotReload>()
+            .register_type::<LdtkCameraBounds>()
             .register_type::<LdtkAdditionalLayers>()
             .register_type::<LdtkAssets>()
             .register_type::<LdtkPatterns>()
-            .register_type::<LdtkGlobalEntityRegistry>();
+            .register_type::<LdtkGlobalEntityRegistry>()
+            .register_type::<LdtkIidMap>()
+            .register_type::<LdtkTileMetadata>();
 
         #[cfg(feature = "algorithm")]
         {
@@ -154,12 +177,70 @@ fn parse_ldtk_json(mut manager: ResMut<LdtkLevelManager>, config: Res<LdtkLoadCo
     manager.reload_json(&config);
 }
 
+pub fn ldtk_hot_reloader(
+    mut commands: Commands,
+    mut hot_reload: ResMut<LdtkHotReload>,
+    mut manager: ResMut<LdtkLevelManager>,
+    config: Res<LdtkLoadConfig>,
+    time: Res<Time>,
+) {
+    if !hot_reload.timer.tick(time.delta()).just_finished() || !manager.is_initialized() {
+        return;
+    }
+
+    let Ok(modified) = std::fs::metadata(&config.file_path).and_then(|m| m.modified()) else {
+        return;
+    };
+
+    match hot_reload.last_modified.replace(modified) {
+        Some(last) if last != modified => {
+            info!("LDtk file {} is modified, reloading.", config.file_path);
+            manager.hot_reload(&mut commands, &config);
+        }
+        _ => {}
+    }
+}
+
 fn global_entity_registerer(
     mut registry: ResMut<LdtkGlobalEntityRegistry>,
     query: Query<(Entity, &EntityIid), Added<GlobalEntity>>,
+    mut removed: RemovedComponents<GlobalEntity>,
+    config: Res<LdtkLoadConfig>,
+) {
+    // Forget the despawned ones, so the iids can be taken by the entities loaded from a save.
+    let removed = removed.read().collect::<HashSet<_>>();
+    if !removed.is_empty() {
+        registry.0.retain(|_, entity| !removed.contains(entity));
+    }
+
+    query.iter().for_each(|(entity, iid)| {
+        config.duplicate_iid_resolution.insert(
+            &mut registry.0,
+            iid.clone(),
+            entity,
+            config.duplicate_iid_policy,
+        );
+    });
+}
+
+fn entity_iid_mapper(
+    mut iid_map: ResMut<LdtkIidMap>,
+    query: Query<(Entity, &EntityIid), Added<EntityIid>>,
+    mut removed: RemovedComponents<EntityIid>,
+    config: Res<LdtkLoadConfig>,
 ) {
+    let removed = removed.read().collect::<HashSet<_>>();
+    if !removed.is_empty() {
+        iid_map.0.retain(|_, entity| !removed.contains(entity));
+    }
+
     query.iter().for_each(|(entity, iid)| {
-        registry.register(iid.clone(), entity);
+        config.duplicate_iid_resolution.insert(
+            &mut iid_map.0,
+            iid.clone(),
+            entity,
+            config.duplicate_iid_policy,
+        );
     });
 }
 
@@ -170,6 +251,10 @@ fn ldtk_temp_tranform_applier(
     entities_query
         .par_iter_mut()
         .for_each(|(entity, mut transform, ldtk_temp)| {
+            // Entities are spawned as if the y axis points up.
+            if !ldtk_temp.y_up {
+                transform.translation.y = -transform.translation.y;
+            }
             transform.translation += ldtk_temp.level_translation.extend(ldtk_temp.z_index);
             commands.command_scope(|mut c| {
                 c.entity(entity).remove::<LdtkTempTransform>();
@@ -222,59 +307,70 @@ pub fn unload_ldtk_layer(
     });
 }
 
+/// The resources levels are loaded from the ldtk json with.
+#[derive(SystemParam)]
+pub struct LdtkLoadResources<'w> {
+    asset_server: Res<'w, AssetServer>,
+    atlas_layouts: ResMut<'w, Assets<TextureAtlasLayout>>,
+    config: Res<'w, LdtkLoadConfig>,
+    manager: ResMut<'w, LdtkLevelManager>,
+    addi_layers: Res<'w, LdtkAdditionalLayers>,
+    ldtk_assets: ResMut<'w, LdtkAssets>,
+    entity_material_assets: ResMut<'w, Assets<LdtkEntityMaterial>>,
+    mesh_assets: ResMut<'w, Assets<Mesh>>,
+    patterns: Res<'w, LdtkPatterns>,
+    global_entities: Res<'w, LdtkGlobalEntityRegistry>,
+    tile_metadata: ResMut<'w, LdtkTileMetadata>,
+}
+
 pub fn load_ldtk_json(
     mut commands: Commands,
     loader_query: Query<(Entity, &LdtkLoader)>,
-    asset_server: Res<AssetServer>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    config: Res<LdtkLoadConfig>,
-    mut manager: ResMut<LdtkLevelManager>,
-    addi_layers: Res<LdtkAdditionalLayers>,
-    mut ldtk_assets: ResMut<LdtkAssets>,
-    mut entity_material_assets: ResMut<Assets<LdtkEntityMaterial>>,
-    mut mesh_assets: ResMut<Assets<Mesh>>,
-    mut patterns: ResMut<LdtkPatterns>,
-    global_entities: Res<LdtkGlobalEntityRegistry>,
+    mut res: LdtkLoadResources,
 ) {
     for (entity, loader) in loader_query.iter() {
-        ldtk_assets.initialize(
-            &config,
-            &manager,
-            &asset_server,
-            &mut atlas_layouts,
-            &mut entity_material_assets,
-            &mut mesh_assets,
+        res.tile_metadata.reload(res.manager.get_cached_data());
+        res.ldtk_assets.initialize(
+            &res.config,
+            &res.manager,
+            &res.asset_server,
+            &mut res.atlas_layouts,
+            &mut res.entity_material_assets,
+            &mut res.mesh_assets,
         );
 
-        load_levels(
-            &mut commands,
-            &config,
-            &mut manager,
-            &addi_layers,
-            loader,
-            &asset_server,
-            entity,
-            &mut ldtk_assets,
-            &mut patterns,
-            &global_entities,
-        );
+        load_levels(&mut commands, &res, loader, entity);
 
         commands.entity(entity).remove::<LdtkLoader>();
     }
 }
 
+/// What the layers of the level being loaded share.
+struct LdtkLevelContext<'a> {
+    translation: Vec2,
+    defs: &'a Definitions,
+    config: &'a LdtkLoadConfig,
+    global_entities: &'a LdtkGlobalEntityRegistry,
+    patterns: &'a LdtkPatterns,
+    loader: &'a LdtkLoader,
+}
+
 fn load_levels(
     commands: &mut Commands,
-    config: &LdtkLoadConfig,
-    manager: &mut LdtkLevelManager,
-    addi_layers: &LdtkAdditionalLayers,
+    res: &LdtkLoadResources,
     loader: &LdtkLoader,
-    asset_server: &AssetServer,
     level_entity: Entity,
-    ldtk_assets: &mut LdtkAssets,
-    patterns: &mut LdtkPatterns,
-    global_entities: &LdtkGlobalEntityRegistry,
 ) {
+    let LdtkLoadResources {
+        asset_server,
+        config,
+        manager,
+        addi_layers,
+        ldtk_assets,
+        patterns,
+        global_entities,
+        ..
+    } = res;
     let ldtk_data = manager.get_cached_data();
 
     let Some((level_index, level)) = ldtk_data
@@ -286,28 +382,39 @@ fn load_levels(
         return;
     };
 
-    let translation = loader
-        .trans_ovrd
-        .unwrap_or_else(|| get_level_translation(&ldtk_data, level_index));
-
     let level_px = UVec2 {
         x: level.px_wid as u32,
         y: level.px_hei as u32,
     };
 
+    // The translation of the top left corner, where tiles and entities are placed from.
+    let translation = loader
+        .trans_ovrd
+        .unwrap_or_else(|| get_level_translation(ldtk_data, level_index, config))
+        + config.coords.level_corner(level_px.as_vec2());
+
     let background = load_background(level, translation, level_px, asset_server, config);
 
     let mut ldtk_layers = LdtkLayers::new(
         level_entity,
         level,
         level.layer_instances.len(),
-        &ldtk_assets,
+        ldtk_assets,
         translation,
         config.z_index,
         loader.mode,
         background,
     );
 
+    let ctx = LdtkLevelContext {
+        translation,
+        defs: &ldtk_data.defs,
+        config,
+        global_entities,
+        patterns,
+        loader,
+    };
+
     for (layer_index, layer) in level.layer_instances.iter().enumerate() {
         #[cfg(feature = "algorithm")]
         if let Some(path) = addi_layers.path_layer.as_ref() {
@@ -328,21 +435,18 @@ fn load_levels(
                         x: layer.c_wid as u32,
                         y: layer.c_hei as u32,
                     },
+                    layer::physics::analyze_slopes(
+                        layer,
+                        phy,
+                        &ldtk_data.defs.tilesets,
+                        loader.mode == LdtkLoaderMode::MapPattern || config.coords.y_up,
+                    ),
                 );
                 continue;
             }
         }
 
-        load_layer(
-            layer_index,
-            layer,
-            &mut ldtk_layers,
-            translation,
-            config,
-            &global_entities,
-            patterns,
-            loader,
-        );
+        load_layer(layer_index, layer, &mut ldtk_layers, &ctx);
     }
 
     commands.entity(level_entity).insert(ldtk_layers);
@@ -367,10 +471,9 @@ fn load_background(
             ..Default::default()
         },
         texture: texture.unwrap_or_default(),
-        transform: Transform::from_xyz(
-            level_px.x as f32 / 2. + translation.x,
-            -(level_px.y as f32) / 2. + translation.y,
-            config.z_index as f32 - level.layer_instances.len() as f32 - 1.,
+        transform: Transform::from_translation(
+            (translation + config.coords.px_to_local(level_px.as_vec2() / 2.))
+                .extend(config.z_index + config.layer_z.background(level.layer_instances.len())),
         ),
         ..Default::default()
     }
@@ -380,15 +483,19 @@ fn load_layer(
     layer_index: usize,
     layer: &LayerInstance,
     ldtk_layers: &mut LdtkLayers,
-    translation: Vec2,
-    config: &LdtkLoadConfig,
-    global_entities: &LdtkGlobalEntityRegistry,
-    patterns: &LdtkPatterns,
-    loader: &LdtkLoader,
+    ctx: &LdtkLevelContext,
 ) {
+    let LdtkLevelContext {
+        translation,
+        defs,
+        config,
+        global_entities,
+        patterns,
+        loader,
+    } = ctx;
     match layer.ty {
-        LayerType::IntGrid | LayerType::AutoLayer => {
-            layer.auto_layer_tiles.iter().for_each(|tile| {
+        LayerType::IntGrid | LayerType::AutoLayer | LayerType::Tiles => {
+            config.sorted_tiles(layer).into_iter().for_each(|tile| {
                 ldtk_layers.set_tile(layer_index, layer, tile, config, patterns, &loader.mode);
             });
         }
@@ -399,81 +506,570 @@ fn load_layer(
                     continue;
                 }
 
-                let fields = entity_instance
+                let mut fields = entity_instance
                     .field_instances
                     .iter()
                     .map(|field| (field.identifier.clone(), field.clone()))
                     .collect();
+                if let Some(entity_def) = defs
+                    .entities
+                    .iter()
+                    .find(|def| def.uid == entity_instance.def_uid)
+                {
+                    entity_def
+                        .field_defs
+                        .iter()
+                        .for_each(|def| def.fill_default(&mut fields));
+                }
                 let packed_entity = PackedLdtkEntity {
                     instance: entity_instance.clone(),
                     fields,
                     iid,
                     transform: LdtkTempTransform {
-                        level_translation: translation,
-                        z_index: config.z_index as f32
-                            - layer_index as f32
-                            - (1. - (order as f32 / layer.entity_instances.len() as f32)),
+                        level_translation: *translation,
+                        y_up: config.coords.y_up,
+                        z_index: config.z_index
+                            + config.layer_z.entity(
+                                layer_index,
+                                order,
+                                layer.entity_instances.len(),
+                            ),
                     },
                 };
                 ldtk_layers.set_entity(packed_entity);
             }
         }
-        LayerType::Tiles => {
-            layer.grid_tiles.iter().for_each(|tile| {
-                ldtk_layers.set_tile(layer_index, layer, tile, config, patterns, &loader.mode);
-            });
-        }
     }
 }
 
-fn get_level_translation(ldtk_data: &LdtkJson, index: usize) -> Vec2 {
+fn get_level_translation(ldtk_data: &LdtkJson, index: usize, config: &LdtkLoadConfig) -> Vec2 {
     let level = &ldtk_data.levels[index];
     match ldtk_data.world_layout.unwrap() {
-        WorldLayout::GridVania | WorldLayout::Free => Vec2 {
-            x: level.world_x as f32,
-            y: -level.world_y as f32,
-        },
+        WorldLayout::GridVania | WorldLayout::Free => config.coords.level_translation(
+            Vec2::new(level.world_x as f32, level.world_y as f32),
+            Vec2::new(level.px_wid as f32, level.px_hei as f32),
+        ),
         WorldLayout::LinearHorizontal | WorldLayout::LinearVertical => Vec2::ZERO,
     }
 }
 
+/// The resources shared by all the levels being spawned.
+#[derive(SystemParam)]
+struct LdtkLayerResources<'w> {
+    ldtk_patterns: ResMut<'w, LdtkPatterns>,
+    entity_registry: Option<NonSend<'w, LdtkEntityRegistry>>,
+    entity_tag_registry: Option<NonSend<'w, LdtkEntityTagRegistry>>,
+    config: Res<'w, LdtkLoadConfig>,
+    ldtk_assets: Res<'w, LdtkAssets>,
+    asset_server: Res<'w, AssetServer>,
+    material_assets: ResMut<'w, Assets<StandardTilemapMaterial>>,
+    textures_assets: ResMut<'w, Assets<TilemapTextures>>,
+}
+
 fn apply_ldtk_layers(
     mut commands: Commands,
+    entities: &Entities,
     mut ldtk_layers_query: Query<(Entity, &mut LdtkLayers)>,
-    mut ldtk_patterns: ResMut<LdtkPatterns>,
-    entity_registry: Option<NonSend<LdtkEntityRegistry>>,
-    entity_tag_registry: Option<NonSend<LdtkEntityTagRegistry>>,
-    config: Res<LdtkLoadConfig>,
-    ldtk_assets: Res<LdtkAssets>,
-    asset_server: Res<AssetServer>,
-    mut material_assets: ResMut<Assets<StandardTilemapMaterial>>,
-    mut textures_assets: ResMut<Assets<TilemapTextures>>,
+    mut res: LdtkLayerResources,
     mut ldtk_events: EventWriter<LdtkEvent>,
     #[cfg(feature = "algorithm")] mut path_tilemaps: ResMut<PathTilemaps>,
 ) {
+    let default_registry = LdtkEntityRegistry::default();
+    let default_tag_registry = LdtkEntityTagRegistry::default();
+    let entity_registry = res.entity_registry.as_deref().unwrap_or(&default_registry);
+    let entity_tag_registry = res
+        .entity_tag_registry
+        .as_deref()
+        .unwrap_or(&default_tag_registry);
+
     for (entity, mut ldtk_layers) in &mut ldtk_layers_query {
-        let entity_registry = entity_registry.as_ref().map(|r| &**r);
-        let entity_tag_registry = entity_tag_registry.as_ref().map(|r| &**r);
+        let level = LevelEvent {
+            identifier: ldtk_layers.level.identifier.clone(),
+            iid: ldtk_layers.level.iid.clone(),
+        };
+
+        if ldtk_layers.ty == LdtkLoaderMode::Tilemap {
+            report_unknown_entities(
+                ldtk_layers.unknown_entities(entity_registry),
+                res.config.unknown_entity_policy,
+                &level,
+                &mut ldtk_events,
+            );
+        }
 
         ldtk_layers.apply_all(
             &mut commands,
-            &mut ldtk_patterns,
-            &entity_registry.unwrap_or(&LdtkEntityRegistry::default()),
-            &entity_tag_registry.unwrap_or(&LdtkEntityTagRegistry::default()),
-            &config,
-            &ldtk_assets,
-            &asset_server,
-            &mut material_assets,
-            &mut textures_assets,
-            #[cfg(feature = "algorithm")]
-            &mut path_tilemaps,
+            entities,
+            LdtkLayerContext {
+                ldtk_patterns: &mut res.ldtk_patterns,
+                entity_registry,
+                entity_tag_registry,
+                config: &res.config,
+                ldtk_assets: &res.ldtk_assets,
+                asset_server: &res.asset_server,
+                material_assets: &mut res.material_assets,
+                textures_assets: &mut res.textures_assets,
+                #[cfg(feature = "algorithm")]
+                path_tilemaps: &mut path_tilemaps,
+            },
         );
 
-        ldtk_events.send(LdtkEvent::LevelLoaded(LevelEvent {
-            identifier: ldtk_layers.level.identifier.clone(),
-            iid: ldtk_layers.level.iid.clone(),
-        }));
+        ldtk_events.send(LdtkEvent::LevelLoaded(level));
 
         commands.entity(entity).remove::<LdtkLayers>();
     }
 }
+
+fn report_unknown_entities(
+    identifiers: Vec<String>,
+    policy: UnknownEntityPolicy,
+    level: &LevelEvent,
+    ldtk_events: &mut EventWriter<LdtkEvent>,
+) {
+    if identifiers.is_empty() {
+        return;
+    }
+
+    match policy {
+        UnknownEntityPolicy::Skip | UnknownEntityPolicy::SpawnPlaceholder => {}
+        UnknownEntityPolicy::Warn => warn!(
+            "Skipped unregistered entities {:?} in level {}! \
+            Register them using App::register_ldtk_entity::<T>().",
+            identifiers, level.identifier
+        ),
+        UnknownEntityPolicy::Error => {
+            error!(
+                "Could not find entity types {:?} in level {}! \
+                You need to register them using App::register_ldtk_entity::<T>() first!",
+                identifiers, level.identifier
+            );
+            ldtk_events.send(LdtkEvent::UnknownEntities(UnknownEntitiesEvent {
+                level: level.clone(),
+                identifiers,
+            }));
+        }
+    }
+}
+
+/// Levels that are still loading or spawning.
+type PendingLevel = Or<(With<LdtkLoader>, With<LdtkLayers>)>;
+
+pub fn ldtk_spawned_events(
+    levels_query: Query<(Entity, &LevelIid), Added<LdtkLoadedLevel>>,
+    pending_query: Query<(), PendingLevel>,
+    manager: Res<LdtkLevelManager>,
+    mut level_events: EventWriter<LevelSpawnedEvent>,
+    mut world_events: EventWriter<WorldSpawnedEvent>,
+    mut spawned: Local<Vec<Entity>>,
+) {
+    levels_query.iter().for_each(|(entity, iid)| {
+        level_events.send(LevelSpawnedEvent {
+            level_iid: iid.0.clone(),
+            entity,
+        });
+        spawned.push(entity);
+    });
+
+    if spawned.is_empty() || !pending_query.is_empty() {
+        return;
+    }
+
+    world_events.send(WorldSpawnedEvent {
+        world_iid: manager.get_cached_data().iid.clone(),
+        levels: std::mem::take(&mut *spawned),
+    });
+}
+
+pub fn level_clear_color_syncer(
+    mut cameras_query: Query<&mut Camera>,
+    mut ldtk_events: EventReader<LdtkEvent>,
+    mut clear_color: ResMut<LdtkLevelClearColor>,
+    manager: Res<LdtkLevelManager>,
+    time: Res<Time>,
+) {
+    for event in ldtk_events.read() {
+        if let LdtkEvent::LevelLoaded(level) = event {
+            clear_color.focused = Some(level.identifier.clone());
+        }
+    }
+
+    let Some(focused) = &clear_color.focused else {
+        return;
+    };
+    if !manager.is_initialized() {
+        return;
+    }
+
+    let ldtk_data = manager.get_cached_data();
+    let Some(level) = ldtk_data
+        .levels
+        .iter()
+        .chain(ldtk_data.worlds.iter().flat_map(|w| w.levels.iter()))
+        .find(|level| &level.identifier == focused)
+    else {
+        return;
+    };
+
+    let target: Vec4 = level.bg_color.into();
+    cameras_query
+        .iter_mut()
+        .filter(|camera| camera.is_active)
+        .for_each(|mut camera| {
+            let current = match camera.clear_color {
+                ClearColorConfig::Custom(color) => Some(Vec4::from(color.as_rgba_f32())),
+                _ => None,
+            };
+            if current == Some(target) {
+                return;
+            }
+
+            let color = match (clear_color.lerp_speed, current) {
+                (Some(speed), Some(current)) => {
+                    let t = (speed * time.delta_seconds()).min(1.);
+                    let color = current.lerp(target, t);
+                    // Snap to the target once it's close enough.
+                    if color.distance_squared(target) < 1e-6 {
+                        target
+                    } else {
+                        color
+                    }
+                }
+                _ => target,
+            };
+            camera.clear_color = ClearColorConfig::Custom(Color::rgba_from_array(color));
+        });
+}
+
+pub fn ldtk_entity_animator(
+    mut entities_query: Query<(&mut LdtkEntityAnimation, &mut TextureAtlas)>,
+    time: Res<Time>,
+) {
+    entities_query
+        .par_iter_mut()
+        .for_each(|(mut animation, mut atlas)| {
+            let index = animation.tick(time.delta_seconds());
+            if atlas.index != index {
+                atlas.index = index;
+            }
+        });
+}
+
+pub fn level_camera_clamper(
+    mut cameras_query: Query<
+        (&Camera, &OrthographicProjection, &mut Transform),
+        Without<LdtkLoadedLevel>,
+    >,
+    levels_query: Query<(&LdtkLoadedLevel, &Transform), Without<Camera>>,
+    mut ldtk_events: EventReader<LdtkEvent>,
+    mut camera_bounds: ResMut<LdtkCameraBounds>,
+    manager: Res<LdtkLevelManager>,
+    config: Res<LdtkLoadConfig>,
+) {
+    for event in ldtk_events.read() {
+        if let LdtkEvent::LevelLoaded(level) = event {
+            camera_bounds.focused = Some(level.identifier.clone());
+        }
+    }
+
+    let Some(focused) = &camera_bounds.focused else {
+        return;
+    };
+    if !manager.is_initialized() {
+        return;
+    }
+
+    let ldtk_data = manager.get_cached_data();
+    let Some(level) = ldtk_data
+        .levels
+        .iter()
+        .chain(ldtk_data.worlds.iter().flat_map(|w| w.levels.iter()))
+        .find(|level| &level.identifier == focused)
+    else {
+        return;
+    };
+    let Some((_, level_transform)) = levels_query
+        .iter()
+        .find(|(loaded, _)| &loaded.identifier == focused)
+    else {
+        return;
+    };
+
+    let mut bounds = level.camera_bounds(&camera_bounds.field, ldtk_data.default_grid_size);
+    if !config.coords.y_up {
+        bounds = Aabb2d::new(bounds.min.x, -bounds.max.y, bounds.max.x, -bounds.min.y);
+    }
+    let bounds = bounds
+        + level_transform.translation.truncate()
+        + config
+            .coords
+            .level_corner(Vec2::new(level.px_wid as f32, level.px_hei as f32));
+
+    cameras_query
+        .iter_mut()
+        .filter(|(camera, ..)| camera.is_active)
+        .for_each(|(_, projection, mut transform)| {
+            // The area the camera can be at without seeing anything outside the bounds.
+            let min = bounds.min - projection.area.min;
+            let max = bounds.max - projection.area.max;
+            let center = (bounds.min + bounds.max) / 2.;
+            let current = transform.translation.truncate();
+            let clamped = Vec2 {
+                x: if min.x > max.x {
+                    center.x
+                } else {
+                    current.x.clamp(min.x, max.x)
+                },
+                y: if min.y > max.y {
+                    center.y
+                } else {
+                    current.y.clamp(min.y, max.y)
+                },
+            };
+
+            if clamped != current {
+                transform.translation = clamped.extend(transform.translation.z);
+            }
+        });
+}
+
+/// Parse the sample project most of the tests are run against.
+#[cfg(test)]
+pub(crate) fn load_grid_vania() -> LdtkJson {
+    serde_json::from_str(&std::fs::read_to_string("assets/ldtk/grid_vania.ldtk").unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::App,
+        ecs::{event::Events, system::RunSystemOnce},
+    };
+
+    use crate::ldtk::json::{field::FieldInstance, GridPoint};
+
+    use super::*;
+
+    #[test]
+    fn test_level_clear_color() {
+        let ldtk_json = load_grid_vania();
+        let level = ldtk_json.levels[0].clone();
+
+        let mut app = App::new();
+        app.add_event::<LdtkEvent>()
+            .init_resource::<Time>()
+            .init_resource::<LdtkLevelClearColor>()
+            .insert_resource(LdtkLevelManager {
+                ldtk_json: Some(ldtk_json),
+                ..Default::default()
+            })
+            .add_systems(Update, level_clear_color_syncer);
+        let camera = app.world.spawn(Camera::default()).id();
+
+        app.world.send_event(LdtkEvent::LevelLoaded(LevelEvent {
+            identifier: level.identifier.clone(),
+            iid: level.iid.clone(),
+        }));
+        app.update();
+
+        assert_eq!(
+            app.world.resource::<LdtkLevelClearColor>().focused,
+            Some(level.identifier.clone())
+        );
+        let ClearColorConfig::Custom(color) = app.world.get::<Camera>(camera).unwrap().clear_color
+        else {
+            panic!("Clear color is not updated!");
+        };
+        let expected: Color = level.bg_color.into();
+        assert_eq!(color.as_rgba_f32(), expected.as_rgba_f32());
+    }
+
+    #[test]
+    fn test_unknown_entities() {
+        let mut app = App::new();
+        app.add_event::<LdtkEvent>();
+        let level = LevelEvent {
+            identifier: "Level_0".to_string(),
+            iid: "a".to_string(),
+        };
+
+        app.world
+            .run_system_once(move |mut ldtk_events: EventWriter<LdtkEvent>| {
+                for (identifiers, policy) in [
+                    (vec!["Warned".to_string()], UnknownEntityPolicy::Warn),
+                    (vec!["Skipped".to_string()], UnknownEntityPolicy::Skip),
+                    (Vec::new(), UnknownEntityPolicy::Error),
+                    (vec!["Enemy".to_string()], UnknownEntityPolicy::Error),
+                ] {
+                    report_unknown_entities(identifiers, policy, &level, &mut ldtk_events);
+                }
+            });
+
+        let events = app.world.resource::<Events<LdtkEvent>>();
+        let mut reader = events.get_reader();
+        let events = reader.read(events).collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        let LdtkEvent::UnknownEntities(unknown) = events[0] else {
+            panic!("Unknown entities are not reported!");
+        };
+        assert_eq!(unknown.level.identifier, "Level_0");
+        assert_eq!(unknown.identifiers, vec!["Enemy".to_string()]);
+    }
+
+    #[test]
+    fn test_level_camera_bounds() {
+        use bevy::{math::Rect, utils::HashMap};
+
+        use crate::{ldtk::json::field::FieldValue, math::aabb::Aabb2d};
+
+        let ldtk_json = load_grid_vania();
+        let grid = ldtk_json.default_grid_size;
+        let mut level = ldtk_json.levels[0].clone();
+        level
+            .field_instances
+            .retain(|f| f.identifier != "CameraBounds");
+
+        // No field, so the whole level.
+        assert_eq!(
+            level.camera_bounds("CameraBounds", grid),
+            Aabb2d::new(0., -level.px_hei as f32, level.px_wid as f32, 0.)
+        );
+
+        let set_bounds = |level: &mut Level, value: FieldValue| {
+            level
+                .field_instances
+                .retain(|f| f.identifier != "CameraBounds");
+            level.field_instances.push(FieldInstance {
+                def_uid: 0,
+                identifier: "CameraBounds".to_string(),
+                tile: None,
+                ty: String::new(),
+                value: Some(value),
+            });
+        };
+
+        set_bounds(&mut level, FieldValue::IntegerArray(vec![16, 8, 32, 24]));
+        assert_eq!(
+            level.camera_bounds("CameraBounds", grid),
+            Aabb2d::new(16., -32., 48., -8.)
+        );
+
+        // Not a rect, so fall back to the whole level.
+        set_bounds(&mut level, FieldValue::IntegerArray(vec![16, 8]));
+        assert_eq!(
+            level.camera_bounds("CameraBounds", grid),
+            Aabb2d::new(0., -level.px_hei as f32, level.px_wid as f32, 0.)
+        );
+
+        // Two corners in any order, both cells are included.
+        set_bounds(
+            &mut level,
+            FieldValue::PointArray(vec![GridPoint { cx: 5, cy: 1 }, GridPoint { cx: 2, cy: 3 }]),
+        );
+        let g = grid as f32;
+        let bounds = Aabb2d::new(2. * g, -4. * g, 6. * g, -g);
+        assert_eq!(level.camera_bounds("CameraBounds", grid), bounds);
+
+        let mut ldtk_json = ldtk_json;
+        ldtk_json.levels[0] = level.clone();
+        let mut app = App::new();
+        app.add_event::<LdtkEvent>()
+            .insert_resource(LdtkCameraBounds {
+                focused: Some(level.identifier.clone()),
+                ..Default::default()
+            })
+            .insert_resource(LdtkLevelManager {
+                ldtk_json: Some(ldtk_json),
+                ..Default::default()
+            })
+            .init_resource::<LdtkLoadConfig>()
+            .add_systems(Update, level_camera_clamper);
+
+        let level_translation = Vec2::new(100., 50.);
+        app.world.spawn((
+            LdtkLoadedLevel {
+                identifier: level.identifier.clone(),
+                layers: HashMap::default(),
+                entities: HashMap::default(),
+                background: Entity::PLACEHOLDER,
+            },
+            Transform::from_translation(level_translation.extend(0.)),
+        ));
+        let camera = app
+            .world
+            .spawn((
+                Camera::default(),
+                OrthographicProjection {
+                    area: Rect::new(-g, -g, g, g),
+                    ..Default::default()
+                },
+                Transform::from_xyz(0., 0., 10.),
+            ))
+            .id();
+        app.update();
+
+        // The camera is moved to the nearest position that can only see the bounds.
+        let bounds = bounds + level_translation;
+        let translation = app.world.get::<Transform>(camera).unwrap().translation;
+        assert_eq!(
+            translation,
+            Vec2::new(bounds.min.x + g, bounds.min.y + g).extend(10.)
+        );
+    }
+
+    #[test]
+    fn test_entity_pivot_translation() {
+        use bevy::{math::Vec3, sprite::Anchor};
+
+        let ldtk_json = load_grid_vania();
+        let mut entity = ldtk_json.levels[0]
+            .layer_instances
+            .iter()
+            .flat_map(|layer| layer.entity_instances.iter())
+            .find(|e| e.tile.is_some())
+            .unwrap()
+            .clone();
+        entity.pivot = [0.5, 1.];
+        entity.local_pos = [40, 64];
+        entity.width = 16;
+        entity.height = 32;
+
+        // The pivot is at the bottom center, so the center is half of the height above it.
+        assert_eq!(
+            entity.anchored_translation(Anchor::Center),
+            Vec2::new(40., -48.)
+        );
+        assert_eq!(
+            entity.anchored_translation(Anchor::BottomCenter),
+            Vec2::new(40., -64.)
+        );
+        assert_eq!(
+            entity.anchored_translation(Anchor::TopLeft),
+            Vec2::new(32., -32.)
+        );
+        assert_eq!(
+            entity.anchored_translation(entity.pivot_anchor()),
+            Vec2::new(40., -64.)
+        );
+
+        let mut app = App::new();
+        app.add_systems(Update, ldtk_temp_tranform_applier);
+        let spawned = app
+            .world
+            .spawn((
+                Transform::from_translation(entity.anchored_translation(Anchor::Center).extend(0.)),
+                LdtkTempTransform {
+                    level_translation: Vec2::new(100., 200.),
+                    y_up: true,
+                    z_index: 3.,
+                },
+            ))
+            .id();
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Transform>(spawned).unwrap().translation,
+            Vec3::new(140., 152., 3.)
+        );
+        assert!(app.world.get::<LdtkTempTransform>(spawned).is_none());
+    }
+}
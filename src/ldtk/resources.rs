@@ -1,4 +1,4 @@
-use std::{fs::read_to_string, path::Path};
+use std::{cmp::Ordering, fs::read_to_string, path::Path, time::SystemTime};
 
 use bevy::{
     asset::{AssetServer, Assets, Handle},
@@ -6,7 +6,7 @@ use bevy::{
         entity::Entity,
         system::{Commands, Resource},
     },
-    log::error,
+    log::{error, warn},
     math::{IVec2, UVec2, Vec2},
     reflect::Reflect,
     render::{
@@ -15,21 +15,29 @@ use bevy::{
         render_resource::{FilterMode, PrimitiveTopology},
     },
     sprite::{Mesh2dHandle, SpriteBundle, TextureAtlasLayout},
+    time::{Timer, TimerMode},
     utils::HashMap,
 };
 
 use crate::{
     serializing::pattern::{PackedPatternLayers, PatternsLayer, TilemapPattern},
     tilemap::{
-        map::{TilemapTexture, TilemapTextureDescriptor},
+        map::{TileAnchor, TilemapTexture, TilemapTextureDescriptor},
         tile::RawTileAnimation,
     },
 };
 
+pub use super::json::coords::LdtkCoordConfig;
+
 use super::{
     components::{EntityIid, LayerIid},
-    json::{definitions::EntityDef, EntityRef, LdtkJson, TocInstance},
-    sprite::{AtlasRect, LdtkEntityMaterial},
+    json::{
+        definitions::{EntityDef, LayerType},
+        field::FieldInstance,
+        level::{LayerInstance, Level, TileInstance},
+        EntityRef, LdtkJson, TocInstance,
+    },
+    sprite::{LdtkEntityAnimationFields, LdtkEntityMaterial, LdtkTileSprite},
     LdtkLoader, LdtkLoaderMode, LdtkUnloader,
 };
 
@@ -153,6 +161,17 @@ impl LdtkAssets {
         self.atlas_handles.get(&tileset_uid).unwrap().clone()
     }
 
+    /// The sprite of the tile of a field, like the icon of an enum value.
+    ///
+    /// Returns `None` if the field has no tile or its tileset isn't loaded.
+    /// The tileset can be a different one from the entity's.
+    pub fn field_tile_sprite(&self, field: &FieldInstance) -> Option<LdtkTileSprite> {
+        let tile_rect = field.tile.as_ref()?;
+        self.tilesets
+            .get(&tile_rect.tileset_uid)
+            .map(|tileset| LdtkTileSprite::new(tile_rect, tileset))
+    }
+
     pub fn get_entity_def(&self, identifier: &String) -> &EntityDef {
         self.entity_defs.get(identifier).unwrap()
     }
@@ -180,6 +199,7 @@ impl LdtkAssets {
     ) {
         self.associated_file = config.file_path.clone();
         self.load_texture(config, manager, asset_server, atlas_layouts);
+        self.resolve_missing_tilesets(config, manager.get_cached_data(), atlas_layouts);
         self.load_entities(config, manager, material_assets, mesh_assets);
     }
 
@@ -215,6 +235,63 @@ impl LdtkAssets {
         });
     }
 
+    /// Make sure every tileset used by the levels has a texture.
+    ///
+    /// Missing ones will be replaced by `LdtkLoadConfig::fallback_tileset`,
+    /// or panic if there's no fallback tileset.
+    pub(crate) fn resolve_missing_tilesets(
+        &mut self,
+        config: &LdtkLoadConfig,
+        ldtk_data: &LdtkJson,
+        atlas_layouts: &mut Assets<TextureAtlasLayout>,
+    ) {
+        let mut used = ldtk_data
+            .levels
+            .iter()
+            .chain(ldtk_data.worlds.iter().flat_map(|w| w.levels.iter()))
+            .flat_map(|level| level.layer_instances.iter())
+            .flat_map(|layer| {
+                layer.tileset_def_uid.into_iter().chain(
+                    layer
+                        .entity_instances
+                        .iter()
+                        .filter_map(|e| e.tile.as_ref().map(|t| t.tileset_uid)),
+                )
+            })
+            .collect::<Vec<_>>();
+        used.sort();
+        used.dedup();
+
+        for uid in used {
+            if self.tilesets.contains_key(&uid) {
+                continue;
+            }
+
+            let ident = ldtk_data
+                .defs
+                .tilesets
+                .iter()
+                .find(|t| t.uid == uid)
+                .map(|t| t.identifier.as_str())
+                .unwrap_or("<undefined>");
+            let Some(fallback) = config.fallback_tileset.as_ref() else {
+                panic!(
+                    "Tileset {}(uid = {}) can't be resolved! \
+                    Set LdtkLoadConfig::fallback_tileset to use a placeholder instead.",
+                    ident, uid
+                );
+            };
+
+            warn!(
+                "Tileset {}(uid = {}) can't be resolved, using the fallback tileset instead.",
+                ident, uid
+            );
+            self.tilesets.insert(uid, fallback.clone());
+            self.atlas_handles
+                .insert(uid, atlas_layouts.add(fallback.as_atlas_layout()));
+        }
+    }
+
     fn load_entities(
         &mut self,
         config: &LdtkLoadConfig,
@@ -240,21 +317,13 @@ impl LdtkAssets {
                     return;
                 };
 
-                let texture_size = self.get_tileset(tile_rect.tileset_uid).desc.size.as_vec2();
+                let sprite =
+                    LdtkTileSprite::new(tile_rect, self.get_tileset(tile_rect.tileset_uid));
                 self.materials.insert(
                     entity_instance.iid.clone(),
                     material_assets.add(LdtkEntityMaterial {
-                        texture: self.get_tileset(tile_rect.tileset_uid).texture.clone(),
-                        atlas_rect: AtlasRect {
-                            min: IVec2::new(tile_rect.x_pos, tile_rect.y_pos).as_vec2()
-                                / texture_size,
-                            max: IVec2::new(
-                                tile_rect.x_pos + tile_rect.width,
-                                tile_rect.y_pos + tile_rect.height,
-                            )
-                            .as_vec2()
-                                / texture_size,
-                        },
+                        atlas_rect: sprite.atlas_rect(),
+                        texture: sprite.texture,
                     }),
                 );
 
@@ -330,6 +399,77 @@ impl LdtkTocs {
     }
 }
 
+/// The custom data and enum tags of a tile in a tileset.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Reflect)]
+pub struct TileMetadata {
+    pub custom_data: Option<String>,
+    pub enum_tags: Vec<String>,
+}
+
+impl TileMetadata {
+    #[inline]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.enum_tags.iter().any(|t| t == tag)
+    }
+}
+
+/// The metadata of the tiles, keyed by tileset uid and then tile id.
+///
+/// Only tiles with custom data or enum tags are included.
+/// This is refreshed every time a level is loaded.
+#[derive(Resource, Debug, Default, Clone, Reflect)]
+pub struct LdtkTileMetadata(pub(crate) HashMap<i32, HashMap<i32, TileMetadata>>);
+
+impl LdtkTileMetadata {
+    pub fn new(ldtk_data: &LdtkJson) -> Self {
+        let mut metadata = Self::default();
+        metadata.reload(ldtk_data);
+        metadata
+    }
+
+    /// Collect the metadata from the tileset definitions again.
+    pub fn reload(&mut self, ldtk_data: &LdtkJson) {
+        self.0 = ldtk_data
+            .defs
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                let mut tiles = HashMap::<i32, TileMetadata>::new();
+                tileset.custom_data.iter().for_each(|d| {
+                    tiles.entry(d.tile_id).or_default().custom_data = Some(d.data.clone());
+                });
+                tileset.enum_tags.iter().for_each(|tag| {
+                    tag.tile_ids.iter().for_each(|id| {
+                        tiles
+                            .entry(*id)
+                            .or_default()
+                            .enum_tags
+                            .push(tag.enum_value_id.clone());
+                    });
+                });
+                (tileset.uid, tiles)
+            })
+            .collect();
+    }
+
+    #[inline]
+    pub fn get(&self, tileset_uid: i32, tile_id: i32) -> Option<&TileMetadata> {
+        self.0.get(&tileset_uid)?.get(&tile_id)
+    }
+
+    #[inline]
+    pub fn get_tileset(&self, tileset_uid: i32) -> Option<&HashMap<i32, TileMetadata>> {
+        self.0.get(&tileset_uid)
+    }
+
+    /// Returns true if the tile is tagged with the enum value `tag`.
+    #[inline]
+    pub fn has_tag(&self, tileset_uid: i32, tile_id: i32, tag: &str) -> bool {
+        self.get(tileset_uid, tile_id)
+            .is_some_and(|metadata| metadata.has_tag(tag))
+    }
+}
+
 /// The additional layers of the LDtk file.
 ///
 /// This includes path layer and physics layer. Entitiles will generate these layers
@@ -352,14 +492,250 @@ pub struct LdtkLoadConfig {
     pub z_index: f32,
     /// Map a certain texture index to a animation.
     pub animation_mapper: HashMap<u32, RawTileAnimation>,
-    pub ignore_unregistered_entities: bool,
+    /// What to do with entities whose identifier is not registered.
+    pub unknown_entity_policy: UnknownEntityPolicy,
     pub ignore_unregistered_entity_tags: bool,
+    /// What to do if some entities share the same iid.
+    pub duplicate_iid_policy: LdtkDuplicateIidPolicy,
+    /// Which entity is kept in the iid maps when some entities share the same iid.
+    pub duplicate_iid_resolution: LdtkDuplicateIidResolution,
+    /// How LDtk pixels are converted into world space.
+    pub coords: LdtkCoordConfig,
+    /// How the layers are ordered along the z axis.
+    pub layer_z: LdtkLayerZ,
+    /// The tileset used when a tileset can't be resolved,
+    /// for example it has no image or the uid doesn't exist.
+    /// It should have the same tile size as the tilesets it replaces.
+    ///
+    /// Loading will panic on missing tilesets if this is `None`.
+    pub fallback_tileset: Option<TilemapTexture>,
+    /// Animate the sprites of entities that have the frames field,
+    /// by adding a `LdtkEntityAnimation` to them. Disabled if `None`.
+    pub entity_animation: Option<LdtkEntityAnimationFields>,
+    /// Override the draw order of the tiles stacked in the same cell,
+    /// for the layers with these identifiers. Other layers keep the order from LDtk.
+    #[reflect(ignore)]
+    pub tile_sorters: HashMap<String, LdtkTileSorter>,
+}
+
+/// Compares two tiles of a layer. The greater one is drawn on top.
+pub type LdtkTileSorter = fn(&TileInstance, &TileInstance) -> Ordering;
+
+impl LdtkLoadConfig {
+    /// The tiles of a tile or auto layer in the order they are drawn.
+    ///
+    /// The sort is stable, so tiles that compare equal keep the order from LDtk.
+    pub fn sorted_tiles<'a>(&self, layer: &'a LayerInstance) -> Vec<&'a TileInstance> {
+        let tiles = match layer.ty {
+            LayerType::Tiles => &layer.grid_tiles,
+            _ => &layer.auto_layer_tiles,
+        };
+        let mut tiles = tiles.iter().collect::<Vec<_>>();
+        if let Some(sorter) = self.tile_sorters.get(&layer.identifier) {
+            tiles.sort_by(|a, b| sorter(a, b));
+        }
+        tiles
+    }
+}
+
+/// Entities need to be registered using `App::register_ldtk_entity::<T>()` before spawning.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum UnknownEntityPolicy {
+    /// Don't spawn them.
+    Skip,
+    /// Spawn them with a `LdtkUnknownEntity` containing the raw fields,
+    /// so they can be handled later or inspected.
+    SpawnPlaceholder,
+    /// Don't spawn them, and log the missing identifiers in each level as a warning.
+    #[default]
+    Warn,
+    /// Don't spawn them, and log the missing identifiers in each level as an error
+    /// along with sending a `LdtkEvent::UnknownEntities`.
+    Error,
+}
+
+/// Duplicate iids (from manual editing or merged files) will make entities
+/// overwrite each other when looking them up by iid.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum LdtkDuplicateIidPolicy {
+    /// Don't check for duplicates.
+    Ignore,
+    /// Log all the duplicates as warnings.
+    #[default]
+    Warn,
+    /// Panic if there's any duplicate.
+    Panic,
+}
+
+/// Which entity a duplicated iid refers to, in `LdtkLoadedLevel::entities`,
+/// `LdtkGlobalEntityRegistry` and `LdtkIidMap`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum LdtkDuplicateIidResolution {
+    #[default]
+    KeepFirst,
+    KeepLast,
+}
+
+impl LdtkDuplicateIidResolution {
+    /// Insert the entity into the iid map. If the iid is already taken, the conflict is
+    /// reported according to `policy` and the preferred entity is kept.
+    ///
+    /// Returns the entity that is not kept, if any.
+    pub fn insert(
+        self,
+        map: &mut HashMap<EntityIid, Entity>,
+        iid: EntityIid,
+        entity: Entity,
+        policy: LdtkDuplicateIidPolicy,
+    ) -> Option<Entity> {
+        let Some(&existing) = map.get(&iid).filter(|e| **e != entity) else {
+            map.insert(iid, entity);
+            return None;
+        };
+
+        let (kept, dropped) = match self {
+            LdtkDuplicateIidResolution::KeepFirst => (existing, entity),
+            LdtkDuplicateIidResolution::KeepLast => (entity, existing),
+        };
+        match policy {
+            LdtkDuplicateIidPolicy::Ignore => {}
+            LdtkDuplicateIidPolicy::Warn => warn!(
+                "Entities {:?} and {:?} share the iid {}! Keeping {:?}.",
+                existing, entity, iid.0, kept
+            ),
+            LdtkDuplicateIidPolicy::Panic => panic!(
+                "Entities {:?} and {:?} share the iid {}!",
+                existing, entity, iid.0
+            ),
+        }
+        map.insert(iid, kept);
+        Some(dropped)
+    }
+}
+
+impl LdtkCoordConfig {
+    /// LDtk places tiles larger than the grid by their top left corner,
+    /// so they overhang the cells to the right and below.
+    #[inline]
+    pub fn tile_anchor(&self) -> TileAnchor {
+        if self.y_up {
+            TileAnchor(Vec2::new(0., 1.))
+        } else {
+            TileAnchor::BOTTOM_LEFT
+        }
+    }
+}
+
+/// The z of the layers, entities and the background of a level,
+/// relative to `LdtkLoadConfig::z_index`.
+///
+/// The first layer in `Level::layer_instances` is the top most one.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct LdtkLayerZ {
+    /// The distance between two layers.
+    ///
+    /// The entities of a layer take up to 1 above the layer,
+    /// so a larger step leaves room for your own entities in between.
+    pub step: f32,
+}
+
+impl Default for LdtkLayerZ {
+    fn default() -> Self {
+        Self { step: 1. }
+    }
+}
+
+impl LdtkLayerZ {
+    #[inline]
+    pub fn layer(&self, layer_index: usize) -> f32 {
+        -(layer_index as f32 + 1.) * self.step
+    }
+
+    /// The z of the `order`th of the `count` entities in a layer.
+    /// Later entities are drawn on top.
+    #[inline]
+    pub fn entity(&self, layer_index: usize, order: usize, count: usize) -> f32 {
+        self.layer(layer_index) + order as f32 / count as f32 * self.step.min(1.)
+    }
+
+    /// The background is below all the layers.
+    #[inline]
+    pub fn background(&self, layer_count: usize) -> f32 {
+        self.layer(layer_count)
+    }
+}
+
+/// Insert this resource to sync the clear color of active cameras
+/// with the `bg_color` of the focused level.
+///
+/// The latest loaded level is focused automatically.
+#[derive(Resource, Default, Reflect)]
+pub struct LdtkLevelClearColor {
+    /// The identifier of the focused level.
+    pub focused: Option<String>,
+    /// Lerp towards the new color at this speed (per second) instead of switching instantly.
+    pub lerp_speed: Option<f32>,
+}
+
+/// Insert this resource to reload the LDtk file and respawn the loaded levels
+/// whenever the file is modified, so you don't need to restart the app after every edit.
+///
+/// The file is read from the disk directly instead of through the `AssetServer`,
+/// so there are no asset events for it and its modification time is polled
+/// every `interval` seconds instead.
+///
+/// The levels are torn down with `LdtkUnloader` like `LdtkLevelManager::unload()` does.
+/// `LdtkLoadedLevel` already records every layer and entity spawned for a level,
+/// so no extra marker component is needed. Global entities, cameras and everything
+/// else not spawned by the levels are kept.
+#[derive(Resource, Reflect)]
+pub struct LdtkHotReload {
+    pub(crate) timer: Timer,
+    #[reflect(ignore)]
+    pub(crate) last_modified: Option<SystemTime>,
+}
+
+impl LdtkHotReload {
+    pub fn new(interval: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(interval, TimerMode::Repeating),
+            last_modified: None,
+        }
+    }
+}
+
+impl Default for LdtkHotReload {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+/// Insert this resource to keep active cameras inside the focused level.
+///
+/// The latest loaded level is focused automatically.
+/// The level rectangle can be overridden by a level field, see `Level::camera_bounds()`.
+#[derive(Resource, Reflect)]
+pub struct LdtkCameraBounds {
+    /// The identifier of the focused level.
+    pub focused: Option<String>,
+    /// The identifier of the level field that overrides the bounds.
+    pub field: String,
+}
+
+impl Default for LdtkCameraBounds {
+    fn default() -> Self {
+        Self {
+            focused: None,
+            field: "CameraBounds".to_string(),
+        }
+    }
 }
 
 #[derive(Resource, Default, Reflect)]
 pub struct LdtkLevelManager {
     pub(crate) ldtk_json: Option<LdtkJson>,
     pub(crate) loaded_levels: HashMap<String, Entity>,
+    pub(crate) trans_ovrds: HashMap<String, Vec2>,
 }
 
 impl LdtkLevelManager {
@@ -370,16 +746,112 @@ impl LdtkLevelManager {
             return;
         }
 
-        let path = std::env::current_dir().unwrap().join(&config.file_path);
-        let str_raw = match read_to_string(&path) {
+        match Self::read_json(config) {
+            Ok(data) => self.ldtk_json = Some(data),
+            Err(e) => panic!("{}", e),
+        }
+
+        self.check_duplicate_iids(config.duplicate_iid_policy);
+    }
+
+    /// Reloads the LDtk file, then unload and load all the loaded levels again.
+    ///
+    /// Unlike `reload_json`, this won't panic if the file can't be parsed,
+    /// as it may be saved halfway. The levels will stay as they are in that case.
+    pub fn hot_reload(&mut self, commands: &mut Commands, config: &LdtkLoadConfig) {
+        let ldtk_json = match Self::read_json(config) {
             Ok(data) => data,
-            Err(e) => panic!("Could not read file at path: {:?}!\n{}", path, e),
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
         };
+        self.ldtk_json = Some(ldtk_json);
+        self.check_duplicate_iids(config.duplicate_iid_policy);
 
-        self.ldtk_json = match serde_json::from_str::<LdtkJson>(&str_raw) {
-            Ok(data) => Some(data),
-            Err(e) => panic!("Could not parse file at path: {}!\n{}", config.file_path, e),
+        let trans_ovrds = self.trans_ovrds.clone();
+        let levels = self.loaded_levels.keys().cloned().collect::<Vec<_>>();
+        self.unload_all(commands);
+
+        for level in levels {
+            if !self
+                .get_cached_data()
+                .levels
+                .iter()
+                .any(|l| l.identifier == level)
+            {
+                warn!("Level {:?} is removed from the LDtk file!", level);
+                continue;
+            }
+
+            let trans_ovrd = trans_ovrds.get(&level).cloned();
+            self.load(commands, level, trans_ovrd);
+        }
+    }
+
+    fn read_json(config: &LdtkLoadConfig) -> Result<LdtkJson, String> {
+        let path = std::env::current_dir().unwrap().join(&config.file_path);
+        let str_raw = read_to_string(&path)
+            .map_err(|e| format!("Could not read file at path: {:?}!\n{}", path, e))?;
+
+        let mut ldtk_json = LdtkJson::parse_str(&str_raw)
+            .map_err(|e| format!("Could not parse file at path: {}!\n{}", config.file_path, e))?;
+
+        if ldtk_json.external_levels {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            for level in ldtk_json.levels.iter_mut().chain(
+                ldtk_json
+                    .worlds
+                    .iter_mut()
+                    .flat_map(|w| w.levels.iter_mut()),
+            ) {
+                Self::splice_external_level(dir, level)?;
+            }
+        }
+
+        Ok(ldtk_json)
+    }
+
+    /// With "Save levels separately", the layers of a level are saved in their own file.
+    fn splice_external_level(dir: &Path, level: &mut Level) -> Result<(), String> {
+        let Some(rel_path) = &level.external_rel_path else {
+            return Ok(());
         };
+
+        let path = dir.join(rel_path);
+        let str_raw = read_to_string(&path)
+            .map_err(|e| format!("Could not read level file at path: {:?}!\n{}", path, e))?;
+        let external = serde_json::from_str::<Level>(&str_raw)
+            .map_err(|e| format!("Could not parse level file at path: {:?}!\n{}", path, e))?;
+
+        level.layer_instances = external.layer_instances;
+        Ok(())
+    }
+
+    fn check_duplicate_iids(&self, policy: LdtkDuplicateIidPolicy) {
+        if policy == LdtkDuplicateIidPolicy::Ignore {
+            return;
+        }
+
+        let duplicates = self.get_cached_data().find_duplicate_iids();
+        if duplicates.is_empty() {
+            return;
+        }
+
+        match policy {
+            LdtkDuplicateIidPolicy::Ignore => {}
+            LdtkDuplicateIidPolicy::Warn => duplicates.iter().for_each(|d| {
+                warn!("Duplicate entity iid found! {}", d);
+            }),
+            LdtkDuplicateIidPolicy::Panic => panic!(
+                "Duplicate entity iids found!\n{}",
+                duplicates
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        }
     }
 
     pub fn get_cached_data(&self) -> &LdtkJson {
@@ -399,6 +871,9 @@ impl LdtkLevelManager {
                 trans_ovrd,
             });
             self.loaded_levels.insert(level.clone(), entity.id());
+            if let Some(trans_ovrd) = trans_ovrd {
+                self.trans_ovrds.insert(level, trans_ovrd);
+            }
         }
     }
 
@@ -437,16 +912,40 @@ impl LdtkLevelManager {
         if let Some(l) = self.loaded_levels.get(&level) {
             commands.entity(*l).insert(LdtkUnloader);
             self.loaded_levels.remove(&level);
+            self.trans_ovrds.remove(&level);
         } else {
             error!("Trying to unload {:?} that is not loaded!", level);
         }
     }
 
+    /// Unload the level with the iid, like `unload()` but for levels in worlds
+    /// which are easier to track by iid.
+    ///
+    /// Only the tiles, entities and render data of this level are removed.
+    /// Other levels and the textures they share are left untouched.
+    pub fn despawn_level(&mut self, commands: &mut Commands, level_iid: &str) {
+        self.check_initialized();
+        let Some(level) = self
+            .get_cached_data()
+            .iter_levels()
+            .find(|level| level.iid == level_iid)
+        else {
+            error!(
+                "Trying to despawn level {:?} that doesn't exist!",
+                level_iid
+            );
+            return;
+        };
+        let identifier = level.identifier.clone();
+        self.unload(commands, identifier);
+    }
+
     pub fn unload_all(&mut self, commands: &mut Commands) {
         for (_, l) in self.loaded_levels.iter() {
             commands.entity(*l).insert(LdtkUnloader);
         }
         self.loaded_levels.clear();
+        self.trans_ovrds.clear();
     }
 
     pub fn is_loaded(&self, level: String) -> bool {
@@ -509,3 +1008,556 @@ impl LdtkGlobalEntityRegistry {
         self.remove_all();
     }
 }
+
+/// All the entities with an `EntityIid` by their iids, global or not.
+///
+/// This is kept up to date as entities are spawned and despawned, so entities loaded
+/// from a save with their iids can be found by the `EntityRef`s pointing to them.
+#[derive(Resource, Default, Reflect)]
+pub struct LdtkIidMap(pub(crate) HashMap<EntityIid, Entity>);
+
+impl LdtkIidMap {
+    #[inline]
+    pub fn get(&self, iid: &EntityIid) -> Option<Entity> {
+        self.0.get(iid).cloned()
+    }
+
+    /// Get the entity an `EntityRef` field points to.
+    #[inline]
+    pub fn resolve(&self, entity_ref: &EntityRef) -> Option<Entity> {
+        self.get(&EntityIid(entity_ref.entity_iid.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ldtk::{layer::LdtkLayers, load_grid_vania};
+
+    use super::*;
+
+    #[test]
+    fn test_fallback_tileset() {
+        let json = load_grid_vania();
+        let level = &json.levels[0];
+        let (layer_index, layer) = level
+            .layer_instances
+            .iter()
+            .enumerate()
+            .find(|(_, l)| l.ty == LayerType::Tiles && !l.grid_tiles.is_empty())
+            .unwrap();
+        let fallback = TilemapTexture {
+            texture: Handle::default(),
+            desc: TilemapTextureDescriptor {
+                size: UVec2::splat(layer.grid_size as u32 * 2),
+                tile_size: UVec2::splat(layer.grid_size as u32),
+            },
+        };
+        let config = LdtkLoadConfig {
+            fallback_tileset: Some(fallback.clone()),
+            ..Default::default()
+        };
+
+        // No textures are loaded, so every tileset is missing.
+        let mut assets = LdtkAssets::default();
+        assets.resolve_missing_tilesets(&config, &json, &mut Assets::default());
+        let uid = layer.tileset_def_uid.unwrap();
+        assert_eq!(assets.get_tileset(uid).desc, fallback.desc);
+
+        let mut layers = LdtkLayers::new(
+            Entity::PLACEHOLDER,
+            level,
+            level.layer_instances.len(),
+            &assets,
+            Vec2::ZERO,
+            0.,
+            LdtkLoaderMode::Tilemap,
+            SpriteBundle::default(),
+        );
+        layer.grid_tiles.iter().for_each(|tile| {
+            layers.set_tile(
+                layer_index,
+                layer,
+                tile,
+                &config,
+                &LdtkPatterns::default(),
+                &LdtkLoaderMode::Tilemap,
+            )
+        });
+        let (pattern, texture, _, _) = layers.layers[layer_index].as_ref().unwrap();
+        assert_eq!(texture.desc, fallback.desc);
+        assert!(!pattern.tiles.tiles.is_empty());
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        use bevy::ecs::{system::CommandQueue, world::World};
+
+        let config = LdtkLoadConfig {
+            file_path: "assets/ldtk/grid_vania.ldtk".to_string(),
+            ..Default::default()
+        };
+        let mut manager = LdtkLevelManager::default();
+        manager.reload_json(&config);
+        let levels = manager
+            .get_cached_data()
+            .levels
+            .iter()
+            .map(|l| l.identifier.clone())
+            .take(2)
+            .collect::<Vec<_>>();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            manager.load(&mut commands, levels[0].clone(), Some(Vec2::splat(10.)));
+            manager.load(&mut commands, levels[1].clone(), None);
+        }
+        queue.apply(&mut world);
+        let old = manager.loaded_levels.clone();
+
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            manager.hot_reload(&mut commands, &config);
+        }
+        queue.apply(&mut world);
+
+        for level in &levels {
+            assert!(world.get::<LdtkUnloader>(old[level]).is_some());
+            let loader = world
+                .get::<LdtkLoader>(manager.loaded_levels[level])
+                .unwrap();
+            assert_eq!(&loader.level, level);
+        }
+        let loader = world
+            .get::<LdtkLoader>(manager.loaded_levels[&levels[0]])
+            .unwrap();
+        assert_eq!(loader.trans_ovrd, Some(Vec2::splat(10.)));
+        let loader = world
+            .get::<LdtkLoader>(manager.loaded_levels[&levels[1]])
+            .unwrap();
+        assert_eq!(loader.trans_ovrd, None);
+
+        // Nothing changes if the file can't be read.
+        let loaded = manager.loaded_levels.clone();
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            manager.hot_reload(
+                &mut commands,
+                &LdtkLoadConfig {
+                    file_path: "assets/ldtk/missing.ldtk".to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+        queue.apply(&mut world);
+        assert_eq!(manager.loaded_levels, loaded);
+        assert!(manager.is_initialized());
+    }
+
+    #[test]
+    fn test_tile_metadata() {
+        use crate::ldtk::json::definitions::{CustomData, EnumTag};
+
+        let mut json = load_grid_vania();
+        let tileset = &mut json.defs.tilesets[0];
+        let uid = tileset.uid;
+        tileset.custom_data.push(CustomData {
+            data: "{\"damage\": 3}".to_string(),
+            tile_id: 7,
+        });
+        tileset.enum_tags.push(EnumTag {
+            enum_value_id: "Damaging".to_string(),
+            tile_ids: vec![7, 8],
+        });
+        tileset.enum_tags.push(EnumTag {
+            enum_value_id: "Grass".to_string(),
+            tile_ids: vec![8],
+        });
+
+        let metadata = LdtkTileMetadata::new(&json);
+        assert_eq!(
+            metadata.get(uid, 7),
+            Some(&TileMetadata {
+                custom_data: Some("{\"damage\": 3}".to_string()),
+                enum_tags: vec!["Damaging".to_string()],
+            })
+        );
+        assert_eq!(metadata.get(uid, 8).unwrap().custom_data, None);
+        assert!(metadata.has_tag(uid, 8, "Damaging"));
+        assert!(metadata.has_tag(uid, 8, "Grass"));
+        assert!(!metadata.has_tag(uid, 7, "Grass"));
+        assert!(metadata.get(uid, 9).is_none());
+        assert!(metadata.get(-1, 7).is_none());
+    }
+
+    #[test]
+    fn test_entity_sprite_sheet() {
+        use bevy::{math::Rect, sprite::Anchor};
+
+        let json = load_grid_vania();
+        let config = LdtkLoadConfig {
+            fallback_tileset: Some(TilemapTexture {
+                texture: Handle::default(),
+                desc: TilemapTextureDescriptor {
+                    size: UVec2::new(512, 1024),
+                    tile_size: UVec2::splat(16),
+                },
+            }),
+            ..Default::default()
+        };
+        let mut assets = LdtkAssets::default();
+        assets.resolve_missing_tilesets(&config, &json, &mut Assets::default());
+
+        let entities = json.levels[0]
+            .layer_instances
+            .iter()
+            .flat_map(|layer| layer.entity_instances.iter())
+            .collect::<Vec<_>>();
+
+        let player = entities.iter().find(|e| e.identifier == "Player").unwrap();
+        let sprite = player.sprite_sheet_bundle(&assets).unwrap();
+        // The tile is at (256, 144) in a 32 columns tileset.
+        assert_eq!(sprite.atlas.index, 9 * 32 + 16);
+        assert_eq!(sprite.sprite.rect, Some(Rect::new(0., 0., 16., 16.)));
+        assert_eq!(
+            sprite.sprite.custom_size,
+            Some(Vec2::new(player.width as f32, player.height as f32))
+        );
+        assert_eq!(sprite.sprite.anchor, Anchor::Center);
+
+        // Unaligned tiles are offset from the cell containing their corner.
+        let mut unaligned = (*player).clone();
+        unaligned.tile.as_mut().unwrap().x_pos += 4;
+        let sprite = unaligned.sprite_sheet_bundle(&assets).unwrap();
+        assert_eq!(sprite.atlas.index, 9 * 32 + 16);
+        assert_eq!(sprite.sprite.rect, Some(Rect::new(4., 0., 20., 16.)));
+
+        let mut no_tile = (*player).clone();
+        no_tile.tile = None;
+        assert!(no_tile.sprite_sheet_bundle(&assets).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_missing_tileset_strict() {
+        let mut assets = LdtkAssets::default();
+        assets.resolve_missing_tilesets(
+            &LdtkLoadConfig::default(),
+            &load_grid_vania(),
+            &mut Assets::default(),
+        );
+    }
+
+    #[test]
+    fn test_layer_z() {
+        for step in [1., 10.] {
+            let layer_z = LdtkLayerZ { step };
+            assert!(layer_z.layer(0) > layer_z.layer(1));
+            assert!(layer_z.layer(1) > layer_z.layer(2));
+            assert!(layer_z.layer(2) > layer_z.background(3));
+
+            // The entities of a layer are above it but below the next one.
+            let first = layer_z.entity(1, 0, 4);
+            let last = layer_z.entity(1, 3, 4);
+            assert!(first >= layer_z.layer(1));
+            assert!(last > first);
+            assert!(last < layer_z.layer(0));
+        }
+
+        // There's room for other entities between the layers.
+        let layer_z = LdtkLayerZ { step: 10. };
+        assert_eq!(layer_z.layer(0), -10.);
+        assert!(layer_z.entity(1, 3, 4) < -11.);
+    }
+
+    #[test]
+    fn test_tile_sorters() {
+        let json = load_grid_vania();
+        let layer = json.levels[0]
+            .layer_instances
+            .iter()
+            .find(|l| l.identifier == "Collisions")
+            .unwrap();
+
+        let mut config = LdtkLoadConfig::default();
+        let ids = |config: &LdtkLoadConfig| {
+            config
+                .sorted_tiles(layer)
+                .into_iter()
+                .map(|t| t.tile_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(&config),
+            layer
+                .auto_layer_tiles
+                .iter()
+                .map(|t| t.tile_id)
+                .collect::<Vec<_>>()
+        );
+
+        config
+            .tile_sorters
+            .insert("Collisions".to_string(), |a, b| b.tile_id.cmp(&a.tile_id));
+        let sorted = ids(&config);
+        assert_eq!(sorted.len(), layer.auto_layer_tiles.len());
+        assert!(sorted.windows(2).all(|w| w[0] >= w[1]));
+        assert_ne!(sorted, ids(&LdtkLoadConfig::default()));
+    }
+
+    #[test]
+    fn test_duplicate_iid_resolution() {
+        let iid = EntityIid("a".to_string());
+        let first = Entity::from_raw(1);
+        let second = Entity::from_raw(2);
+
+        let mut map = HashMap::default();
+        let keep_first = LdtkDuplicateIidResolution::KeepFirst;
+        let policy = LdtkDuplicateIidPolicy::Warn;
+        assert_eq!(
+            keep_first.insert(&mut map, iid.clone(), first, policy),
+            None
+        );
+        assert_eq!(
+            keep_first.insert(&mut map, iid.clone(), second, policy),
+            Some(second)
+        );
+        assert_eq!(map[&iid], first);
+        // Inserting the same entity again is not a conflict.
+        assert_eq!(
+            keep_first.insert(&mut map, iid.clone(), first, policy),
+            None
+        );
+
+        let mut map = HashMap::default();
+        let keep_last = LdtkDuplicateIidResolution::KeepLast;
+        keep_last.insert(&mut map, iid.clone(), first, policy);
+        assert_eq!(
+            keep_last.insert(&mut map, iid.clone(), second, policy),
+            Some(first)
+        );
+        assert_eq!(map[&iid], second);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn test_despawn_level() {
+        use bevy::{
+            ecs::{
+                event::Events,
+                system::{CommandQueue, RunSystemOnce},
+                world::World,
+            },
+            math::IVec2,
+            tasks::{ComputeTaskPool, TaskPool},
+        };
+
+        use crate::{
+            ldtk::{
+                components::{LdtkLoadedLevel, LevelIid},
+                events::LdtkEvent,
+                unload_ldtk_layer, unload_ldtk_level,
+            },
+            math::TileArea,
+            tilemap::{
+                despawn::{despawn_applier, DespawnMe},
+                map::TilemapStorage,
+                tile::TileBuilder,
+            },
+        };
+
+        let mut manager = LdtkLevelManager::default();
+        manager.reload_json(&LdtkLoadConfig {
+            file_path: "assets/ldtk/grid_vania.ldtk".to_string(),
+            ..Default::default()
+        });
+        let levels = manager.get_cached_data().levels[..2].to_vec();
+
+        // The despawning systems use parallel queries.
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        let mut world = World::new();
+        world.init_resource::<Events<LdtkEvent>>();
+        world.init_resource::<LdtkGlobalEntityRegistry>();
+
+        // A loaded level with one layer of 4 tiles and one entity.
+        let mut spawn_level = |world: &mut World, level: &Level| {
+            let tilemap = world.spawn_empty().id();
+            let mut storage = TilemapStorage::new(4, tilemap);
+            let mut queue = CommandQueue::default();
+            storage.fill_rect(
+                &mut Commands::new(&mut queue, world),
+                TileArea::new(IVec2::ZERO, UVec2::splat(2)),
+                TileBuilder::new(),
+            );
+            queue.apply(world);
+            let tiles = storage.storage.iter_some().copied().collect::<Vec<_>>();
+            world.entity_mut(tilemap).insert(storage);
+
+            let entity = world.spawn(EntityIid(format!("{}-entity", level.iid))).id();
+            let background = world.spawn_empty().id();
+            let level_entity = world
+                .spawn((
+                    LdtkLoadedLevel {
+                        identifier: level.identifier.clone(),
+                        layers: HashMap::from_iter([(
+                            LayerIid(format!("{}-layer", level.iid)),
+                            tilemap,
+                        )]),
+                        entities: HashMap::from_iter([(
+                            EntityIid(format!("{}-entity", level.iid)),
+                            entity,
+                        )]),
+                        background,
+                    },
+                    LevelIid(level.iid.clone()),
+                ))
+                .id();
+            manager
+                .loaded_levels
+                .insert(level.identifier.clone(), level_entity);
+            (level_entity, entity, tiles)
+        };
+        let (despawned_level, despawned_entity, despawned_tiles) =
+            spawn_level(&mut world, &levels[0]);
+        let (kept_level, kept_entity, kept_tiles) = spawn_level(&mut world, &levels[1]);
+
+        let mut queue = CommandQueue::default();
+        manager.despawn_level(&mut Commands::new(&mut queue, &world), &levels[0].iid);
+        queue.apply(&mut world);
+        world.run_system_once(unload_ldtk_level);
+        world.run_system_once(unload_ldtk_layer);
+        assert!(despawned_tiles
+            .iter()
+            .all(|t| world.get::<DespawnMe>(*t).is_some()));
+        world.run_system_once(despawn_applier);
+
+        assert!(world.get_entity(despawned_level).is_none());
+        assert!(world.get_entity(despawned_entity).is_none());
+        assert!(despawned_tiles
+            .iter()
+            .all(|t| world.get_entity(*t).is_none()));
+
+        assert!(world.get::<LdtkLoadedLevel>(kept_level).is_some());
+        assert!(world.get_entity(kept_entity).is_some());
+        assert_eq!(kept_tiles.len(), 4);
+        assert!(kept_tiles.iter().all(|t| world.get_entity(*t).is_some()));
+        assert!(!manager.is_loaded(levels[0].identifier.clone()));
+        assert!(manager.is_loaded(levels[1].identifier.clone()));
+    }
+
+    #[test]
+    fn test_entity_refs_across_save() {
+        use bevy::ecs::{system::RunSystemOnce, world::World};
+
+        use crate::ldtk::{
+            components::{GlobalEntity, LdtkFields},
+            entity_iid_mapper, global_entity_registerer,
+            json::field::{FieldInstance, FieldValue},
+        };
+
+        let fields = |target: &str| {
+            LdtkFields(HashMap::from_iter([(
+                "Target".to_string(),
+                FieldInstance {
+                    def_uid: 0,
+                    identifier: "Target".to_string(),
+                    tile: None,
+                    ty: String::new(),
+                    value: Some(FieldValue::EntityRef(EntityRef {
+                        entity_iid: target.to_string(),
+                        layer_iid: "layer".to_string(),
+                        level_iid: "level".to_string(),
+                        world_iid: "world".to_string(),
+                    })),
+                },
+            )]))
+        };
+        let resolve = |world: &World, entity: Entity| {
+            world
+                .resource::<LdtkIidMap>()
+                .resolve(world.get::<LdtkFields>(entity)?.get_entity_ref("Target")?)
+        };
+        let update = |world: &mut World| {
+            world.run_system_once(global_entity_registerer);
+            world.run_system_once(entity_iid_mapper);
+        };
+
+        let mut world = World::new();
+        world.init_resource::<LdtkGlobalEntityRegistry>();
+        world.init_resource::<LdtkIidMap>();
+        world.init_resource::<LdtkLoadConfig>();
+
+        // A global entity and a level entity referencing each other.
+        let a = world
+            .spawn((EntityIid("a".to_string()), GlobalEntity, fields("b")))
+            .id();
+        let b = world.spawn((EntityIid("b".to_string()), fields("a"))).id();
+        update(&mut world);
+        assert_eq!(resolve(&world, a), Some(b));
+        assert_eq!(resolve(&world, b), Some(a));
+
+        // Save them along with their iids, and despawn them.
+        let saved = serde_json::to_string(&[a, b].map(|e| {
+            (
+                world.get::<EntityIid>(e).cloned(),
+                world.get::<LdtkFields>(e).cloned(),
+            )
+        }))
+        .unwrap();
+        world.despawn(a);
+        world.despawn(b);
+        update(&mut world);
+        assert!(!world
+            .resource::<LdtkGlobalEntityRegistry>()
+            .contains(&EntityIid("a".to_string())));
+        assert!(world.resource::<LdtkIidMap>().0.is_empty());
+
+        // Load them as new entities, the references point to them again.
+        let loaded = serde_json::from_str::<Vec<(EntityIid, LdtkFields)>>(&saved)
+            .unwrap()
+            .into_iter()
+            .map(|(iid, fields)| world.spawn((iid, fields)).id())
+            .collect::<Vec<_>>();
+        world.entity_mut(loaded[0]).insert(GlobalEntity);
+        update(&mut world);
+        assert!(!loaded.contains(&a) && !loaded.contains(&b));
+        assert_eq!(resolve(&world, loaded[0]), Some(loaded[1]));
+        assert_eq!(resolve(&world, loaded[1]), Some(loaded[0]));
+        assert_eq!(
+            world
+                .resource::<LdtkGlobalEntityRegistry>()
+                .get(&EntityIid("a".to_string())),
+            Some(loaded[0])
+        );
+    }
+
+    #[test]
+    fn test_iid_map_duplicates() {
+        use bevy::ecs::{system::RunSystemOnce, world::World};
+
+        use crate::ldtk::entity_iid_mapper;
+
+        let iid = EntityIid("a".to_string());
+        for (resolution, keep_first) in [
+            (LdtkDuplicateIidResolution::KeepFirst, true),
+            (LdtkDuplicateIidResolution::KeepLast, false),
+        ] {
+            let mut world = World::new();
+            world.init_resource::<LdtkIidMap>();
+            world.insert_resource(LdtkLoadConfig {
+                duplicate_iid_policy: LdtkDuplicateIidPolicy::Ignore,
+                duplicate_iid_resolution: resolution,
+                ..Default::default()
+            });
+
+            let first = world.spawn(iid.clone()).id();
+            world.run_system_once(entity_iid_mapper);
+            let second = world.spawn(iid.clone()).id();
+            world.run_system_once(entity_iid_mapper);
+
+            let expected = if keep_first { first } else { second };
+            assert_eq!(world.resource::<LdtkIidMap>().get(&iid), Some(expected));
+        }
+    }
+}
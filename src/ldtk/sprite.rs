@@ -1,23 +1,35 @@
+use std::ops::Range;
+
 use bevy::{
     asset::{Asset, Handle},
-    math::{IVec2, IVec4, Vec2, Vec4},
+    ecs::{component::Component, system::EntityCommands},
+    math::{IVec2, IVec4, Rect, Vec2, Vec4},
     reflect::Reflect,
     render::{
+        color::Color,
         render_resource::{AsBindGroup, ShaderRef, ShaderType},
         texture::Image,
     },
-    sprite::Material2d,
+    sprite::{
+        Anchor, Material2d, MaterialMesh2dBundle, Sprite, SpriteBundle, SpriteSheetBundle,
+        TextureAtlas,
+    },
+    transform::components::Transform,
     utils::HashMap,
 };
-use serde::{Deserialize, Serialize};
 
-use crate::{math::extension::DivToCeil, utils::mesh::clip_quad_mesh};
+use crate::{
+    math::extension::DivToCeil, tilemap::map::TilemapTexture, utils::mesh::clip_quad_mesh,
+};
 
 use super::{
     json::{
-        definitions::{EntityDef, TilesetRect},
+        definitions::{EntityDef, NineSliceBorders, TileRenderMode, TilesetRect},
+        field::{FieldInstance, FieldValue},
         level::EntityInstance,
+        LdtkColor,
     },
+    resources::LdtkAssets,
     ENTITY_SPRITE_SHADER,
 };
 
@@ -36,6 +48,58 @@ impl From<TilesetRect> for AtlasRect {
     }
 }
 
+/// A tile picked from a tileset by a `TilesetRect`,
+/// like the icon of an enum value in `FieldInstance::tile`.
+#[derive(Debug, Clone, Reflect)]
+pub struct LdtkTileSprite {
+    pub texture: Handle<Image>,
+    /// The size of the whole tileset in pixels.
+    pub texture_size: Vec2,
+    /// The rect of the tile in pixels, from the top left corner of the tileset.
+    pub rect: Rect,
+}
+
+impl LdtkTileSprite {
+    pub fn new(tile_rect: &TilesetRect, tileset: &TilemapTexture) -> Self {
+        Self {
+            texture: tileset.texture.clone(),
+            texture_size: tileset.desc.size.as_vec2(),
+            rect: Rect::new(
+                tile_rect.x_pos as f32,
+                tile_rect.y_pos as f32,
+                (tile_rect.x_pos + tile_rect.width) as f32,
+                (tile_rect.y_pos + tile_rect.height) as f32,
+            ),
+        }
+    }
+
+    /// The rect in uvs, which is what `LdtkEntityMaterial` takes.
+    #[inline]
+    pub fn atlas_rect(&self) -> AtlasRect {
+        AtlasRect {
+            min: self.rect.min / self.texture_size,
+            max: self.rect.max / self.texture_size,
+        }
+    }
+
+    pub fn sprite_bundle(&self) -> SpriteBundle {
+        SpriteBundle {
+            sprite: Sprite {
+                rect: Some(self.rect),
+                ..Default::default()
+            },
+            texture: self.texture.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<LdtkColor> for Color {
+    fn from(value: LdtkColor) -> Self {
+        Color::rgba(value.r, value.g, value.b, value.a)
+    }
+}
+
 #[derive(AsBindGroup, Asset, Debug, Clone, Reflect)]
 pub struct LdtkEntityMaterial {
     #[texture(0)]
@@ -51,17 +115,6 @@ impl Material2d for LdtkEntityMaterial {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
-pub enum TileRenderMode {
-    Cover,
-    FitInside,
-    Repeat,
-    Stretch,
-    FullSizeCropped,
-    FullSizeUncropped,
-    NineSlice,
-}
-
 impl TileRenderMode {
     pub fn as_shader_def(&self) -> String {
         match self {
@@ -198,15 +251,6 @@ impl TileRenderMode {
     }
 }
 
-#[derive(Serialize, Debug, Clone, Copy, Reflect)]
-pub struct NineSliceBorders {
-    pub is_valid: bool,
-    pub up: i32,
-    pub right: i32,
-    pub down: i32,
-    pub left: i32,
-}
-
 #[derive(Reflect)]
 pub struct SpriteMesh {
     pub vertices: Vec<Vec2>,
@@ -355,7 +399,7 @@ impl NineSliceBorders {
         let valid_rects = [
             [
                 Vec2::new(border_pxs.z, 0.),
-                Vec2::new(render_size.x - border_pxs.z, border_pxs.x),
+                Vec2::new(render_size.x - border_pxs.w, border_pxs.x),
             ],
             [
                 Vec2::new(border_pxs.z, render_size.y - border_pxs.y),
@@ -405,10 +449,10 @@ impl NineSliceBorders {
         // inner
         let origin = Vec2::new(border_pxs.z, border_pxs.x);
         let inner_slice_uvs = [
-            Vec2::new(border_uvs.x, border_uvs.z),
-            Vec2::new(1. - border_uvs.y, border_uvs.z),
-            Vec2::new(1. - border_uvs.y, 1. - border_uvs.w),
-            Vec2::new(border_uvs.x, 1. - border_uvs.w),
+            Vec2::new(border_uvs.z, border_uvs.x),
+            Vec2::new(1. - border_uvs.w, border_uvs.x),
+            Vec2::new(1. - border_uvs.w, 1. - border_uvs.y),
+            Vec2::new(border_uvs.z, 1. - border_uvs.y),
         ];
         let valid_inner_range = [
             Vec2::new(border_pxs.z, border_pxs.x),
@@ -442,3 +486,338 @@ impl NineSliceBorders {
         }
     }
 }
+
+impl EntityInstance {
+    pub fn generate_sprite(&self, commands: &mut EntityCommands, assets: &LdtkAssets) {
+        if self.tile.is_none() {
+            return;
+        }
+
+        commands.insert(MaterialMesh2dBundle {
+            mesh: assets.clone_mesh_handle(&self.iid),
+            material: assets.clone_material_handle(&self.iid),
+            transform: Transform::from_xyz(self.local_pos[0] as f32, -self.local_pos[1] as f32, 0.),
+            ..Default::default()
+        });
+    }
+
+    /// Build a sprite sheet from the tile of this entity, or `None` if it doesn't have one.
+    ///
+    /// The sprite is stretched to the size of the entity, so resized entities look
+    /// the same as in the editor.
+    pub fn sprite_sheet_bundle(&self, assets: &LdtkAssets) -> Option<SpriteSheetBundle> {
+        let tile = self.tile.as_ref()?;
+        let tileset = assets.get_tileset(tile.tileset_uid);
+        let tile_size = tileset.desc.tile_size.as_ivec2();
+        let columns = tileset.desc.size.x / tileset.desc.tile_size.x;
+
+        // The rect doesn't have to be aligned to the grid, so select the cell containing
+        // the top left corner and offset the sprite rect from there.
+        let min = IVec2::new(tile.x_pos, tile.y_pos);
+        let cell = min / tile_size;
+        let offset = (min - cell * tile_size).as_vec2();
+
+        Some(SpriteSheetBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(self.width as f32, self.height as f32)),
+                rect: Some(Rect::from_corners(
+                    offset,
+                    offset + Vec2::new(tile.width as f32, tile.height as f32),
+                )),
+                ..Default::default()
+            },
+            texture: tileset.texture.clone(),
+            atlas: TextureAtlas {
+                layout: assets.clone_atlas_handle(tile.tileset_uid),
+                index: (cell.y as u32 * columns + cell.x as u32) as usize,
+            },
+            transform: Transform::from_translation(
+                self.anchored_translation(Anchor::Center).extend(0.),
+            ),
+            ..Default::default()
+        })
+    }
+
+    /// The pivot of this entity as a sprite anchor.
+    #[inline]
+    pub fn pivot_anchor(&self) -> Anchor {
+        Anchor::Custom(Vec2::new(self.pivot[0] - 0.5, 0.5 - self.pivot[1]))
+    }
+
+    /// Get the translation in the level for things anchored at `anchor`,
+    /// so the pivot of them lines up with the one in LDtk.
+    ///
+    /// For example, the center of a bottom-center pivoted entity is half
+    /// of its height above the position in LDtk.
+    pub fn anchored_translation(&self, anchor: Anchor) -> Vec2 {
+        let size = Vec2::new(self.width as f32, self.height as f32);
+        Vec2::new(self.local_pos[0] as f32, -self.local_pos[1] as f32)
+            + (anchor.as_vec() - self.pivot_anchor().as_vec()) * size
+    }
+}
+
+/// The fields read to animate the sprites of entities.
+/// See `LdtkLoadConfig::entity_animation`.
+#[derive(Debug, Clone, Reflect)]
+pub struct LdtkEntityAnimationFields {
+    /// An int field, the number of tiles in the strip starting at the tile of the entity.
+    /// Entities with less than 2 frames are not animated.
+    pub frames: String,
+    /// A float field, the frames per second.
+    pub fps: String,
+    /// Used when the fps field is missing or null.
+    pub default_fps: f32,
+}
+
+impl Default for LdtkEntityAnimationFields {
+    fn default() -> Self {
+        Self {
+            frames: "frames".to_string(),
+            fps: "fps".to_string(),
+            default_fps: 10.,
+        }
+    }
+}
+
+/// Cycles the `TextureAtlas::index` of an entity sprite through a contiguous range of tiles.
+///
+/// The range continues on the next row of the tileset if it reaches the end of a row.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct LdtkEntityAnimation {
+    pub first: usize,
+    pub frames: usize,
+    pub fps: f32,
+    pub(crate) elapsed: f32,
+}
+
+impl LdtkEntityAnimation {
+    pub fn new(first: usize, frames: usize, fps: f32) -> Self {
+        assert!(frames > 0, "The animation must have at least one frame!");
+        Self {
+            first,
+            frames,
+            fps,
+            elapsed: 0.,
+        }
+    }
+
+    /// Read the frame count and fps from the fields.
+    ///
+    /// Returns `None` if the frames field is missing or less than 2.
+    pub fn from_fields(
+        fields: &HashMap<String, FieldInstance>,
+        names: &LdtkEntityAnimationFields,
+        first: usize,
+    ) -> Option<Self> {
+        let frames = match fields.get(&names.frames)?.value.as_ref()? {
+            FieldValue::Integer(frames) if *frames > 1 => *frames as usize,
+            _ => return None,
+        };
+        let fps = match fields.get(&names.fps).and_then(|f| f.value.as_ref()) {
+            Some(FieldValue::Float(fps)) => *fps,
+            Some(FieldValue::Integer(fps)) => *fps as f32,
+            _ => names.default_fps,
+        };
+        Some(Self::new(first, frames, fps))
+    }
+
+    #[inline]
+    pub fn atlas_indices(&self) -> Range<usize> {
+        self.first..self.first + self.frames
+    }
+
+    /// The atlas index of the current frame.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.first + (self.elapsed * self.fps) as usize % self.frames
+    }
+
+    /// Advance the animation and return the atlas index of the current frame.
+    pub fn tick(&mut self, delta: f32) -> usize {
+        if self.fps > 0. {
+            self.elapsed = (self.elapsed + delta) % (self.frames as f32 / self.fps);
+        }
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ldtk::load_grid_vania;
+
+    use super::*;
+
+    #[test]
+    fn test_nine_slice_mesh() {
+        let def = serde_json::from_str::<EntityDef>(
+            r##"{
+                "color": "#FFFFFF",
+                "identifier": "Panel",
+                "nineSliceBorders": [4, 3, 2, 5],
+                "pivotX": 0,
+                "pivotY": 0,
+                "tileRect": null,
+                "tileRenderMode": "NineSlice",
+                "tilesetId": null,
+                "uiTileRect": null,
+                "uid": 0,
+                "width": 16,
+                "height": 16
+            }"##,
+        )
+        .unwrap();
+        assert!(matches!(def.tile_render_mode, TileRenderMode::NineSlice));
+        let borders = def.nine_slice_borders;
+        assert!(borders.is_valid);
+        assert_eq!(
+            IVec4::new(borders.up, borders.right, borders.down, borders.left),
+            IVec4::new(4, 3, 2, 5)
+        );
+
+        let tile_rect = TilesetRect {
+            tileset_uid: 0,
+            x_pos: 0,
+            y_pos: 0,
+            width: 16,
+            height: 16,
+        };
+        let ldtk_json = load_grid_vania();
+        let entity = EntityInstance {
+            identifier: "Panel".to_string(),
+            pivot: [0., 0.],
+            tile: Some(tile_rect.clone()),
+            width: 48,
+            height: 32,
+            ..ldtk_json.levels[0]
+                .layer_instances
+                .iter()
+                .flat_map(|layer| layer.entity_instances.iter())
+                .next()
+                .unwrap()
+                .clone()
+        };
+        let defs = [("Panel".to_string(), def.clone())]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let mesh = def.tile_render_mode.get_mesh(&entity, &tile_rect, &defs);
+
+        // 4 corners, 5 slices on up and down, 3 slices on left and right, and 5x3 in the middle.
+        assert_eq!(mesh.vertices.len(), (4 + 10 + 6 + 15) * 4);
+        assert_eq!(mesh.indices.len(), (4 + 10 + 6 + 15) * 6);
+
+        // The upper left corner keeps its size.
+        assert_eq!(
+            mesh.vertices[0..4],
+            [
+                Vec2::new(0., 0.),
+                Vec2::new(5., 0.),
+                Vec2::new(5., -4.),
+                Vec2::new(0., -4.),
+            ]
+        );
+        assert_eq!(mesh.uvs[2], Vec2::new(5., 4.) / 16.);
+
+        // The last slice on the up border reaches the right border.
+        assert_eq!(mesh.vertices[8 * 4 + 1], Vec2::new(45., 0.));
+
+        // The first slice in the middle samples the center of the tile.
+        assert_eq!(mesh.vertices[20 * 4], Vec2::new(5., -4.));
+        assert_eq!(mesh.uvs[20 * 4], Vec2::new(5., 4.) / 16.);
+        assert_eq!(mesh.uvs[20 * 4 + 2], Vec2::new(13., 14.) / 16.);
+    }
+
+    #[test]
+    fn test_field_tile_sprite() {
+        use bevy::math::UVec2;
+
+        use crate::{
+            ldtk::{json::field::FieldInstance, resources::LdtkAssets},
+            tilemap::map::TilemapTextureDescriptor,
+        };
+
+        let field = FieldInstance {
+            def_uid: 0,
+            identifier: "Item".to_string(),
+            tile: Some(TilesetRect {
+                tileset_uid: 7,
+                x_pos: 16,
+                y_pos: 32,
+                width: 16,
+                height: 16,
+            }),
+            ty: "LocalEnum.Item".to_string(),
+            value: None,
+        };
+
+        let mut assets = LdtkAssets::default();
+        assert!(assets.field_tile_sprite(&field).is_none());
+
+        assets.tilesets.insert(
+            7,
+            TilemapTexture {
+                texture: Handle::default(),
+                desc: TilemapTextureDescriptor::new(UVec2::new(64, 128), UVec2::splat(16)),
+            },
+        );
+        let sprite = assets.field_tile_sprite(&field).unwrap();
+        assert_eq!(sprite.rect, Rect::new(16., 32., 32., 48.));
+        assert_eq!(sprite.atlas_rect().min, Vec2::new(0.25, 0.25));
+        assert_eq!(sprite.atlas_rect().max, Vec2::new(0.5, 0.375));
+        assert_eq!(sprite.sprite_bundle().sprite.rect, Some(sprite.rect));
+    }
+
+    #[test]
+    fn test_default_render_mode() {
+        let def = serde_json::from_str::<EntityDef>(
+            r##"{
+                "color": "#FFFFFF",
+                "identifier": "Chest",
+                "nineSliceBorders": [],
+                "pivotX": 0.5,
+                "pivotY": 1,
+                "tileRect": null,
+                "tilesetId": null,
+                "uiTileRect": null,
+                "uid": 0,
+                "width": 16,
+                "height": 16
+            }"##,
+        )
+        .unwrap();
+        assert!(matches!(def.tile_render_mode, TileRenderMode::Stretch));
+        assert!(!def.nine_slice_borders.is_valid);
+    }
+
+    #[test]
+    fn test_entity_animation() {
+        let field = |identifier: &str, value: FieldValue| {
+            (
+                identifier.to_string(),
+                FieldInstance {
+                    def_uid: 0,
+                    identifier: identifier.to_string(),
+                    tile: None,
+                    ty: String::new(),
+                    value: Some(value),
+                },
+            )
+        };
+        let names = LdtkEntityAnimationFields::default();
+
+        let fields = HashMap::from_iter([
+            field("frames", FieldValue::Integer(4)),
+            field("fps", FieldValue::Float(2.)),
+        ]);
+        let mut animation = LdtkEntityAnimation::from_fields(&fields, &names, 5).unwrap();
+        assert_eq!(animation.atlas_indices(), 5..9);
+        assert_eq!(animation.tick(0.), 5);
+        assert_eq!(animation.tick(0.5), 6);
+        assert_eq!(animation.tick(1.), 8);
+        // Wraps around after 4 frames.
+        assert_eq!(animation.tick(0.5), 5);
+
+        let fields = HashMap::from_iter([field("frames", FieldValue::Integer(1))]);
+        assert!(LdtkEntityAnimation::from_fields(&fields, &names, 5).is_none());
+        assert!(LdtkEntityAnimation::from_fields(&HashMap::default(), &names, 5).is_none());
+    }
+}
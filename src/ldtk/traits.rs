@@ -3,6 +3,7 @@ use std::marker::PhantomData;
 use bevy::{
     asset::AssetServer,
     ecs::{bundle::Bundle, component::Component, system::EntityCommands},
+    sprite::SpriteSheetBundle,
     utils::HashMap,
 };
 
@@ -14,12 +15,14 @@ use super::{
 pub type LdtkEntityRegistry = HashMap<String, Box<dyn PhantomLdtkEntityTrait>>;
 
 pub trait LdtkEntity {
+    /// `sprite` is built from the tile of the entity, and is `None` if the entity has no tile.
     fn initialize(
         commands: &mut EntityCommands,
         entity_instance: &EntityInstance,
         fields: &HashMap<String, FieldInstance>,
         asset_server: &AssetServer,
         ldtk_assets: &LdtkAssets,
+        sprite: Option<SpriteSheetBundle>,
     );
 }
 
@@ -43,6 +46,7 @@ pub trait PhantomLdtkEntityTrait {
         fields: &HashMap<String, FieldInstance>,
         asset_server: &AssetServer,
         assets: &LdtkAssets,
+        sprite: Option<SpriteSheetBundle>,
     );
 }
 
@@ -54,8 +58,16 @@ impl<T: LdtkEntity + Bundle> PhantomLdtkEntityTrait for PhantomLdtkEntity<T> {
         fields: &HashMap<String, FieldInstance>,
         asset_server: &AssetServer,
         ldtk_assets: &LdtkAssets,
+        sprite: Option<SpriteSheetBundle>,
     ) {
-        T::initialize(commands, entity_instance, fields, asset_server, ldtk_assets);
+        T::initialize(
+            commands,
+            entity_instance,
+            fields,
+            asset_server,
+            ldtk_assets,
+            sprite,
+        );
     }
 }
 
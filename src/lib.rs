@@ -40,6 +40,7 @@ pub mod prelude {
         chunk::{
             load::{ChunkLoadCache, ChunkLoadConfig},
             save::{ChunkSaveCache, ChunkSaveConfig},
+            streaming::{ChunkStreaming, ChunkStreamingConfig},
         },
         map::{load::TilemapLoader, save::TilemapSaver},
     };
@@ -8,7 +8,7 @@ use super::{extension::Vec2Integerize, TileArea};
 
 macro_rules! declare_aabb {
     ($aabb_ty: ident, $data_ty: ty) => {
-        #[derive(Clone, Copy, Default, Debug, Reflect, ShaderType)]
+        #[derive(Clone, Copy, Default, Debug, PartialEq, Reflect, ShaderType)]
         #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
         pub struct $aabb_ty {
             pub min: $data_ty,
@@ -274,6 +274,33 @@ impl Aabb2d {
         )
     }
 
+    /// The aabb of the tiles inside `tiles`, which is in index space.
+    pub fn from_tiles(
+        tiles: IAabb2d,
+        ty: TilemapType,
+        tile_pivot: Vec2,
+        axis_flip: TilemapAxisFlip,
+        slot_size: Vec2,
+        transform: TilemapTransform,
+    ) -> Self {
+        // The tiles at the corners are always the outermost ones.
+        let mut aabb = Self::from_tilemap(
+            tiles.min, 1, ty, tile_pivot, axis_flip, slot_size, transform,
+        );
+        [
+            tiles.max,
+            IVec2::new(tiles.min.x, tiles.max.y),
+            IVec2::new(tiles.max.x, tiles.min.y),
+        ]
+        .into_iter()
+        .for_each(|corner| {
+            aabb.expand(Self::from_tilemap(
+                corner, 1, ty, tile_pivot, axis_flip, slot_size, transform,
+            ))
+        });
+        aabb
+    }
+
     #[inline]
     pub fn size(&self) -> Vec2 {
         self.max - self.min
@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+
+use bevy::math::IVec2;
+
+/// The number of orthogonal steps between two cells.
+#[inline]
+pub fn manhattan(a: IVec2, b: IVec2) -> u32 {
+    let d = (a - b).abs();
+    d.x as u32 + d.y as u32
+}
+
+/// The number of king moves between two cells.
+#[inline]
+pub fn chebyshev(a: IVec2, b: IVec2) -> u32 {
+    (a - b).abs().max_element() as u32
+}
+
+/// The straight line distance between two cells.
+#[inline]
+pub fn euclidean(a: IVec2, b: IVec2) -> f32 {
+    a.as_vec2().distance(b.as_vec2())
+}
+
+/// Check if there's no solid cell on the line between the centers of `from` and `to`.
+///
+/// This walks every cell the line passes through (a supercover line), not only the
+/// ones Bresenham's algorithm picks. When the line passes exactly through a corner,
+/// both of the cells beside it must be open, so it never leaks through wall corners.
+pub fn line_of_sight(from: IVec2, to: IVec2, is_solid: impl Fn(IVec2) -> bool) -> bool {
+    let delta = (to - from).abs();
+    let step = (to - from).signum();
+    // The steps taken along each axis.
+    let mut taken = IVec2::ZERO;
+    let mut cur = from;
+
+    if is_solid(cur) {
+        return false;
+    }
+
+    while taken != delta {
+        // Compare where the line crosses the next vertical and horizontal cell borders,
+        // `(0.5 + taken.x) / delta.x` and `(0.5 + taken.y) / delta.y`, without dividing.
+        match ((1 + 2 * taken.x) * delta.y).cmp(&((1 + 2 * taken.y) * delta.x)) {
+            Ordering::Less => {
+                cur.x += step.x;
+                taken.x += 1;
+            }
+            Ordering::Greater => {
+                cur.y += step.y;
+                taken.y += 1;
+            }
+            Ordering::Equal => {
+                if is_solid(IVec2::new(cur.x + step.x, cur.y))
+                    || is_solid(IVec2::new(cur.x, cur.y + step.y))
+                {
+                    return false;
+                }
+                cur += step;
+                taken += 1;
+            }
+        }
+
+        if is_solid(cur) {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, collections::HashSet};
+
+    use super::*;
+
+    #[test]
+    fn test_distances() {
+        let a = IVec2::new(-1, 2);
+        let b = IVec2::new(3, -1);
+        assert_eq!(manhattan(a, b), 7);
+        assert_eq!(chebyshev(a, b), 4);
+        assert_eq!(euclidean(a, b), 5.);
+        assert_eq!(manhattan(a, a), 0);
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let open = |_: IVec2| false;
+        assert!(line_of_sight(IVec2::ZERO, IVec2::ZERO, open));
+        assert!(line_of_sight(IVec2::new(-3, 2), IVec2::new(4, -5), open));
+        assert!(!line_of_sight(IVec2::ZERO, IVec2::ZERO, |_| true));
+
+        // Two walls touching at their corners, the diagonal between them must be blocked.
+        let walls = HashSet::from([IVec2::new(1, 0), IVec2::new(0, 1)]);
+        let is_wall = |index: IVec2| walls.contains(&index);
+        assert!(!line_of_sight(IVec2::ZERO, IVec2::ONE, is_wall));
+        assert!(!line_of_sight(IVec2::ONE, IVec2::ZERO, is_wall));
+        // A single wall beside the corner also blocks it.
+        assert!(!line_of_sight(IVec2::ZERO, IVec2::new(2, 2), |index| {
+            index == IVec2::new(1, 0)
+        }));
+        assert!(line_of_sight(IVec2::ZERO, IVec2::new(2, 2), |index| {
+            index == IVec2::new(2, 0)
+        }));
+
+        // A shallow line clips cells that Bresenham's algorithm skips.
+        let visited = RefCell::new(Vec::new());
+        assert!(line_of_sight(IVec2::ZERO, IVec2::new(4, 1), |index| {
+            visited.borrow_mut().push(index);
+            false
+        }));
+        assert_eq!(
+            visited.into_inner(),
+            vec![
+                IVec2::new(0, 0),
+                IVec2::new(1, 0),
+                IVec2::new(2, 0),
+                IVec2::new(2, 1),
+                IVec2::new(3, 1),
+                IVec2::new(4, 1),
+            ]
+        );
+        assert!(!line_of_sight(IVec2::ZERO, IVec2::new(4, 1), |index| {
+            index == IVec2::new(2, 0)
+        }));
+    }
+}
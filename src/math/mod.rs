@@ -17,6 +17,7 @@ use self::aabb::{Aabb2d, IAabb2d};
 
 pub mod aabb;
 pub mod extension;
+pub mod grid;
 
 pub struct EntiTilesMathPlugin;
 
@@ -190,20 +190,25 @@ fn set_tile(
     let texture_image = &texture_images[layer.texture_index as usize];
     let tile_px = texture.get_atlas_urect(layer.atlas_index as u32);
 
-    for mut y in 0..texture.desc.tile_size.y {
-        for mut x in 0..texture.desc.tile_size.x {
+    let tile_size = texture.desc.tile_size;
+    for y in 0..tile_size.y {
+        for x in 0..tile_size.x {
+            // The pixel of the texture that shows up at (x, y).
+            let mut src = UVec2 { x, y };
+            if layer.flip.contains(TileFlip::DIAGONAL) {
+                src = UVec2 {
+                    x: y * tile_size.x / tile_size.y,
+                    y: x * tile_size.y / tile_size.x,
+                };
+            }
             if layer.flip.contains(TileFlip::HORIZONTAL) {
-                x = texture.desc.tile_size.x - x - 1;
+                src.x = tile_size.x - src.x - 1;
             }
             if layer.flip.contains(TileFlip::VERTICAL) {
-                y = texture.desc.tile_size.y - y - 1;
+                src.y = tile_size.y - src.y - 1;
             }
 
-            let tile_px_col = get_pixel(
-                &texture_image.data,
-                texture.desc.size,
-                tile_px.min + UVec2 { x, y },
-            );
+            let tile_px_col = get_pixel(&texture_image.data, texture.desc.size, tile_px.min + src);
 
             let map_px_col = get_pixel(
                 bake_target,
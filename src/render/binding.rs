@@ -1,27 +1,30 @@
 use bevy::{
-    asset::{AssetId, Handle},
-    ecs::{component::Component, entity::EntityHashMap, system::Resource},
+    asset::AssetId,
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityHashMap},
+        system::Resource,
+    },
     log::error,
     render::{
         render_asset::RenderAssets,
-        render_resource::{BindGroup, BindGroupEntries, BindGroupLayout},
+        render_resource::{BindGroup, BindGroupEntries, BindGroupLayout, BufferId},
         renderer::RenderDevice,
         texture::{FallbackImage, Image},
     },
     utils::HashMap,
 };
 
-use crate::tilemap::map::TilemapTextures;
-
 use super::{
     buffer::{
         PerTilemapBuffersStorage, TilemapAnimationBuffer, TilemapUniformBuffer, UniformBuffer,
     },
+    diagnostics::TilemapRenderMetrics,
     extract::ExtractedTilemap,
     material::TilemapMaterial,
     pipeline::EntiTilesPipeline,
     resources::ExtractedTilemapMaterials,
-    texture::TilemapTexturesStorage,
+    texture::{TilemapTexturesKey, TilemapTexturesStorage},
 };
 
 #[cfg(feature = "atlas")]
@@ -36,10 +39,19 @@ pub struct TilemapViewBindGroup {
 pub struct TilemapBindGroups<M: TilemapMaterial> {
     pub uniform_buffer: Option<BindGroup>,
     pub storage_buffers: EntityHashMap<BindGroup>,
-    pub textures: HashMap<Handle<TilemapTextures>, BindGroup>,
+    pub textures: HashMap<TilemapTexturesKey, BindGroup>,
     pub materials: HashMap<AssetId<M>, BindGroup>,
+    /// The buffers the cached bind groups are bound to,
+    /// so they are only recreated when the buffers are reallocated.
+    uniform_buffer_id: Option<BufferId>,
+    pub(crate) storage_buffer_ids: EntityHashMap<StorageBufferIds>,
 }
 
+#[cfg(not(feature = "atlas"))]
+pub(crate) type StorageBufferIds = BufferId;
+#[cfg(feature = "atlas")]
+pub(crate) type StorageBufferIds = (BufferId, BufferId);
+
 impl<M: TilemapMaterial> Default for TilemapBindGroups<M> {
     fn default() -> Self {
         Self {
@@ -47,26 +59,45 @@ impl<M: TilemapMaterial> Default for TilemapBindGroups<M> {
             storage_buffers: Default::default(),
             textures: Default::default(),
             materials: Default::default(),
+            uniform_buffer_id: None,
+            storage_buffer_ids: Default::default(),
         }
     }
 }
 
 impl<M: TilemapMaterial> TilemapBindGroups<M> {
+    /// Drop the bind groups that belong to a despawned tilemap.
+    #[inline]
+    pub fn remove_tilemap(&mut self, tilemap: Entity) {
+        self.storage_buffers.remove(&tilemap);
+        self.storage_buffer_ids.remove(&tilemap);
+    }
+
+    /// All the tilemaps share one dynamic uniform buffer, so the bind group
+    /// only needs to be recreated when the buffer is reallocated.
     pub fn bind_uniform_buffers(
         &mut self,
         render_device: &RenderDevice,
         uniform_buffers: &mut TilemapUniformBuffer<M>,
         entitiles_pipeline: &EntiTilesPipeline<M>,
     ) {
+        let Some(buffer_id) = uniform_buffers.buffer().buffer().map(|b| b.id()) else {
+            return;
+        };
+        if self.uniform_buffer.is_some() && self.uniform_buffer_id == Some(buffer_id) {
+            return;
+        }
         let Some(uniform_buffer) = uniform_buffers.binding() else {
             return;
         };
 
+        entitiles_pipeline.metrics.add_bind_group();
         self.uniform_buffer = Some(render_device.create_bind_group(
             Some("tilemap_uniform_buffers_bind_group"),
             &entitiles_pipeline.uniform_buffers_layout,
             &BindGroupEntries::single(uniform_buffer),
         ));
+        self.uniform_buffer_id = Some(buffer_id);
     }
 
     pub fn bind_tilemap_storage_buffers(
@@ -85,13 +116,31 @@ impl<M: TilemapMaterial> TilemapBindGroups<M> {
                 error!("It seems that there are some tilemaps that have textures but no `TilemapAnimations`, which is not allowed");
                 return;
             };
-            
+
             #[cfg(feature = "atlas")]
             let Some(tex_desc) = tex_desc_bindings.get(tilemap) else {
                 error!("It seems that there are some tilemaps that have textures but no `TilemapAnimations`, which is not allowed");
                 return;
             };
 
+            #[cfg(not(feature = "atlas"))]
+            let buffer_ids = animation_buffers.buffer_id(*tilemap);
+            #[cfg(feature = "atlas")]
+            let buffer_ids = animation_buffers
+                .buffer_id(*tilemap)
+                .zip(texture_desc_buffers.buffer_id(*tilemap));
+            let Some(buffer_ids) = buffer_ids else {
+                continue;
+            };
+            if self.storage_buffers.contains_key(tilemap)
+                && self.storage_buffer_ids.get(tilemap) == Some(&buffer_ids)
+            {
+                continue;
+            }
+
+            entitiles_pipeline.metrics.add_bind_group();
+            self.storage_buffer_ids.insert(*tilemap, buffer_ids);
+
             #[cfg(not(feature = "atlas"))]
             self.storage_buffers.insert(
                 *tilemap,
@@ -117,6 +166,7 @@ impl<M: TilemapMaterial> TilemapBindGroups<M> {
     pub fn prepare_material_bind_groups(
         &mut self,
         layout: &BindGroupLayout,
+        metrics: &TilemapRenderMetrics,
         render_device: &RenderDevice,
         images: &RenderAssets<Image>,
         fallback_image: &FallbackImage,
@@ -129,6 +179,7 @@ impl<M: TilemapMaterial> TilemapBindGroups<M> {
                 let bind_group = material
                     .as_bind_group(layout, render_device, images, fallback_image)
                     .unwrap();
+                metrics.add_bind_group();
                 self.materials.insert(*id, bind_group.bind_group);
             });
     }
@@ -145,13 +196,16 @@ impl<M: TilemapMaterial> TilemapBindGroups<M> {
             return true;
         };
 
-        let Some(texture) = textures_storage.get_texture(tilemap_texture) else {
-            return !textures_storage.contains(tilemap_texture);
+        let Some(texture) = textures_storage.get_texture(tilemap_texture, tilemap.texture_mode)
+        else {
+            return !textures_storage.contains(tilemap_texture, tilemap.texture_mode);
         };
 
-        if !self.textures.contains_key(tilemap_texture) {
+        let key = (tilemap_texture.clone_weak(), tilemap.texture_mode);
+        if !self.textures.contains_key(&key) {
+            entitiles_pipeline.metrics.add_bind_group();
             self.textures.insert(
-                tilemap_texture.clone_weak(),
+                key,
                 render_device.create_bind_group(
                     Some("color_texture_bind_group"),
                     &entitiles_pipeline.texture_layout,
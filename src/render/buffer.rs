@@ -6,8 +6,8 @@ use bevy::{
     prelude::{Component, Resource, Vec2},
     render::{
         render_resource::{
-            encase::internal::WriteInto, BindingResource, DynamicUniformBuffer, ShaderSize,
-            ShaderType, StorageBuffer,
+            encase::internal::WriteInto, BindingResource, BufferId, DynamicUniformBuffer,
+            ShaderSize, ShaderType, StorageBuffer,
         },
         renderer::{RenderDevice, RenderQueue},
     },
@@ -64,13 +64,22 @@ pub trait PerTilemapBuffersStorage<U: ShaderType + WriteInto + ShaderSize + 'sta
         &mut self.get_mapper_mut().entry(tilemap).or_default().1
     }
 
-    fn bindings(&mut self) -> EntityHashMap<BindingResource> {
-        self.get_mapper_mut()
+    fn bindings(&self) -> EntityHashMap<BindingResource<'_>> {
+        self.get_mapper()
             .iter()
             .filter_map(|(tilemap, (buffer, _))| buffer.binding().map(|res| (*tilemap, res)))
             .collect()
     }
 
+    /// The id of the GPU buffer of the tilemap, which changes whenever the buffer is reallocated.
+    #[inline]
+    fn buffer_id(&self, tilemap: Entity) -> Option<BufferId> {
+        self.get_mapper()
+            .get(&tilemap)
+            .and_then(|(buffer, _)| buffer.buffer())
+            .map(|b| b.id())
+    }
+
     #[inline]
     fn remove(&mut self, tilemap: Entity) {
         self.get_mapper_mut().remove(&tilemap);
@@ -107,10 +116,12 @@ pub struct TilemapUniform {
     pub tile_render_size: Vec2,
     pub slot_size: Vec2,
     pub pivot: Vec2,
+    pub anchor: Vec2,
     pub layer_opacities: Vec4,
     pub axis_dir: Vec2,
     pub hex_legs: f32,
     pub time: f32,
+    pub alpha: f32,
 }
 
 #[derive(Resource)]
@@ -145,6 +156,7 @@ impl<M: TilemapMaterial> UniformBuffer<(&ExtractedTilemap<M>, f32), TilemapUnifo
             tile_render_size: extracted.tile_render_size,
             slot_size: extracted.slot_size,
             pivot: extracted.tile_pivot,
+            anchor: extracted.tile_anchor,
             layer_opacities: extracted.layer_opacities,
             axis_dir: extracted.axis_flip.as_vec2(),
             hex_legs: match extracted.ty {
@@ -152,6 +164,7 @@ impl<M: TilemapMaterial> UniformBuffer<(&ExtractedTilemap<M>, f32), TilemapUnifo
                 _ => 0.,
             },
             time,
+            alpha: extracted.alpha,
         }))
     }
 
@@ -167,6 +180,8 @@ pub struct GpuTilemapTextureDescriptor {
     pub tile_count: bevy::math::UVec2,
     pub tile_uv_size: Vec2,
     pub uv_scale: Vec2,
+    /// The index of the first tile of this texture when it's packed in `Array` mode.
+    pub start_index: u32,
 }
 
 #[derive(Resource, Default)]
@@ -10,21 +10,25 @@ use bevy::{
         mesh::{GpuBufferInfo, GpuMesh, Indices},
         render_asset::RenderAssetUsages,
         render_resource::{BufferInitDescriptor, BufferUsages, IndexFormat, PrimitiveTopology},
-        renderer::RenderDevice,
+        renderer::{RenderDevice, RenderQueue},
     },
     utils::HashMap,
 };
 
 use crate::{
-    math::{aabb::Aabb2d, extension::DivToFloor},
+    math::{
+        aabb::{Aabb2d, IAabb2d},
+        extension::DivToFloor,
+    },
     tilemap::{
         map::{TilemapTextures, TilemapType},
-        tile::TileTexture,
+        tile::{TileLayer, TileTexture},
     },
     MAX_LAYER_COUNT,
 };
 
 use super::{
+    diagnostics::TilemapRenderMetrics,
     extract::{ExtractedTile, ExtractedTilemap},
     material::TilemapMaterial,
     TILEMAP_MESH_ATTR_ATLAS_INDICES, TILEMAP_MESH_ATTR_COLOR, TILEMAP_MESH_ATTR_INDEX,
@@ -67,6 +71,8 @@ pub struct TilemapRenderChunk<M: TilemapMaterial> {
     pub mesh: Mesh,
     pub gpu_mesh: Option<GpuMesh>,
     pub aabb: Aabb2d,
+    /// `TilemapClip`
+    pub clip: Option<IAabb2d>,
     pub marker: PhantomData<M>,
 }
 
@@ -94,15 +100,13 @@ impl<M: TilemapMaterial> TilemapRenderChunk<M> {
                 tilemap.slot_size,
                 tilemap.transform,
             ),
+            clip: tilemap.clip,
             marker: PhantomData,
         }
     }
 
-    /// Update the raw mesh for GPU processing.
-    pub fn try_update_mesh(&mut self, render_device: &RenderDevice) {
-        if !self.dirty_mesh {
-            return;
-        }
+    /// Build the mesh on the CPU, without the tiles outside of `clip`.
+    pub fn update_mesh(&mut self) {
         let is_pure_color = self.texture.is_none();
 
         let mut v_index = 0;
@@ -116,42 +120,45 @@ impl<M: TilemapMaterial> TilemapRenderChunk<M> {
         let mut vertex_indices = Vec::with_capacity(len * 6);
         let mut color = Vec::with_capacity(len * 4);
 
-        for tile_data in self.tiles.iter() {
-            if let Some(tile) = tile_data {
-                if !is_pure_color {
-                    #[cfg(feature = "atlas")]
-                    texture_indices.extend_from_slice(&[
-                        tile.texture_indices,
-                        tile.texture_indices,
-                        tile.texture_indices,
-                        tile.texture_indices,
-                    ]);
-
-                    atlas_indices.extend_from_slice(&[
-                        tile.atlas_indices,
-                        tile.atlas_indices,
-                        tile.atlas_indices,
-                        tile.atlas_indices,
-                    ]);
-                }
+        let tiles = self.tiles.iter().flatten().filter(|tile| {
+            self.clip
+                .is_none_or(|c| c.contains(IVec2::new(tile.index.x, tile.index.y)))
+        });
 
-                let pos = Vec3::ZERO;
-                positions.extend_from_slice(&[pos, pos, pos, pos]);
+        for tile in tiles {
+            if !is_pure_color {
+                #[cfg(feature = "atlas")]
+                texture_indices.extend_from_slice(&[
+                    tile.texture_indices,
+                    tile.texture_indices,
+                    tile.texture_indices,
+                    tile.texture_indices,
+                ]);
 
-                vertex_indices.extend_from_slice(&[
-                    v_index,
-                    v_index + 1,
-                    v_index + 3,
-                    v_index + 1,
-                    v_index + 2,
-                    v_index + 3,
+                atlas_indices.extend_from_slice(&[
+                    tile.atlas_indices,
+                    tile.atlas_indices,
+                    tile.atlas_indices,
+                    tile.atlas_indices,
                 ]);
+            }
 
-                v_index += 4;
+            let pos = Vec3::ZERO;
+            positions.extend_from_slice(&[pos, pos, pos, pos]);
 
-                grid_indices.extend_from_slice(&[tile.index, tile.index, tile.index, tile.index]);
-                color.extend_from_slice(&[tile.tint, tile.tint, tile.tint, tile.tint]);
-            }
+            vertex_indices.extend_from_slice(&[
+                v_index,
+                v_index + 1,
+                v_index + 3,
+                v_index + 1,
+                v_index + 2,
+                v_index + 3,
+            ]);
+
+            v_index += 4;
+
+            grid_indices.extend_from_slice(&[tile.index, tile.index, tile.index, tile.index]);
+            color.extend_from_slice(&[tile.tint, tile.tint, tile.tint, tile.tint]);
         }
 
         self.mesh
@@ -169,39 +176,80 @@ impl<M: TilemapMaterial> TilemapRenderChunk<M> {
             }
         }
         self.mesh.insert_indices(Indices::U32(vertex_indices));
+    }
+
+    /// Update the raw mesh for GPU processing. Returns the number of bytes uploaded.
+    ///
+    /// The GPU buffers are reused as long as the new mesh fits in them,
+    /// so editing tiles doesn't reallocate the buffers every time.
+    pub fn try_update_mesh(
+        &mut self,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+    ) -> u64 {
+        if !self.dirty_mesh {
+            return 0;
+        }
+        self.update_mesh();
+        self.dirty_mesh = false;
 
-        let mesh_vert_count = self.mesh.count_vertices() as u32;
-        let mesh_indices_count = self.mesh.indices().unwrap().len() as u32;
+        let vertex_count = self.mesh.count_vertices() as u32;
+        let index_count = self.mesh.indices().unwrap().len() as u32;
+        let vertex_data = self.mesh.get_vertex_buffer_data();
+        let index_data = self.mesh.get_index_buffer_bytes().unwrap();
+        let upload_bytes = (vertex_data.len() + index_data.len()) as u64;
+
+        if let Some(gpu_mesh) = &mut self.gpu_mesh {
+            if let GpuBufferInfo::Indexed { buffer, count, .. } = &mut gpu_mesh.buffer_info {
+                if gpu_mesh.vertex_buffer.size() >= vertex_data.len() as u64
+                    && buffer.size() >= index_data.len() as u64
+                {
+                    render_queue.write_buffer(&gpu_mesh.vertex_buffer, 0, &vertex_data);
+                    render_queue.write_buffer(buffer, 0, index_data);
+                    gpu_mesh.vertex_count = vertex_count;
+                    *count = index_count;
+                    return upload_bytes;
+                }
+            }
+        }
 
         let vertex_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
             label: Some("tilemap_vertex_buffer"),
-            contents: &self.mesh.get_vertex_buffer_data(),
-            usage: BufferUsages::VERTEX,
+            contents: &vertex_data,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
         });
 
-        let buffer_info =
-            self.mesh
-                .get_index_buffer_bytes()
-                .map_or(GpuBufferInfo::NonIndexed, |data| GpuBufferInfo::Indexed {
-                    buffer: render_device.create_buffer_with_data(&BufferInitDescriptor {
-                        label: Some("tilemap_index_buffer"),
-                        contents: data,
-                        usage: BufferUsages::INDEX,
-                    }),
-                    count: mesh_indices_count,
-                    index_format: IndexFormat::Uint32,
-                });
+        let buffer_info = GpuBufferInfo::Indexed {
+            buffer: render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("tilemap_index_buffer"),
+                contents: index_data,
+                usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            }),
+            count: index_count,
+            index_format: IndexFormat::Uint32,
+        };
 
         self.gpu_mesh = Some(GpuMesh {
             vertex_buffer,
-            vertex_count: mesh_vert_count,
+            vertex_count,
             morph_targets: None,
             buffer_info,
             primitive_topology: PrimitiveTopology::TriangleList,
             layout: self.mesh.get_mesh_vertex_buffer_layout(),
         });
 
-        self.dirty_mesh = false;
+        upload_bytes
+    }
+
+    /// The size of the vertex and index buffers on the GPU.
+    pub fn gpu_mesh_size(&self) -> u64 {
+        self.gpu_mesh.as_ref().map_or(0, |mesh| {
+            mesh.vertex_buffer.size()
+                + match &mesh.buffer_info {
+                    GpuBufferInfo::Indexed { buffer, .. } => buffer.size(),
+                    GpuBufferInfo::NonIndexed => 0,
+                }
+        })
     }
 
     /// Set a tile in the chunk. Overwrites the previous tile.
@@ -231,19 +279,18 @@ impl<M: TilemapMaterial> TilemapRenderChunk<M> {
         };
 
         if let TileTexture::Static(tex) = &tile.texture {
-            tex.iter()
+            sort_layers(tex)
+                .into_iter()
                 .enumerate()
-                .rev()
-                .take(MAX_LAYER_COUNT)
-                .for_each(|(i, t)| {
+                .for_each(|(i, (layer, t))| {
                     #[cfg(feature = "atlas")]
                     {
                         texture_indices[i] = t.texture_index;
                     }
-                    let flip = t.flip.bits() as i32;
-                    // Shift 29 bits but not 30 because it's a signed integer,
-                    // and we need to identify if the layer is empty or not according to the sign.
-                    atlas_indices[i] = t.atlas_index | (flip << 29);
+                    // The opacity is picked by the layer index, not the slot it's drawn in.
+                    atlas_indices[i] = t.atlas_index
+                        | t.flip.to_uv_transform()
+                        | (layer as i32) << LAYER_INDEX_SHIFT;
                 });
         }
 
@@ -258,6 +305,24 @@ impl<M: TilemapMaterial> TilemapRenderChunk<M> {
     }
 }
 
+/// The bits of the packed atlas index that hold the index of the layer
+/// in `TilemapLayerOpacities`, right below the diagonal flip.
+pub(crate) const LAYER_INDEX_SHIFT: i32 = 26;
+
+/// Take the top `MAX_LAYER_COUNT` layers and sort them by their sublayers,
+/// so the shader can draw them back-to-front.
+///
+/// Each layer comes with its index among the taken ones, which selects its opacity.
+pub(crate) fn sort_layers(layers: &[TileLayer]) -> Vec<(usize, &TileLayer)> {
+    let mut sorted = layers[layers.len().saturating_sub(MAX_LAYER_COUNT)..]
+        .iter()
+        .enumerate()
+        .collect::<Vec<_>>();
+    // This is a stable sort, so layers with the same sublayer keep their order.
+    sorted.sort_by_key(|(_, l)| l.sublayer);
+    sorted
+}
+
 #[derive(Resource)]
 pub struct RenderChunkStorage<M: TilemapMaterial> {
     pub(crate) value: EntityHashMap<HashMap<IVec2, TilemapRenderChunk<M>>>,
@@ -273,11 +338,25 @@ impl<M: TilemapMaterial> Default for RenderChunkStorage<M> {
 
 impl<M: TilemapMaterial> RenderChunkStorage<M> {
     /// Update the mesh for all chunks of a tilemap.
-    pub fn prepare_chunks(&mut self, tilemap: &ExtractedTilemap<M>, render_device: &RenderDevice) {
+    pub fn prepare_chunks(
+        &mut self,
+        tilemap: &ExtractedTilemap<M>,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        metrics: &TilemapRenderMetrics,
+    ) {
         if let Some(chunks) = self.value.get_mut(&tilemap.id) {
             chunks
                 .values_mut()
-                .for_each(|c| c.try_update_mesh(render_device));
+                .filter(|c| c.clip != tilemap.clip)
+                .for_each(|c| {
+                    c.clip = tilemap.clip;
+                    c.dirty_mesh = true;
+                });
+
+            chunks.values_mut().filter(|c| c.dirty_mesh).for_each(|c| {
+                metrics.add_dirty_chunk(c.try_update_mesh(render_device, render_queue));
+            });
         }
     }
 
@@ -307,3 +386,98 @@ impl<M: TilemapMaterial> RenderChunkStorage<M> {
         self.value.get_mut(&tilemap).and_then(|c| c.remove(&index))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::{math::IVec3, render::color::Color};
+
+    use crate::{render::material::StandardTilemapMaterial, tilemap::tile::Tile};
+
+    use super::*;
+
+    fn empty_chunk(size: u32) -> TilemapRenderChunk<StandardTilemapMaterial> {
+        TilemapRenderChunk {
+            visible: true,
+            index: IVec2::ZERO,
+            dirty_mesh: false,
+            ty: TilemapType::Square,
+            size,
+            texture: None,
+            tiles: vec![None; (size * size) as usize],
+            mesh: Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::RENDER_WORLD,
+            ),
+            gpu_mesh: None,
+            aabb: Aabb2d::default(),
+            clip: None,
+            marker: PhantomData,
+        }
+    }
+
+    #[test]
+    fn test_sublayer_order() {
+        let mut chunk = empty_chunk(2);
+
+        let layer = |atlas_index: i32, sublayer: i32| {
+            TileLayer {
+                atlas_index,
+                ..Default::default()
+            }
+            .with_sublayer(sublayer)
+        };
+        // The first layer is added first but should be drawn on top.
+        let tile = Tile {
+            tilemap_id: Entity::PLACEHOLDER,
+            chunk_index: IVec2::ZERO,
+            in_chunk_index: 0,
+            index: IVec2::ZERO,
+            texture: TileTexture::Static(vec![layer(1, 1), layer(2, 0), layer(3, 0)]),
+            tint: Color::WHITE,
+        };
+        chunk.set_tile(0, Some(&tile));
+
+        // The shader draws the layers from x to w.
+        let data = chunk.tiles[3].as_ref().unwrap();
+        let mask = (1 << LAYER_INDEX_SHIFT) - 1;
+        assert_eq!(
+            data.atlas_indices
+                .to_array()
+                .map(|i| if i < 0 { i } else { i & mask }),
+            [2, 3, 1, -1]
+        );
+        // But they keep the opacities of their own layers.
+        assert_eq!(
+            data.atlas_indices.truncate() >> LAYER_INDEX_SHIFT,
+            IVec3::new(1, 2, 0)
+        );
+        assert!(chunk.dirty_mesh);
+    }
+
+    #[test]
+    fn test_clip() {
+        let mut chunk = empty_chunk(8);
+        for y in 0..8 {
+            for x in 0..8 {
+                let tile = Tile {
+                    tilemap_id: Entity::PLACEHOLDER,
+                    chunk_index: IVec2::ZERO,
+                    in_chunk_index: (y * 8 + x) as usize,
+                    index: IVec2::new(x, y),
+                    texture: TileTexture::Static(vec![TileLayer::default()]),
+                    tint: Color::WHITE,
+                };
+                chunk.set_tile(tile.in_chunk_index, Some(&tile));
+            }
+        }
+
+        chunk.update_mesh();
+        assert_eq!(chunk.mesh.count_vertices(), 64 * 4);
+
+        // The bottom left quadrant.
+        chunk.clip = Some(IAabb2d::new(0, 0, 3, 3));
+        chunk.update_mesh();
+        assert_eq!(chunk.mesh.count_vertices(), 16 * 4);
+        assert_eq!(chunk.mesh.indices().unwrap().len(), 16 * 6);
+    }
+}
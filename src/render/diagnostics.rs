@@ -0,0 +1,198 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    ecs::{
+        query::With,
+        system::{Query, Res, Resource},
+    },
+    render::RenderApp,
+};
+
+use crate::tilemap::tile::Tile;
+
+/// Reports tilemap metrics to `DiagnosticsStore`, so they show up in
+/// `LogDiagnosticsPlugin` and other diagnostics overlays.
+///
+/// The render metrics are measured in the render world,
+/// so they lag one frame behind when pipelined rendering is enabled.
+///
+/// This is not part of `EntiTilesPlugin`, add it along with `EntiTilesPlugin`
+/// to collect the metrics.
+pub struct EntiTilesDiagnosticsPlugin;
+
+impl Plugin for EntiTilesDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let metrics = TilemapRenderMetrics::default();
+
+        app.register_diagnostic(Diagnostic::new(TilemapDiagnostics::TILE_COUNT))
+            .register_diagnostic(Diagnostic::new(TilemapDiagnostics::DRAW_INSTANCES))
+            .register_diagnostic(Diagnostic::new(TilemapDiagnostics::DIRTY_CHUNKS))
+            .register_diagnostic(Diagnostic::new(TilemapDiagnostics::UPLOAD_BYTES).with_suffix("B"))
+            .register_diagnostic(Diagnostic::new(
+                TilemapDiagnostics::PIPELINE_SPECIALIZATIONS,
+            ))
+            .register_diagnostic(Diagnostic::new(TilemapDiagnostics::BIND_GROUPS))
+            .insert_resource(metrics.clone())
+            .add_systems(Update, tilemap_diagnostics);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.insert_resource(metrics);
+        }
+    }
+}
+
+pub struct TilemapDiagnostics;
+
+impl TilemapDiagnostics {
+    /// The number of tiles in all tilemaps.
+    pub const TILE_COUNT: DiagnosticPath = DiagnosticPath::const_new("entitiles/tile_count");
+    /// The number of tilemaps drawn in all views.
+    pub const DRAW_INSTANCES: DiagnosticPath =
+        DiagnosticPath::const_new("entitiles/draw_instances");
+    /// The number of chunks whose mesh is rebuilt.
+    pub const DIRTY_CHUNKS: DiagnosticPath = DiagnosticPath::const_new("entitiles/dirty_chunks");
+    /// The size of the chunk meshes uploaded to the GPU.
+    pub const UPLOAD_BYTES: DiagnosticPath = DiagnosticPath::const_new("entitiles/upload_bytes");
+    /// The number of render pipelines specialized.
+    pub const PIPELINE_SPECIALIZATIONS: DiagnosticPath =
+        DiagnosticPath::const_new("entitiles/pipeline_specializations");
+    /// The number of bind groups created.
+    pub const BIND_GROUPS: DiagnosticPath = DiagnosticPath::const_new("entitiles/bind_groups");
+}
+
+/// Counters written by the render world and read by the main world,
+/// reset every time they are reported.
+#[derive(Resource, Default, Clone)]
+pub struct TilemapRenderMetrics(Arc<RenderMetricsCounters>);
+
+#[derive(Default)]
+struct RenderMetricsCounters {
+    draw_instances: AtomicU64,
+    dirty_chunks: AtomicU64,
+    upload_bytes: AtomicU64,
+    pipeline_specializations: AtomicU64,
+    bind_groups: AtomicU64,
+}
+
+impl TilemapRenderMetrics {
+    #[inline]
+    pub fn add_draw_instances(&self, count: u64) {
+        self.0.draw_instances.fetch_add(count, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_dirty_chunk(&self, upload_bytes: u64) {
+        self.0.dirty_chunks.fetch_add(1, Ordering::Relaxed);
+        self.0
+            .upload_bytes
+            .fetch_add(upload_bytes, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_pipeline_specialization(&self) {
+        self.0
+            .pipeline_specializations
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_bind_group(&self) {
+        self.0.bind_groups.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn tilemap_diagnostics(
+    mut diagnostics: Diagnostics,
+    tiles_query: Query<(), With<Tile>>,
+    metrics: Res<TilemapRenderMetrics>,
+) {
+    diagnostics.add_measurement(&TilemapDiagnostics::TILE_COUNT, || {
+        tiles_query.iter().len() as f64
+    });
+
+    let counters = &metrics.0;
+    for (path, counter) in [
+        (
+            &TilemapDiagnostics::DRAW_INSTANCES,
+            &counters.draw_instances,
+        ),
+        (&TilemapDiagnostics::DIRTY_CHUNKS, &counters.dirty_chunks),
+        (&TilemapDiagnostics::UPLOAD_BYTES, &counters.upload_bytes),
+        (
+            &TilemapDiagnostics::PIPELINE_SPECIALIZATIONS,
+            &counters.pipeline_specializations,
+        ),
+        (&TilemapDiagnostics::BIND_GROUPS, &counters.bind_groups),
+    ] {
+        let value = counter.swap(0, Ordering::Relaxed);
+        diagnostics.add_measurement(path, || value as f64);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        diagnostic::DiagnosticsStore,
+        ecs::{
+            entity::Entity,
+            system::{CommandQueue, Commands},
+        },
+        math::{IVec2, UVec2},
+    };
+
+    use crate::{
+        math::TileArea,
+        tilemap::{map::TilemapStorage, tile::TileBuilder},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let mut app = App::new();
+        app.add_plugins(EntiTilesDiagnosticsPlugin);
+
+        let mut queue = CommandQueue::default();
+        let mut storage = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        storage.fill_rect(
+            &mut Commands::new(&mut queue, &app.world),
+            TileArea::new(IVec2::ZERO, UVec2::new(3, 2)),
+            TileBuilder::new(),
+        );
+        queue.apply(&mut app.world);
+
+        let metrics = app.world.resource::<TilemapRenderMetrics>().clone();
+        metrics.add_draw_instances(2);
+        metrics.add_dirty_chunk(128);
+        metrics.add_dirty_chunk(64);
+        metrics.add_pipeline_specialization();
+        metrics.add_bind_group();
+        app.update();
+
+        let value = |app: &App, path: DiagnosticPath| {
+            app.world
+                .resource::<DiagnosticsStore>()
+                .get(&path)
+                .and_then(|d| d.value())
+        };
+        assert_eq!(value(&app, TilemapDiagnostics::TILE_COUNT), Some(6.));
+        assert_eq!(value(&app, TilemapDiagnostics::DRAW_INSTANCES), Some(2.));
+        assert_eq!(value(&app, TilemapDiagnostics::DIRTY_CHUNKS), Some(2.));
+        assert_eq!(value(&app, TilemapDiagnostics::UPLOAD_BYTES), Some(192.));
+        assert_eq!(
+            value(&app, TilemapDiagnostics::PIPELINE_SPECIALIZATIONS),
+            Some(1.)
+        );
+        assert_eq!(value(&app, TilemapDiagnostics::BIND_GROUPS), Some(1.));
+
+        // The render metrics are per frame.
+        app.update();
+        assert_eq!(value(&app, TilemapDiagnostics::DRAW_INSTANCES), Some(0.));
+        assert_eq!(value(&app, TilemapDiagnostics::TILE_COUNT), Some(6.));
+    }
+}
@@ -10,23 +10,28 @@ use bevy::{
         },
     },
     log::error,
+    math::{UVec4, Vec4Swizzles},
     render::{
         mesh::GpuBufferInfo,
-        render_phase::{RenderCommand, RenderCommandResult, SetItemPipeline, TrackedRenderPass},
-        view::ViewUniformOffset,
+        render_phase::{RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::PipelineCache,
+        view::{ExtractedView, ViewUniformOffset},
     },
 };
 
+use crate::math::aabb::UAabb2d;
+
 use super::{
     binding::{TilemapBindGroups, TilemapViewBindGroup},
     buffer::{DynamicOffsetComponent, TilemapUniform},
     chunk::RenderChunkStorage,
     material::TilemapMaterial,
+    pipeline::TilemapLastGoodPipelines,
     resources::TilemapInstances,
 };
 
 pub type DrawTilemapTextured<M> = (
-    SetItemPipeline,
+    SetTilemapPipeline<M>,
     SetTilemapViewBindGroup<0>,
     SetTilemapUniformBufferBindGroup<1, M>,
     SetTilemapMaterialBindGroup<2, M>,
@@ -36,13 +41,46 @@ pub type DrawTilemapTextured<M> = (
 );
 
 pub type DrawTilemapNonTextured<M> = (
-    SetItemPipeline,
+    SetTilemapPipeline<M>,
     SetTilemapViewBindGroup<0>,
     SetTilemapUniformBufferBindGroup<1, M>,
     SetTilemapMaterialBindGroup<2, M>,
     DrawTileMesh<M>,
 );
 
+/// Like `SetItemPipeline`, but falls back to the last working pipeline
+/// while the shaders are being recompiled or fail to compile.
+#[derive(Default)]
+pub struct SetTilemapPipeline<M: TilemapMaterial>(PhantomData<M>);
+impl<M: TilemapMaterial> RenderCommand<Transparent2d> for SetTilemapPipeline<M> {
+    type Param = (SRes<PipelineCache>, SRes<TilemapLastGoodPipelines<M>>);
+
+    type ViewQuery = ();
+
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        item: &Transparent2d,
+        _view: ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        (pipeline_cache, last_good): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let pipeline = pipeline_cache
+            .into_inner()
+            .get_render_pipeline(item.pipeline)
+            .or_else(|| last_good.into_inner().get(item.pipeline));
+
+        if let Some(pipeline) = pipeline {
+            pass.set_render_pipeline(pipeline);
+            RenderCommandResult::Success
+        } else {
+            RenderCommandResult::Failure
+        }
+    }
+}
+
 pub struct SetTilemapViewBindGroup<const I: usize>;
 impl<const I: usize> RenderCommand<Transparent2d> for SetTilemapViewBindGroup<I> {
     type Param = ();
@@ -180,11 +218,16 @@ impl<const I: usize, M: TilemapMaterial> RenderCommand<Transparent2d>
         (bind_groups, instances): SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
-        let Some(textures) = instances.0.get(&item.entity).unwrap().texture.as_ref() else {
+        let tilemap = instances.0.get(&item.entity).unwrap();
+        let Some(textures) = tilemap.texture.as_ref() else {
             return RenderCommandResult::Success;
         };
 
-        if let Some(bind_group) = &bind_groups.into_inner().textures.get(textures) {
+        if let Some(bind_group) = &bind_groups
+            .into_inner()
+            .textures
+            .get(&(textures.clone_weak(), tilemap.texture_mode))
+        {
             pass.set_bind_group(I, bind_group, &[]);
             RenderCommandResult::Success
         } else {
@@ -197,20 +240,29 @@ impl<const I: usize, M: TilemapMaterial> RenderCommand<Transparent2d>
 #[derive(Default)]
 pub struct DrawTileMesh<M: TilemapMaterial>(PhantomData<M>);
 impl<M: TilemapMaterial> RenderCommand<Transparent2d> for DrawTileMesh<M> {
-    type Param = SRes<RenderChunkStorage<M>>;
+    type Param = (SRes<RenderChunkStorage<M>>, SRes<TilemapInstances<M>>);
 
-    type ViewQuery = ();
+    type ViewQuery = Read<ExtractedView>;
 
     type ItemQuery = ();
 
     #[inline]
     fn render<'w>(
         item: &Transparent2d,
-        _view: ROQueryItem<'w, Self::ViewQuery>,
+        view: ROQueryItem<'w, Self::ViewQuery>,
         _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
-        render_chunks: SystemParamItem<'w, '_, Self::Param>,
+        (render_chunks, instances): SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
+        let scissor = instances.0.get(&item.entity).and_then(|t| t.scissor);
+        if let Some(scissor) = scissor {
+            let Some(rect) = scissor_rect(scissor, view.viewport) else {
+                // Nothing is inside the viewport.
+                return RenderCommandResult::Success;
+            };
+            pass.set_scissor_rect(rect.min.x, rect.min.y, rect.width(), rect.height());
+        }
+
         if let Some(chunks) = render_chunks.into_inner().get_chunks(item.entity) {
             for chunk in chunks.values() {
                 if !chunk.visible {
@@ -236,6 +288,54 @@ impl<M: TilemapMaterial> RenderCommand<Transparent2d> for DrawTileMesh<M> {
             }
         }
 
+        if scissor.is_some() {
+            // Don't clip the things drawn after this tilemap.
+            let viewport = view.viewport;
+            pass.set_scissor_rect(viewport.x, viewport.y, viewport.z, viewport.w);
+        }
+
         RenderCommandResult::Success
     }
 }
+
+/// Move the scissor rect of a tilemap into the render target space, and clip it by the viewport.
+///
+/// `viewport` is the position and size of the viewport in the render target.
+/// Returns `None` if the rect is empty after clipping.
+pub fn scissor_rect(scissor: UAabb2d, viewport: UVec4) -> Option<UAabb2d> {
+    let origin = viewport.xy();
+    let rect = UAabb2d {
+        min: (origin + scissor.min).min(origin + viewport.zw()),
+        max: (origin + scissor.max).min(origin + viewport.zw()),
+    };
+
+    if rect.min.cmplt(rect.max).all() {
+        Some(rect)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scissor_rect() {
+        let viewport = UVec4::new(100, 50, 800, 600);
+
+        assert_eq!(
+            scissor_rect(UAabb2d::new(10, 20, 110, 220), viewport),
+            Some(UAabb2d::new(110, 70, 210, 270))
+        );
+        // Clipped by the viewport.
+        assert_eq!(
+            scissor_rect(UAabb2d::new(700, 500, 1000, 1000), viewport),
+            Some(UAabb2d::new(800, 550, 900, 650))
+        );
+        // Outside the viewport.
+        assert_eq!(scissor_rect(UAabb2d::new(800, 0, 900, 100), viewport), None);
+        // Empty.
+        assert_eq!(scissor_rect(UAabb2d::new(10, 10, 10, 20), viewport), None);
+    }
+}
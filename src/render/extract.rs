@@ -3,7 +3,7 @@ use bevy::{
     ecs::{
         entity::EntityHashMap,
         event::EventReader,
-        query::{Or, With},
+        query::{Added, Or, With},
         system::{Res, ResMut},
     },
     prelude::{Changed, Commands, Component, Entity, Query, Vec2, Vec4},
@@ -11,13 +11,17 @@ use bevy::{
 };
 
 use crate::{
-    math::CameraAabb2d,
+    math::{
+        aabb::{IAabb2d, UAabb2d},
+        CameraAabb2d,
+    },
     tilemap::{
         despawn::{DespawnedTile, DespawnedTilemap},
         map::{
-            TilePivot, TileRenderSize, TilemapAnimations, TilemapAxisFlip, TilemapLayerOpacities,
-            TilemapName, TilemapSlotSize, TilemapStorage, TilemapTextures, TilemapTransform,
-            TilemapType,
+            DenseTilemapStorage, TileAnchor, TilePivot, TileRenderSize, TilemapAnimations,
+            TilemapAxisFlip, TilemapClip, TilemapLayerOpacities, TilemapName, TilemapScissor,
+            TilemapSlotSize, TilemapStorage, TilemapTextures, TilemapTransform, TilemapType,
+            TilemapVisibility,
         },
         tile::Tile,
     },
@@ -26,7 +30,9 @@ use crate::{
 use super::{
     chunk::{ChunkUnload, UnloadRenderChunk},
     cull::FrustumCulling,
-    material::TilemapMaterial,
+    material::{
+        ShaderDefines, TilemapBlendMode, TilemapMaterial, TilemapShaderFeatures, TilemapTextureMode,
+    },
     resources::{ExtractedTilemapMaterials, TilemapInstances},
 };
 
@@ -41,51 +47,72 @@ pub struct ExtractedTilemap<M: TilemapMaterial> {
     pub slot_size: Vec2,
     pub ty: TilemapType,
     pub tile_pivot: Vec2,
+    /// `TileAnchor`
+    pub tile_anchor: Vec2,
     pub layer_opacities: Vec4,
+    /// `TilemapVisibility::alpha`
+    pub alpha: f32,
     pub transform: TilemapTransform,
     pub axis_flip: TilemapAxisFlip,
     pub material: Handle<M>,
+    pub texture_mode: TilemapTextureMode,
+    pub blend_mode: TilemapBlendMode,
+    pub shader_features: TilemapShaderFeatures,
+    pub shader_defines: ShaderDefines,
     pub texture: Option<Handle<TilemapTextures>>,
     pub animations: Option<TilemapAnimations>,
     pub chunk_size: u32,
+    pub scissor: Option<UAabb2d>,
+    /// `TilemapClip`
+    pub clip: Option<IAabb2d>,
 }
 
 pub type ExtractedTile = Tile;
 
 pub type ExtractedView = CameraAabb2d;
 
+type ChangedTilemap<M> = (
+    Entity,
+    &'static TilemapName,
+    &'static TileRenderSize,
+    &'static TilemapSlotSize,
+    &'static TilemapType,
+    &'static TilePivot,
+    &'static TilemapLayerOpacities,
+    &'static TilemapTransform,
+    &'static TilemapAxisFlip,
+    &'static TilemapStorage,
+    &'static Handle<M>,
+    Option<&'static Handle<TilemapTextures>>,
+    Option<&'static TilemapAnimations>,
+    Option<&'static TilemapScissor>,
+    (
+        Option<&'static TilemapVisibility>,
+        Option<&'static TileAnchor>,
+        Option<&'static TilemapClip>,
+    ),
+);
+
+type TilemapChanged<M> = Or<(
+    Changed<TileRenderSize>,
+    Changed<TilemapSlotSize>,
+    Changed<TilemapType>,
+    Changed<TilePivot>,
+    Changed<TilemapLayerOpacities>,
+    Changed<TilemapTransform>,
+    Changed<TilemapAxisFlip>,
+    Changed<Handle<M>>,
+    Changed<Handle<TilemapTextures>>,
+    Changed<TilemapAnimations>,
+    Changed<TilemapScissor>,
+    Changed<TilemapVisibility>,
+    Changed<TileAnchor>,
+    Changed<TilemapClip>,
+)>;
+
 pub fn extract_changed_tilemaps<M: TilemapMaterial>(
-    tilemaps_query: Extract<
-        Query<
-            (
-                Entity,
-                &TilemapName,
-                &TileRenderSize,
-                &TilemapSlotSize,
-                &TilemapType,
-                &TilePivot,
-                &TilemapLayerOpacities,
-                &TilemapTransform,
-                &TilemapAxisFlip,
-                &TilemapStorage,
-                &Handle<M>,
-                Option<&Handle<TilemapTextures>>,
-                Option<&TilemapAnimations>,
-            ),
-            Or<(
-                Changed<TileRenderSize>,
-                Changed<TilemapSlotSize>,
-                Changed<TilemapType>,
-                Changed<TilePivot>,
-                Changed<TilemapLayerOpacities>,
-                Changed<TilemapTransform>,
-                Changed<TilemapAxisFlip>,
-                Changed<Handle<M>>,
-                Changed<Handle<TilemapTextures>>,
-                Changed<TilemapAnimations>,
-            )>,
-        >,
-    >,
+    tilemaps_query: Extract<Query<ChangedTilemap<M>, TilemapChanged<M>>>,
+    materials: Extract<Res<Assets<M>>>,
     mut instances: ResMut<TilemapInstances<M>>,
 ) {
     tilemaps_query.iter().for_each(
@@ -103,6 +130,8 @@ pub fn extract_changed_tilemaps<M: TilemapMaterial>(
             material,
             texture,
             animations,
+            scissor,
+            (visibility, tile_anchor, clip),
         )| {
             assert_ne!(
                 storage.tilemap,
@@ -120,28 +149,54 @@ pub fn extract_changed_tilemaps<M: TilemapMaterial>(
                     slot_size: slot_size.0,
                     ty: *ty,
                     tile_pivot: tile_pivot.0,
+                    tile_anchor: tile_anchor.copied().unwrap_or_default().0,
                     layer_opacities: layer_opacities.0,
+                    alpha: visibility.map_or(1., |v| v.alpha),
                     transform: *transform,
                     axis_flip: *axis_flip,
                     texture: texture.cloned(),
                     material: material.clone(),
+                    texture_mode: materials
+                        .get(material)
+                        .map(|m| m.texture_mode())
+                        .unwrap_or_default(),
+                    blend_mode: materials
+                        .get(material)
+                        .map(|m| m.blend_mode())
+                        .unwrap_or_default(),
+                    shader_features: materials
+                        .get(material)
+                        .map(|m| m.shader_features())
+                        .unwrap_or_default(),
+                    shader_defines: materials
+                        .get(material)
+                        .map(|m| m.shader_defines())
+                        .unwrap_or_default(),
                     animations: animations.cloned(),
                     chunk_size: storage.storage.chunk_size,
+                    scissor: scissor.and_then(|s| s.0),
+                    clip: clip.and_then(|c| c.0),
                 },
             );
         },
     );
 }
 
+type VisibleTilemap = (
+    Entity,
+    &'static InheritedVisibility,
+    Option<&'static TilemapVisibility>,
+);
+
 pub fn extract_tilemaps(
     mut commands: Commands,
-    tilemaps_query: Extract<Query<(Entity, &InheritedVisibility), With<TilemapStorage>>>,
+    tilemaps_query: Extract<Query<VisibleTilemap, With<TilemapStorage>>>,
 ) {
     commands.insert_or_spawn_batch(
         tilemaps_query
             .iter()
-            .filter_map(|(entity, inherited_visibility)| {
-                if inherited_visibility.get() {
+            .filter_map(|(entity, inherited_visibility, visibility)| {
+                if inherited_visibility.get() && visibility.is_none_or(|v| v.visible) {
                     Some((entity, TilemapInstance))
                 } else {
                     None
@@ -175,10 +230,25 @@ pub fn extract_tiles(
     );
 }
 
+/// The tiles of immutable tilemaps have no entities, so the whole dense storage
+/// is extracted once it's built.
+pub fn extract_dense_tilemaps(
+    mut commands: Commands,
+    tilemaps_query: Extract<Query<(Entity, &DenseTilemapStorage), Added<DenseTilemapStorage>>>,
+) {
+    commands.insert_or_spawn_batch(
+        tilemaps_query
+            .iter()
+            .map(|(entity, dense)| (entity, dense.clone()))
+            .collect::<Vec<_>>(),
+    );
+}
+
 pub fn extract_materials<M: TilemapMaterial>(
     mut commands: Commands,
     mut events: Extract<EventReader<AssetEvent<M>>>,
     assets: Extract<Res<Assets<M>>>,
+    mut instances: ResMut<TilemapInstances<M>>,
 ) {
     let mats = events
         .read()
@@ -197,6 +267,20 @@ pub fn extract_materials<M: TilemapMaterial>(
             acc
         });
 
+    // The texture mode, blend mode, features and defines may change along with the material.
+    mats.changed.iter().for_each(|(id, mat)| {
+        instances
+            .0
+            .values_mut()
+            .filter(|tilemap| tilemap.material.id() == *id)
+            .for_each(|tilemap| {
+                tilemap.texture_mode = mat.texture_mode();
+                tilemap.blend_mode = mat.blend_mode();
+                tilemap.shader_features = mat.shader_features();
+                tilemap.shader_defines = mat.shader_defines();
+            });
+    });
+
     commands.insert_resource(mats);
 }
 
@@ -225,8 +309,20 @@ pub fn extract_unloaded_chunks(
     ));
 }
 
-pub fn extract_resources(mut commands: Commands, frustum_culling: Extract<Res<FrustumCulling>>) {
+pub fn extract_resources(
+    mut commands: Commands,
+    frustum_culling: Extract<Res<FrustumCulling>>,
+    #[cfg(feature = "debug")] scrubber: Extract<
+        Option<Res<crate::debug::scrubber::AnimationTimeScrubber>>,
+    >,
+) {
     commands.insert_resource(FrustumCulling(frustum_culling.0));
+    #[cfg(feature = "debug")]
+    if let Some(scrubber) = scrubber.as_ref() {
+        commands.insert_resource(**scrubber);
+    } else {
+        commands.remove_resource::<crate::debug::scrubber::AnimationTimeScrubber>();
+    }
 }
 
 pub fn extract_despawned_tilemaps(
@@ -1,11 +1,16 @@
-use std::marker::PhantomData;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    sync::Arc,
+};
 
 use bevy::{
     app::{App, Plugin},
     asset::{Asset, AssetApp},
     core_pipeline::core_2d::Transparent2d,
     ecs::schedule::IntoSystemConfigs,
-    reflect::TypePath,
+    reflect::{Reflect, TypePath},
     render::{
         color::Color,
         render_phase::AddRenderCommand,
@@ -24,7 +29,7 @@ use super::{
     cull,
     draw::{DrawTilemapNonTextured, DrawTilemapTextured},
     extract,
-    pipeline::EntiTilesPipeline,
+    pipeline::{self, EntiTilesPipeline, TilemapLastGoodPipelines},
     prepare, queue,
     resources::{ExtractedTilemapMaterials, TilemapInstances},
 };
@@ -54,6 +59,7 @@ impl<M: TilemapMaterial> Plugin for EntiTilesMaterialPlugin<M> {
                     prepare::prepare_tilemaps_a::<M>,
                     prepare::prepare_tilemaps_b::<M>,
                     prepare::prepare_tiles::<M>,
+                    prepare::prepare_dense_tiles::<M>,
                     prepare::prepare_unloaded_chunks::<M>,
                     prepare::prepare_despawned_tilemaps::<M>,
                     prepare::prepare_despawned_tiles::<M>,
@@ -61,12 +67,19 @@ impl<M: TilemapMaterial> Plugin for EntiTilesMaterialPlugin<M> {
                 )
                     .in_set(RenderSet::Prepare),
             )
-            .add_systems(Render, queue::queue::<M>.in_set(RenderSet::Queue))
+            .add_systems(
+                Render,
+                (
+                    queue::queue::<M>.in_set(RenderSet::Queue),
+                    pipeline::last_good_pipelines_updater::<M>.in_set(RenderSet::PhaseSort),
+                ),
+            )
             .init_resource::<RenderChunkStorage<M>>()
             .init_resource::<TilemapUniformBuffer<M>>()
             .init_resource::<TilemapBindGroups<M>>()
             .init_resource::<TilemapInstances<M>>()
             .init_resource::<ExtractedTilemapMaterials<M>>()
+            .init_resource::<TilemapLastGoodPipelines<M>>()
             .add_render_command::<Transparent2d, DrawTilemapTextured<M>>()
             .add_render_command::<Transparent2d, DrawTilemapNonTextured<M>>();
     }
@@ -80,7 +93,195 @@ impl<M: TilemapMaterial> Plugin for EntiTilesMaterialPlugin<M> {
     }
 }
 
+/// How the textures of a tilemap are packed on the GPU.
+///
+/// This is chosen per material through `TilemapMaterial::texture_mode()`,
+/// so array and atlas tilemaps can be rendered side by side. Both modes are bound
+/// as a `texture_2d_array`, only the layers they are split into differ.
+///
+/// `Atlas` is still only available with the `atlas` feature. Atlas tiles have to tell
+/// which texture they are sampled from, and `TileLayer::texture_index` along with
+/// the matching mesh attribute and serialized formats only exist with that feature,
+/// so the mode can't be picked at runtime in builds without it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TilemapTextureMode {
+    /// Every tile is copied into its own layer of a texture array.
+    ///
+    /// Tiles never bleed into their neighbours, even with linear filtering and mipmaps,
+    /// but all the textures must share the same tile size, and the total tile count
+    /// is limited by `max_texture_array_layers` (usually 256 or 2048).
+    Array,
+    /// Every texture is copied into a layer of a texture array as a whole,
+    /// and tiles are sampled from it using uvs.
+    ///
+    /// Only one layer is used per texture, so large tilesets fit, and the tile sizes
+    /// can differ between textures. But neighbouring tiles may bleed into each other
+    /// when filtered linearly or mipmapped, so pad the tiles if you need those.
+    ///
+    /// This requires the `atlas` feature as tiles need to carry `texture_index`.
+    #[cfg(feature = "atlas")]
+    Atlas,
+}
+
+impl Default for TilemapTextureMode {
+    fn default() -> Self {
+        #[cfg(not(feature = "atlas"))]
+        return Self::Array;
+        #[cfg(feature = "atlas")]
+        return Self::Atlas;
+    }
+}
+
+/// How tilemaps are blended with what's behind them.
+///
+/// This is chosen per material through `TilemapMaterial::blend_mode()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TilemapBlendMode {
+    /// Straight alpha blending.
+    ///
+    /// The tile layers are still composited with premultiplied colors,
+    /// so translucent layers don't darken the ones below them.
+    #[default]
+    Alpha,
+    /// The tile layers are composited and blended with premultiplied colors.
+    ///
+    /// Use this for foliage, glass and other translucent tiles, it stays correct
+    /// when linearly filtered or when translucent tilemaps are stacked.
+    PremultipliedAlpha,
+    /// The alpha is turned into an MSAA coverage mask instead of being blended,
+    /// so tiles don't depend on the drawing order, but only get as many levels
+    /// of transparency as there are samples.
+    ///
+    /// Falls back to `Alpha` when MSAA is disabled.
+    AlphaToCoverage,
+}
+
+bitflags::bitflags! {
+    /// The built-in features of the tilemap shader.
+    ///
+    /// The disabled ones are compiled out of the pipeline variant, so tilemaps
+    /// that don't need them save the work.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TilemapShaderFeatures: u8 {
+        /// Multiply tiles by their `Tile::tint`. Defines `TINT`.
+        const TINT = 1;
+        /// Play animated tiles. Defines `ANIMATION`,
+        /// animated tiles show nothing without it.
+        const ANIMATION = 1 << 1;
+    }
+}
+
+impl Default for TilemapShaderFeatures {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Extra shader defines for the tilemaps using a material, which can be checked
+/// with `#ifdef` in the shaders.
+///
+/// Every different set of defines gets its own pipeline variant.
+/// The set is shared and hashed once, so it's cheap to put into pipeline keys.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serializing",
+    serde(from = "BTreeSet<String>", into = "BTreeSet<String>")
+)]
+pub struct ShaderDefines {
+    defines: Arc<BTreeSet<String>>,
+    hash: u64,
+}
+
+impl Default for ShaderDefines {
+    fn default() -> Self {
+        BTreeSet::new().into()
+    }
+}
+
+impl From<BTreeSet<String>> for ShaderDefines {
+    fn from(defines: BTreeSet<String>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        defines.hash(&mut hasher);
+        Self {
+            defines: Arc::new(defines),
+            hash: hasher.finish(),
+        }
+    }
+}
+
+impl From<ShaderDefines> for BTreeSet<String> {
+    fn from(value: ShaderDefines) -> Self {
+        Arc::unwrap_or_clone(value.defines)
+    }
+}
+
+impl PartialEq for ShaderDefines {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+            && (Arc::ptr_eq(&self.defines, &other.defines) || self.defines == other.defines)
+    }
+}
+
+impl Eq for ShaderDefines {}
+
+impl Hash for ShaderDefines {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl ShaderDefines {
+    #[inline]
+    pub fn with(mut self, define: impl Into<String>) -> Self {
+        self.insert(define);
+        self
+    }
+
+    pub fn insert(&mut self, define: impl Into<String>) {
+        let mut defines = Arc::unwrap_or_clone(std::mem::take(&mut self.defines));
+        defines.insert(define.into());
+        *self = defines.into();
+    }
+
+    #[inline]
+    pub fn contains(&self, define: &str) -> bool {
+        self.defines.contains(define)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.defines.iter()
+    }
+}
+
 pub trait TilemapMaterial: Default + Asset + AsBindGroup + TypePath + Clone {
+    /// The texture mode of tilemaps using this material.
+    fn texture_mode(&self) -> TilemapTextureMode {
+        TilemapTextureMode::default()
+    }
+
+    /// The blend mode of tilemaps using this material.
+    fn blend_mode(&self) -> TilemapBlendMode {
+        TilemapBlendMode::default()
+    }
+
+    /// The built-in shader features tilemaps using this material need.
+    fn shader_features(&self) -> TilemapShaderFeatures {
+        TilemapShaderFeatures::default()
+    }
+
+    /// The extra shader defines of tilemaps using this material.
+    fn shader_defines(&self) -> ShaderDefines {
+        ShaderDefines::default()
+    }
+
+    /// Shaders loaded from a path are hot reloaded when bevy's `file_watcher` feature
+    /// is enabled. The built-in shaders are embedded into the binary, so copy them
+    /// into your assets to iterate on them.
     fn vertex_shader() -> ShaderRef {
         super::TILEMAP_SHADER.into()
     }
@@ -109,9 +310,29 @@ impl From<&StandardTilemapMaterial> for StandardTilemapUniform {
 #[uniform(0, StandardTilemapUniform)]
 pub struct StandardTilemapMaterial {
     pub tint: Color,
+    pub texture_mode: TilemapTextureMode,
+    pub blend_mode: TilemapBlendMode,
+    pub shader_features: TilemapShaderFeatures,
+    pub shader_defines: ShaderDefines,
 }
 
 impl TilemapMaterial for StandardTilemapMaterial {
+    fn texture_mode(&self) -> TilemapTextureMode {
+        self.texture_mode
+    }
+
+    fn blend_mode(&self) -> TilemapBlendMode {
+        self.blend_mode
+    }
+
+    fn shader_features(&self) -> TilemapShaderFeatures {
+        self.shader_features
+    }
+
+    fn shader_defines(&self) -> ShaderDefines {
+        self.shader_defines.clone()
+    }
+
     fn vertex_shader() -> ShaderRef {
         super::TILEMAP_SHADER.into()
     }
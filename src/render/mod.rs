@@ -25,6 +25,7 @@ pub mod binding;
 pub mod buffer;
 pub mod chunk;
 pub mod cull;
+pub mod diagnostics;
 pub mod draw;
 pub mod extract;
 pub mod material;
@@ -102,6 +103,7 @@ impl Plugin for EntiTilesRendererPlugin {
                 (
                     extract::extract_tilemaps,
                     extract::extract_tiles,
+                    extract::extract_dense_tilemaps,
                     extract::extract_view,
                     extract::extract_unloaded_chunks,
                     extract::extract_resources,
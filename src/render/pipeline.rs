@@ -2,25 +2,41 @@ use std::marker::PhantomData;
 
 use bevy::{
     asset::{AssetServer, Handle},
-    ecs::world::World,
+    core_pipeline::core_2d::Transparent2d,
+    ecs::{
+        system::{Query, Res, ResMut},
+        world::World,
+    },
+    log::warn,
     prelude::{FromWorld, Resource},
     render::{
+        render_phase::{DrawFunctions, RenderPhase},
         render_resource::{
-            BindGroupLayout, BindGroupLayoutEntries, BlendState, ColorTargetState, ColorWrites,
-            Face, FragmentState, FrontFace, MultisampleState, PolygonMode, PrimitiveState,
-            PrimitiveTopology, RenderPipelineDescriptor, SamplerBindingType, Shader, ShaderDefVal,
-            ShaderRef, ShaderStages, SpecializedRenderPipeline, TextureFormat, TextureSampleType,
-            VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+            BindGroupLayout, BindGroupLayoutEntries, BlendState, CachedPipelineState,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, Face, FragmentState, FrontFace,
+            MultisampleState, Pipeline, PipelineCache, PolygonMode, PrimitiveState,
+            PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, SamplerBindingType,
+            Shader, ShaderDefVal, ShaderRef, ShaderStages, SpecializedRenderPipeline,
+            TextureFormat, TextureSampleType, VertexBufferLayout, VertexFormat, VertexState,
+            VertexStepMode,
         },
         renderer::RenderDevice,
         texture::BevyDefault,
         view::ViewUniform,
     },
+    utils::{HashMap, HashSet},
 };
 
 use crate::tilemap::map::TilemapType;
 
-use super::{buffer::TilemapUniform, material::TilemapMaterial};
+use super::{
+    buffer::TilemapUniform,
+    diagnostics::TilemapRenderMetrics,
+    draw::{DrawTilemapNonTextured, DrawTilemapTextured},
+    material::{
+        ShaderDefines, TilemapBlendMode, TilemapMaterial, TilemapShaderFeatures, TilemapTextureMode,
+    },
+};
 
 #[cfg(feature = "atlas")]
 use super::buffer::GpuTilemapTextureDescriptor;
@@ -36,6 +52,7 @@ pub struct EntiTilesPipeline<M: TilemapMaterial> {
     pub material_layout: BindGroupLayout,
     pub vertex_shader: Handle<Shader>,
     pub fragment_shader: Handle<Shader>,
+    pub metrics: TilemapRenderMetrics,
     pub marker: PhantomData<M>,
 }
 
@@ -44,6 +61,60 @@ pub struct EntiTilesPipelineKey {
     pub msaa: u32,
     pub map_type: TilemapType,
     pub is_pure_color: bool,
+    pub texture_mode: TilemapTextureMode,
+    pub blend_mode: TilemapBlendMode,
+    pub shader_features: TilemapShaderFeatures,
+    pub shader_defines: ShaderDefines,
+}
+
+impl EntiTilesPipelineKey {
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        let mut shader_defs: Vec<ShaderDefVal> = vec![];
+        shader_defs.push(
+            {
+                match self.map_type {
+                    TilemapType::Square => "SQUARE",
+                    TilemapType::Isometric => "ISOMETRIC",
+                    TilemapType::Hexagonal(_) => "HEXAGONAL",
+                }
+            }
+            .into(),
+        );
+
+        // Tiles carry texture indices whenever the `atlas` feature is enabled,
+        // but they are only sampled as atlases in `Atlas` mode.
+        #[cfg(feature = "atlas")]
+        {
+            shader_defs.push("TEXTURE_INDICES".into());
+            if self.texture_mode == TilemapTextureMode::Atlas {
+                shader_defs.push("ATLAS".into());
+            }
+        }
+
+        if self.is_pure_color {
+            shader_defs.push("PURE_COLOR".into());
+        }
+
+        if self.blend_mode == TilemapBlendMode::PremultipliedAlpha {
+            shader_defs.push("PREMULTIPLIED_ALPHA".into());
+        }
+
+        // Pure color tiles are their tint, so it's never compiled out there.
+        if self.shader_features.contains(TilemapShaderFeatures::TINT) {
+            shader_defs.push("TINT".into());
+        }
+
+        if self
+            .shader_features
+            .contains(TilemapShaderFeatures::ANIMATION)
+        {
+            shader_defs.push("ANIMATION".into());
+        }
+
+        shader_defs.extend(self.shader_defines.iter().map(|d| d.as_str().into()));
+
+        shader_defs
+    }
 }
 
 impl<M: TilemapMaterial> FromWorld for EntiTilesPipeline<M> {
@@ -117,6 +188,10 @@ impl<M: TilemapMaterial> FromWorld for EntiTilesPipeline<M> {
                 ShaderRef::Handle(handle) => handle,
                 ShaderRef::Path(path) => asset_server.load(path),
             },
+            metrics: world
+                .get_resource::<TilemapRenderMetrics>()
+                .cloned()
+                .unwrap_or_default(),
             marker: PhantomData,
         }
     }
@@ -126,19 +201,16 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
     type Key = EntiTilesPipelineKey;
 
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
-        let mut shader_defs: Vec<ShaderDefVal> = vec![];
-        shader_defs.push(
-            {
-                match key.map_type {
-                    TilemapType::Square => "SQUARE",
-                    TilemapType::Isometric => "ISOMETRIC",
-                    TilemapType::Hexagonal(_) => "HEXAGONAL",
-                }
-            }
-            .into(),
-        );
-        #[cfg(feature = "atlas")]
-        shader_defs.push("ATLAS".into());
+        self.metrics.add_pipeline_specialization();
+        let shader_defs = key.shader_defs();
+
+        // Alpha to coverage only works with multisampling.
+        let alpha_to_coverage = key.blend_mode == TilemapBlendMode::AlphaToCoverage && key.msaa > 1;
+        let blend = match key.blend_mode {
+            TilemapBlendMode::PremultipliedAlpha => Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            TilemapBlendMode::AlphaToCoverage if alpha_to_coverage => None,
+            _ => Some(BlendState::ALPHA_BLENDING),
+        };
 
         let mut vtx_fmt = vec![
             // position
@@ -149,9 +221,7 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
             VertexFormat::Float32x4,
         ];
 
-        if key.is_pure_color {
-            shader_defs.push("PURE_COLOR".into());
-        } else {
+        if !key.is_pure_color {
             // atlas indices
             vtx_fmt.push(VertexFormat::Sint32x4);
 
@@ -195,7 +265,7 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
                 entry_point: "tilemap_fragment".into(),
                 targets: vec![Some(ColorTargetState {
                     format: TextureFormat::bevy_default(),
-                    blend: Some(BlendState::ALPHA_BLENDING),
+                    blend,
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -212,7 +282,7 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
             multisample: MultisampleState {
                 count: key.msaa,
                 mask: !0,
-                alpha_to_coverage_enabled: false,
+                alpha_to_coverage_enabled: alpha_to_coverage,
             },
         };
 
@@ -221,3 +291,194 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
         desc
     }
 }
+
+/// The last tilemap pipelines that compiled successfully.
+///
+/// When a shader is modified, `PipelineCache` recompiles every pipeline using it,
+/// so they pick up the changes without restarting.
+/// Tilemaps keep being drawn with these while that happens,
+/// or if the modified shader fails to compile.
+#[derive(Resource)]
+pub struct TilemapLastGoodPipelines<M: TilemapMaterial> {
+    pipelines: HashMap<CachedRenderPipelineId, Option<RenderPipeline>>,
+    failed: HashSet<CachedRenderPipelineId>,
+    marker: PhantomData<M>,
+}
+
+impl<M: TilemapMaterial> Default for TilemapLastGoodPipelines<M> {
+    fn default() -> Self {
+        Self {
+            pipelines: Default::default(),
+            failed: Default::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M: TilemapMaterial> TilemapLastGoodPipelines<M> {
+    #[inline]
+    pub fn get(&self, id: CachedRenderPipelineId) -> Option<&RenderPipeline> {
+        self.pipelines.get(&id).and_then(|p| p.as_ref())
+    }
+}
+
+pub fn last_good_pipelines_updater<M: TilemapMaterial>(
+    pipeline_cache: Res<PipelineCache>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    phases_query: Query<&RenderPhase<Transparent2d>>,
+    mut last_good: ResMut<TilemapLastGoodPipelines<M>>,
+) {
+    let TilemapLastGoodPipelines {
+        pipelines, failed, ..
+    } = last_good.as_mut();
+
+    let draw_functions = draw_functions.read();
+    let tilemap_draw_functions = [
+        draw_functions.get_id::<DrawTilemapTextured<M>>(),
+        draw_functions.get_id::<DrawTilemapNonTextured<M>>(),
+    ];
+    phases_query
+        .iter()
+        .flat_map(|phase| phase.items.iter())
+        .filter(|item| tilemap_draw_functions.contains(&Some(item.draw_function)))
+        .for_each(|item| {
+            pipelines.entry(item.pipeline).or_default();
+        });
+
+    pipelines.iter_mut().for_each(|(id, last_good)| {
+        match pipeline_cache.get_render_pipeline_state(*id) {
+            CachedPipelineState::Ok(Pipeline::RenderPipeline(pipeline)) => {
+                *last_good = Some(pipeline.clone());
+                failed.remove(id);
+            }
+            // The error itself is logged by `PipelineCache`.
+            CachedPipelineState::Err(_) if last_good.is_some() && failed.insert(*id) => {
+                warn!("Failed to recompile a tilemap pipeline, the last working one will be used until it's fixed.");
+            }
+            _ => {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{math::UVec2, render::render_resource::FilterMode};
+
+    use crate::{
+        render::{material::StandardTilemapMaterial, texture::texture_array_extent},
+        tilemap::map::{TilemapTexture, TilemapTextureDescriptor, TilemapTextures},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_texture_modes() {
+        let textures = TilemapTextures::new(
+            vec![
+                TilemapTexture::new(
+                    Default::default(),
+                    TilemapTextureDescriptor::new(UVec2::new(64, 32), UVec2::splat(16)),
+                ),
+                TilemapTexture::new(
+                    Default::default(),
+                    TilemapTextureDescriptor::new(UVec2::splat(32), UVec2::splat(16)),
+                ),
+            ],
+            FilterMode::Nearest,
+        );
+
+        let key = |material: &StandardTilemapMaterial| EntiTilesPipelineKey {
+            msaa: 1,
+            map_type: TilemapType::Square,
+            is_pure_color: false,
+            texture_mode: material.texture_mode(),
+            blend_mode: material.blend_mode(),
+            shader_features: material.shader_features(),
+            shader_defines: material.shader_defines(),
+        };
+        let is_atlas = |key: &EntiTilesPipelineKey| {
+            key.shader_defs()
+                .iter()
+                .any(|def| matches!(def, ShaderDefVal::Bool(name, true) if name == "ATLAS"))
+        };
+
+        let array = StandardTilemapMaterial {
+            texture_mode: TilemapTextureMode::Array,
+            ..Default::default()
+        };
+        assert!(!is_atlas(&key(&array)));
+        let extent = texture_array_extent(&textures, array.texture_mode());
+        assert_eq!(
+            (extent.width, extent.height, extent.depth_or_array_layers),
+            (16, 16, 12)
+        );
+
+        #[cfg(feature = "atlas")]
+        {
+            let atlas = StandardTilemapMaterial {
+                texture_mode: TilemapTextureMode::Atlas,
+                ..Default::default()
+            };
+            assert!(is_atlas(&key(&atlas)));
+            assert!(key(&atlas) != key(&array));
+            let extent = texture_array_extent(&textures, atlas.texture_mode());
+            assert_eq!(
+                (extent.width, extent.height, extent.depth_or_array_layers),
+                (64, 32, 2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_shader_defines() {
+        let has_def = |key: &EntiTilesPipelineKey, def: &str| {
+            key.shader_defs()
+                .iter()
+                .any(|d| matches!(d, ShaderDefVal::Bool(name, true) if name == def))
+        };
+        let key = |material: &StandardTilemapMaterial| EntiTilesPipelineKey {
+            msaa: 1,
+            map_type: TilemapType::Square,
+            is_pure_color: false,
+            texture_mode: material.texture_mode(),
+            blend_mode: material.blend_mode(),
+            shader_features: material.shader_features(),
+            shader_defines: material.shader_defines(),
+        };
+
+        let plain = StandardTilemapMaterial::default();
+        let lit = StandardTilemapMaterial {
+            shader_defines: ShaderDefines::default().with("LIGHTING").with("FOG"),
+            ..Default::default()
+        };
+        let lit_again = StandardTilemapMaterial {
+            shader_defines: ShaderDefines::default().with("FOG").with("LIGHTING"),
+            ..Default::default()
+        };
+        let static_untinted = StandardTilemapMaterial {
+            shader_features: TilemapShaderFeatures::empty(),
+            ..Default::default()
+        };
+
+        assert!(!has_def(&key(&plain), "LIGHTING"));
+        assert!(has_def(&key(&lit), "LIGHTING"));
+        assert!(has_def(&key(&lit), "FOG"));
+        assert!(has_def(&key(&lit), "SQUARE"));
+
+        // Built-in features are on by default and can be compiled out.
+        assert!(has_def(&key(&plain), "TINT"));
+        assert!(has_def(&key(&plain), "ANIMATION"));
+        assert!(!has_def(&key(&static_untinted), "TINT"));
+        assert!(!has_def(&key(&static_untinted), "ANIMATION"));
+        assert!(key(&plain) != key(&static_untinted));
+
+        // Keys compare by the set of defines, not the order they were added in.
+        assert!(key(&plain) != key(&lit));
+        assert!(key(&lit) == key(&lit_again));
+        let mut cloned = lit.shader_defines.clone();
+        assert!(cloned == lit_again.shader_defines);
+        cloned.insert("SHADOWS");
+        assert!(cloned != lit_again.shader_defines);
+        assert!(!lit.shader_defines.contains("SHADOWS"));
+    }
+}
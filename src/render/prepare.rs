@@ -1,17 +1,16 @@
 use bevy::{
-    ecs::{entity::Entity, query::With},
+    ecs::{entity::Entity, query::With, system::SystemParam},
     prelude::{Commands, Query, Res, ResMut},
     render::{
         render_asset::RenderAssets,
         renderer::{RenderDevice, RenderQueue},
         texture::{FallbackImage, Image},
     },
-    time::Time,
 };
 
 use crate::tilemap::{
     despawn::{DespawnedTile, DespawnedTilemap},
-    map::TilemapTextures,
+    map::{DenseTilemapStorage, TilemapTextures},
 };
 
 use super::{
@@ -30,23 +29,35 @@ use super::{
 
 #[cfg(feature = "atlas")]
 use super::buffer::TilemapTextureDescriptorBuffer;
+#[cfg(feature = "debug")]
+use crate::debug::scrubber::AnimationTime;
+#[cfg(not(feature = "debug"))]
+type AnimationTime<'w> = Res<'w, bevy::time::Time>;
+
+/// The resources tilemap uniforms, render chunks and material bind groups
+/// are prepared with.
+#[derive(SystemParam)]
+pub struct TilemapPrepareResources<'w, M: TilemapMaterial> {
+    render_device: Res<'w, RenderDevice>,
+    render_queue: Res<'w, RenderQueue>,
+    entitiles_pipeline: Res<'w, EntiTilesPipeline<M>>,
+    time: AnimationTime<'w>,
+    images: Res<'w, RenderAssets<Image>>,
+    fallback_image: Res<'w, FallbackImage>,
+    extracted_materials: Res<'w, ExtractedTilemapMaterials<M>>,
+}
 
 pub fn prepare_tilemaps_a<M: TilemapMaterial>(
     mut commands: Commands,
-    render_device: Res<RenderDevice>,
-    render_queue: Res<RenderQueue>,
     extracted_tilemaps: Query<Entity, With<TilemapInstance>>,
     mut render_chunks: ResMut<RenderChunkStorage<M>>,
     mut uniform_buffers: ResMut<TilemapUniformBuffer<M>>,
-    entitiles_pipeline: Res<EntiTilesPipeline<M>>,
     mut bind_groups: ResMut<TilemapBindGroups<M>>,
-    time: Res<Time>,
     tilemap_instances: Res<TilemapInstances<M>>,
-    images: Res<RenderAssets<Image>>,
-    fallback_image: Res<FallbackImage>,
-    extracted_materials: Res<ExtractedTilemapMaterials<M>>,
+    res: TilemapPrepareResources<M>,
 ) {
     uniform_buffers.clear();
+    let time = res.time.elapsed_seconds();
 
     extracted_tilemaps
         .iter()
@@ -54,19 +65,29 @@ pub fn prepare_tilemaps_a<M: TilemapMaterial>(
         .for_each(|tilemap| {
             commands
                 .entity(tilemap.id)
-                .insert(uniform_buffers.insert(&(tilemap, time.elapsed_seconds())));
+                .insert(uniform_buffers.insert(&(tilemap, time)));
 
-            render_chunks.prepare_chunks(tilemap, &render_device);
+            render_chunks.prepare_chunks(
+                tilemap,
+                &res.render_device,
+                &res.render_queue,
+                &res.entitiles_pipeline.metrics,
+            );
         });
 
-    uniform_buffers.write(&render_device, &render_queue);
-    bind_groups.bind_uniform_buffers(&render_device, &mut uniform_buffers, &entitiles_pipeline);
+    uniform_buffers.write(&res.render_device, &res.render_queue);
+    bind_groups.bind_uniform_buffers(
+        &res.render_device,
+        &mut uniform_buffers,
+        &res.entitiles_pipeline,
+    );
     bind_groups.prepare_material_bind_groups(
-        &entitiles_pipeline.material_layout,
-        &render_device,
-        &images,
-        &fallback_image,
-        &extracted_materials,
+        &res.entitiles_pipeline.material_layout,
+        &res.entitiles_pipeline.metrics,
+        &res.render_device,
+        &res.images,
+        &res.fallback_image,
+        &res.extracted_materials,
     );
 }
 
@@ -103,16 +124,17 @@ pub fn prepare_tilemaps_b<M: TilemapMaterial>(
             #[cfg(feature = "atlas")]
             texture_desc_buffers
                 .get_or_insert_buffer(tilemap.id)
-                .extend(_textures.textures.iter().enumerate().map(|(i, t)| {
+                .extend(_textures.iter_packed().enumerate().map(|(i, (t, start))| {
                     super::buffer::GpuTilemapTextureDescriptor {
                         tile_count: t.desc.size / t.desc.tile_size,
                         tile_uv_size: t.desc.tile_size.as_vec2() / t.desc.size.as_vec2(),
                         uv_scale: _textures.uv_scales[i],
+                        start_index: start,
                     }
                 }));
 
-            if !textures_storage.contains(textures_handle) {
-                textures_storage.insert(textures_handle.clone());
+            if !textures_storage.contains(textures_handle, tilemap.texture_mode) {
+                textures_storage.insert(textures_handle.clone(), tilemap.texture_mode);
             }
         });
 
@@ -150,6 +172,45 @@ pub fn prepare_tiles<M: TilemapMaterial>(
     });
 }
 
+pub fn prepare_dense_tiles<M: TilemapMaterial>(
+    extracted_dense: Query<&DenseTilemapStorage>,
+    mut render_chunks: ResMut<RenderChunkStorage<M>>,
+    tilemap_instances: Res<TilemapInstances<M>>,
+) {
+    extracted_dense.iter().for_each(|dense| {
+        let Some(tilemap) = tilemap_instances.0.get(&dense.tilemap()) else {
+            return;
+        };
+
+        let chunks = render_chunks.value.entry(dense.tilemap()).or_default();
+        let storage = dense.get_storage_raw();
+        storage.chunks.iter().for_each(|(chunk_index, tiles)| {
+            let chunk = chunks
+                .entry(*chunk_index)
+                .or_insert_with(|| TilemapRenderChunk::from_index(*chunk_index, tilemap));
+
+            tiles
+                .iter()
+                .enumerate()
+                .filter_map(|(in_chunk_index, id)| id.map(|id| (in_chunk_index, id)))
+                .for_each(|(in_chunk_index, id)| {
+                    let tile = &dense.palette()[id.palette_index()];
+                    chunk.set_tile(
+                        in_chunk_index,
+                        Some(&ExtractedTile {
+                            tilemap_id: dense.tilemap(),
+                            chunk_index: *chunk_index,
+                            in_chunk_index,
+                            index: storage.inverse_transform_index(*chunk_index, in_chunk_index),
+                            texture: tile.texture.clone(),
+                            tint: tile.tint,
+                        }),
+                    );
+                });
+        });
+    });
+}
+
 pub fn prepare_unloaded_chunks<M: TilemapMaterial>(
     mut render_chunks: ResMut<RenderChunkStorage<M>>,
     extracted_tilemaps: Query<(Entity, &UnloadRenderChunk)>,
@@ -161,15 +222,22 @@ pub fn prepare_unloaded_chunks<M: TilemapMaterial>(
     });
 }
 
+/// Free everything the render world keeps for the despawned tilemaps,
+/// so spawning and despawning tilemaps doesn't leak GPU memory.
 pub fn prepare_despawned_tilemaps<M: TilemapMaterial>(
     mut render_chunks: ResMut<RenderChunkStorage<M>>,
-    mut storage_buffers: ResMut<TilemapAnimationBuffer>,
+    mut animation_buffers: ResMut<TilemapAnimationBuffer>,
+    mut bind_groups: ResMut<TilemapBindGroups<M>>,
     mut tilemap_instaces: ResMut<TilemapInstances<M>>,
     tilemaps_query: Query<&DespawnedTilemap>,
+    #[cfg(feature = "atlas")] mut texture_desc_buffers: ResMut<TilemapTextureDescriptorBuffer>,
 ) {
     tilemaps_query.iter().for_each(|map| {
         render_chunks.remove_tilemap(map.0);
-        storage_buffers.remove(map.0);
+        animation_buffers.remove(map.0);
+        #[cfg(feature = "atlas")]
+        texture_desc_buffers.remove(map.0);
+        bind_groups.remove_tilemap(map.0);
         tilemap_instaces.0.remove(&map.0);
     });
 }
@@ -187,3 +255,246 @@ pub fn prepare_despawned_tiles<M: TilemapMaterial>(
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::{App, Last, PostUpdate, PreUpdate, Update},
+        ecs::{schedule::IntoSystemConfigs, system::CommandQueue},
+        math::{IVec2, UVec2, Vec2, Vec4},
+        render::render_resource::BufferId,
+        utils::HashMap,
+    };
+
+    use crate::{
+        math::TileArea,
+        render::{
+            extract::ExtractedTilemap,
+            material::{StandardTilemapMaterial, TilemapTextureMode},
+        },
+        tilemap::{
+            despawn::{despawn_applier, despawn_tilemap},
+            map::{dense_storage_builder, ImmutableTilemap, TilemapStorage, TilemapType},
+            tile::{Tile, TileBuilder},
+        },
+    };
+
+    use super::*;
+
+    fn extracted_tilemap(tilemap: Entity) -> ExtractedTilemap<StandardTilemapMaterial> {
+        ExtractedTilemap {
+            id: tilemap,
+            name: String::new(),
+            tile_render_size: Vec2::splat(16.),
+            slot_size: Vec2::splat(16.),
+            ty: TilemapType::Square,
+            tile_pivot: Vec2::ZERO,
+            tile_anchor: Vec2::ZERO,
+            layer_opacities: Vec4::ONE,
+            alpha: 1.,
+            transform: Default::default(),
+            axis_flip: Default::default(),
+            material: Default::default(),
+            texture_mode: TilemapTextureMode::Array,
+            blend_mode: Default::default(),
+            shader_features: Default::default(),
+            shader_defines: Default::default(),
+            texture: None,
+            animations: None,
+            chunk_size: 4,
+            scissor: None,
+            clip: None,
+        }
+    }
+
+    #[test]
+    fn test_despawned_tilemaps() {
+        type M = StandardTilemapMaterial;
+
+        let mut app = App::new();
+        app.init_resource::<RenderChunkStorage<M>>()
+            .init_resource::<TilemapAnimationBuffer>()
+            .init_resource::<TilemapBindGroups<M>>()
+            .init_resource::<TilemapInstances<M>>()
+            .add_systems(PreUpdate, despawn_applier)
+            .add_systems(PostUpdate, despawn_tilemap)
+            .add_systems(Last, prepare_despawned_tilemaps::<M>);
+        #[cfg(feature = "atlas")]
+        app.init_resource::<TilemapTextureDescriptorBuffer>();
+
+        app.update();
+        let baseline = app.world.entities().len();
+
+        for _ in 0..10 {
+            let tilemaps = (0..8)
+                .map(|_| {
+                    let entity = app.world.spawn_empty().id();
+                    app.world
+                        .entity_mut(entity)
+                        .insert(TilemapStorage::new(16, entity));
+                    entity
+                })
+                .collect::<Vec<_>>();
+
+            // What the render world keeps for each tilemap.
+            for tilemap in &tilemaps {
+                app.world
+                    .resource_mut::<RenderChunkStorage<M>>()
+                    .value
+                    .insert(*tilemap, HashMap::default());
+                app.world
+                    .resource_mut::<TilemapAnimationBuffer>()
+                    .get_or_insert_buffer(*tilemap);
+                #[cfg(not(feature = "atlas"))]
+                let ids = BufferId::new();
+                #[cfg(feature = "atlas")]
+                let ids = (BufferId::new(), BufferId::new());
+                app.world
+                    .resource_mut::<TilemapBindGroups<M>>()
+                    .storage_buffer_ids
+                    .insert(*tilemap, ids);
+            }
+            app.update();
+
+            tilemaps.into_iter().for_each(|e| {
+                app.world.despawn(e);
+            });
+            app.update();
+
+            assert!(app
+                .world
+                .resource::<RenderChunkStorage<M>>()
+                .value
+                .is_empty());
+            assert_eq!(app.world.resource::<TilemapAnimationBuffer>().len(), 0);
+            assert!(app
+                .world
+                .resource::<TilemapBindGroups<M>>()
+                .storage_buffer_ids
+                .is_empty());
+        }
+
+        // The announcements are despawned as well.
+        app.update();
+        assert_eq!(app.world.entities().len(), baseline);
+    }
+
+    #[test]
+    fn test_dirty_chunks() {
+        type M = StandardTilemapMaterial;
+
+        let mut app = App::new();
+        app.init_resource::<RenderChunkStorage<M>>()
+            .init_resource::<TilemapInstances<M>>()
+            .add_systems(Update, prepare_tiles::<M>);
+
+        let tilemap = app.world.spawn_empty().id();
+        app.world
+            .resource_mut::<TilemapInstances<M>>()
+            .0
+            .insert(tilemap, extracted_tilemap(tilemap));
+
+        // The tiles in this world play the extracted tiles, which only live for a frame.
+        let mut storage = TilemapStorage::new(4, tilemap);
+        let mut flush = |app: &mut App, f: &dyn Fn(&mut TilemapStorage, &mut Commands)| {
+            let mut queue = CommandQueue::default();
+            f(&mut storage, &mut Commands::new(&mut queue, &app.world));
+            queue.apply(&mut app.world);
+            app.update();
+
+            let tiles = app
+                .world
+                .query_filtered::<Entity, With<Tile>>()
+                .iter(&app.world)
+                .collect::<Vec<_>>();
+            tiles.into_iter().for_each(|t| {
+                app.world.despawn(t);
+            });
+
+            let mut chunks = app.world.resource_mut::<RenderChunkStorage<M>>();
+            let chunks = chunks.get_chunks_mut(tilemap).unwrap();
+            let mut dirty = chunks
+                .iter_mut()
+                .filter(|(_, c)| c.dirty_mesh)
+                .map(|(i, c)| {
+                    // What `prepare_chunks` does after uploading.
+                    c.dirty_mesh = false;
+                    *i
+                })
+                .collect::<Vec<_>>();
+            dirty.sort_by_key(|i| (i.y, i.x));
+            dirty
+        };
+
+        let dirty = flush(&mut app, &|storage, commands| {
+            storage.fill_rect(
+                commands,
+                TileArea::new(IVec2::ZERO, UVec2::new(8, 8)),
+                TileBuilder::new(),
+            )
+        });
+        assert_eq!(dirty, vec![IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]);
+
+        let dirty = flush(&mut app, &|storage, commands| {
+            storage.set(commands, IVec2::new(12, 1), TileBuilder::new())
+        });
+        assert_eq!(dirty, vec![IVec2::new(3, 0)]);
+
+        assert!(flush(&mut app, &|_, _| {}).is_empty());
+    }
+
+    #[test]
+    fn test_dense_tiles() {
+        type M = StandardTilemapMaterial;
+
+        let mut app = App::new();
+        app.init_resource::<RenderChunkStorage<M>>()
+            .init_resource::<TilemapInstances<M>>()
+            .add_systems(
+                Update,
+                (dense_storage_builder, prepare_dense_tiles::<M>).chain(),
+            );
+
+        let tilemap = app.world.spawn_empty().id();
+        app.world
+            .resource_mut::<TilemapInstances<M>>()
+            .0
+            .insert(tilemap, extracted_tilemap(tilemap));
+
+        let mut storage = TilemapStorage::new(4, tilemap);
+        let mut queue = CommandQueue::default();
+        let indices = [IVec2::new(0, 0), IVec2::new(5, 1), IVec2::new(-100, 40)];
+        {
+            let mut commands = Commands::new(&mut queue, &app.world);
+            indices.iter().for_each(|index| {
+                storage.set(&mut commands, *index, TileBuilder::new());
+            });
+        }
+        queue.apply(&mut app.world);
+        app.world
+            .entity_mut(tilemap)
+            .insert((storage, ImmutableTilemap));
+
+        app.update();
+
+        // The tiles are rendered from the dense storage, without any tile entities.
+        assert_eq!(app.world.query::<&Tile>().iter(&app.world).count(), 0);
+        let render_chunks = app.world.resource::<RenderChunkStorage<M>>();
+        let chunks = render_chunks.get_chunks(tilemap).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks
+                .values()
+                .flat_map(|c| c.tiles.iter())
+                .filter(|t| t.is_some())
+                .count(),
+            indices.len()
+        );
+        assert!(chunks[&IVec2::new(1, 0)]
+            .tiles
+            .iter()
+            .any(|t| t
+                .as_ref()
+                .is_some_and(|t| t.index.truncate().truncate() == IVec2::new(5, 1))));
+    }
+}
@@ -41,27 +41,18 @@ pub fn queue<M: TilemapMaterial>(
     tilemap_instances: Res<TilemapInstances<M>>,
     textures_assets: Res<RenderAssets<TilemapTextures>>,
     render_queue: Res<RenderQueue>,
-    #[cfg(not(feature = "atlas"))] render_images: Res<RenderAssets<Image>>,
-    #[cfg(feature = "atlas")] mut render_images: ResMut<RenderAssets<Image>>,
+    render_images: Res<RenderAssets<Image>>,
 ) {
     let Some(view_binding) = view_uniforms.uniforms.binding() else {
         return;
     };
 
-    #[cfg(not(feature = "atlas"))]
     textures_storage.queue_textures(
         &render_device,
         &render_queue,
         &render_images,
         &textures_assets,
     );
-    #[cfg(feature = "atlas")]
-    textures_storage.queue_textures(
-        &render_device,
-        &render_queue,
-        &mut render_images,
-        &textures_assets,
-    );
 
     for (view_entity, mut transparent_phase) in views_query.iter_mut() {
         commands.entity(view_entity).insert(TilemapViewBindGroup {
@@ -96,6 +87,10 @@ pub fn queue<M: TilemapMaterial>(
                     msaa: msaa.samples(),
                     map_type: tilemap.ty,
                     is_pure_color,
+                    texture_mode: tilemap.texture_mode,
+                    blend_mode: tilemap.blend_mode,
+                    shader_features: tilemap.shader_features,
+                    shader_defines: tilemap.shader_defines.clone(),
                 },
             );
 
@@ -122,5 +117,9 @@ pub fn queue<M: TilemapMaterial>(
                 dynamic_offset: None,
             });
         }
+
+        entitiles_pipeline
+            .metrics
+            .add_draw_instances(tilemaps.len() as u64);
     }
 }
@@ -10,7 +10,7 @@ struct TilemapVertexInput {
     @location(2) tint: vec4f,
 #ifndef PURE_COLOR
     @location(3) atlas_indices: vec4i,
-#ifdef ATLAS
+#ifdef TEXTURE_INDICES
     @location(4) texture_indices: vec4i,
 #endif
 #endif
@@ -24,7 +24,7 @@ struct TilemapVertexOutput {
     @location(2) atlas_indices: vec4i,
     // Indicates whether the tile is animated.
     @location(3) anim_flag: i32,
-#ifdef ATLAS
+#ifdef TEXTURE_INDICES
     @location(4) texture_indices: vec4i,
 #endif
 #endif
@@ -36,11 +36,13 @@ struct Tilemap {
     tile_render_size: vec2f,
     slot_size: vec2f,
     pivot: vec2f,
+    anchor: vec2f,
     layer_opacities: vec4f,
     axis_dir: vec2f,
     // this value will only be meaningful when the tilemap is hexagonal!
     hex_legs: f32,
     time: f32,
+    alpha: f32,
 }
 
 struct StandardTilemapUniform {
@@ -63,11 +65,12 @@ var color_texture_sampler: sampler;
 @group(4) @binding(0)
 var<storage> anim_seqs: array<i32>;
 
-#ifdef ATLAS
+#ifdef TEXTURE_INDICES
 struct TilemapTextureDescriptor {
     tile_count: vec2u,
     tile_uv_size: vec2f,
     uv_scale: vec2f,
+    start_index: u32,
 }
 
 @group(4) @binding(1)
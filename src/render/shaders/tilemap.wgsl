@@ -30,7 +30,8 @@ fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
     );
 
     var position_model = (translations[input.v_index % 4u] - tilemap.pivot)
-                          * tilemap.tile_render_size + mesh_origin;
+                          * tilemap.tile_render_size + mesh_origin
+                          + tilemap.anchor * (tilemap.slot_size - tilemap.tile_render_size);
     var position_world = vec4<f32>((tilemap.rot_mat * position_model) + tilemap.translation, 0., 1.);
 
     output.position = view.view_proj * position_world;
@@ -54,7 +55,12 @@ fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
 #endif // ATLAS
     output.uv = uvs[(input.v_index) % 4u];
     output.anim_flag = input.index.z;
+    output.atlas_indices = input.atlas_indices;
+#ifdef TEXTURE_INDICES
+    output.texture_indices = input.texture_indices;
+#endif // TEXTURE_INDICES
 
+#ifdef ANIMATION
     if input.index.z != -1 {
         // Means that this tile is a animated tile
         let start = input.index.z;
@@ -63,18 +69,14 @@ fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
         // See `register` function in TilemapAnimations.
         let fps = f32(anim_seqs[start - 1]);
         var frame = i32(tilemap.time * fps) % length;
-#ifdef ATLAS
+#ifdef TEXTURE_INDICES
         output.texture_indices[0] = anim_seqs[start + frame * 2];
         output.atlas_indices[0] = anim_seqs[start + frame * 2 + 1];
-#else // ATLAS
+#else // TEXTURE_INDICES
         output.atlas_indices[0] = anim_seqs[start + frame];
-#endif // ATLAS
-    } else {
-        output.atlas_indices = input.atlas_indices;
-#ifdef ATLAS
-        output.texture_indices = input.texture_indices;
-#endif // ATLAS
+#endif // TEXTURE_INDICES
     }
+#endif // ANIMATION
 #endif // PURE_COLOR
 
     return output;
@@ -83,7 +85,12 @@ fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
 @fragment
 fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
 #ifdef PURE_COLOR
-    return input.tint;
+    let color = input.tint * vec4f(1., 1., 1., tilemap.alpha);
+#ifdef PREMULTIPLIED_ALPHA
+    return vec4f(color.rgb * color.a, color.a);
+#else // PREMULTIPLIED_ALPHA
+    return color;
+#endif // PREMULTIPLIED_ALPHA
 #else // PURE_COLOR
     var color = vec4<f32>(0., 0., 0., 0.);
 
@@ -93,20 +100,26 @@ fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
             // No texture for this layer.
             continue;
         }
-        let atlas_index = u32(input.atlas_indices[i] & 0x1FFFFFFF);
+        let atlas_index = u32(input.atlas_indices[i] & 0x03FFFFFF);
+        // Layers are sorted by sublayers, so the opacity is looked up by the layer index.
+        let opacity = tilemap.layer_opacities[(input.atlas_indices[i] >> 26) & 3];
         // Shift 29 bits but not 30 because it's a signed integer,
         // and we need to identify if the layer is empty or not according to the sign.
         let flip = input.atlas_indices[i] >> 29;
+        let diagonal = (input.atlas_indices[i] >> 28) & 1;
 
-#ifdef ATLAS
+#ifdef TEXTURE_INDICES
         if input.texture_indices[i] < 0 {
             // No texture for this layer.
             continue;
         }
-#endif // ATLAS
+#endif // TEXTURE_INDICES
 
         var uv = input.uv;
         // Flip the uv if needed.
+        if diagonal != 0 {
+            uv = uv.yx;
+        }
         if (flip & 2) != 0 {
             uv.x = 1. - uv.x;
         }
@@ -126,21 +139,41 @@ fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
                                       bevy_entitiles::common::color_texture_sampler,
                                       atlas_uv, texture_index);
 #else // ATLAS
+#ifdef TEXTURE_INDICES
+        // Tiles are packed one per layer, so find the layer of the first tile of this texture.
+        let layer = texture_descs[input.texture_indices[i]].start_index + atlas_index;
+#else // TEXTURE_INDICES
+        let layer = atlas_index;
+#endif // TEXTURE_INDICES
         // Otherwise, sample the texture at the right layer using the uv directly.
         let tex_color = textureSample(bevy_entitiles::common::color_texture,
                                       bevy_entitiles::common::color_texture_sampler,
-                                      uv, atlas_index);
+                                      uv, layer);
 #endif // ATLAS
-        // Mix the color of each layer.
-        color = mix(color, tex_color, tex_color.a * tilemap.layer_opacities[i]);
+        // Composite each layer over the ones below, with premultiplied colors.
+        let layer_alpha = tex_color.a * opacity;
+        color = vec4f(tex_color.rgb * layer_alpha, layer_alpha) + color * (1. - layer_alpha);
 
+#ifdef ANIMATION
         if input.anim_flag != -1 {
             // Indicates that this tile is a animated tile.
             // We only need to sample the first layer as animated tiles are always single layered.
             break;
         }
+#endif // ANIMATION
     }
-    // Apply the tint of the tile and the tilemap.
-    return color * input.tint * material.color;
+    // Apply the tint of the tile and the tilemap, and fade the whole tilemap.
+#ifdef TINT
+    let tint = input.tint * material.color * vec4f(1., 1., 1., tilemap.alpha);
+#else // TINT
+    let tint = material.color * vec4f(1., 1., 1., tilemap.alpha);
+#endif // TINT
+#ifdef PREMULTIPLIED_ALPHA
+    return color * vec4f(tint.rgb * tint.a, tint.a);
+#else // PREMULTIPLIED_ALPHA
+    // Straight alpha blending and alpha to coverage need the color without the alpha applied.
+    let straight = select(vec3f(0.), color.rgb / color.a, color.a > 0.);
+    return vec4f(straight, color.a) * tint;
+#endif // PREMULTIPLIED_ALPHA
 #endif // PURE_COLOR
 }
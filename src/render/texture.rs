@@ -1,17 +1,21 @@
+use std::fmt::{Display, Formatter};
+
 use bevy::{
     asset::{Assets, Handle},
     ecs::{
         entity::Entity,
         query::With,
-        system::{Commands, Query, Res, ResMut, Resource},
+        system::{Commands, Query, ResMut, Resource},
     },
+    log::{error, warn},
+    math::UVec2,
     prelude::Image,
     render::{
         render_asset::RenderAssets,
         render_resource::{
-            AddressMode, Extent3d, ImageCopyTexture, Origin3d, SamplerDescriptor, TextureAspect,
-            TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-            TextureViewDescriptor, TextureViewDimension,
+            AddressMode, CommandEncoder, Extent3d, FilterMode, ImageCopyTexture, Origin3d,
+            SamplerDescriptor, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages, TextureViewDescriptor, TextureViewDimension,
         },
         renderer::{RenderDevice, RenderQueue},
         texture::{BevyDefault, GpuImage},
@@ -21,28 +25,35 @@ use bevy::{
 
 use crate::tilemap::map::{TilemapTextures, WaitForTextureUsageChange};
 
+use super::material::TilemapTextureMode;
+
+/// The textures of a tilemap packed in a certain mode.
+pub type TilemapTexturesKey = (Handle<TilemapTextures>, TilemapTextureMode);
+
 #[derive(Resource, Default)]
 pub struct TilemapTexturesStorage {
-    textures: HashMap<Handle<TilemapTextures>, GpuImage>,
-    prepare_queue: HashSet<Handle<TilemapTextures>>,
-    queue_queue: HashSet<Handle<TilemapTextures>>,
+    textures: HashMap<TilemapTexturesKey, GpuImage>,
+    prepare_queue: HashSet<TilemapTexturesKey>,
+    queue_queue: HashSet<TilemapTexturesKey>,
+    /// Textures that can't be packed, so they are not retried every frame.
+    failed: HashSet<TilemapTexturesKey>,
 }
 
 impl TilemapTexturesStorage {
-    pub fn insert(&mut self, textures: Handle<TilemapTextures>) {
-        #[cfg(not(feature = "atlas"))]
-        self.prepare_queue.insert(textures);
-        #[cfg(feature = "atlas")]
-        self.queue_queue.insert(textures);
+    pub fn insert(&mut self, textures: Handle<TilemapTextures>, mode: TilemapTextureMode) {
+        self.prepare_queue.insert((textures, mode));
     }
 
     /// Try to get the processed texture array.
-    pub fn get_texture(&self, handle: &Handle<TilemapTextures>) -> Option<&GpuImage> {
-        self.textures.get(handle)
+    pub fn get_texture(
+        &self,
+        handle: &Handle<TilemapTextures>,
+        mode: TilemapTextureMode,
+    ) -> Option<&GpuImage> {
+        self.textures.get(&(handle.clone_weak(), mode))
     }
 
     /// Prepare the texture, creating the texture array and translate images in `queue_texture` function.
-    #[cfg(not(feature = "atlas"))]
     pub fn prepare_textures(
         &mut self,
         render_device: &RenderDevice,
@@ -54,126 +65,35 @@ impl TilemapTexturesStorage {
 
         let to_prepare = self.prepare_queue.drain().collect::<Vec<_>>();
 
-        for textures_handle in &to_prepare {
+        for key in to_prepare {
+            let (textures_handle, mode) = &key;
             let Some(textures) = textures_assets.get(textures_handle) else {
-                self.prepare_queue.insert(textures_handle.clone());
+                self.prepare_queue.insert(key);
                 continue;
             };
 
-            textures.assert_uniform_tile_size();
+            if mode == &TilemapTextureMode::Array {
+                textures.assert_uniform_tile_size();
+            }
             if textures.textures.is_empty() {
                 continue;
             }
 
-            let desc = &textures.textures[0].desc;
-            let tile_count = textures.total_tile_count();
-
-            let texture = render_device.create_texture(&TextureDescriptor {
-                label: Some("tilemap_texture_array"),
-                size: Extent3d {
-                    width: desc.tile_size.x,
-                    height: desc.tile_size.y,
-                    depth_or_array_layers: tile_count,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: TextureFormat::bevy_default(),
-                usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            });
-
-            let sampler = render_device.create_sampler(&SamplerDescriptor {
-                label: Some("tilemap_texture_array_sampler"),
-                address_mode_u: AddressMode::ClampToEdge,
-                address_mode_v: AddressMode::ClampToEdge,
-                address_mode_w: AddressMode::ClampToEdge,
-                mag_filter: textures.filter_mode,
-                min_filter: textures.filter_mode,
-                mipmap_filter: textures.filter_mode,
-                lod_min_clamp: 0.,
-                lod_max_clamp: f32::MAX,
-                compare: None,
-                anisotropy_clamp: 1,
-                border_color: None,
-            });
-
-            let texture_view = texture.create_view(&TextureViewDescriptor {
-                label: Some("tilemap_texture_array_view"),
-                format: Some(TextureFormat::bevy_default()),
-                dimension: Some(TextureViewDimension::D2Array),
-                aspect: TextureAspect::All,
-                base_mip_level: 0,
-                base_array_layer: 0,
-                mip_level_count: None,
-                array_layer_count: Some(tile_count),
-            });
-
-            let gpu_image = GpuImage {
-                texture_format: texture.format(),
-                mip_level_count: texture.mip_level_count(),
-                texture,
-                texture_view,
-                sampler,
-                size: bevy::math::Vec2::new(desc.tile_size.x as f32, desc.tile_size.y as f32),
-            };
-
-            self.textures.insert(textures_handle.clone(), gpu_image);
-            self.queue_queue.insert(textures_handle.clone());
-        }
-    }
-
-    #[cfg(feature = "atlas")]
-    pub fn prepare_textures(
-        &mut self,
-        render_device: &RenderDevice,
-        textures_assets: &RenderAssets<TilemapTextures>,
-    ) {
-        if self.prepare_queue.is_empty() {
-            return;
-        }
-
-        let to_prepare = self.prepare_queue.drain().collect::<Vec<_>>();
-
-        for textures_handle in &to_prepare {
-            let Some(textures) = textures_assets.get(textures_handle) else {
-                self.prepare_queue.insert(textures_handle.clone());
-                continue;
-            };
-
-            if textures.textures.is_empty() {
+            if let Err(err) = validate_texture_array(
+                textures,
+                *mode,
+                render_device.limits().max_texture_array_layers,
+            ) {
+                error!("Failed to prepare {:?}: {}", textures_handle, err);
+                self.failed.insert(key);
                 continue;
             }
 
-            let texture = render_device.create_texture(&TextureDescriptor {
-                label: Some("tilemap_texture_array"),
-                size: Extent3d {
-                    width: textures.max_size.x,
-                    height: textures.max_size.y,
-                    depth_or_array_layers: textures.textures.len() as u32,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: TextureFormat::bevy_default(),
-                usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            });
+            let descriptor = texture_array_descriptor(textures, *mode);
+            let size = descriptor.size;
+            let texture = render_device.create_texture(&descriptor);
 
-            let sampler = render_device.create_sampler(&SamplerDescriptor {
-                label: Some("tilemap_texture_array_sampler"),
-                address_mode_u: AddressMode::ClampToEdge,
-                address_mode_v: AddressMode::ClampToEdge,
-                address_mode_w: AddressMode::ClampToEdge,
-                mag_filter: textures.filter_mode,
-                min_filter: textures.filter_mode,
-                mipmap_filter: textures.filter_mode,
-                lod_min_clamp: 0.,
-                lod_max_clamp: f32::MAX,
-                compare: None,
-                anisotropy_clamp: 1,
-                border_color: None,
-            });
+            let sampler = render_device.create_sampler(&texture_array_sampler_descriptor(textures));
 
             let texture_view = texture.create_view(&TextureViewDescriptor {
                 label: Some("tilemap_texture_array_view"),
@@ -183,7 +103,7 @@ impl TilemapTexturesStorage {
                 base_mip_level: 0,
                 base_array_layer: 0,
                 mip_level_count: None,
-                array_layer_count: Some(textures.textures.len() as u32),
+                array_layer_count: Some(size.depth_or_array_layers),
             });
 
             let gpu_image = GpuImage {
@@ -192,16 +112,15 @@ impl TilemapTexturesStorage {
                 texture,
                 texture_view,
                 sampler,
-                size: textures.max_size.as_vec2(),
+                size: bevy::math::Vec2::new(size.width as f32, size.height as f32),
             };
 
-            self.textures.insert(textures_handle.clone(), gpu_image);
-            self.queue_queue.insert(textures_handle.clone());
+            self.textures.insert(key.clone(), gpu_image);
+            self.queue_queue.insert(key);
         }
     }
 
     /// Translate images to texture array.
-    #[cfg(not(feature = "atlas"))]
     pub fn queue_textures(
         &mut self,
         render_device: &RenderDevice,
@@ -216,143 +135,293 @@ impl TilemapTexturesStorage {
         let to_queue = self.queue_queue.drain().collect::<Vec<_>>();
         let mut command_encoder = render_device.create_command_encoder(&Default::default());
 
-        for textures_handle in &to_queue {
-            let Some(textures) = textures_assets.get(textures_handle) else {
-                self.queue_queue.insert(textures_handle.clone());
+        for key in to_queue {
+            let Some(textures) = textures_assets.get(&key.0) else {
+                self.queue_queue.insert(key);
                 continue;
             };
 
-            for (texture, start_index) in textures.iter_packed() {
-                let image_handle = texture.handle();
-                let desc = texture.desc();
-
-                let Some(raw_gpu_image) = render_images.get(image_handle) else {
-                    self.queue_queue.insert(textures_handle.clone());
-                    continue;
-                };
-
-                if !raw_gpu_image
-                    .texture
-                    .usage()
-                    .contains(TextureUsages::COPY_SRC)
-                {
-                    self.queue_queue.insert(textures_handle.clone());
-                    continue;
-                }
+            let Some(destination) = self.textures.get(&key) else {
+                self.prepare_queue.insert(key);
+                continue;
+            };
 
-                let tile_count = desc.size / desc.tile_size;
-                let array_gpu_image = self.textures.get(textures_handle).unwrap();
-
-                for index_y in 0..tile_count.y {
-                    for index_x in 0..tile_count.x {
-                        command_encoder.copy_texture_to_texture(
-                            ImageCopyTexture {
-                                texture: &raw_gpu_image.texture,
-                                mip_level: 0,
-                                origin: Origin3d {
-                                    x: index_x * desc.tile_size.x,
-                                    y: index_y * desc.tile_size.y,
-                                    z: 0,
-                                },
-                                aspect: TextureAspect::All,
-                            },
-                            ImageCopyTexture {
-                                texture: &array_gpu_image.texture,
-                                mip_level: 0,
-                                origin: Origin3d {
-                                    x: 0,
-                                    y: 0,
-                                    z: index_x + index_y * tile_count.x + start_index,
-                                },
-                                aspect: TextureAspect::All,
-                            },
-                            Extent3d {
-                                width: desc.tile_size.x,
-                                height: desc.tile_size.y,
-                                depth_or_array_layers: 1,
-                            },
-                        );
-                    }
+            let queued = match key.1 {
+                TilemapTextureMode::Array => {
+                    queue_array(&mut command_encoder, render_images, textures, destination)
                 }
+                #[cfg(feature = "atlas")]
+                TilemapTextureMode::Atlas => {
+                    queue_atlas(&mut command_encoder, render_images, textures, destination)
+                }
+            };
+
+            if !queued {
+                self.queue_queue.insert(key);
             }
         }
 
         render_queue.submit(vec![command_encoder.finish()]);
     }
 
-    #[cfg(feature = "atlas")]
-    pub fn queue_textures(
-        &mut self,
-        render_device: &RenderDevice,
-        render_queue: &RenderQueue,
-        render_images: &mut RenderAssets<Image>,
-        textures_assets: &RenderAssets<TilemapTextures>,
-    ) {
-        if self.queue_queue.is_empty() {
-            return;
+    pub fn contains(&self, handle: &Handle<TilemapTextures>, mode: TilemapTextureMode) -> bool {
+        let key = (handle.clone_weak(), mode);
+        self.textures.contains_key(&key)
+            || self.queue_queue.contains(&key)
+            || self.prepare_queue.contains(&key)
+            || self.failed.contains(&key)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TilemapTextureError {
+    /// The texture array needs more layers than the GPU supports.
+    TooManyLayers {
+        /// The index of the first texture that doesn't fit.
+        texture_index: usize,
+        /// The path of that texture, if it's loaded from a file.
+        path: Option<String>,
+        required: u32,
+        limit: u32,
+    },
+}
+
+impl Display for TilemapTextureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TilemapTextureError::TooManyLayers {
+                texture_index,
+                path,
+                required,
+                limit,
+            } => {
+                write!(
+                    f,
+                    "texture {} ({}) needs {} texture array layers, but the limit is {}. \
+                    Split the textures into multiple tilemaps",
+                    texture_index,
+                    path.as_deref().unwrap_or("unnamed"),
+                    required,
+                    limit
+                )?;
+                #[cfg(feature = "atlas")]
+                write!(f, " or use `TilemapTextureMode::Atlas`")?;
+                #[cfg(not(feature = "atlas"))]
+                write!(
+                    f,
+                    " or enable the `atlas` feature to use `TilemapTextureMode::Atlas`"
+                )?;
+                Ok(())
+            }
         }
+    }
+}
 
-        let to_queue = self.queue_queue.drain().collect::<Vec<_>>();
-        let mut command_encoder = render_device.create_command_encoder(&Default::default());
+impl std::error::Error for TilemapTextureError {}
+
+/// Check if `textures` fit in a texture array with at most `max_layers` layers
+/// when packed in `mode`.
+pub fn validate_texture_array(
+    textures: &TilemapTextures,
+    mode: TilemapTextureMode,
+    max_layers: u32,
+) -> Result<(), TilemapTextureError> {
+    let required = texture_array_extent(textures, mode).depth_or_array_layers;
+    if required <= max_layers {
+        return Ok(());
+    }
 
-        for textures_handle in to_queue.into_iter() {
-            let Some(textures) = textures_assets.get(&textures_handle) else {
-                self.queue_queue.insert(textures_handle.clone());
-                continue;
-            };
+    let (texture_index, texture) = match mode {
+        TilemapTextureMode::Array => textures
+            .iter_packed()
+            .enumerate()
+            .find(|(_, (texture, start_index))| start_index + texture.tile_count() > max_layers)
+            .map(|(index, (texture, _))| (index, texture))
+            .unwrap(),
+        #[cfg(feature = "atlas")]
+        TilemapTextureMode::Atlas => (max_layers as usize, &textures.textures[max_layers as usize]),
+    };
+
+    Err(TilemapTextureError::TooManyLayers {
+        texture_index,
+        path: texture.handle().path().map(|p| p.to_string()),
+        required,
+        limit: max_layers,
+    })
+}
 
-            let Some(destination) = self.textures.get(&textures_handle) else {
-                self.prepare_queue.insert(textures_handle);
-                continue;
-            };
+/// The descriptor of the texture array used to store `textures` in `mode`.
+pub fn texture_array_descriptor(
+    textures: &TilemapTextures,
+    mode: TilemapTextureMode,
+) -> TextureDescriptor<'static> {
+    TextureDescriptor {
+        label: Some("tilemap_texture_array"),
+        size: texture_array_extent(textures, mode),
+        mip_level_count: textures.mip_level_count(),
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::bevy_default(),
+        usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    }
+}
 
-            for (index, texture) in textures.textures.iter().enumerate() {
-                let Some(source) = render_images.get_mut(texture.handle()) else {
-                    self.queue_queue.insert(textures_handle.clone());
-                    continue;
-                };
-
-                command_encoder.copy_texture_to_texture(
-                    ImageCopyTexture {
-                        texture: &source.texture,
-                        mip_level: 0,
-                        origin: Origin3d::ZERO,
-                        aspect: TextureAspect::All,
-                    },
-                    ImageCopyTexture {
-                        texture: &destination.texture,
-                        mip_level: 0,
-                        origin: Origin3d {
-                            x: 0,
-                            y: 0,
-                            z: index as u32,
-                        },
-                        aspect: TextureAspect::All,
-                    },
-                    Extent3d {
-                        width: texture.desc.size.x,
-                        height: texture.desc.size.y,
-                        depth_or_array_layers: 1,
-                    },
-                );
+/// The descriptor of the sampler used to sample `textures`.
+///
+/// Magnification follows `TilemapTextures::filter_mode()`, so `FilterMode::Nearest`
+/// keeps the pixels crisp when zooming in.
+pub fn texture_array_sampler_descriptor(textures: &TilemapTextures) -> SamplerDescriptor<'static> {
+    // Trilinear filtering when zooming out.
+    let min_filter = if textures.mipmaps {
+        FilterMode::Linear
+    } else {
+        textures.filter_mode
+    };
+
+    SamplerDescriptor {
+        label: Some("tilemap_texture_array_sampler"),
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: textures.filter_mode,
+        min_filter,
+        mipmap_filter: min_filter,
+        lod_min_clamp: 0.,
+        lod_max_clamp: f32::MAX,
+        compare: None,
+        anisotropy_clamp: 1,
+        border_color: None,
+    }
+}
+
+/// The size of the texture array used to store `textures` in `mode`.
+pub fn texture_array_extent(textures: &TilemapTextures, mode: TilemapTextureMode) -> Extent3d {
+    match mode {
+        TilemapTextureMode::Array => {
+            let desc = &textures.textures[0].desc;
+            Extent3d {
+                width: desc.tile_size.x,
+                height: desc.tile_size.y,
+                depth_or_array_layers: textures.total_tile_count(),
             }
         }
+        #[cfg(feature = "atlas")]
+        TilemapTextureMode::Atlas => Extent3d {
+            width: textures.max_size.x,
+            height: textures.max_size.y,
+            depth_or_array_layers: textures.textures.len() as u32,
+        },
+    }
+}
 
-        render_queue.submit(vec![command_encoder.finish()]);
+/// Copy every tile into its own layer. Returns false if some images are not ready.
+fn queue_array(
+    command_encoder: &mut CommandEncoder,
+    render_images: &RenderAssets<Image>,
+    textures: &TilemapTextures,
+    destination: &GpuImage,
+) -> bool {
+    for (texture, start_index) in textures.iter_packed() {
+        let desc = texture.desc();
+
+        let Some(source) = render_images.get(texture.handle()) else {
+            return false;
+        };
+
+        if !source.texture.usage().contains(TextureUsages::COPY_SRC) {
+            return false;
+        }
+
+        let tile_count = desc.size / desc.tile_size;
+
+        for mip_level in 0..destination.mip_level_count.min(source.mip_level_count) {
+            let tile_size = desc.tile_size >> mip_level;
+            for index_y in 0..tile_count.y {
+                for index_x in 0..tile_count.x {
+                    command_encoder.copy_texture_to_texture(
+                        ImageCopyTexture {
+                            texture: &source.texture,
+                            mip_level,
+                            origin: Origin3d {
+                                x: index_x * tile_size.x,
+                                y: index_y * tile_size.y,
+                                z: 0,
+                            },
+                            aspect: TextureAspect::All,
+                        },
+                        ImageCopyTexture {
+                            texture: &destination.texture,
+                            mip_level,
+                            origin: Origin3d {
+                                x: 0,
+                                y: 0,
+                                z: index_x + index_y * tile_count.x + start_index,
+                            },
+                            aspect: TextureAspect::All,
+                        },
+                        Extent3d {
+                            width: tile_size.x,
+                            height: tile_size.y,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                }
+            }
+        }
     }
 
-    pub fn contains(&self, handle: &Handle<TilemapTextures>) -> bool {
-        self.textures.contains_key(handle)
-            || self.queue_queue.contains(handle)
-            || self.prepare_queue.contains(handle)
+    true
+}
+
+/// Copy every texture into its own layer. Returns false if some images are not ready.
+#[cfg(feature = "atlas")]
+fn queue_atlas(
+    command_encoder: &mut CommandEncoder,
+    render_images: &RenderAssets<Image>,
+    textures: &TilemapTextures,
+    destination: &GpuImage,
+) -> bool {
+    for (index, texture) in textures.textures.iter().enumerate() {
+        let Some(source) = render_images.get(texture.handle()) else {
+            return false;
+        };
+
+        for mip_level in 0..destination.mip_level_count.min(source.mip_level_count) {
+            command_encoder.copy_texture_to_texture(
+                ImageCopyTexture {
+                    texture: &source.texture,
+                    mip_level,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                ImageCopyTexture {
+                    texture: &destination.texture,
+                    mip_level,
+                    origin: Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: index as u32,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                Extent3d {
+                    width: texture.desc.size.x >> mip_level,
+                    height: texture.desc.size.y >> mip_level,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
     }
+
+    true
 }
 
 pub fn set_texture_usage(
     mut commands: Commands,
     tilemaps_query: Query<(Entity, &Handle<TilemapTextures>), With<WaitForTextureUsageChange>>,
     mut image_assets: ResMut<Assets<Image>>,
-    textures_assets: Res<Assets<TilemapTextures>>,
+    mut textures_assets: ResMut<Assets<TilemapTextures>>,
 ) {
     // Bevy doesn't set the `COPY_SRC` usage for images by default, so we need to do it manually.
     tilemaps_query.iter().for_each(|(entity, textures)| {
@@ -362,8 +431,11 @@ pub fn set_texture_usage(
             )
         };
 
+        let mip_level_count = t.mip_level_count();
+        let mut mipmaps_failed = false;
+
         for tex in &t.textures {
-            let Some(image) = image_assets.get(&tex.clone_weak()) else {
+            let Some(image) = image_assets.get(tex.handle()) else {
                 return;
             };
 
@@ -373,12 +445,30 @@ pub fn set_texture_usage(
                 .contains(TextureUsages::COPY_SRC)
             {
                 image_assets
-                    .get_mut(&tex.clone_weak())
+                    .get_mut(tex.handle())
                     .unwrap()
                     .texture_descriptor
                     .usage
                     .set(TextureUsages::COPY_SRC, true);
             }
+
+            let image = image_assets.get(tex.handle()).unwrap();
+            if image.texture_descriptor.mip_level_count < mip_level_count {
+                let image = image_assets.get_mut(tex.handle()).unwrap();
+                if generate_mipmaps(image, mip_level_count) < mip_level_count {
+                    warn!(
+                        "Failed to generate mipmaps for {:?}, only Rgba8UnormSrgb and Rgba8Unorm textures are supported. \
+                        Mipmaps are disabled for these textures.",
+                        tex.handle()
+                    );
+                    mipmaps_failed = true;
+                }
+            }
+        }
+
+        // Otherwise the texture array would have mip levels that are never written.
+        if mipmaps_failed {
+            textures_assets.get_mut(textures).unwrap().mipmaps = false;
         }
 
         commands
@@ -386,3 +476,237 @@ pub fn set_texture_usage(
             .remove::<WaitForTextureUsageChange>();
     });
 }
+
+/// Generate mip levels for the image using a 2x2 box filter, until there are `mip_level_count`
+/// levels or the size can't be halved anymore. Returns the number of mip levels the image has.
+///
+/// Only `Rgba8UnormSrgb` and `Rgba8Unorm` images are supported.
+pub fn generate_mipmaps(image: &mut Image, mip_level_count: u32) -> u32 {
+    let desc = &image.texture_descriptor;
+    let srgb = match desc.format {
+        TextureFormat::Rgba8UnormSrgb => true,
+        TextureFormat::Rgba8Unorm => false,
+        _ => return desc.mip_level_count,
+    };
+    if desc.mip_level_count >= mip_level_count || desc.size.depth_or_array_layers != 1 {
+        return desc.mip_level_count;
+    }
+
+    let decode = |c: u8| {
+        let c = c as f32 / 255.;
+        if srgb {
+            c.powf(2.2)
+        } else {
+            c
+        }
+    };
+    let encode = |c: f32| {
+        let c = if srgb { c.powf(1. / 2.2) } else { c };
+        (c * 255.).round() as u8
+    };
+
+    let mut size = UVec2::new(desc.size.width, desc.size.height);
+    let mut data = image.data[..(size.x * size.y * 4) as usize].to_vec();
+    let mut level_start = 0;
+    let mut levels = 1;
+
+    while levels < mip_level_count && size.x.is_multiple_of(2) && size.y.is_multiple_of(2) {
+        let next = size / 2;
+        let mut level = Vec::with_capacity((next.x * next.y * 4) as usize);
+        for y in 0..next.y {
+            for x in 0..next.x {
+                for channel in 0..4 {
+                    let texel = |dx: u32, dy: u32| {
+                        let c = data[level_start
+                            + (((y * 2 + dy) * size.x + x * 2 + dx) * 4 + channel) as usize];
+                        // Alpha is always linear.
+                        if channel == 3 {
+                            c as f32 / 255.
+                        } else {
+                            decode(c)
+                        }
+                    };
+                    let avg = (texel(0, 0) + texel(1, 0) + texel(0, 1) + texel(1, 1)) / 4.;
+                    level.push(if channel == 3 {
+                        (avg * 255.).round() as u8
+                    } else {
+                        encode(avg)
+                    });
+                }
+            }
+        }
+
+        level_start += (size.x * size.y * 4) as usize;
+        data.extend(level);
+        size = next;
+        levels += 1;
+    }
+
+    image.data = data;
+    image.texture_descriptor.mip_level_count = levels;
+    levels
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        math::UVec2,
+        render::{render_asset::RenderAssetUsages, render_resource::FilterMode},
+    };
+
+    use crate::tilemap::map::{TilemapTexture, TilemapTextureDescriptor};
+
+    use super::*;
+
+    #[test]
+    fn test_mipmaps() {
+        let texture = TilemapTexture::new(
+            Handle::default(),
+            TilemapTextureDescriptor::new(UVec2::new(32, 32), UVec2::new(16, 16)),
+        );
+        let textures = TilemapTextures::single(texture, FilterMode::Nearest);
+        assert_eq!(
+            texture_array_descriptor(&textures, TilemapTextureMode::Array).mip_level_count,
+            1
+        );
+
+        let textures = textures.with_mipmaps(true);
+        let descriptor = texture_array_descriptor(&textures, TilemapTextureMode::Array);
+        assert_eq!(descriptor.mip_level_count, 5);
+        assert_eq!(descriptor.size.width >> 4, 1);
+
+        // Two 2x2 tiles side by side, red and blue.
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let data = [red, red, blue, blue, red, red, blue, blue].concat();
+        let mut image = Image::new(
+            Extent3d {
+                width: 4,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::all(),
+        );
+
+        // The tiles are 2x2, so only one level is generated.
+        assert_eq!(generate_mipmaps(&mut image, 2), 2);
+        assert_eq!(image.texture_descriptor.mip_level_count, 2);
+        // Colors don't bleed into the neighbouring tile.
+        assert_eq!(&image.data[32..], &[red, blue].concat());
+        // The size can't be halved anymore.
+        assert_eq!(generate_mipmaps(&mut image, 5), 2);
+    }
+
+    #[test]
+    fn test_mipmaps_fallback() {
+        use bevy::ecs::{system::RunSystemOnce, world::World};
+
+        use crate::tilemap::map::WaitForTextureUsageChange;
+
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<Assets<TilemapTextures>>();
+
+        // Mipmaps can't be generated for float textures.
+        let image = world.resource_mut::<Assets<Image>>().add(Image::new_fill(
+            Extent3d {
+                width: 32,
+                height: 32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0; 16],
+            TextureFormat::Rgba32Float,
+            RenderAssetUsages::all(),
+        ));
+        let textures = world.resource_mut::<Assets<TilemapTextures>>().add(
+            TilemapTextures::single(
+                TilemapTexture::new(
+                    image,
+                    TilemapTextureDescriptor::new(UVec2::new(32, 32), UVec2::new(16, 16)),
+                ),
+                FilterMode::Nearest,
+            )
+            .with_mipmaps(true),
+        );
+        world.spawn((textures.clone(), WaitForTextureUsageChange));
+        world.run_system_once(set_texture_usage);
+
+        let textures = world
+            .resource::<Assets<TilemapTextures>>()
+            .get(&textures)
+            .unwrap();
+        assert!(!textures.mipmaps());
+        assert_eq!(
+            texture_array_descriptor(textures, TilemapTextureMode::Array).mip_level_count,
+            1
+        );
+    }
+
+    #[test]
+    fn test_too_many_layers() {
+        let small = TilemapTexture::new(
+            Handle::default(),
+            TilemapTextureDescriptor::new(UVec2::new(32, 32), UVec2::new(16, 16)),
+        );
+        // 1024 tiles.
+        let large = TilemapTexture::new(
+            Handle::default(),
+            TilemapTextureDescriptor::new(UVec2::new(512, 512), UVec2::new(16, 16)),
+        );
+        let textures = TilemapTextures::new(vec![small, large], FilterMode::Nearest);
+
+        assert_eq!(
+            validate_texture_array(&textures, TilemapTextureMode::Array, 2048),
+            Ok(())
+        );
+        let err = validate_texture_array(&textures, TilemapTextureMode::Array, 256).unwrap_err();
+        assert_eq!(
+            err,
+            TilemapTextureError::TooManyLayers {
+                texture_index: 1,
+                path: None,
+                required: 1028,
+                limit: 256,
+            }
+        );
+        assert!(err.to_string().contains("texture 1"));
+        assert!(err.to_string().contains("the limit is 256"));
+        assert_eq!(
+            err.to_string().contains("enable the `atlas` feature"),
+            cfg!(not(feature = "atlas"))
+        );
+
+        #[cfg(feature = "atlas")]
+        assert_eq!(
+            validate_texture_array(&textures, TilemapTextureMode::Atlas, 256),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_sampler_filter_mode() {
+        let texture = TilemapTexture::new(
+            Handle::default(),
+            TilemapTextureDescriptor::new(UVec2::new(32, 32), UVec2::new(16, 16)),
+        );
+
+        let nearest = TilemapTextures::single(texture.clone(), FilterMode::Nearest);
+        let descriptor = texture_array_sampler_descriptor(&nearest);
+        assert_eq!(descriptor.mag_filter, FilterMode::Nearest);
+        assert_eq!(descriptor.min_filter, FilterMode::Nearest);
+
+        let linear = nearest.clone().with_filter_mode(FilterMode::Linear);
+        let descriptor = texture_array_sampler_descriptor(&linear);
+        assert_eq!(descriptor.mag_filter, FilterMode::Linear);
+        assert_eq!(descriptor.min_filter, FilterMode::Linear);
+
+        // Mipmaps only change the minification.
+        let descriptor = texture_array_sampler_descriptor(&nearest.with_mipmaps(true));
+        assert_eq!(descriptor.mag_filter, FilterMode::Nearest);
+        assert_eq!(descriptor.min_filter, FilterMode::Linear);
+    }
+}
@@ -0,0 +1,638 @@
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+};
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{entity::Entity, system::Commands},
+    math::{IVec2, Vec2, Vec4},
+    render::color::Color,
+};
+
+use crate::{
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        buffers::TileBuilderBuffer,
+        bundles::StandardPureColorTilemapBundle,
+        map::{
+            TilePivot, TileRenderSize, TilemapAnimations, TilemapAxisFlip, TilemapLayerOpacities,
+            TilemapName, TilemapRotation, TilemapSlotSize, TilemapStorage, TilemapTextures,
+            TilemapTransform, TilemapType,
+        },
+        tile::{TileAnimation, TileBuilder, TileFlip, TileLayer, TileTexture},
+    },
+};
+
+/// The first bytes of every binary tilemap.
+pub const BINARY_TILEMAP_MAGIC: [u8; 4] = *b"ETTM";
+/// The version of the binary format. Bump this when the layout changes,
+/// and add a migration to `BINARY_TILEMAP_MIGRATIONS`.
+pub const BINARY_TILEMAP_VERSION: u8 = 3;
+
+/// Upgrades the data after the version byte to the next version.
+pub type BinaryTilemapMigration = fn(&[u8]) -> Result<Vec<u8>, BinaryTilemapError>;
+
+/// `BINARY_TILEMAP_MIGRATIONS[n]` upgrades the data from version `n + 1` to `n + 2`.
+///
+/// Old saves are upgraded step by step until they reach `BINARY_TILEMAP_VERSION`,
+/// so saves created by older versions of the game can still be loaded.
+pub const BINARY_TILEMAP_MIGRATIONS: &[BinaryTilemapMigration] =
+    &[migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Version 2 stores the name of the tilemap before everything else.
+fn migrate_v1_to_v2(data: &[u8]) -> Result<Vec<u8>, BinaryTilemapError> {
+    let mut w = Writer(Vec::with_capacity(data.len() + 4));
+    w.str("");
+    w.0.extend_from_slice(data);
+    Ok(w.0)
+}
+
+/// Version 3 stores the axis flip after the chunk size.
+fn migrate_v2_to_v3(data: &[u8]) -> Result<Vec<u8>, BinaryTilemapError> {
+    let mut r = Reader(data);
+    r.str()?;
+    if r.u8()? == 2 {
+        r.u32()?;
+    }
+    // The sizes, pivot, opacities, transform and chunk size.
+    r.take(57)?;
+
+    let header_len = data.len() - r.0.len();
+    let mut w = Writer(Vec::with_capacity(data.len() + 1));
+    w.0.extend_from_slice(&data[..header_len]);
+    w.u8(TilemapAxisFlip::NONE.bits() as u8);
+    w.0.extend_from_slice(r.0);
+    Ok(w.0)
+}
+
+/// Everything needed to lay out the tiles exactly like they were saved.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TilemapHeader {
+    pub ty: TilemapType,
+    pub tile_render_size: TileRenderSize,
+    pub slot_size: TilemapSlotSize,
+    pub tile_pivot: TilePivot,
+    pub axis_flip: TilemapAxisFlip,
+    pub transform: TilemapTransform,
+    pub chunk_size: u32,
+}
+
+/// A compact tilemap representation which can be saved into bytes.
+/// This is much smaller and faster than the ron files, so it's suitable for save games.
+#[derive(Debug, Clone)]
+pub struct BinaryTilemap {
+    pub name: TilemapName,
+    pub header: TilemapHeader,
+    pub layer_opacities: TilemapLayerOpacities,
+    pub animations: TilemapAnimations,
+    pub tiles: TileBuilderBuffer,
+}
+
+impl BinaryTilemap {
+    /// Spawn the tilemap with the same layout it was saved with.
+    ///
+    /// The textures are not saved, so pass the ones to use, or `None` for a pure color tilemap.
+    /// Returns `BinaryTilemapError::MissingTextures` if they are not in `textures_assets`.
+    pub fn spawn(
+        self,
+        commands: &mut Commands,
+        material: Handle<StandardTilemapMaterial>,
+        textures: Option<Handle<TilemapTextures>>,
+        textures_assets: &Assets<TilemapTextures>,
+    ) -> Result<Entity, BinaryTilemapError> {
+        if textures
+            .as_ref()
+            .is_some_and(|t| !textures_assets.contains(t))
+        {
+            return Err(BinaryTilemapError::MissingTextures);
+        }
+
+        let entity = commands.spawn_empty().id();
+        let header = self.header;
+        let mut storage = TilemapStorage::new(header.chunk_size, entity);
+        storage.fill_with_buffer(commands, IVec2::ZERO, self.tiles);
+
+        let bundle = StandardPureColorTilemapBundle {
+            name: self.name,
+            tile_render_size: header.tile_render_size,
+            slot_size: header.slot_size,
+            ty: header.ty,
+            tile_pivot: header.tile_pivot,
+            layer_opacities: self.layer_opacities,
+            storage,
+            transform: header.transform,
+            axis_flip: header.axis_flip,
+            material,
+            ..Default::default()
+        };
+        match textures {
+            Some(textures) => commands
+                .entity(entity)
+                .insert(bundle.convert_to_texture_bundle(textures, self.animations)),
+            None => commands.entity(entity).insert(bundle),
+        };
+
+        Ok(entity)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryTilemapError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEof,
+    InvalidData(&'static str),
+    /// The textures to spawn the tilemap with are not loaded.
+    MissingTextures,
+}
+
+impl Display for BinaryTilemapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryTilemapError::InvalidMagic => f.write_str("not a binary tilemap"),
+            BinaryTilemapError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported binary tilemap version {}, expected {}",
+                v, BINARY_TILEMAP_VERSION
+            ),
+            BinaryTilemapError::UnexpectedEof => f.write_str("unexpected end of data"),
+            BinaryTilemapError::InvalidData(what) => write!(f, "invalid {}", what),
+            BinaryTilemapError::MissingTextures => f.write_str("the textures are not loaded"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryTilemapError {}
+
+/// Save the tilemap into bytes.
+///
+/// Tiles are written in index order, so the same tilemap always produces the same bytes.
+pub fn save_tilemap(tilemap: &BinaryTilemap) -> Vec<u8> {
+    let mut w = Writer(Vec::with_capacity(64 + tilemap.tiles.tiles.len() * 32));
+    w.0.extend_from_slice(&BINARY_TILEMAP_MAGIC);
+    w.u8(BINARY_TILEMAP_VERSION);
+    w.str(&tilemap.name.0);
+
+    let header = &tilemap.header;
+    match header.ty {
+        TilemapType::Square => w.u8(0),
+        TilemapType::Isometric => w.u8(1),
+        TilemapType::Hexagonal(legs) => {
+            w.u8(2);
+            w.u32(legs);
+        }
+    }
+    w.vec2(header.tile_render_size.0);
+    w.vec2(header.slot_size.0);
+    w.vec2(header.tile_pivot.0);
+    w.vec4(tilemap.layer_opacities.0);
+    w.vec2(header.transform.translation);
+    w.f32(header.transform.z_index);
+    w.u8(match header.transform.rotation {
+        TilemapRotation::None => 0,
+        TilemapRotation::Cw90 => 1,
+        TilemapRotation::Cw180 => 2,
+        TilemapRotation::Cw270 => 3,
+    });
+    w.u32(header.chunk_size);
+    w.u8(header.axis_flip.bits() as u8);
+
+    w.u32(tilemap.animations.0.len() as u32);
+    tilemap.animations.0.iter().for_each(|v| w.i32(*v));
+
+    let mut tiles = tilemap.tiles.tiles.iter().collect::<Vec<_>>();
+    tiles.sort_by_key(|(index, _)| (index.y, index.x));
+    w.u32(tiles.len() as u32);
+    for (index, tile) in tiles {
+        w.i32(index.x);
+        w.i32(index.y);
+        w.vec4(Vec4::from_array(tile.tint.as_rgba_f32()));
+        match &tile.texture {
+            TileTexture::Static(layers) => {
+                w.u8(0);
+                w.u32(layers.len() as u32);
+                for layer in layers {
+                    #[cfg(feature = "atlas")]
+                    w.i32(layer.texture_index);
+                    #[cfg(not(feature = "atlas"))]
+                    w.i32(0);
+                    w.i32(layer.atlas_index);
+                    w.u8(layer.flip.bits() as u8);
+                    w.i32(layer.sublayer);
+                }
+            }
+            TileTexture::Animated(anim) => {
+                w.u8(1);
+                w.u32(anim.start);
+                w.u32(anim.length);
+                w.u32(anim.fps);
+            }
+        }
+    }
+
+    w.0
+}
+
+/// Load the tilemap from bytes created by `save_tilemap`.
+///
+/// Data saved in older versions will be migrated to the current one.
+pub fn load_tilemap(bytes: &[u8]) -> Result<BinaryTilemap, BinaryTilemapError> {
+    let mut r = Reader(bytes);
+    if r.take(4)? != BINARY_TILEMAP_MAGIC {
+        return Err(BinaryTilemapError::InvalidMagic);
+    }
+    let version = r.u8()?;
+    if version == 0 || version > BINARY_TILEMAP_VERSION {
+        return Err(BinaryTilemapError::UnsupportedVersion(version));
+    }
+
+    let mut data = Cow::Borrowed(r.0);
+    for migration in &BINARY_TILEMAP_MIGRATIONS[version as usize - 1..] {
+        data = Cow::Owned(migration(&data)?);
+    }
+
+    read_tilemap(&mut Reader(&data))
+}
+
+fn read_tilemap(r: &mut Reader) -> Result<BinaryTilemap, BinaryTilemapError> {
+    let name = TilemapName(r.str()?);
+    let ty = match r.u8()? {
+        0 => TilemapType::Square,
+        1 => TilemapType::Isometric,
+        2 => TilemapType::Hexagonal(r.u32()?),
+        _ => return Err(BinaryTilemapError::InvalidData("tilemap type")),
+    };
+    let tile_render_size = TileRenderSize(r.vec2()?);
+    let slot_size = TilemapSlotSize(r.vec2()?);
+    let tile_pivot = TilePivot(r.vec2()?);
+    let layer_opacities = TilemapLayerOpacities(r.vec4()?);
+    let transform = TilemapTransform {
+        translation: r.vec2()?,
+        z_index: r.f32()?,
+        rotation: match r.u8()? {
+            0 => TilemapRotation::None,
+            1 => TilemapRotation::Cw90,
+            2 => TilemapRotation::Cw180,
+            3 => TilemapRotation::Cw270,
+            _ => return Err(BinaryTilemapError::InvalidData("tilemap rotation")),
+        },
+    };
+    let chunk_size = r.u32()?;
+    let axis_flip = TilemapAxisFlip::from_bits(r.u8()? as u32)
+        .ok_or(BinaryTilemapError::InvalidData("axis flip"))?;
+
+    let anim_count = r.u32()?;
+    let mut animations = TilemapAnimations::default();
+    for _ in 0..anim_count {
+        animations.0.push(r.i32()?);
+    }
+
+    let tile_count = r.u32()?;
+    let mut tiles = TileBuilderBuffer::new();
+    for _ in 0..tile_count {
+        let index = IVec2::new(r.i32()?, r.i32()?);
+        let tint = Color::rgba_from_array(r.vec4()?);
+        let texture = match r.u8()? {
+            0 => {
+                let layer_count = r.u32()?;
+                let mut layers = Vec::new();
+                for _ in 0..layer_count {
+                    #[cfg(feature = "atlas")]
+                    let texture_index = r.i32()?;
+                    #[cfg(not(feature = "atlas"))]
+                    r.i32()?;
+                    layers.push(TileLayer {
+                        #[cfg(feature = "atlas")]
+                        texture_index,
+                        atlas_index: r.i32()?,
+                        flip: TileFlip::from_bits(r.u8()? as u32)
+                            .ok_or(BinaryTilemapError::InvalidData("tile flip"))?,
+                        sublayer: r.i32()?,
+                    });
+                }
+                TileTexture::Static(layers)
+            }
+            1 => TileTexture::Animated(TileAnimation {
+                start: r.u32()?,
+                length: r.u32()?,
+                fps: r.u32()?,
+            }),
+            _ => return Err(BinaryTilemapError::InvalidData("tile texture")),
+        };
+        tiles.set(index, TileBuilder { texture, tint });
+    }
+
+    if !r.0.is_empty() {
+        return Err(BinaryTilemapError::InvalidData("trailing bytes"));
+    }
+
+    Ok(BinaryTilemap {
+        name,
+        header: TilemapHeader {
+            ty,
+            tile_render_size,
+            slot_size,
+            tile_pivot,
+            axis_flip,
+            transform,
+            chunk_size,
+        },
+        layer_opacities,
+        animations,
+        tiles,
+    })
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn vec2(&mut self, v: Vec2) {
+        self.f32(v.x);
+        self.f32(v.y);
+    }
+
+    fn vec4(&mut self, v: Vec4) {
+        v.to_array().into_iter().for_each(|c| self.f32(c));
+    }
+
+    fn str(&mut self, v: &str) {
+        self.u32(v.len() as u32);
+        self.0.extend_from_slice(v.as_bytes());
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BinaryTilemapError> {
+        if self.0.len() < n {
+            return Err(BinaryTilemapError::UnexpectedEof);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, BinaryTilemapError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, BinaryTilemapError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, BinaryTilemapError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, BinaryTilemapError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn vec2(&mut self) -> Result<Vec2, BinaryTilemapError> {
+        Ok(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    fn str(&mut self) -> Result<String, BinaryTilemapError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| BinaryTilemapError::InvalidData("string"))
+    }
+
+    fn vec4(&mut self) -> Result<Vec4, BinaryTilemapError> {
+        Ok(Vec4::new(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::{system::CommandQueue, world::World};
+
+    use super::*;
+
+    fn random_tilemap(seed: &mut u64) -> BinaryTilemap {
+        let mut next = || {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            *seed
+        };
+
+        let mut tiles = TileBuilderBuffer::new();
+        for _ in 0..next() % 64 {
+            let index = IVec2::new((next() % 32) as i32 - 16, (next() % 32) as i32 - 16);
+            let tint = Color::rgba(
+                (next() % 256) as f32 / 255.,
+                (next() % 256) as f32 / 255.,
+                (next() % 256) as f32 / 255.,
+                (next() % 256) as f32 / 255.,
+            );
+            let texture = if next() % 4 == 0 {
+                TileTexture::Animated(TileAnimation {
+                    start: (next() % 16) as u32,
+                    length: (next() % 8) as u32,
+                    fps: (next() % 60) as u32,
+                })
+            } else {
+                TileTexture::Static(
+                    (0..next() % 4)
+                        .map(|_| TileLayer {
+                            #[cfg(feature = "atlas")]
+                            texture_index: (next() % 4) as i32,
+                            atlas_index: (next() % 128) as i32,
+                            flip: TileFlip::from_bits((next() % 4) as u32).unwrap(),
+                            sublayer: (next() % 3) as i32 - 1,
+                        })
+                        .collect(),
+                )
+            };
+            tiles.set(index, TileBuilder { texture, tint });
+        }
+
+        BinaryTilemap {
+            name: TilemapName(format!("tilemap_{}", next() % 100)),
+            header: TilemapHeader {
+                ty: match next() % 3 {
+                    0 => TilemapType::Square,
+                    1 => TilemapType::Isometric,
+                    _ => TilemapType::Hexagonal((next() % 32) as u32),
+                },
+                tile_render_size: TileRenderSize(Vec2::splat((next() % 64) as f32)),
+                slot_size: TilemapSlotSize(Vec2::splat((next() % 64) as f32)),
+                tile_pivot: TilePivot(Vec2::new(0.5, (next() % 2) as f32)),
+                axis_flip: TilemapAxisFlip::from_bits((next() % 4) as u32).unwrap(),
+                transform: TilemapTransform {
+                    translation: Vec2::new((next() % 100) as f32, -((next() % 100) as f32)),
+                    z_index: (next() % 10) as f32,
+                    rotation: TilemapRotation::Cw90,
+                },
+                chunk_size: (next() % 32) as u32 + 1,
+            },
+            layer_opacities: TilemapLayerOpacities(Vec4::splat((next() % 10) as f32 / 10.)),
+            animations: TilemapAnimations((0..next() % 16).map(|v| v as i32).collect()),
+            tiles,
+        }
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut seed = 0x2545f4914f6cdd1d;
+        for _ in 0..32 {
+            let tilemap = random_tilemap(&mut seed);
+            let bytes = save_tilemap(&tilemap);
+            let loaded = load_tilemap(&bytes).unwrap();
+
+            assert_eq!(save_tilemap(&loaded), bytes);
+            assert_eq!(loaded.tiles.tiles.len(), tilemap.tiles.tiles.len());
+            for (index, tile) in tilemap.tiles.tiles.iter() {
+                let other = loaded.tiles.get(*index).unwrap();
+                assert_eq!(other.tint.as_rgba_f32(), tile.tint.as_rgba_f32());
+                assert_eq!(
+                    format!("{:?}", other.texture),
+                    format!("{:?}", tile.texture)
+                );
+            }
+        }
+
+        let bytes = save_tilemap(&random_tilemap(&mut seed));
+        assert_eq!(
+            load_tilemap(&bytes[..bytes.len() - 1]).unwrap_err(),
+            BinaryTilemapError::UnexpectedEof
+        );
+        let mut bad = bytes.clone();
+        bad[4] = BINARY_TILEMAP_VERSION + 1;
+        assert_eq!(
+            load_tilemap(&bad).unwrap_err(),
+            BinaryTilemapError::UnsupportedVersion(BINARY_TILEMAP_VERSION + 1)
+        );
+        assert_eq!(
+            load_tilemap(b"nope").unwrap_err(),
+            BinaryTilemapError::InvalidMagic
+        );
+    }
+
+    #[test]
+    fn test_binary_migration() {
+        assert_eq!(
+            BINARY_TILEMAP_MIGRATIONS.len(),
+            BINARY_TILEMAP_VERSION as usize - 1
+        );
+
+        // A square tilemap with a single tile saved in version 1.
+        let mut w = Writer(Vec::new());
+        w.0.extend_from_slice(&BINARY_TILEMAP_MAGIC);
+        w.u8(1);
+        w.u8(0);
+        w.vec2(Vec2::splat(16.));
+        w.vec2(Vec2::splat(16.));
+        w.vec2(Vec2::ZERO);
+        w.vec4(Vec4::ONE);
+        w.vec2(Vec2::new(10., 20.));
+        w.f32(1.);
+        w.u8(0);
+        w.u32(32);
+        w.u32(0);
+        w.u32(1);
+        w.i32(3);
+        w.i32(-2);
+        w.vec4(Vec4::ONE);
+        w.u8(0);
+        w.u32(1);
+        w.i32(0);
+        w.i32(7);
+        w.u8(TileFlip::HORIZONTAL.bits() as u8);
+        w.i32(0);
+
+        let tilemap = load_tilemap(&w.0).unwrap();
+        assert_eq!(tilemap.name.0, "");
+        assert_eq!(tilemap.header.ty, TilemapType::Square);
+        assert_eq!(tilemap.header.slot_size.0, Vec2::splat(16.));
+        assert_eq!(tilemap.header.transform.translation, Vec2::new(10., 20.));
+        assert_eq!(tilemap.header.chunk_size, 32);
+        assert_eq!(
+            tilemap.header.axis_flip.bits(),
+            TilemapAxisFlip::NONE.bits()
+        );
+        let TileTexture::Static(layers) = &tilemap.tiles.get(IVec2::new(3, -2)).unwrap().texture
+        else {
+            panic!("Expected a static tile!");
+        };
+        assert_eq!(layers[0].atlas_index, 7);
+        assert_eq!(layers[0].flip.bits(), TileFlip::HORIZONTAL.bits());
+
+        let bytes = save_tilemap(&tilemap);
+        assert_eq!(bytes[4], BINARY_TILEMAP_VERSION);
+        assert_eq!(save_tilemap(&load_tilemap(&bytes).unwrap()), bytes);
+    }
+
+    #[test]
+    fn test_binary_spawn() {
+        let mut seed = 0x9e3779b97f4a7c15;
+        let tilemap = random_tilemap(&mut seed);
+        let tile_count = tilemap.tiles.tiles.len();
+        let tilemap = load_tilemap(&save_tilemap(&tilemap)).unwrap();
+        let header = tilemap.header;
+
+        let mut world = World::new();
+        let textures_assets = Assets::<TilemapTextures>::default();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        // The textures are not loaded.
+        assert_eq!(
+            tilemap
+                .clone()
+                .spawn(
+                    &mut commands,
+                    Handle::default(),
+                    Some(Handle::default()),
+                    &textures_assets,
+                )
+                .unwrap_err(),
+            BinaryTilemapError::MissingTextures
+        );
+
+        let entity = tilemap
+            .spawn(&mut commands, Handle::default(), None, &textures_assets)
+            .unwrap();
+        queue.apply(&mut world);
+
+        let transform = world.get::<TilemapTransform>(entity).unwrap();
+        assert_eq!(transform.translation, header.transform.translation);
+        assert_eq!(transform.z_index, header.transform.z_index);
+        assert_eq!(transform.rotation, header.transform.rotation);
+        let storage = world.get::<TilemapStorage>(entity).unwrap();
+        assert_eq!(storage.storage.chunk_size, header.chunk_size);
+        assert_eq!(storage.storage.iter_some().count(), tile_count);
+        assert_eq!(
+            world.get::<TilemapAxisFlip>(entity).unwrap().bits(),
+            header.axis_flip.bits()
+        );
+        assert_eq!(
+            world.get::<TilemapSlotSize>(entity).unwrap().0,
+            header.slot_size.0
+        );
+    }
+}
@@ -5,19 +5,38 @@ use bevy::{
         query::With,
         system::{ParallelCommands, Query, Res},
     },
+    math::IVec2,
+    utils::HashMap,
 };
+use serde::{Deserialize, Serialize};
 
 use self::{
     load::{ChunkLoadCache, ChunkLoadConfig, ScheduledLoadChunks},
-    save::{ChunkSaveCache, ChunkSaveConfig, ScheduledSaveChunks},
+    save::{ChunkManifests, ChunkSaveCache, ChunkSaveConfig, ScheduledSaveChunks},
+    streaming::{ChunkGenerationRequest, ChunkStreamingConfig},
 };
 
 pub mod load;
 pub mod save;
+pub mod streaming;
 
 pub const TILE_CHUNKS_FOLDER: &str = "tile_chunks";
 pub const PATH_TILE_CHUNKS_FOLDER: &str = "path_tile_chunks";
 pub const PHYSICS_TILE_CHUNKS_FOLDER: &str = "physics_tile_chunks";
+pub const CHUNK_MANIFEST: &str = "manifest.ron";
+
+/// Records every chunk saved for a tilemap and how many times it has been saved.
+///
+/// This is written to `(path)/(tilemap name)/manifest.ron` alongside the chunk folders.
+/// To load a large world lazily, read the manifest using `load_object` when the tilemap is spawned,
+/// and only schedule the chunks you need (for example the ones near the camera)
+/// using `ChunkLoadCache::schedule`. The versions can be compared with the ones you've loaded
+/// to find out which chunks are outdated.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ChunkManifest {
+    pub chunk_size: u32,
+    pub chunks: HashMap<IVec2, u32>,
+}
 
 pub struct EntiTilesChunkSerializingPlugin;
 
@@ -26,6 +45,7 @@ impl Plugin for EntiTilesChunkSerializingPlugin {
         app.add_systems(
             Update,
             (
+                streaming::chunk_streaming,
                 save::save_color_layer,
                 #[cfg(feature = "algorithm")]
                 save::save_path_layer,
@@ -42,12 +62,17 @@ impl Plugin for EntiTilesChunkSerializingPlugin {
         );
 
         app.register_type::<ChunkSaveConfig>()
-            .register_type::<ChunkLoadConfig>();
+            .register_type::<ChunkLoadConfig>()
+            .register_type::<ChunkStreamingConfig>();
+
+        app.add_event::<ChunkGenerationRequest>();
 
         app.init_resource::<ChunkLoadCache>()
             .init_resource::<ChunkLoadConfig>()
             .init_resource::<ChunkSaveCache>()
-            .init_resource::<ChunkSaveConfig>();
+            .init_resource::<ChunkManifests>()
+            .init_resource::<ChunkSaveConfig>()
+            .init_resource::<ChunkStreamingConfig>();
     }
 }
 
@@ -16,7 +16,7 @@ use bevy::{
 use crate::{
     math::{aabb::IAabb2d, extension::ChunkIndex},
     render::chunk::{ChunkUnload, UnloadRenderChunk},
-    serializing::{map::TilemapLayer, save_object},
+    serializing::{load_object, map::TilemapLayer, save_object},
     tilemap::{
         buffers::TileBuilderBuffer,
         map::{TilemapName, TilemapStorage},
@@ -24,7 +24,7 @@ use crate::{
     },
 };
 
-use super::TILE_CHUNKS_FOLDER;
+use super::{ChunkManifest, CHUNK_MANIFEST, TILE_CHUNKS_FOLDER};
 
 #[cfg(feature = "algorithm")]
 use crate::{
@@ -91,6 +91,30 @@ impl ChunkSaveCache {
         commands.entity(tilemap).insert(ScheduledSaveChunks);
     }
 
+    /// Schedule all the chunks that are modified since the last call of this method,
+    /// so only the changed part of the tilemap will be written.
+    ///
+    /// Setting, updating or removing tiles through `TilemapStorage` marks their chunks as dirty.
+    pub fn save_dirty_chunks(
+        &mut self,
+        commands: &mut Commands,
+        tilemap: Entity,
+        storage: &mut TilemapStorage,
+        layers: TilemapLayer,
+    ) {
+        let dirty = storage.take_dirty_chunks();
+        if dirty.is_empty() {
+            return;
+        }
+
+        self.schedule_many(
+            commands,
+            tilemap,
+            layers,
+            dirty.into_iter().map(|chunk_index| (chunk_index, false)),
+        );
+    }
+
     #[inline]
     pub fn pop_chunk(&mut self, tilemap: Entity, layer: TilemapLayer) -> Option<(IVec2, bool)> {
         self.0.get_mut(&tilemap).and_then(|layers| {
@@ -102,6 +126,17 @@ impl ChunkSaveCache {
     }
 }
 
+/// The manifests of the tilemaps that have saved chunks.
+#[derive(Resource, Default)]
+pub struct ChunkManifests(pub(crate) EntityHashMap<ChunkManifest>);
+
+impl ChunkManifests {
+    #[inline]
+    pub fn get(&self, tilemap: Entity) -> Option<&ChunkManifest> {
+        self.0.get(&tilemap)
+    }
+}
+
 pub fn render_chunk_remover(mut tilemaps_query: Query<(&mut TilemapStorage, &UnloadRenderChunk)>) {
     tilemaps_query
         .par_iter_mut()
@@ -122,11 +157,19 @@ pub fn save_color_layer(
     mut chunk_unload: EventWriter<ChunkUnload>,
     config: Res<ChunkSaveConfig>,
     mut cache: ResMut<ChunkSaveCache>,
+    mut manifests: ResMut<ChunkManifests>,
 ) {
     tilemaps_query
         .iter_mut()
         .for_each(|(entity, name, mut storage)| {
             let map_path = Path::new(&config.path).join(&name.0);
+            let manifest = manifests.0.entry(entity).or_insert_with(|| {
+                load_object(&map_path, CHUNK_MANIFEST).unwrap_or_else(|_| ChunkManifest {
+                    chunk_size: storage.storage.chunk_size,
+                    ..Default::default()
+                })
+            });
+            let mut manifest_changed = false;
 
             (0..config.chunks_per_frame).into_iter().for_each(|_| {
                 let Some((chunk_index, remove_after_save)) =
@@ -176,6 +219,9 @@ pub fn save_color_layer(
                         },
                     },
                 );
+                storage.dirty_chunks.remove(&chunk_index);
+                *manifest.chunks.entry(chunk_index).or_default() += 1;
+                manifest_changed = true;
 
                 if remove_after_save {
                     storage.remove_chunk(&mut commands, chunk_index);
@@ -185,6 +231,10 @@ pub fn save_color_layer(
                     });
                 }
             });
+
+            if manifest_changed {
+                save_object(&map_path, CHUNK_MANIFEST, manifest);
+            }
         });
 }
 
@@ -256,6 +306,7 @@ pub fn save_path_layer(
 
             if remove_after_save {
                 path_tilemap.storage.remove_chunk(chunk_index);
+                path_tilemap.mark_changed();
             }
         });
     });
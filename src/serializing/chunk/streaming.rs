@@ -0,0 +1,332 @@
+use std::path::Path;
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        system::{Commands, Query, Res, ResMut, Resource, SystemParam},
+    },
+    math::IVec2,
+    reflect::Reflect,
+    render::camera::Camera,
+    transform::components::GlobalTransform,
+    utils::HashSet,
+};
+
+use crate::{
+    math::extension::ChunkIndex,
+    serializing::map::TilemapLayer,
+    tilemap::{
+        coordinates::world_to_index,
+        map::{
+            TilePivot, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType,
+        },
+    },
+};
+
+use super::{
+    load::{ChunkLoadCache, ChunkLoadConfig},
+    save::ChunkSaveCache,
+    PATH_TILE_CHUNKS_FOLDER, PHYSICS_TILE_CHUNKS_FOLDER, TILE_CHUNKS_FOLDER,
+};
+
+/// The radiuses (in chunks) around the main camera used to stream chunks.
+///
+/// Chunks within `load_radius` are loaded, and chunks outside `unload_radius` are
+/// saved and then removed. `unload_radius` is larger than `load_radius` so moving the camera
+/// back and forth across a chunk boundary won't keep loading and unloading the same chunks.
+#[derive(Resource, Debug, Clone, Copy, Reflect)]
+pub struct ChunkStreamingConfig {
+    pub load_radius: u32,
+    pub unload_radius: u32,
+}
+
+impl ChunkStreamingConfig {
+    pub fn new(load_radius: u32, unload_radius: u32) -> Self {
+        assert!(
+            unload_radius > load_radius,
+            "unload_radius must be > load_radius!"
+        );
+
+        Self {
+            load_radius,
+            unload_radius,
+        }
+    }
+}
+
+impl Default for ChunkStreamingConfig {
+    fn default() -> Self {
+        Self {
+            load_radius: 2,
+            unload_radius: 3,
+        }
+    }
+}
+
+/// Add this to a tilemap to load and unload its chunks around the main camera.
+///
+/// Chunks are loaded using `ChunkLoadCache` and saved using `ChunkSaveCache`,
+/// so `ChunkLoadConfig` and `ChunkSaveConfig` must point to the same path.
+/// Chunks that have never been saved are not loaded, a `ChunkGenerationRequest`
+/// is sent instead so you can generate them.
+#[derive(Component, Debug, Clone)]
+pub struct ChunkStreaming {
+    pub(crate) layers: TilemapLayer,
+    pub(crate) center: Option<IVec2>,
+    /// The chunks loaded or generated by streaming. Only these are unloaded,
+    /// so the chunks spawned in other ways are left alone.
+    pub(crate) resident: HashSet<IVec2>,
+}
+
+impl ChunkStreaming {
+    pub fn new(layers: TilemapLayer) -> Self {
+        Self {
+            layers,
+            center: None,
+            resident: HashSet::new(),
+        }
+    }
+
+    /// The chunk the main camera is in.
+    #[inline]
+    pub fn center(&self) -> Option<IVec2> {
+        self.center
+    }
+
+    /// Returns true if the chunk is loaded, or scheduled to be loaded/generated.
+    #[inline]
+    pub fn is_resident(&self, chunk_index: IVec2) -> bool {
+        self.resident.contains(&chunk_index)
+    }
+}
+
+impl Default for ChunkStreaming {
+    fn default() -> Self {
+        Self::new(TilemapLayer::COLOR)
+    }
+}
+
+/// Sent when a chunk enters the load radius but there's nothing saved for it.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ChunkGenerationRequest {
+    pub tilemap: Entity,
+    pub index: IVec2,
+}
+
+fn is_chunk_saved(root: &Path, layers: TilemapLayer, chunk_index: IVec2) -> bool {
+    let file = format!("{}.ron", chunk_index.chunk_file_name());
+    layers.iter().any(|layer| {
+        let folder = if layer == TilemapLayer::COLOR {
+            TILE_CHUNKS_FOLDER
+        } else if layer == TilemapLayer::PATH {
+            PATH_TILE_CHUNKS_FOLDER
+        } else {
+            PHYSICS_TILE_CHUNKS_FOLDER
+        };
+        root.join(folder).join(&file).exists()
+    })
+}
+
+/// The resources `chunk_streaming` loads and saves the chunks with.
+#[derive(SystemParam)]
+pub struct ChunkStreamingCaches<'w> {
+    config: Res<'w, ChunkStreamingConfig>,
+    load_config: Res<'w, ChunkLoadConfig>,
+    load_cache: ResMut<'w, ChunkLoadCache>,
+    save_cache: ResMut<'w, ChunkSaveCache>,
+    generation_request: EventWriter<'w, ChunkGenerationRequest>,
+}
+
+type StreamingTilemap = (
+    Entity,
+    &'static TilemapName,
+    &'static TilemapType,
+    &'static TilemapTransform,
+    &'static TilePivot,
+    &'static TilemapSlotSize,
+    &'static TilemapStorage,
+    &'static mut ChunkStreaming,
+);
+
+pub fn chunk_streaming(
+    mut commands: Commands,
+    cameras_query: Query<(&Camera, &GlobalTransform)>,
+    mut tilemaps_query: Query<StreamingTilemap>,
+    mut caches: ChunkStreamingCaches,
+) {
+    let Some((_, camera_transform)) = cameras_query
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .min_by_key(|(camera, _)| camera.order)
+    else {
+        return;
+    };
+    let camera_pos = camera_transform.translation().truncate();
+
+    tilemaps_query.iter_mut().for_each(
+        |(entity, name, ty, transform, pivot, slot_size, storage, mut streaming)| {
+            let chunk_size = storage.storage.chunk_size as i32;
+            let center = world_to_index(camera_pos, *ty, transform, pivot.0, slot_size.0)
+                .div_euclid(IVec2::splat(chunk_size));
+            if streaming.center == Some(center) {
+                return;
+            }
+            streaming.center = Some(center);
+
+            let root = Path::new(&caches.load_config.path).join(&name.0);
+            let load_radius = caches.config.load_radius as i32;
+            let mut to_load = Vec::new();
+            for y in -load_radius..=load_radius {
+                for x in -load_radius..=load_radius {
+                    let chunk_index = center + IVec2 { x, y };
+                    if streaming.resident.contains(&chunk_index)
+                        || storage.storage.chunks.contains_key(&chunk_index)
+                    {
+                        continue;
+                    }
+                    streaming.resident.insert(chunk_index);
+
+                    if is_chunk_saved(&root, streaming.layers, chunk_index) {
+                        to_load.push(chunk_index);
+                    } else {
+                        caches.generation_request.send(ChunkGenerationRequest {
+                            tilemap: entity,
+                            index: chunk_index,
+                        });
+                    }
+                }
+            }
+
+            let unload_radius = caches.config.unload_radius as i32;
+            let to_unload = streaming
+                .resident
+                .iter()
+                .filter(|chunk_index| (**chunk_index - center).abs().max_element() > unload_radius)
+                .cloned()
+                .collect::<HashSet<_>>();
+            streaming
+                .resident
+                .retain(|chunk_index| !to_unload.contains(chunk_index));
+
+            if !to_load.is_empty() {
+                caches.load_cache.schedule_many(
+                    &mut commands,
+                    entity,
+                    streaming.layers,
+                    to_load.into_iter(),
+                );
+            }
+
+            if !to_unload.is_empty() {
+                caches.save_cache.schedule_many(
+                    &mut commands,
+                    entity,
+                    streaming.layers,
+                    to_unload.iter().map(|chunk_index| (*chunk_index, true)),
+                );
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{event::Events, system::RunSystemOnce, world::World},
+        math::{Vec2, Vec3},
+        transform::components::Transform,
+    };
+
+    use crate::{math::TileArea, tilemap::tile::TileBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_chunk_streaming() {
+        let mut world = World::new();
+        world.insert_resource(ChunkStreamingConfig::new(1, 2));
+        world.insert_resource(ChunkLoadConfig {
+            path: "generated/test_chunk_streaming".to_string(),
+            chunks_per_frame: 1,
+        });
+        world.init_resource::<ChunkLoadCache>();
+        world.init_resource::<ChunkSaveCache>();
+        world.init_resource::<Events<ChunkGenerationRequest>>();
+
+        let camera = world
+            .spawn((Camera::default(), GlobalTransform::default()))
+            .id();
+        let tilemap = world.spawn_empty().id();
+        let mut storage = TilemapStorage::new(4, tilemap);
+        {
+            let mut queue = bevy::ecs::system::CommandQueue::default();
+            let mut commands = Commands::new(&mut queue, &world);
+            // Some chunks that are far away from the camera.
+            storage.fill_rect(
+                &mut commands,
+                TileArea::new(IVec2::splat(16), bevy::math::UVec2::splat(4)),
+                TileBuilder::new(),
+            );
+            queue.apply(&mut world);
+        }
+        world.entity_mut(tilemap).insert((
+            TilemapName("test_chunk_streaming".to_string()),
+            TilemapType::Square,
+            TilemapTransform::default(),
+            TilePivot::default(),
+            TilemapSlotSize(Vec2::splat(16.)),
+            storage,
+            ChunkStreaming::default(),
+        ));
+
+        world.run_system_once(chunk_streaming);
+
+        let streaming = world.get::<ChunkStreaming>(tilemap).unwrap();
+        assert_eq!(streaming.center(), Some(IVec2::ZERO));
+        assert_eq!(streaming.resident.len(), 9);
+        // Nothing is saved so every chunk needs to be generated.
+        assert_eq!(world.resource::<Events<ChunkGenerationRequest>>().len(), 9);
+        // The chunks that are not loaded by streaming are left alone.
+        assert!(!world.resource::<ChunkSaveCache>().0.contains_key(&tilemap));
+
+        // Moving inside the same chunk does nothing.
+        world
+            .entity_mut(camera)
+            .insert(GlobalTransform::from(Transform::from_translation(
+                Vec3::new(60., 60., 0.),
+            )));
+        world.run_system_once(chunk_streaming);
+        assert_eq!(world.resource::<Events<ChunkGenerationRequest>>().len(), 9);
+
+        // Move one chunk to the right, chunks at x = -1 are still within the unload radius.
+        world
+            .entity_mut(camera)
+            .insert(GlobalTransform::from(Transform::from_translation(
+                Vec3::new(70., 0., 0.),
+            )));
+        world.run_system_once(chunk_streaming);
+        let streaming = world.get::<ChunkStreaming>(tilemap).unwrap();
+        assert_eq!(streaming.center(), Some(IVec2::X));
+        assert_eq!(streaming.resident.len(), 12);
+        assert!(streaming.is_resident(IVec2::NEG_X));
+
+        // Move further, and the chunks at x = -1 will be unloaded.
+        world
+            .entity_mut(camera)
+            .insert(GlobalTransform::from(Transform::from_translation(
+                Vec3::new(140., 0., 0.),
+            )));
+        world.run_system_once(chunk_streaming);
+        let streaming = world.get::<ChunkStreaming>(tilemap).unwrap();
+        assert_eq!(streaming.center(), Some(IVec2::new(2, 0)));
+        assert!(!streaming.is_resident(IVec2::NEG_X));
+        assert!(streaming.is_resident(IVec2::ZERO));
+        let save_cache = &world.resource::<ChunkSaveCache>().0[&tilemap][&TilemapLayer::COLOR];
+        for y in -1..=1 {
+            assert!(save_cache.contains(&(IVec2::new(-1, y), true)));
+        }
+        assert!(!save_cache.contains(&(IVec2::splat(4), true)));
+    }
+}
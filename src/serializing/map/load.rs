@@ -160,6 +160,7 @@ pub fn load<M: TilemapMaterial + DeserializeOwned>(
                 entity,
                 PathTilemap {
                     storage: path_storage,
+                    version: 0,
                 },
             );
         }
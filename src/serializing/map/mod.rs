@@ -131,7 +131,7 @@ impl<M: TilemapMaterial> SerializedTilemap<M> {
             material,
             transform: self.tilemap_transform,
             textures,
-            animations: self.animations.clone().unwrap(),
+            animations: self.animations.clone().unwrap_or_default(),
             ..Default::default()
         }
     }
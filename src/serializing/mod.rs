@@ -6,6 +6,7 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::render::material::TilemapMaterial;
 
+pub mod binary;
 pub mod chunk;
 pub mod map;
 pub mod pattern;
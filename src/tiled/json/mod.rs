@@ -0,0 +1,526 @@
+use std::path::Path;
+
+use bevy::{
+    asset::{AssetServer, Assets, Handle},
+    ecs::{entity::Entity, system::Commands},
+    log::warn,
+    math::{IVec2, UVec2, Vec2},
+    reflect::Reflect,
+    render::{color::Color, render_resource::FilterMode, view::Visibility},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        buffers::TileBuilderBuffer,
+        bundles::StandardTilemapBundle,
+        map::{
+            TileRenderSize, TilemapAxisFlip, TilemapName, TilemapSlotSize, TilemapStorage,
+            TilemapTexture, TilemapTextureDescriptor, TilemapTextures, TilemapTransform,
+            TilemapType,
+        },
+        tile::{TileBuilder, TileFlip, TileLayer},
+    },
+    utils::asset::AssetPath,
+    DEFAULT_CHUNK_SIZE,
+};
+
+use super::xml::{
+    default::{default_onef, default_true},
+    MapOrientation,
+};
+
+/// Masks out the flip flags and the hexagonal rotation flag, which is not supported.
+const GID_MASK: u32 = 0x0FFF_FFFF;
+
+/// Split a global tile id into the id and the flip of the tile.
+pub fn decode_gid(gid: u32) -> (u32, TileFlip) {
+    (gid & GID_MASK, TileFlip::from_tiled(gid))
+}
+
+/// A Tiled map saved as json (`.tmj`).
+///
+/// Only finite orthogonal maps and their tile layers are supported for now.
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct TiledJsonTilemap {
+    pub orientation: MapOrientation,
+
+    /// The map width in tiles.
+    pub width: u32,
+
+    /// The map height in tiles.
+    pub height: u32,
+
+    #[serde(rename = "tilewidth")]
+    pub tile_width: u32,
+
+    #[serde(rename = "tileheight")]
+    pub tile_height: u32,
+
+    #[serde(default)]
+    pub infinite: bool,
+
+    #[serde(default)]
+    pub layers: Vec<TiledJsonLayer>,
+
+    #[serde(default)]
+    pub tilesets: Vec<TiledJsonTilesetDef>,
+}
+
+impl TiledJsonTilemap {
+    /// Parse the content of a `.tmj` file.
+    ///
+    /// External tilesets are not loaded, use `resolve_tilesets()` or `load()` instead.
+    #[inline]
+    pub fn parse_str(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Read a `.tmj` file and the external tilesets it uses.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let mut map = Self::parse_str(
+            &std::fs::read_to_string(path)
+                .unwrap_or_else(|err| panic!("Failed to read {:?}\n{:?}", path, err)),
+        )
+        .unwrap_or_else(|err| panic!("Failed to parse {:?}\n{:?}", path, err));
+        map.resolve_tilesets(path.parent().unwrap());
+        map
+    }
+
+    /// Replace the external tilesets with their content.
+    ///
+    /// The image paths of these tilesets are rewritten to be relative to `map_dir`.
+    pub fn resolve_tilesets(&mut self, map_dir: &Path) {
+        self.tilesets.iter_mut().for_each(|def| {
+            let TiledJsonTilesetSource::External { source } = &def.tileset else {
+                return;
+            };
+
+            let tileset_path = map_dir.join(source);
+            let mut tileset = TiledJsonTileset::parse_str(
+                &std::fs::read_to_string(&tileset_path)
+                    .unwrap_or_else(|err| panic!("Failed to read {:?}\n{:?}", tileset_path, err)),
+            )
+            .unwrap_or_else(|err| panic!("Failed to parse {:?}\n{:?}", tileset_path, err));
+
+            tileset.image = Path::new(source)
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(&tileset.image)
+                .to_string_lossy()
+                .to_string();
+            def.tileset = TiledJsonTilesetSource::Embedded(tileset);
+        });
+    }
+
+    /// The index of the tileset containing the tile, and the tile's index in it.
+    pub fn find_tileset(&self, tile_id: u32) -> Option<(usize, u32)> {
+        self.tilesets
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, def)| def.first_gid <= tile_id)
+            .map(|(index, def)| (index, tile_id - def.first_gid))
+    }
+
+    /// All the tile layers, including the ones in groups.
+    pub fn iter_tile_layers(&self) -> impl Iterator<Item = &TiledJsonTileLayer> {
+        fn collect<'a>(layers: &'a [TiledJsonLayer], result: &mut Vec<&'a TiledJsonTileLayer>) {
+            layers.iter().for_each(|layer| match layer {
+                TiledJsonLayer::Tiles(layer) => result.push(layer),
+                TiledJsonLayer::Group(group) => collect(&group.layers, result),
+                TiledJsonLayer::Other => {}
+            });
+        }
+
+        let mut result = Vec::new();
+        collect(&self.layers, &mut result);
+        result.into_iter()
+    }
+
+    /// Load the images of the tilesets, the texture index of a tileset is its index in the map.
+    ///
+    /// External tilesets must be resolved first.
+    pub fn load_textures(&self, map_dir: &Path, asset_server: &AssetServer) -> TilemapTextures {
+        TilemapTextures::new(
+            self.tilesets
+                .iter()
+                .map(|def| {
+                    let TiledJsonTilesetSource::Embedded(tileset) = &def.tileset else {
+                        panic!("Tileset {:?} is not resolved!", def.tileset);
+                    };
+
+                    TilemapTexture::new(
+                        asset_server.load(map_dir.join(&tileset.image).to_asset_path()),
+                        TilemapTextureDescriptor::new(
+                            UVec2::new(tileset.image_width, tileset.image_height),
+                            UVec2::new(tileset.tile_width, tileset.tile_height),
+                        ),
+                    )
+                })
+                .collect(),
+            FilterMode::Nearest,
+        )
+    }
+
+    /// Spawn a tilemap for each tile layer. Returns the spawned tilemaps.
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        textures: Handle<TilemapTextures>,
+        material_assets: &mut Assets<StandardTilemapMaterial>,
+        z_index: f32,
+    ) -> Vec<Entity> {
+        if self.orientation != MapOrientation::Orthogonal || self.infinite {
+            warn!("Only finite orthogonal tiled maps can be loaded from json!");
+            return Vec::new();
+        }
+
+        let mut spawner = TiledJsonSpawner {
+            commands,
+            textures,
+            material_assets,
+            z: z_index,
+            entities: Vec::new(),
+        };
+        self.spawn_layers(&mut spawner, &self.layers, LayerParent::default());
+        spawner.entities
+    }
+
+    fn spawn_layers(
+        &self,
+        spawner: &mut TiledJsonSpawner,
+        layers: &[TiledJsonLayer],
+        parent: LayerParent,
+    ) {
+        layers.iter().for_each(|layer| match layer {
+            TiledJsonLayer::Tiles(layer) => {
+                spawner.z += 0.1;
+                let entity = self.spawn_tile_layer(spawner, layer, parent);
+                spawner.entities.push(entity);
+            }
+            TiledJsonLayer::Group(group) => self.spawn_layers(
+                spawner,
+                &group.layers,
+                LayerParent {
+                    offset: parent.offset + Vec2::new(group.offset_x, group.offset_y),
+                    opacity: parent.opacity * group.opacity,
+                },
+            ),
+            TiledJsonLayer::Other => {}
+        });
+    }
+
+    fn spawn_tile_layer(
+        &self,
+        spawner: &mut TiledJsonSpawner,
+        layer: &TiledJsonTileLayer,
+        parent: LayerParent,
+    ) -> Entity {
+        let tile_size = Vec2::new(self.tile_width as f32, self.tile_height as f32);
+        let entity = spawner.commands.spawn_empty().id();
+        let mut tilemap = StandardTilemapBundle {
+            name: TilemapName(layer.name.clone()),
+            tile_render_size: TileRenderSize(tile_size),
+            slot_size: TilemapSlotSize(tile_size),
+            ty: TilemapType::Square,
+            storage: TilemapStorage::new(DEFAULT_CHUNK_SIZE, entity),
+            transform: TilemapTransform::from_translation_3d(
+                parent.offset + Vec2::new(layer.offset_x, layer.offset_y),
+                spawner.z,
+            ),
+            textures: spawner.textures.clone(),
+            material: spawner.material_assets.add(StandardTilemapMaterial {
+                tint: Color::rgba(1., 1., 1., layer.opacity * parent.opacity),
+                ..Default::default()
+            }),
+            axis_flip: TilemapAxisFlip::Y,
+            visibility: if layer.visible {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            },
+            ..Default::default()
+        };
+
+        let mut buffer = TileBuilderBuffer::new();
+        layer.iter_tiles(self).for_each(|(index, builder)| {
+            buffer.set(index, builder);
+        });
+        tilemap
+            .storage
+            .fill_with_buffer(spawner.commands, IVec2::ZERO, buffer);
+        spawner.commands.entity(entity).insert(tilemap);
+
+        entity
+    }
+}
+
+/// What all the layers of a map are spawned with.
+struct TiledJsonSpawner<'a, 'w, 's> {
+    commands: &'a mut Commands<'w, 's>,
+    textures: Handle<TilemapTextures>,
+    material_assets: &'a mut Assets<StandardTilemapMaterial>,
+    /// The z index of the last spawned layer.
+    z: f32,
+    entities: Vec<Entity>,
+}
+
+/// What the layers inherit from the groups they are in.
+#[derive(Debug, Clone, Copy)]
+struct LayerParent {
+    offset: Vec2,
+    opacity: f32,
+}
+
+impl Default for LayerParent {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            opacity: 1.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TiledJsonLayer {
+    #[serde(rename = "tilelayer")]
+    Tiles(TiledJsonTileLayer),
+    #[serde(rename = "group")]
+    Group(TiledJsonGroupLayer),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct TiledJsonTileLayer {
+    pub id: u32,
+
+    #[serde(default)]
+    pub name: String,
+
+    /// The width of the layer in tiles.
+    pub width: u32,
+
+    /// The height of the layer in tiles.
+    pub height: u32,
+
+    #[serde(default = "default_onef")]
+    pub opacity: f32,
+
+    #[serde(default = "default_true")]
+    pub visible: bool,
+
+    /// Horizontal offset for this layer in pixels.
+    #[serde(rename = "offsetx")]
+    #[serde(default)]
+    pub offset_x: f32,
+
+    /// Vertical offset for this layer in pixels.
+    #[serde(rename = "offsety")]
+    #[serde(default)]
+    pub offset_y: f32,
+
+    /// The global tile ids, row by row. Only the csv encoding is supported.
+    #[serde(default)]
+    pub data: Vec<u32>,
+}
+
+impl TiledJsonTileLayer {
+    /// The tiles of this layer, tiles outside of the tilesets of `map` are skipped.
+    pub fn iter_tiles<'a>(
+        &'a self,
+        map: &'a TiledJsonTilemap,
+    ) -> impl Iterator<Item = (IVec2, TileBuilder)> + 'a {
+        let width = self.width.max(1) as i32;
+        self.data
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, gid)| {
+                let (tile_id, flip) = decode_gid(*gid);
+                if tile_id == 0 {
+                    return None;
+                }
+
+                let Some((texture_index, atlas_index)) = map.find_tileset(tile_id) else {
+                    warn!("Tile {} is not in any tileset!", tile_id);
+                    return None;
+                };
+
+                let index = index as i32;
+                Some((
+                    IVec2::new(index % width, index / width),
+                    TileBuilder::new().with_layer(
+                        0,
+                        TileLayer {
+                            texture_index: texture_index as i32,
+                            atlas_index: atlas_index as i32,
+                            flip,
+                            ..Default::default()
+                        },
+                    ),
+                ))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct TiledJsonGroupLayer {
+    pub id: u32,
+
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default = "default_onef")]
+    pub opacity: f32,
+
+    #[serde(rename = "offsetx")]
+    #[serde(default)]
+    pub offset_x: f32,
+
+    #[serde(rename = "offsety")]
+    #[serde(default)]
+    pub offset_y: f32,
+
+    #[serde(default)]
+    #[reflect(ignore)]
+    pub layers: Vec<TiledJsonLayer>,
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct TiledJsonTilesetDef {
+    /// The first global tile id of this tileset.
+    #[serde(rename = "firstgid")]
+    pub first_gid: u32,
+
+    #[serde(flatten)]
+    pub tileset: TiledJsonTilesetSource,
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TiledJsonTilesetSource {
+    /// A `.tsj` file, relative to the map.
+    External {
+        source: String,
+    },
+    Embedded(TiledJsonTileset),
+}
+
+/// A Tiled tileset saved as json (`.tsj`), or embedded in a map.
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct TiledJsonTileset {
+    pub name: String,
+
+    #[serde(rename = "tilewidth")]
+    pub tile_width: u32,
+
+    #[serde(rename = "tileheight")]
+    pub tile_height: u32,
+
+    #[serde(rename = "tilecount")]
+    pub tile_count: u32,
+
+    pub columns: u32,
+
+    /// The path of the image, relative to the tileset.
+    pub image: String,
+
+    #[serde(rename = "imagewidth")]
+    pub image_width: u32,
+
+    #[serde(rename = "imageheight")]
+    pub image_height: u32,
+}
+
+impl TiledJsonTileset {
+    /// Parse the content of a `.tsj` file.
+    #[inline]
+    pub fn parse_str(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::{system::CommandQueue, world::World};
+
+    use crate::tilemap::tile::{Tile, TileTexture};
+
+    use super::*;
+
+    #[test]
+    fn test_load_tmj() {
+        let mut map = TiledJsonTilemap::load("assets/tiled/tilemaps/orthogonal.tmj");
+        assert_eq!(map.iter_tile_layers().count(), 2);
+        let TiledJsonTilesetSource::Embedded(tileset) = &map.tilesets[1].tileset else {
+            panic!("The external tileset is not resolved!");
+        };
+        assert_eq!(tileset.name, "Squares");
+        assert_eq!(
+            Path::new(&tileset.image),
+            Path::new("../tilesets/../test_square.png")
+        );
+
+        assert_eq!(decode_gid(0x8000_0002), (2, TileFlip::HORIZONTAL));
+        assert_eq!(map.find_tileset(6), Some((1, 1)));
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut materials = Assets::<StandardTilemapMaterial>::default();
+        let tilemaps = map.spawn(
+            &mut Commands::new(&mut queue, &world),
+            Handle::default(),
+            &mut materials,
+            0.,
+        );
+        queue.apply(&mut world);
+
+        assert_eq!(tilemaps.len(), 2);
+        assert_eq!(world.query::<&Tile>().iter(&world).count(), 10);
+
+        let layer = |world: &World, tilemap: Entity, index: IVec2| {
+            let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+            let tile = world.get::<Tile>(storage.get(index).unwrap()).unwrap();
+            match &tile.texture {
+                TileTexture::Static(layers) => layers[0],
+                TileTexture::Animated(_) => unreachable!(),
+            }
+        };
+        let ground = layer(&world, tilemaps[0], IVec2::new(1, 1));
+        assert_eq!(
+            (ground.texture_index, ground.atlas_index, ground.flip),
+            (0, 1, TileFlip::HORIZONTAL)
+        );
+        assert_eq!(
+            layer(&world, tilemaps[0], IVec2::new(2, 1)).flip,
+            TileFlip::VERTICAL
+        );
+        assert_eq!(
+            layer(&world, tilemaps[0], IVec2::new(0, 2)).flip,
+            TileFlip::DIAGONAL
+        );
+        let decoration = layer(&world, tilemaps[1], IVec2::new(3, 2));
+        assert_eq!((decoration.texture_index, decoration.atlas_index), (1, 3));
+        assert_eq!(
+            world
+                .get::<TilemapTransform>(tilemaps[1])
+                .unwrap()
+                .translation,
+            Vec2::new(4., 8.)
+        );
+
+        map.orientation = MapOrientation::Isometric;
+        assert!(map
+            .spawn(
+                &mut Commands::new(&mut queue, &world),
+                Handle::default(),
+                &mut materials,
+                0.,
+            )
+            .is_empty());
+    }
+}
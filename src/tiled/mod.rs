@@ -40,6 +40,7 @@ use self::{
 
 pub mod app_ext;
 pub mod components;
+pub mod json;
 pub mod resources;
 pub mod sprite;
 pub mod traits;
@@ -295,7 +296,10 @@ fn load_layer(
                 ),
                 textures,
                 animations,
-                material: tilemap_material_assets.add(StandardTilemapMaterial { tint }),
+                material: tilemap_material_assets.add(StandardTilemapMaterial {
+                    tint,
+                    ..Default::default()
+                }),
                 axis_flip: match tiled_data.xml.orientation {
                     MapOrientation::Isometric => TilemapAxisFlip::all(),
                     _ => TilemapAxisFlip::Y,
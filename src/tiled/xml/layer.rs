@@ -293,14 +293,14 @@ impl Tiles {
                 let mut builder = TileBuilder::new();
                 let mut layer = TileLayer::default();
 
-                let flip = *tile_id >> 30;
-                let tile_id = tile_id & 0x3FFF_FFFF;
+                let flip = TileFlip::from_tiled(*tile_id);
+                let tile_id = tile_id & 0x0FFF_FFFF;
 
                 let (tileset, tileset_meta) =
                     tiled_assets.get_tileset(tile_id, &tiled_tilemap_name);
                 let atlas_index = tile_id - tileset_meta.first_gid;
 
-                layer.flip = TileFlip::from_bits(flip).unwrap();
+                layer.flip = flip;
                 layer.texture_index = tileset_meta.texture_index as i32;
 
                 if let Some(anim) = tileset.animated_tiles.get(&atlas_index) {
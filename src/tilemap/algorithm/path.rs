@@ -22,6 +22,9 @@ impl Tiles for PathTile {}
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct PathTilemap {
     pub(crate) storage: PathTileChunkedStorage,
+    /// Increased every time the path tiles are modified.
+    #[cfg_attr(feature = "serializing", serde(skip))]
+    pub(crate) version: u32,
 }
 
 impl PathTilemap {
@@ -31,6 +34,7 @@ impl PathTilemap {
     pub fn new() -> Self {
         Self {
             storage: ChunkedStorage::default(),
+            version: 0,
         }
     }
 
@@ -38,6 +42,7 @@ impl PathTilemap {
     pub fn new_with_chunk_size(chunk_size: u32) -> Self {
         Self {
             storage: ChunkedStorage::new(chunk_size),
+            version: 0,
         }
     }
 
@@ -46,17 +51,32 @@ impl PathTilemap {
     }
 
     pub fn get_mut(&mut self, index: IVec2) -> Option<&mut PathTile> {
+        self.mark_changed();
         self.storage.get_elem_mut(index)
     }
 
     pub fn set(&mut self, index: IVec2, tile: PathTile) {
+        self.mark_changed();
         self.storage.set_elem(index, tile)
     }
 
     pub fn remove(&mut self, index: IVec2) -> Option<PathTile> {
+        self.mark_changed();
         self.storage.remove_elem(index)
     }
 
+    /// A counter that changes every time the path tiles are modified.
+    /// Use this to find out if anything computed from this tilemap is outdated.
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub(crate) fn mark_changed(&mut self) {
+        self.version = self.version.wrapping_add(1);
+    }
+
     /// Set path-finding data using a custom function.
     pub fn fill_path_rect_custom(
         &mut self,
@@ -0,0 +1,325 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        system::{Commands, Query},
+    },
+    math::IVec2,
+    reflect::Reflect,
+    utils::{HashMap, HashSet},
+};
+
+use super::{
+    chunking::storage::{ChunkedStorage, NEIGHBOUR_OFFSETS},
+    map::TilemapStorage,
+    tile::{LayerUpdater, TileBuilder, TileLayer, TileLayerPosition, TileUpdater},
+};
+
+/// How the neighbours of a cell are turned into a bitmask.
+///
+/// Bit `i` is set if the `i`th neighbour in `NEIGHBOUR_OFFSETS` is a wall,
+/// so N = 1, E = 2, S = 4, W = 8, NE = 16, SE = 32, SW = 64, NW = 128.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum AutoTileMode {
+    /// Only the edge neighbours are counted, which needs 16 tiles.
+    FourBit,
+    /// The diagonal neighbours are also counted, but only if both of the edge neighbours
+    /// next to them are walls. This leaves 47 distinct bitmasks, known as the blob tileset.
+    Blob,
+}
+
+/// Pick the tile of each wall cell according to its neighbours at runtime,
+/// so procedurally edited maps don't need to be autotiled in advance.
+///
+/// Add this to the tilemap and modify the walls with `set_wall()`.
+/// The affected tiles are updated by `autotiler`.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct AutoTiler {
+    pub mode: AutoTileMode,
+    /// Maps the bitmasks to atlas indices.
+    /// Cells whose bitmask has no rule have their layer cleared.
+    pub rules: HashMap<u8, i32>,
+    /// The layer of the tiles to set the atlas index on.
+    /// The other layers of the tiles are left untouched.
+    pub layer: usize,
+    pub(crate) walls: ChunkedStorage<bool>,
+    pub(crate) changed: HashSet<IVec2>,
+    /// The cells whose layer was set by the autotiler,
+    /// so only these are cleared when they stop being walls.
+    pub(crate) written: HashSet<IVec2>,
+}
+
+impl AutoTiler {
+    pub fn new(mode: AutoTileMode, rules: HashMap<u8, i32>) -> Self {
+        Self {
+            mode,
+            rules,
+            layer: 0,
+            walls: ChunkedStorage::default(),
+            changed: HashSet::default(),
+            written: HashSet::default(),
+        }
+    }
+
+    pub fn with_layer(mut self, layer: usize) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    #[inline]
+    pub fn is_wall(&self, index: IVec2) -> bool {
+        self.walls.get_elem(index).is_some_and(|w| *w)
+    }
+
+    /// Set whether a cell is a wall. The tiles around it will be updated later.
+    pub fn set_wall(&mut self, index: IVec2, is_wall: bool) {
+        if self.is_wall(index) == is_wall {
+            return;
+        }
+
+        if is_wall {
+            self.walls.set_elem(index, true);
+        } else {
+            self.walls.remove_elem(index);
+        }
+        self.changed.insert(index);
+    }
+
+    /// The bitmask of the neighbours of a cell, see `AutoTileMode`.
+    pub fn bitmask(&self, index: IVec2) -> u8 {
+        let mut mask = NEIGHBOUR_OFFSETS
+            .iter()
+            .enumerate()
+            .filter(|(_, offset)| self.is_wall(index + **offset))
+            .fold(0, |mask, (bit, _)| mask | (1 << bit));
+
+        match self.mode {
+            AutoTileMode::FourBit => mask & 0b1111,
+            AutoTileMode::Blob => {
+                // Each diagonal sits between the edge neighbours `bit - 4` and `bit - 3`.
+                for bit in 4..8 {
+                    let edges = (1 << (bit - 4)) | (1 << ((bit - 3) % 4));
+                    if mask & edges != edges {
+                        mask &= !(1 << bit);
+                    }
+                }
+                mask
+            }
+        }
+    }
+
+    /// The atlas index of the tile at `index`, or `None` if there should be no tile.
+    #[inline]
+    pub fn tile(&self, index: IVec2) -> Option<i32> {
+        if self.is_wall(index) {
+            self.rules.get(&self.bitmask(index)).copied()
+        } else {
+            None
+        }
+    }
+
+    /// Update the tiles of the changed cells and their neighbours.
+    ///
+    /// Only `layer` of the tiles is modified, and it's only cleared on the cells
+    /// the autotiler wrote before, so the tiles painted by others survive.
+    pub fn apply(
+        &mut self,
+        commands: &mut Commands,
+        tilemap: &mut TilemapStorage,
+        changed_cells: impl IntoIterator<Item = IVec2>,
+    ) {
+        let cells = changed_cells
+            .into_iter()
+            .flat_map(|index| {
+                NEIGHBOUR_OFFSETS
+                    .iter()
+                    .map(move |offset| index + *offset)
+                    .chain(std::iter::once(index))
+            })
+            .collect::<HashSet<_>>();
+
+        for index in cells {
+            let Some(atlas_index) = self.tile(index) else {
+                if self.written.remove(&index) {
+                    tilemap.update(
+                        commands,
+                        index,
+                        TileUpdater {
+                            layer: Some(LayerUpdater {
+                                position: TileLayerPosition::Index(self.layer),
+                                layer: TileLayer::default(),
+                            }),
+                            ..Default::default()
+                        },
+                    );
+                }
+                continue;
+            };
+            self.written.insert(index);
+
+            let layer = TileLayer {
+                atlas_index,
+                ..Default::default()
+            };
+            if tilemap.get(index).is_some() {
+                tilemap.update(
+                    commands,
+                    index,
+                    TileUpdater {
+                        layer: Some(LayerUpdater {
+                            position: TileLayerPosition::Index(self.layer),
+                            layer,
+                        }),
+                        ..Default::default()
+                    },
+                );
+            } else {
+                tilemap.set(
+                    commands,
+                    index,
+                    TileBuilder::new().with_layer(self.layer, layer),
+                );
+            }
+        }
+    }
+}
+
+pub fn autotiler(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(&mut AutoTiler, &mut TilemapStorage)>,
+) {
+    tilemaps_query
+        .iter_mut()
+        .for_each(|(mut auto_tiler, mut storage)| {
+            if auto_tiler.changed.is_empty() {
+                return;
+            }
+
+            let changed = std::mem::take(&mut auto_tiler.changed);
+            auto_tiler.apply(&mut commands, &mut storage, changed);
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::app::{App, Update};
+
+    use crate::tilemap::tile::{Tile, TileTexture};
+
+    use super::*;
+
+    const N: u8 = 1;
+    const E: u8 = 2;
+    const S: u8 = 4;
+    const W: u8 = 8;
+    const NE: u8 = 16;
+    const SE: u8 = 32;
+    const SW: u8 = 64;
+    const NW: u8 = 128;
+
+    #[test]
+    fn test_autotile() {
+        // A 3x3 room of walls with the north east corner missing.
+        let mut auto_tiler = AutoTiler::new(AutoTileMode::Blob, HashMap::default());
+        for y in 0..3 {
+            for x in 0..3 {
+                auto_tiler.set_wall(IVec2::new(x, y), (x, y) != (2, 2));
+            }
+        }
+
+        // The center is an inner corner, as N and E are walls but NE isn't.
+        let inner_corner = N | E | S | W | SE | SW | NW;
+        assert_eq!(auto_tiler.bitmask(IVec2::ONE), inner_corner);
+        assert_eq!(auto_tiler.bitmask(IVec2::ZERO), N | E | NE);
+        // SE is ignored as E isn't a wall.
+        assert_eq!(auto_tiler.bitmask(IVec2::new(1, 2)), S | W | SW);
+
+        auto_tiler.mode = AutoTileMode::FourBit;
+        assert_eq!(auto_tiler.bitmask(IVec2::ONE), N | E | S | W);
+        auto_tiler.mode = AutoTileMode::Blob;
+        auto_tiler.rules = HashMap::from([(inner_corner, 34), (N | E | NE, 5)]);
+
+        let mut app = App::new();
+        app.add_systems(Update, autotiler);
+        let tilemap = app.world.spawn_empty().id();
+        app.world
+            .entity_mut(tilemap)
+            .insert((auto_tiler, TilemapStorage::new(4, tilemap)));
+        app.update();
+
+        let atlas_index = |app: &App, index: IVec2| {
+            let storage = app.world.get::<TilemapStorage>(tilemap).unwrap();
+            let tile = app.world.get::<Tile>(storage.get(index)?).unwrap();
+            match &tile.texture {
+                TileTexture::Static(layers) => Some(layers[0].atlas_index),
+                TileTexture::Animated(_) => None,
+            }
+        };
+        assert_eq!(atlas_index(&app, IVec2::ONE), Some(34));
+        assert_eq!(atlas_index(&app, IVec2::ZERO), Some(5));
+        assert_eq!(atlas_index(&app, IVec2::new(2, 2)), None);
+        // No rule for this bitmask.
+        assert_eq!(atlas_index(&app, IVec2::new(1, 0)), None);
+    }
+
+    #[test]
+    fn test_autotile_keeps_other_tiles() {
+        use bevy::ecs::{schedule::IntoSystemConfigs, system::CommandQueue};
+
+        use crate::tilemap::tile::tile_updater;
+
+        let mut app = App::new();
+        app.add_systems(Update, (autotiler, tile_updater).chain());
+        let tilemap = app.world.spawn_empty().id();
+        let mut storage = TilemapStorage::new(4, tilemap);
+        let floor = TileLayer {
+            atlas_index: 7,
+            ..Default::default()
+        };
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        for y in 0..3 {
+            for x in 0..3 {
+                storage.set(
+                    &mut commands,
+                    IVec2::new(x, y),
+                    TileBuilder::new().with_layer(0, floor),
+                );
+            }
+        }
+        commands.entity(tilemap).insert((
+            AutoTiler::new(AutoTileMode::FourBit, HashMap::from([(0, 3)])).with_layer(1),
+            storage,
+        ));
+        queue.apply(&mut app.world);
+
+        let layers = |app: &App, index: IVec2| {
+            let storage = app.world.get::<TilemapStorage>(tilemap).unwrap();
+            let tile = app.world.get::<Tile>(storage.get(index)?).unwrap();
+            match &tile.texture {
+                TileTexture::Static(layers) => {
+                    Some(layers.iter().map(|l| l.atlas_index).collect::<Vec<_>>())
+                }
+                TileTexture::Animated(_) => None,
+            }
+        };
+
+        app.world
+            .get_mut::<AutoTiler>(tilemap)
+            .unwrap()
+            .set_wall(IVec2::ONE, true);
+        app.update();
+        assert_eq!(layers(&app, IVec2::ONE), Some(vec![7, 3]));
+        // The floor around the wall is untouched.
+        assert_eq!(layers(&app, IVec2::ZERO), Some(vec![7]));
+        assert_eq!(layers(&app, IVec2::new(1, 2)), Some(vec![7]));
+
+        app.world
+            .get_mut::<AutoTiler>(tilemap)
+            .unwrap()
+            .set_wall(IVec2::ONE, false);
+        app.update();
+        // Only the wall layer is cleared, the floor under it survives.
+        assert_eq!(layers(&app, IVec2::ONE), Some(vec![7, -1]));
+        assert_eq!(layers(&app, IVec2::new(2, 1)), Some(vec![7]));
+    }
+}
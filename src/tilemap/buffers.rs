@@ -1,10 +1,18 @@
-use std::fmt::Debug;
+use std::{borrow::Cow, fmt::Debug};
 
-use bevy::{math::IVec2, reflect::Reflect, utils::HashMap};
+use bevy::{
+    ecs::system::Query,
+    math::{IVec2, UVec2},
+    reflect::Reflect,
+    utils::HashMap,
+};
 
-use crate::math::aabb::IAabb2d;
+use crate::math::{aabb::IAabb2d, TileArea};
 
-use super::tile::{Tile, TileBuilder};
+use super::{
+    map::{TilemapRotation, TilemapStorage},
+    tile::{Tile, TileBuilder, TileFlip, TileTexture},
+};
 
 /// A marker trait
 pub trait Tiles: Debug + Clone + Reflect {}
@@ -77,3 +85,197 @@ impl<T: Tiles> TileBuffer<T> {
         self.aabb
     }
 }
+
+/// A rectangular region of a tilemap that is detached from it.
+///
+/// It keeps the textures (including flips) and tints of the tiles,
+/// so it can be pasted to another location or even another tilemap.
+/// See `TilemapStorage::copy_region()` and `TilemapStorage::paste()`.
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileClip {
+    pub(crate) size: UVec2,
+    pub(crate) anchor: IVec2,
+    pub(crate) tiles: Vec<Option<TileBuilder>>,
+}
+
+impl TileClip {
+    /// Create an empty clip.
+    pub fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            anchor: IVec2::ZERO,
+            tiles: vec![None; (size.x * size.y) as usize],
+        }
+    }
+
+    /// Set the anchor of the clip, relative to its bottom left corner.
+    /// The anchor is the cell that lands on the destination when pasting.
+    pub fn with_anchor(mut self, anchor: IVec2) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    #[inline]
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    #[inline]
+    pub fn anchor(&self) -> IVec2 {
+        self.anchor
+    }
+
+    /// Get the tile at the given index relative to the bottom left corner.
+    pub fn get(&self, index: IVec2) -> Option<&TileBuilder> {
+        self.linear_index(index)
+            .and_then(|i| self.tiles[i].as_ref())
+    }
+
+    /// Set the tile at the given index relative to the bottom left corner.
+    ///
+    /// Indices out of the clip are ignored.
+    pub fn set(&mut self, index: IVec2, tile: Option<TileBuilder>) {
+        if let Some(i) = self.linear_index(index) {
+            self.tiles[i] = tile;
+        }
+    }
+
+    /// Iterate over all the cells of this clip, including the empty ones.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, Option<&TileBuilder>)> {
+        let width = self.size.x as i32;
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(move |(i, t)| (IVec2::new(i as i32 % width, i as i32 / width), t.as_ref()))
+    }
+
+    /// Rotate the whole clip clockwise, including the anchor and the textures of the tiles.
+    ///
+    /// The size is swapped when rotating by 90 or 270 degrees.
+    /// This is meant for square tilemaps, and animated tiles are moved but not rotated.
+    pub fn rotated(&self, rotation: TilemapRotation) -> Self {
+        let max = self.size.as_ivec2() - IVec2::ONE;
+        match rotation {
+            TilemapRotation::None => self.clone(),
+            TilemapRotation::Cw90 => self.transformed(
+                UVec2::new(self.size.y, self.size.x),
+                |i| IVec2::new(i.y, max.x - i.x),
+                |f| f.rotated(rotation),
+            ),
+            TilemapRotation::Cw180 => {
+                self.transformed(self.size, |i| max - i, |f| f.rotated(rotation))
+            }
+            TilemapRotation::Cw270 => self.transformed(
+                UVec2::new(self.size.y, self.size.x),
+                |i| IVec2::new(max.y - i.y, i.x),
+                |f| f.rotated(rotation),
+            ),
+        }
+    }
+
+    /// Mirror the whole clip, including the anchor and the textures of the tiles.
+    ///
+    /// Only `HORIZONTAL` and `VERTICAL` of `flip` are taken into account.
+    pub fn flipped(&self, flip: TileFlip) -> Self {
+        let max = self.size.as_ivec2() - IVec2::ONE;
+        self.transformed(
+            self.size,
+            |i| IVec2 {
+                x: if flip.contains(TileFlip::HORIZONTAL) {
+                    max.x - i.x
+                } else {
+                    i.x
+                },
+                y: if flip.contains(TileFlip::VERTICAL) {
+                    max.y - i.y
+                } else {
+                    i.y
+                },
+            },
+            |f| f.flipped(flip),
+        )
+    }
+
+    fn transformed(
+        &self,
+        size: UVec2,
+        index: impl Fn(IVec2) -> IVec2,
+        flip: impl Fn(TileFlip) -> TileFlip,
+    ) -> Self {
+        let mut clip = Self::new(size).with_anchor(index(self.anchor));
+        for (i, tile) in self.iter() {
+            let Some(mut tile) = tile.cloned() else {
+                continue;
+            };
+            if let TileTexture::Static(layers) = &mut tile.texture {
+                layers.iter_mut().for_each(|l| l.flip = flip(l.flip));
+            }
+            clip.set(index(i), Some(tile));
+        }
+        clip
+    }
+
+    #[inline]
+    fn linear_index(&self, index: IVec2) -> Option<usize> {
+        if index.cmplt(IVec2::ZERO).any() || index.cmpge(self.size.as_ivec2()).any() {
+            None
+        } else {
+            Some((index.y * self.size.x as i32 + index.x) as usize)
+        }
+    }
+}
+
+/// How a brush is transformed when stamping. The flip is applied before the rotation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BrushTransform {
+    pub rotation: TilemapRotation,
+    pub flip: TileFlip,
+}
+
+/// An immutable template of tiles, like a tree or a room,
+/// which can be stamped into tilemaps again and again.
+///
+/// Unlike pasting a `TileClip`, stamping never removes tiles, as empty cells are skipped.
+/// See `TilemapStorage::stamp()`.
+#[derive(Debug, Clone, PartialEq, Reflect)]
+pub struct Brush {
+    clip: TileClip,
+}
+
+impl Brush {
+    /// Create a brush from the tiles in `area`. The bottom left corner is the anchor.
+    pub fn from_tilemap(
+        tilemap: &TilemapStorage,
+        tiles_query: &Query<&Tile>,
+        area: TileArea,
+    ) -> Self {
+        Self::from_clip(tilemap.copy_region(tiles_query, area))
+    }
+
+    #[inline]
+    pub fn from_clip(clip: TileClip) -> Self {
+        Self { clip }
+    }
+
+    #[inline]
+    pub fn clip(&self) -> &TileClip {
+        &self.clip
+    }
+
+    /// The tiles of the brush after the transform.
+    ///
+    /// Stamping the same transform many times transforms the brush every time,
+    /// so you may want to create another brush from the result.
+    pub fn transformed(&self, transform: BrushTransform) -> Cow<'_, TileClip> {
+        if transform == BrushTransform::default() {
+            Cow::Borrowed(&self.clip)
+        } else {
+            Cow::Owned(
+                self.clip
+                    .flipped(transform.flip)
+                    .rotated(transform.rotation),
+            )
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::{
+    asset::Handle,
+    ecs::{entity::Entity, system::Commands},
+    math::Vec2,
+};
+
+use crate::{math::TileArea, render::material::StandardTilemapMaterial, DEFAULT_CHUNK_SIZE};
+
+use super::{
+    bundles::StandardPureColorTilemapBundle,
+    map::{
+        TileRenderSize, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTextures,
+        TilemapTransform, TilemapType,
+    },
+    tile::TileBuilder,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TilemapBuilderError {
+    InvalidSlotSize(Vec2),
+    InvalidRenderSize(Vec2),
+    ZeroChunkSize,
+    MissingMaterial,
+    /// The legs of a hexagonal tilemap are longer than the slots.
+    InvalidHexLegs {
+        legs: u32,
+        slot_height: f32,
+    },
+}
+
+impl Display for TilemapBuilderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TilemapBuilderError::InvalidSlotSize(size) => {
+                write!(f, "slot size {} must be positive", size)
+            }
+            TilemapBuilderError::InvalidRenderSize(size) => {
+                write!(f, "tile render size {} must be positive", size)
+            }
+            TilemapBuilderError::ZeroChunkSize => f.write_str("chunk size must not be zero"),
+            TilemapBuilderError::MissingMaterial => {
+                f.write_str("no material, use `with_material()` to set one")
+            }
+            TilemapBuilderError::InvalidHexLegs { legs, slot_height } => write!(
+                f,
+                "hexagonal legs {} are longer than the slot height {}",
+                legs, slot_height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TilemapBuilderError {}
+
+/// Build a tilemap without assembling the bundle by hand.
+///
+/// ```ignore
+/// TilemapBuilder::new(TilemapType::Square, Vec2::splat(16.))
+///     .with_material(materials.add(StandardTilemapMaterial::default()))
+///     .with_textures(textures)
+///     .with_fill(TileArea::new(IVec2::ZERO, UVec2::splat(64)), TileBuilder::new())
+///     .build(&mut commands)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct TilemapBuilder {
+    pub name: String,
+    pub ty: TilemapType,
+    pub slot_size: Vec2,
+    /// Defaults to `slot_size`.
+    pub tile_render_size: Option<Vec2>,
+    pub chunk_size: u32,
+    pub transform: TilemapTransform,
+    pub material: Option<Handle<StandardTilemapMaterial>>,
+    /// Spawns a pure color tilemap if `None`.
+    pub textures: Option<Handle<TilemapTextures>>,
+    pub fills: Vec<(TileArea, TileBuilder)>,
+}
+
+impl TilemapBuilder {
+    pub fn new(ty: TilemapType, slot_size: Vec2) -> Self {
+        Self {
+            name: String::new(),
+            ty,
+            slot_size,
+            tile_render_size: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            transform: TilemapTransform::default(),
+            material: None,
+            textures: None,
+            fills: Vec::new(),
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_tile_render_size(mut self, tile_render_size: Vec2) -> Self {
+        self.tile_render_size = Some(tile_render_size);
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: TilemapTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<StandardTilemapMaterial>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn with_textures(mut self, textures: Handle<TilemapTextures>) -> Self {
+        self.textures = Some(textures);
+        self
+    }
+
+    /// Fill the area with the tile when building. Later fills overwrite earlier ones.
+    pub fn with_fill(mut self, area: TileArea, tile: TileBuilder) -> Self {
+        self.fills.push((area, tile));
+        self
+    }
+
+    /// Check the settings without building.
+    pub fn validate(&self) -> Result<(), TilemapBuilderError> {
+        let is_valid = |size: Vec2| size.is_finite() && size.cmpgt(Vec2::ZERO).all();
+
+        if !is_valid(self.slot_size) {
+            return Err(TilemapBuilderError::InvalidSlotSize(self.slot_size));
+        }
+        if let Some(size) = self.tile_render_size.filter(|s| !is_valid(*s)) {
+            return Err(TilemapBuilderError::InvalidRenderSize(size));
+        }
+        if self.chunk_size == 0 {
+            return Err(TilemapBuilderError::ZeroChunkSize);
+        }
+        if self.material.is_none() {
+            return Err(TilemapBuilderError::MissingMaterial);
+        }
+        if let TilemapType::Hexagonal(legs) = self.ty {
+            if legs as f32 > self.slot_size.y {
+                return Err(TilemapBuilderError::InvalidHexLegs {
+                    legs,
+                    slot_height: self.slot_size.y,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawn the tilemap and its tiles.
+    pub fn build(self, commands: &mut Commands) -> Result<Entity, TilemapBuilderError> {
+        self.validate()?;
+
+        let entity = commands.spawn_empty().id();
+        let mut storage = TilemapStorage::new(self.chunk_size, entity);
+        self.fills
+            .into_iter()
+            .for_each(|(area, tile)| storage.fill_rect(commands, area, tile));
+
+        let bundle = StandardPureColorTilemapBundle {
+            name: TilemapName(self.name),
+            tile_render_size: TileRenderSize(self.tile_render_size.unwrap_or(self.slot_size)),
+            slot_size: TilemapSlotSize(self.slot_size),
+            ty: self.ty,
+            storage,
+            transform: self.transform,
+            material: self.material.unwrap(),
+            ..Default::default()
+        };
+
+        match self.textures {
+            Some(textures) => commands
+                .entity(entity)
+                .insert(bundle.convert_to_texture_bundle(textures, Default::default())),
+            None => commands.entity(entity).insert(bundle),
+        };
+
+        Ok(entity)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{system::CommandQueue, world::World},
+        math::{IVec2, UVec2},
+    };
+
+    use crate::tilemap::tile::Tile;
+
+    use super::*;
+
+    #[test]
+    fn test_tilemap_builder() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let builder = TilemapBuilder::new(TilemapType::Square, Vec2::splat(16.))
+            .with_material(Handle::default())
+            .with_chunk_size(8)
+            .with_fill(
+                TileArea::new(IVec2::ZERO, UVec2::splat(10)),
+                TileBuilder::new(),
+            );
+
+        let entity = builder
+            .clone()
+            .build(&mut Commands::new(&mut queue, &world))
+            .unwrap();
+        queue.apply(&mut world);
+
+        let storage = world.get::<TilemapStorage>(entity).unwrap();
+        assert_eq!(storage.tilemap, entity);
+        assert_eq!(storage.storage.chunk_size, 8);
+        assert!(storage.get(IVec2::new(9, 9)).is_some());
+        assert!(world.get::<Handle<TilemapTextures>>(entity).is_none());
+        assert_eq!(world.query::<&Tile>().iter(&world).count(), 100);
+        assert_eq!(
+            world.get::<TileRenderSize>(entity).unwrap().0,
+            Vec2::splat(16.)
+        );
+
+        assert_eq!(
+            builder.clone().with_chunk_size(0).validate(),
+            Err(TilemapBuilderError::ZeroChunkSize)
+        );
+        assert_eq!(
+            TilemapBuilder::new(TilemapType::Square, Vec2::new(16., 0.)).validate(),
+            Err(TilemapBuilderError::InvalidSlotSize(Vec2::new(16., 0.)))
+        );
+        assert_eq!(
+            TilemapBuilder::new(TilemapType::Square, Vec2::splat(16.)).validate(),
+            Err(TilemapBuilderError::MissingMaterial)
+        );
+        let mut hex = builder;
+        hex.ty = TilemapType::Hexagonal(20);
+        assert_eq!(
+            hex.validate(),
+            Err(TilemapBuilderError::InvalidHexLegs {
+                legs: 20,
+                slot_height: 16.
+            })
+        );
+    }
+}
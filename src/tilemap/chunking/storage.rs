@@ -3,7 +3,7 @@ use std::fmt::Debug;
 use bevy::{ecs::entity::Entity, math::IVec2, reflect::Reflect, utils::HashMap};
 
 use crate::{
-    math::extension::DivToFloor,
+    math::{aabb::IAabb2d, extension::DivToFloor},
     tilemap::tile::{Tile, TileBuilder},
     DEFAULT_CHUNK_SIZE,
 };
@@ -22,6 +22,31 @@ pub type PhysicsTileChunkedStorage = ChunkedStorage<crate::tilemap::physics::Phy
 pub type PackedPhysicsTileChunkedStorage =
     ChunkedStorage<crate::tilemap::physics::PackedPhysicsTile>;
 
+/// The offsets of the neighbours returned by `neighbours()`, in the order
+/// N, E, S, W, NE, SE, SW, NW. North is +y.
+pub const NEIGHBOUR_OFFSETS: [IVec2; 8] = [
+    IVec2::Y,
+    IVec2::X,
+    IVec2::NEG_Y,
+    IVec2::NEG_X,
+    IVec2::ONE,
+    IVec2::new(1, -1),
+    IVec2::NEG_ONE,
+    IVec2::new(-1, 1),
+];
+
+fn neighbours_of<'a, T: 'a, const N: usize>(
+    index: IVec2,
+    get: impl Fn(IVec2) -> Option<&'a T>,
+) -> [Option<&'a T>; N] {
+    assert!(
+        N == 4 || N == 8,
+        "Only 4 or 8 neighbours are supported, but {} are requested!",
+        N
+    );
+    std::array::from_fn(|i| get(index + NEIGHBOUR_OFFSETS[i]))
+}
+
 #[derive(Debug, Clone, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChunkedStorage<T: Debug + Clone + Reflect> {
@@ -62,6 +87,15 @@ impl<T: Debug + Clone + Reflect> ChunkedStorage<T> {
             .and_then(|t| t.as_ref())
     }
 
+    /// Get the 4 (N, E, S, W) or 8 (and then NE, SE, SW, NW) neighbours of a element,
+    /// see `NEIGHBOUR_OFFSETS`. Empty neighbours are `None`.
+    ///
+    /// This is meant for square and isometric tilemaps, for example autotiling.
+    #[inline]
+    pub fn neighbours<const N: usize>(&self, index: IVec2) -> [Option<&T>; N] {
+        neighbours_of(index, |i| self.get_elem(i))
+    }
+
     pub fn get_elem_mut(&mut self, index: IVec2) -> Option<&mut T> {
         let idx = self.transform_index(index);
         if let Some(chunk) = self.chunks.get_mut(&idx.0) {
@@ -176,6 +210,87 @@ impl<T: Debug + Clone + Reflect> ChunkedStorage<T> {
         self.iter_mut().map(|o| o.as_mut()).flatten()
     }
 
+    /// The aabb of all the elements in index space, or `None` if there are none.
+    ///
+    /// Unlike the chunks, this only covers the elements that are set.
+    pub fn occupied_aabb(&self) -> Option<IAabb2d> {
+        self.chunked_iter_some()
+            .map(|(chunk_index, in_chunk_index, _)| {
+                self.inverse_transform_index(chunk_index, in_chunk_index)
+            })
+            .fold(None, |aabb: Option<IAabb2d>, index| match aabb {
+                Some(mut aabb) => {
+                    aabb.expand_to_contain(index);
+                    Some(aabb)
+                }
+                None => Some(IAabb2d::splat(index)),
+            })
+    }
+
+    /// The aabb of all the allocated chunks in index space, or `None` if there are none.
+    ///
+    /// This is cheaper than `occupied_aabb` but may cover empty slots.
+    pub fn allocated_aabb(&self) -> Option<IAabb2d> {
+        let chunk_size = self.chunk_size as i32;
+        self.chunks
+            .keys()
+            .fold(None, |aabb: Option<IAabb2d>, chunk_index| {
+                let chunk = IAabb2d {
+                    min: *chunk_index * chunk_size,
+                    max: (*chunk_index + 1) * chunk_size - 1,
+                };
+                match aabb {
+                    Some(mut aabb) => {
+                        aabb.expand(chunk);
+                        Some(aabb)
+                    }
+                    None => Some(chunk),
+                }
+            })
+    }
+
+    /// Iterate over every index between `min` and `max` (both inclusive) along with
+    /// the element there, including the empty slots.
+    ///
+    /// The rect is clipped to `allocated_aabb()`, and only the chunks overlapping it
+    /// are looked up. The indices are visited chunk by chunk, so they are not in row order.
+    pub fn iter_region(
+        &self,
+        min: IVec2,
+        max: IVec2,
+    ) -> impl Iterator<Item = (IVec2, Option<&T>)> + '_ {
+        let chunk_size = self.chunk_size as i32;
+        let region = self
+            .allocated_aabb()
+            .map(|bounds| IAabb2d { min, max }.intersection(bounds))
+            .filter(|region| region.min.cmple(region.max).all());
+
+        region.into_iter().flat_map(move |region| {
+            let min_chunk = region.min.div_to_floor(IVec2::splat(chunk_size));
+            let max_chunk = region.max.div_to_floor(IVec2::splat(chunk_size));
+
+            (min_chunk.y..=max_chunk.y)
+                .flat_map(move |y| (min_chunk.x..=max_chunk.x).map(move |x| IVec2 { x, y }))
+                .flat_map(move |chunk_index| {
+                    let chunk = self.chunks.get(&chunk_index);
+                    let origin = chunk_index * chunk_size;
+                    let min = origin.max(region.min);
+                    let max = (origin + chunk_size - 1).min(region.max);
+
+                    (min.y..=max.y).flat_map(move |y| {
+                        (min.x..=max.x).map(move |x| {
+                            let index = IVec2 { x, y };
+                            let local = index - origin;
+                            let elem = chunk.and_then(|c| {
+                                c[(local.y * chunk_size + local.x) as usize].as_ref()
+                            });
+                            (index, elem)
+                        })
+                    })
+                })
+        })
+    }
+
     #[inline]
     pub fn chunked_iter_some(&self) -> impl Iterator<Item = (ChunkIndex, InChunkIndex, &T)> {
         self.chunks
@@ -191,4 +306,345 @@ impl<T: Debug + Clone + Reflect> ChunkedStorage<T> {
             })
             .flatten()
     }
+
+    /// Iterate over the elements that are set along with their indices.
+    #[inline]
+    pub fn indexed_iter_some(&self) -> impl Iterator<Item = (IVec2, &T)> {
+        self.chunked_iter_some()
+            .map(|(chunk_index, in_chunk_index, elem)| {
+                (
+                    self.inverse_transform_index(chunk_index, in_chunk_index),
+                    elem,
+                )
+            })
+    }
+}
+
+pub type DenseTileChunkedStorage = DenseChunkedStorage<crate::tilemap::map::DenseTileId>;
+
+type DenseChunk<T> = Box<[Option<T>]>;
+
+/// A compact, read-only version of `ChunkedStorage`.
+///
+/// All the chunks are packed into a single boxed slice, and each chunk index
+/// maps to its slot in it, so only the chunks that exist take up memory
+/// no matter how far apart they are.
+/// This is suitable for large static maps that never change after spawning.
+#[derive(Debug, Clone)]
+pub struct DenseChunkedStorage<T: Debug + Clone> {
+    pub chunk_size: u32,
+    pub(crate) chunk_aabb: IAabb2d,
+    /// The slot of each chunk in `chunks`.
+    pub(crate) slots: HashMap<ChunkIndex, u32>,
+    pub(crate) chunks: Box<[(ChunkIndex, DenseChunk<T>)]>,
+}
+
+impl<T: Debug + Clone + Reflect> From<&ChunkedStorage<T>> for DenseChunkedStorage<T> {
+    fn from(value: &ChunkedStorage<T>) -> Self {
+        Self::from_mapped(value, |elem| Some(elem.clone()))
+    }
+}
+
+impl<T: Debug + Clone> DenseChunkedStorage<T> {
+    /// Pack a `ChunkedStorage`, converting every element with `f`.
+    /// The elements `f` returns `None` for are left empty.
+    pub fn from_mapped<U: Debug + Clone + Reflect>(
+        storage: &ChunkedStorage<U>,
+        mut f: impl FnMut(&U) -> Option<T>,
+    ) -> Self {
+        Self::from_chunks(
+            storage.chunk_size,
+            storage
+                .chunks
+                .iter()
+                .map(|(index, chunk)| {
+                    (
+                        *index,
+                        chunk
+                            .iter()
+                            .map(|elem| elem.as_ref().and_then(&mut f))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn from_chunks(chunk_size: u32, chunks: Vec<(ChunkIndex, DenseChunk<T>)>) -> Self {
+        let mut chunk_aabb: Option<IAabb2d> = None;
+        chunks.iter().for_each(|(chunk_index, _)| {
+            if let Some(aabb) = &mut chunk_aabb {
+                aabb.expand_to_contain(*chunk_index);
+            } else {
+                chunk_aabb = Some(IAabb2d::splat(*chunk_index));
+            }
+        });
+
+        Self {
+            chunk_size,
+            chunk_aabb: chunk_aabb.unwrap_or_default(),
+            slots: chunks
+                .iter()
+                .enumerate()
+                .map(|(slot, (chunk_index, _))| (*chunk_index, slot as u32))
+                .collect(),
+            chunks: chunks.into_boxed_slice(),
+        }
+    }
+
+    /// Get a element.
+    #[inline]
+    pub fn get_elem(&self, index: IVec2) -> Option<&T> {
+        let (chunk_index, in_chunk_index) = self.transform_index(index);
+        self.get_chunk(chunk_index)
+            .and_then(|c| c.get(in_chunk_index))
+            .and_then(|t| t.as_ref())
+    }
+
+    /// Get the neighbours of a element, see `ChunkedStorage::neighbours()`.
+    #[inline]
+    pub fn neighbours<const N: usize>(&self, index: IVec2) -> [Option<&T>; N] {
+        neighbours_of(index, |i| self.get_elem(i))
+    }
+
+    /// Get a chunk.
+    #[inline]
+    pub fn get_chunk(&self, index: IVec2) -> Option<&[Option<T>]> {
+        if !self.chunk_aabb.contains(index) {
+            return None;
+        }
+
+        self.slots
+            .get(&index)
+            .map(|slot| &*self.chunks[*slot as usize].1)
+    }
+
+    /// The number of chunks.
+    #[inline]
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The aabb of all the chunks in chunk coordinates.
+    #[inline]
+    pub fn chunk_aabb(&self) -> IAabb2d {
+        self.chunk_aabb
+    }
+
+    pub fn transform_index(&self, index: IVec2) -> (ChunkIndex, InChunkIndex) {
+        let isize = IVec2::splat(self.chunk_size as i32);
+        let c = index.div_to_floor(isize);
+        let idx = index - c * isize;
+        (c, (idx.y * isize.x + idx.x) as usize)
+    }
+
+    pub fn inverse_transform_index(&self, chunk_index: IVec2, in_chunk_index: usize) -> IVec2 {
+        let isize = IVec2::splat(self.chunk_size as i32);
+        let idx = IVec2::new(
+            in_chunk_index as i32 % self.chunk_size as i32,
+            in_chunk_index as i32 / self.chunk_size as i32,
+        );
+        chunk_index * isize + idx
+    }
+
+    #[inline]
+    pub fn iter_some(&self) -> impl Iterator<Item = &T> {
+        self.chunks
+            .iter()
+            .flat_map(|(_, c)| c.iter())
+            .filter_map(|t| t.as_ref())
+    }
+
+    #[inline]
+    pub fn chunked_iter_some(&self) -> impl Iterator<Item = (ChunkIndex, InChunkIndex, &T)> {
+        self.chunks.iter().flat_map(|(chunk_index, chunk)| {
+            chunk
+                .iter()
+                .enumerate()
+                .filter_map(move |(in_chunk_index, elem)| {
+                    elem.as_ref()
+                        .map(|elem| (*chunk_index, in_chunk_index, elem))
+                })
+        })
+    }
+
+    /// Iterate over all the elements along with their indices.
+    #[inline]
+    pub fn indexed_iter_some(&self) -> impl Iterator<Item = (IVec2, &T)> {
+        self.chunked_iter_some()
+            .map(move |(chunk_index, in_chunk_index, elem)| {
+                (
+                    self.inverse_transform_index(chunk_index, in_chunk_index),
+                    elem,
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dense_storage() {
+        let mut storage = ChunkedStorage::<i32>::new(4);
+        for y in -9..7 {
+            for x in -5..13 {
+                if (x + y) % 3 != 0 {
+                    storage.set_elem(IVec2::new(x, y), x * 100 + y);
+                }
+            }
+        }
+        storage.set_elem(IVec2::new(40, -30), 1);
+        assert_eq!(storage.occupied_aabb(), Some(IAabb2d::new(-5, -30, 40, 6)));
+
+        let dense = DenseChunkedStorage::from(&storage);
+
+        for y in -40..40 {
+            for x in -40..50 {
+                let index = IVec2::new(x, y);
+                assert_eq!(storage.get_elem(index), dense.get_elem(index));
+            }
+        }
+        assert_eq!(storage.iter_some().count(), dense.iter_some().count());
+        dense
+            .chunked_iter_some()
+            .for_each(|(chunk_index, in_chunk_index, elem)| {
+                assert_eq!(
+                    storage.get_elem(storage.inverse_transform_index(chunk_index, in_chunk_index)),
+                    Some(elem)
+                );
+            });
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let mut storage = ChunkedStorage::<i32>::new(2);
+        for y in 0..3 {
+            for x in 0..3 {
+                storage.set_elem(IVec2::new(x, y), x * 10 + y);
+            }
+        }
+
+        // The bottom left corner.
+        assert_eq!(
+            storage.neighbours(IVec2::ZERO),
+            [Some(&1), Some(&10), None, None]
+        );
+        assert_eq!(
+            storage.neighbours(IVec2::ZERO),
+            [Some(&1), Some(&10), None, None, Some(&11), None, None, None]
+        );
+
+        // The top right corner, across the chunk border.
+        let dense = DenseChunkedStorage::from(&storage);
+        assert_eq!(
+            dense.neighbours(IVec2::new(2, 2)).map(|n| n.copied()),
+            [None, None, Some(21), Some(12), None, None, Some(11), None]
+        );
+        assert_eq!(
+            dense.neighbours::<8>(IVec2::new(1, 1)),
+            storage.neighbours::<8>(IVec2::new(1, 1))
+        );
+        assert!(storage
+            .neighbours::<8>(IVec2::new(1, 1))
+            .iter()
+            .all(|n| n.is_some()));
+    }
+
+    #[test]
+    fn test_sparse_negative_storage() {
+        let mut storage = ChunkedStorage::<i32>::new(16);
+        storage.set_elem(IVec2::new(-1000, -1000), 1);
+        // Only the chunk containing the tile is allocated.
+        assert_eq!(storage.chunks.len(), 1);
+        assert_eq!(
+            storage.transform_index(IVec2::new(-1000, -1000)),
+            (IVec2::splat(-63), 8 * 16 + 8)
+        );
+
+        storage.set_elem(IVec2::new(-1, -1), 2);
+        storage.set_elem(IVec2::new(-1_000_000, 2_000_000), 3);
+        assert_eq!(storage.chunks.len(), 3);
+
+        assert_eq!(storage.get_elem(IVec2::new(-1000, -1000)), Some(&1));
+        assert_eq!(storage.get_elem(IVec2::new(-1, -1)), Some(&2));
+        assert_eq!(
+            storage.get_elem(IVec2::new(-1_000_000, 2_000_000)),
+            Some(&3)
+        );
+        assert_eq!(storage.get_elem(IVec2::new(-999, -1000)), None);
+        assert_eq!(storage.get_elem(IVec2::new(1000, 1000)), None);
+        assert_eq!(
+            storage.occupied_aabb(),
+            Some(IAabb2d::new(-1_000_000, -1000, -1, 2_000_000))
+        );
+
+        // Only the chunks that exist are packed, however far apart they are.
+        let dense = DenseChunkedStorage::from(&storage);
+        assert_eq!(dense.chunk_count(), 3);
+        assert_eq!(dense.get_elem(IVec2::new(-1_000_000, 2_000_000)), Some(&3));
+        assert_eq!(dense.get_elem(IVec2::new(-1, -1)), Some(&2));
+        assert_eq!(dense.get_elem(IVec2::new(-999, -1000)), None);
+
+        let mut indices = storage
+            .indexed_iter_some()
+            .map(|(index, elem)| (*elem, index))
+            .collect::<Vec<_>>();
+        indices.sort_by_key(|(elem, _)| *elem);
+        assert_eq!(
+            indices,
+            vec![
+                (1, IVec2::new(-1000, -1000)),
+                (2, IVec2::new(-1, -1)),
+                (3, IVec2::new(-1_000_000, 2_000_000)),
+            ]
+        );
+
+        assert_eq!(storage.remove_elem(IVec2::new(-1000, -1000)), Some(1));
+        assert_eq!(storage.get_elem(IVec2::new(-1000, -1000)), None);
+    }
+
+    #[test]
+    fn test_iter_region() {
+        let mut storage = ChunkedStorage::<i32>::new(4);
+        storage.set_elem(IVec2::new(0, 0), 1);
+        storage.set_elem(IVec2::new(3, 3), 2);
+        storage.set_elem(IVec2::new(4, 5), 3);
+        storage.set_elem(IVec2::new(7, 7), 4);
+        // The chunks (1, 0) and (0, 1) are not allocated.
+        assert_eq!(storage.chunks.len(), 2);
+
+        // A 5x5 region across the chunk borders.
+        let region = storage
+            .iter_region(IVec2::new(2, 2), IVec2::new(6, 6))
+            .collect::<Vec<_>>();
+        assert_eq!(region.len(), 25);
+        let visited = region
+            .iter()
+            .map(|(index, _)| *index)
+            .collect::<bevy::utils::HashSet<_>>();
+        assert_eq!(visited.len(), 25);
+        assert!(visited
+            .iter()
+            .all(|index| IAabb2d::new(2, 2, 6, 6).contains(*index)));
+        region
+            .iter()
+            .for_each(|(index, elem)| assert_eq!(*elem, storage.get_elem(*index)));
+        assert_eq!(region.iter().filter(|(_, elem)| elem.is_some()).count(), 2);
+
+        // Clipped to the allocated chunks.
+        assert_eq!(
+            storage
+                .iter_region(IVec2::new(-3, -3), IVec2::new(1, 1))
+                .count(),
+            4
+        );
+        assert_eq!(
+            storage
+                .iter_region(IVec2::new(10, 10), IVec2::new(12, 12))
+                .count(),
+            0
+        );
+    }
 }
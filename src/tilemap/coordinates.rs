@@ -41,6 +41,140 @@ pub fn index_to_rel(
     index_to_world(index, ty, transform, pivot, slot_size) - transform.translation
 }
 
+/// Where a position should be snapped to inside a grid cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GridSnap {
+    #[default]
+    Center,
+    Corner,
+}
+
+/// Convert a world position into the space where the corner of the slot `(0, 0)` is the origin.
+fn world_to_grid(
+    world_pos: Vec2,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    grid_size: Vec2,
+) -> Vec2 {
+    transform.get_rotation_matrix().transpose() * (world_pos - transform.translation)
+        + pivot * grid_size
+}
+
+/// Snap a world position to the grid of a square tilemap.
+///
+/// Every cell is split into `subdivisions x subdivisions` sub cells,
+/// so `0` or `1` means snapping to the cells themselves.
+pub fn snap_to_grid(
+    world_pos: Vec2,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    grid_size: Vec2,
+    subdivisions: u32,
+    snap: GridSnap,
+) -> Vec2 {
+    let step = grid_size / subdivisions.max(1) as f32;
+    let grid_pos = world_to_grid(world_pos, transform, pivot, grid_size) / step;
+    let snapped = match snap {
+        GridSnap::Center => grid_pos.floor() + 0.5,
+        GridSnap::Corner => grid_pos.round(),
+    } * step;
+    transform.transform_point(snapped - pivot * grid_size)
+}
+
+/// Get the index of the cell of a square tilemap that contains the world position.
+///
+/// Shorthand for `world_to_index()` with `TilemapType::Square`.
+#[inline]
+pub fn snap_to_cell(
+    world_pos: Vec2,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    grid_size: Vec2,
+) -> IVec2 {
+    world_to_index(world_pos, TilemapType::Square, transform, pivot, grid_size)
+}
+
+/// The position of the bottom left corner of a slot's mesh, relative to the tilemap.
+fn slot_origin(index: Vec2, ty: TilemapType, slot_size: Vec2) -> Vec2 {
+    match ty {
+        TilemapType::Square => index * slot_size,
+        TilemapType::Isometric => Vec2::new(index.x - index.y, index.x + index.y) / 2. * slot_size,
+        TilemapType::Hexagonal(legs) => Vec2::new(
+            slot_size.x * (index.x - 0.5 * index.y),
+            (slot_size.y + legs as f32) / 2. * index.y,
+        ),
+    }
+}
+
+/// The corners of a tile mesh relative to the tilemap, in the same order as the vertices
+/// generated in `tilemap.wgsl`: bottom left, top left, top right and bottom right.
+///
+/// `anchor` is `TileAnchor`, see it for how tiles larger than their slots are placed.
+pub fn tile_mesh_corners(
+    index: IVec2,
+    ty: TilemapType,
+    pivot: Vec2,
+    anchor: Vec2,
+    render_size: Vec2,
+    slot_size: Vec2,
+) -> [Vec2; 4] {
+    let origin = slot_origin(index.as_vec2(), ty, slot_size) + anchor * (slot_size - render_size);
+    [Vec2::ZERO, Vec2::Y, Vec2::ONE, Vec2::X].map(|corner| (corner - pivot) * render_size + origin)
+}
+
+/// Get the world position of the center of a slot.
+///
+/// This is the inverse of `world_to_index()`.
+pub fn index_to_world_center(
+    index: IVec2,
+    ty: TilemapType,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    slot_size: Vec2,
+) -> Vec2 {
+    transform.transform_point(
+        slot_origin(index.as_vec2(), ty, slot_size) + (Vec2::splat(0.5) - pivot) * slot_size,
+    )
+}
+
+/// Get the index of the slot that contains the world position.
+///
+/// Positions right on the boundary belong to the slot on the right/top.
+/// For hexagonal tilemaps, the slot with the nearest center is picked.
+pub fn world_to_index(
+    world_pos: Vec2,
+    ty: TilemapType,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    slot_size: Vec2,
+) -> IVec2 {
+    // Relative to the center of the slot at the origin.
+    let rel = transform.get_rotation_matrix().transpose() * (world_pos - transform.translation)
+        - (Vec2::splat(0.5) - pivot) * slot_size;
+
+    match ty {
+        TilemapType::Square => (rel / slot_size + 0.5).floor().as_ivec2(),
+        TilemapType::Isometric => {
+            let rel = rel / slot_size;
+            (Vec2::new(rel.x + rel.y, rel.y - rel.x) + 0.5)
+                .floor()
+                .as_ivec2()
+        }
+        TilemapType::Hexagonal(legs) => {
+            let y = (rel.y / ((slot_size.y + legs as f32) / 2.)).round() as i32;
+            let x = (rel.x / slot_size.x + 0.5 * y as f32).round() as i32;
+            (y - 1..=y + 1)
+                .flat_map(|y| (x - 1..=x + 1).map(move |x| IVec2 { x, y }))
+                .min_by(|a, b| {
+                    let da = slot_origin(a.as_vec2(), ty, slot_size).distance_squared(rel);
+                    let db = slot_origin(b.as_vec2(), ty, slot_size).distance_squared(rel);
+                    da.total_cmp(&db)
+                })
+                .unwrap()
+        }
+    }
+}
+
 /// Get the tile collider in local space.
 pub fn get_tile_collider(
     ty: TilemapType,
@@ -224,6 +358,8 @@ pub fn destaggerize_index(index: IVec2, staggered_mode: StaggerMode) -> IVec2 {
 
 #[cfg(test)]
 mod test {
+    use crate::tilemap::map::TileAnchor;
+
     use super::*;
 
     #[test]
@@ -234,4 +370,172 @@ mod test {
         let size = calculate_map_size_staggered(size, slot_size, leg);
         assert_eq!(size, Vec2::new(112., 66.));
     }
+
+    #[test]
+    fn test_tile_mesh_corners() {
+        // A 16x32 tree over 16x16 slots.
+        let corners = |anchor| {
+            tile_mesh_corners(
+                IVec2::new(2, 1),
+                TilemapType::Square,
+                Vec2::ZERO,
+                anchor,
+                Vec2::new(16., 32.),
+                Vec2::splat(16.),
+            )
+        };
+        let bottom = [
+            Vec2::new(32., 16.),
+            Vec2::new(32., 48.),
+            Vec2::new(48., 48.),
+            Vec2::new(48., 16.),
+        ];
+        assert_eq!(corners(TileAnchor::BOTTOM.0), bottom);
+        assert_eq!(corners(TileAnchor::BOTTOM_LEFT.0), bottom);
+        assert_eq!(
+            corners(TileAnchor::CENTER.0),
+            bottom.map(|c| c - Vec2::new(0., 8.))
+        );
+
+        // A 32x32 tile is centered horizontally.
+        let corners = tile_mesh_corners(
+            IVec2::ZERO,
+            TilemapType::Square,
+            Vec2::ZERO,
+            TileAnchor::BOTTOM.0,
+            Vec2::splat(32.),
+            Vec2::splat(16.),
+        );
+        assert_eq!(corners[0], Vec2::new(-8., 0.));
+        assert_eq!(corners[2], Vec2::new(24., 32.));
+    }
+
+    #[test]
+    fn test_world_to_index() {
+        let slot_size = Vec2::splat(16.);
+        let transform = TilemapTransform::IDENTITY;
+        let square =
+            |pos, pivot| world_to_index(pos, TilemapType::Square, &transform, pivot, slot_size);
+
+        // Boundaries belong to the slot on the right/top.
+        assert_eq!(square(Vec2::new(16., 0.), Vec2::ZERO), IVec2::new(1, 0));
+        assert_eq!(square(Vec2::new(15.9, 15.9), Vec2::ZERO), IVec2::new(0, 0));
+        assert_eq!(square(Vec2::new(0., -16.), Vec2::ZERO), IVec2::new(0, -1));
+        assert_eq!(
+            square(Vec2::new(-0.1, -16.1), Vec2::ZERO),
+            IVec2::new(-1, -2)
+        );
+        // Tiles are centered on their slots.
+        assert_eq!(
+            square(Vec2::new(-8., 7.9), Vec2::splat(0.5)),
+            IVec2::new(0, 0)
+        );
+        assert_eq!(
+            square(Vec2::new(-8.1, -8.), Vec2::splat(0.5)),
+            IVec2::new(-1, 0)
+        );
+
+        let transform = TilemapTransform {
+            translation: Vec2::new(100., -50.),
+            rotation: crate::tilemap::map::TilemapRotation::Cw90,
+            ..Default::default()
+        };
+        for ty in [
+            TilemapType::Square,
+            TilemapType::Isometric,
+            TilemapType::Hexagonal(8),
+        ] {
+            for index in [
+                IVec2::new(0, 0),
+                IVec2::new(3, -2),
+                IVec2::new(-5, -7),
+                IVec2::new(-1, 4),
+            ] {
+                let center = index_to_world_center(index, ty, &transform, Vec2::ZERO, slot_size);
+                assert_eq!(
+                    world_to_index(center, ty, &transform, Vec2::ZERO, slot_size),
+                    index
+                );
+            }
+        }
+
+        // The right corner of the isometric tile at the origin.
+        let iso = |pos| {
+            world_to_index(
+                pos,
+                TilemapType::Isometric,
+                &TilemapTransform::IDENTITY,
+                Vec2::ZERO,
+                slot_size,
+            )
+        };
+        assert_eq!(iso(Vec2::new(15.9, 8.)), IVec2::new(0, 0));
+        assert_eq!(iso(Vec2::new(16., 8.)), IVec2::new(1, 0));
+        assert_eq!(iso(Vec2::new(-0.1, 8.)), IVec2::new(-1, 1));
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let grid_size = Vec2::splat(16.);
+        let transform = TilemapTransform::IDENTITY;
+        let snap = |pos, sub, mode| snap_to_grid(pos, &transform, Vec2::ZERO, grid_size, sub, mode);
+
+        assert_eq!(
+            snap(Vec2::new(5., 7.), 1, GridSnap::Center),
+            Vec2::new(8., 8.)
+        );
+        assert_eq!(
+            snap(Vec2::new(9., 7.), 1, GridSnap::Corner),
+            Vec2::new(16., 0.)
+        );
+        assert_eq!(
+            snap(Vec2::new(-3., 20.), 0, GridSnap::Center),
+            Vec2::new(-8., 24.)
+        );
+        assert_eq!(
+            snap(Vec2::new(5., 7.), 2, GridSnap::Center),
+            Vec2::new(4., 4.)
+        );
+        assert_eq!(
+            snap(Vec2::new(13., -3.), 2, GridSnap::Corner),
+            Vec2::new(16., 0.)
+        );
+        assert_eq!(
+            snap_to_cell(Vec2::new(-3., 20.), &transform, Vec2::ZERO, grid_size),
+            IVec2::new(-1, 1)
+        );
+
+        let transform = TilemapTransform::from_translation(Vec2::new(100., 0.));
+        let pivot = Vec2::splat(0.5);
+        assert_eq!(
+            snap_to_grid(
+                Vec2::new(103., 5.),
+                &transform,
+                pivot,
+                grid_size,
+                1,
+                GridSnap::Center
+            ),
+            Vec2::new(100., 0.)
+        );
+        assert_eq!(
+            snap_to_grid(
+                Vec2::new(103., 5.),
+                &transform,
+                pivot,
+                grid_size,
+                1,
+                GridSnap::Corner
+            ),
+            Vec2::new(108., 8.)
+        );
+        assert_eq!(
+            snap_to_cell(Vec2::new(93., 0.), &transform, pivot, grid_size),
+            IVec2::ZERO
+        );
+        assert_eq!(
+            snap_to_cell(Vec2::new(91., 0.), &transform, pivot, grid_size),
+            IVec2::new(-1, 0)
+        );
+    }
 }
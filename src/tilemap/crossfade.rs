@@ -0,0 +1,162 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::Without,
+        system::{Commands, Query, Res},
+    },
+    math::Vec4,
+    reflect::Reflect,
+    time::Time,
+};
+
+use super::{
+    despawn::DespawnMe,
+    map::{TilemapLayerOpacities, TilemapTransform},
+};
+
+/// Fade a tilemap in while fading another one out, for example swapping
+/// the day version of a map for the night version.
+///
+/// Add this to the new tilemap. It will be aligned to the old one so the tiles
+/// line up, and the old one will be despawned once the crossfade is finished.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TilemapCrossfade {
+    /// The old tilemap.
+    pub from: Entity,
+    /// The duration in seconds.
+    pub duration: f32,
+    pub(crate) elapsed: f32,
+    /// The opacities of the old and new tilemap before the crossfade.
+    pub(crate) opacities: Option<(Vec4, Vec4)>,
+}
+
+impl TilemapCrossfade {
+    pub fn new(from: Entity, duration: f32) -> Self {
+        Self {
+            from,
+            duration,
+            elapsed: 0.,
+            opacities: None,
+        }
+    }
+
+    /// How much the new tilemap has faded in, from 0 to 1.
+    #[inline]
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0. {
+            1.
+        } else {
+            (self.elapsed / self.duration).clamp(0., 1.)
+        }
+    }
+}
+
+pub fn tilemap_crossfader(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut crossfades_query: Query<(
+        Entity,
+        &mut TilemapCrossfade,
+        &mut TilemapLayerOpacities,
+        &mut TilemapTransform,
+    )>,
+    mut tilemaps_query: Query<
+        (&mut TilemapLayerOpacities, &TilemapTransform),
+        Without<TilemapCrossfade>,
+    >,
+) {
+    crossfades_query.iter_mut().for_each(
+        |(entity, mut crossfade, mut opacities, mut transform)| {
+            let mut from = tilemaps_query.get_mut(crossfade.from).ok();
+
+            let (from_opacities, to_opacities) = match crossfade.opacities {
+                Some(o) => o,
+                None => {
+                    let from_opacities = from.as_ref().map(|(o, _)| o.0).unwrap_or_default();
+                    if let Some((_, from_transform)) = &from {
+                        *transform = **from_transform;
+                    }
+                    crossfade.opacities = Some((from_opacities, opacities.0));
+                    (from_opacities, opacities.0)
+                }
+            };
+
+            crossfade.elapsed += time.delta_seconds();
+            let progress = crossfade.progress();
+            opacities.0 = to_opacities * progress;
+            if let Some((from, _)) = &mut from {
+                from.0 = from_opacities * (1. - progress);
+            }
+
+            if progress >= 1. {
+                if from.is_some() {
+                    commands.entity(crossfade.from).insert(DespawnMe);
+                }
+                commands.entity(entity).remove::<TilemapCrossfade>();
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::app::{App, PreUpdate, Update};
+
+    use crate::tilemap::despawn::despawn_applier;
+
+    use super::*;
+
+    #[test]
+    fn test_crossfade() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_systems(PreUpdate, despawn_applier)
+            .add_systems(Update, tilemap_crossfader);
+
+        let transform = TilemapTransform::from_translation(bevy::math::Vec2::new(32., -16.));
+        let day = app
+            .world
+            .spawn((TilemapLayerOpacities::default(), transform))
+            .id();
+        let night = app
+            .world
+            .spawn((
+                TilemapLayerOpacities(Vec4::new(1., 1., 0.5, 0.5)),
+                TilemapTransform::default(),
+                TilemapCrossfade::new(day, 2.),
+            ))
+            .id();
+
+        app.world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(1));
+        app.update();
+
+        let opacities =
+            |app: &App, entity| app.world.get::<TilemapLayerOpacities>(entity).unwrap().0;
+        assert!(opacities(&app, day).abs_diff_eq(Vec4::splat(0.5), 1e-4));
+        assert!(opacities(&app, night).abs_diff_eq(Vec4::new(0.5, 0.5, 0.25, 0.25), 1e-4));
+        assert_eq!(
+            app.world
+                .get::<TilemapTransform>(night)
+                .unwrap()
+                .translation,
+            transform.translation
+        );
+
+        app.world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(1));
+        app.update();
+        assert!(opacities(&app, day).abs_diff_eq(Vec4::ZERO, 1e-4));
+        assert!(opacities(&app, night).abs_diff_eq(Vec4::new(1., 1., 0.5, 0.5), 1e-4));
+        assert!(app.world.get::<TilemapCrossfade>(night).is_none());
+
+        app.update();
+        assert!(app.world.get_entity(day).is_none());
+        assert!(app.world.get_entity(night).is_some());
+    }
+}
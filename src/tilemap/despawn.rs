@@ -3,6 +3,7 @@ use bevy::{
         component::Component,
         entity::Entity,
         query::{Or, With},
+        removal_detection::RemovedComponents,
         system::{Commands, ParallelCommands, Query},
     },
     math::IVec2,
@@ -37,15 +38,10 @@ pub fn despawn_applier(
     });
 }
 
-pub fn despawn_tilemap(
-    mut commands: Commands,
-    query: Query<Entity, (With<DespawnMe>, With<TilemapStorage>)>,
-) {
-    let mut despawned_tilemaps = Vec::new();
-
-    query.iter().for_each(|entity| {
-        despawned_tilemaps.push(DespawnedTilemap(entity));
-    });
+/// Announce the tilemaps that are despawned or lost their `TilemapStorage`,
+/// no matter whether they are despawned using `DespawnMe` or not.
+pub fn despawn_tilemap(mut commands: Commands, mut removed: RemovedComponents<TilemapStorage>) {
+    let despawned_tilemaps = removed.read().map(DespawnedTilemap).collect::<Vec<_>>();
 
     commands.spawn_batch(despawned_tilemaps);
 }
@@ -0,0 +1,210 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{ecs::system::Query, math::IVec2, reflect::Reflect, utils::HashMap};
+
+use crate::math::grid;
+
+use super::{map::TilemapStorage, tile::Tile};
+
+/// How the distance between two cells is measured.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum DistanceMetric {
+    /// Diagonal steps cost the same as orthogonal ones, see `grid::chebyshev()`.
+    #[default]
+    Chebyshev,
+    /// The straight line distance, rounded to the nearest integer.
+    Euclidean,
+}
+
+impl DistanceMetric {
+    /// The value used to order cells. It's the squared distance for `Euclidean`.
+    #[inline]
+    fn key(self, delta: IVec2) -> u32 {
+        match self {
+            DistanceMetric::Chebyshev => grid::chebyshev(delta, IVec2::ZERO),
+            DistanceMetric::Euclidean => delta.length_squared() as u32,
+        }
+    }
+
+    #[inline]
+    fn distance(self, delta: IVec2) -> u32 {
+        match self {
+            DistanceMetric::Chebyshev => self.key(delta),
+            DistanceMetric::Euclidean => (self.key(delta) as f32).sqrt().round() as u32,
+        }
+    }
+}
+
+/// The distance from each tile to the nearest solid tile.
+/// Created by `TilemapStorage::distance_field()`.
+#[derive(Debug, Default, Clone, Reflect)]
+pub struct DistanceField {
+    pub(crate) metric: DistanceMetric,
+    pub(crate) distances: HashMap<IVec2, u32>,
+}
+
+impl DistanceField {
+    /// Get the distance to the nearest solid tile. Solid tiles themselves are `0`.
+    ///
+    /// Returns `u32::MAX` if there's no tile at `index`, or no solid tile can be reached from it.
+    #[inline]
+    pub fn at(&self, index: IVec2) -> u32 {
+        self.distances.get(&index).cloned().unwrap_or(u32::MAX)
+    }
+
+    #[inline]
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&IVec2, &u32)> {
+        self.distances.iter()
+    }
+}
+
+impl TilemapStorage {
+    /// Compute the distance from each tile to the nearest tile that `solid` returns true for.
+    ///
+    /// This is a multi-source search starting from all the solid tiles,
+    /// which only walks through cells that have tiles. For `Euclidean`,
+    /// every cell takes the nearest solid tile of its neighbours,
+    /// which is exact in most cases but can be off by one around concave walls.
+    pub fn distance_field(
+        &self,
+        tiles_query: &Query<&Tile>,
+        metric: DistanceMetric,
+        solid: impl Fn(&Tile) -> bool,
+    ) -> DistanceField {
+        let mut tiles = HashMap::new();
+        for (chunk_index, chunk) in self.storage.chunks.iter() {
+            for (in_chunk_index, entity) in chunk.iter().enumerate() {
+                let Some(tile) = entity.and_then(|e| tiles_query.get(e).ok()) else {
+                    continue;
+                };
+                let index = self
+                    .storage
+                    .inverse_transform_index(*chunk_index, in_chunk_index);
+                tiles.insert(index, solid(tile));
+            }
+        }
+
+        // The nearest solid tile of each cell.
+        let mut nearest = HashMap::with_capacity(tiles.len());
+        let mut queue = BinaryHeap::new();
+        for (index, _) in tiles.iter().filter(|(_, solid)| **solid) {
+            nearest.insert(*index, *index);
+            queue.push(Reverse((0, index.x, index.y)));
+        }
+
+        while let Some(Reverse((key, x, y))) = queue.pop() {
+            let index = IVec2 { x, y };
+            let source = nearest[&index];
+            if metric.key(index - source) != key {
+                // This cell has found a nearer solid tile.
+                continue;
+            }
+
+            for offset in [
+                IVec2::Y,
+                IVec2::X,
+                IVec2::NEG_X,
+                IVec2::NEG_Y,
+                IVec2::ONE,
+                IVec2::new(1, -1),
+                IVec2::new(-1, 1),
+                IVec2::NEG_ONE,
+            ] {
+                let neighbour = index + offset;
+                if !tiles.contains_key(&neighbour) {
+                    continue;
+                }
+
+                let key = metric.key(neighbour - source);
+                if nearest
+                    .get(&neighbour)
+                    .is_none_or(|n| key < metric.key(neighbour - *n))
+                {
+                    nearest.insert(neighbour, source);
+                    queue.push(Reverse((key, neighbour.x, neighbour.y)));
+                }
+            }
+        }
+
+        DistanceField {
+            metric,
+            distances: nearest
+                .into_iter()
+                .map(|(index, source)| (index, metric.distance(index - source)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::{
+        entity::Entity,
+        system::{CommandQueue, Commands, SystemState},
+        world::World,
+    };
+
+    use crate::{
+        math::TileArea,
+        tilemap::tile::{TileBuilder, TileLayer, TileTexture},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_distance_field() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut storage = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            storage.fill_rect_custom(
+                &mut commands,
+                TileArea::new(IVec2::ZERO, bevy::math::UVec2::new(6, 3)),
+                |index| {
+                    // A wall on the left column.
+                    let wall = index.x == 0;
+                    Some(TileBuilder::new().with_layer(
+                        0,
+                        TileLayer {
+                            atlas_index: wall as i32,
+                            ..Default::default()
+                        },
+                    ))
+                },
+                false,
+            );
+        }
+        queue.apply(&mut world);
+
+        let mut state = SystemState::<Query<&Tile>>::new(&mut world);
+        let tiles_query = state.get(&world);
+        let is_wall = |tile: &Tile| match &tile.texture {
+            TileTexture::Static(layers) => layers[0].atlas_index == 1,
+            TileTexture::Animated(_) => false,
+        };
+
+        for metric in [DistanceMetric::Chebyshev, DistanceMetric::Euclidean] {
+            let field = storage.distance_field(&tiles_query, metric, is_wall);
+            for y in 0..3 {
+                for x in 0..6 {
+                    assert_eq!(field.at(IVec2::new(x, y)), x as u32);
+                }
+            }
+            assert_eq!(field.at(IVec2::new(6, 0)), u32::MAX);
+        }
+
+        // A single wall tile at the corner.
+        let is_corner = |tile: &Tile| tile.index == IVec2::ZERO;
+        let chebyshev = storage.distance_field(&tiles_query, DistanceMetric::Chebyshev, is_corner);
+        let euclidean = storage.distance_field(&tiles_query, DistanceMetric::Euclidean, is_corner);
+        assert_eq!(chebyshev.at(IVec2::new(2, 2)), 2);
+        assert_eq!(euclidean.at(IVec2::new(2, 2)), 3);
+        assert_eq!(euclidean.at(IVec2::new(5, 0)), 5);
+    }
+}
@@ -1,16 +1,17 @@
-use std::{f32::consts::SQRT_2, fmt::Debug};
+use std::{f32::consts::SQRT_2, fmt::Debug, num::NonZeroU32};
 
 use bevy::{
     asset::{Asset, Handle},
     ecs::{
         component::Component,
-        query::Changed,
+        query::{Added, Changed, Or},
         system::{Query, SystemParamItem},
     },
     math::{Mat2, Quat, Vec4},
     prelude::{Commands, Entity, IVec2, Image, UVec2, Vec2},
     reflect::Reflect,
     render::{
+        color::Color,
         render_asset::{PrepareAssetError, RenderAsset, RenderAssetUsages},
         render_resource::FilterMode,
     },
@@ -26,10 +27,13 @@ use crate::math::{
 use crate::tilemap::tile::RawTileAnimation;
 
 use super::{
-    buffers::TileBuilderBuffer,
-    chunking::storage::{ChunkedStorage, EntityChunkedStorage},
+    buffers::{Brush, BrushTransform, TileBuilderBuffer, TileClip},
+    chunking::storage::{
+        ChunkedStorage, DenseChunkedStorage, DenseTileChunkedStorage, EntityChunkedStorage,
+    },
+    coordinates::world_to_index,
     despawn::DespawnMe,
-    tile::{TileAnimation, TileBuilder, TileUpdater},
+    tile::{Tile, TileAnimation, TileBuilder, TileLayer, TileTexture, TileUpdater},
 };
 
 /// Defines the shape of tiles in a tilemap.
@@ -197,6 +201,7 @@ pub struct TilemapTextures {
     pub(crate) max_size: UVec2,
     #[reflect(ignore)]
     pub(crate) filter_mode: FilterMode,
+    pub(crate) mipmaps: bool,
 }
 
 impl RenderAsset for TilemapTextures {
@@ -241,7 +246,54 @@ impl TilemapTextures {
             start_index,
             max_size,
             filter_mode,
+            mipmaps: false,
+        }
+    }
+
+    /// Use `FilterMode::Nearest` for pixel art, and `FilterMode::Linear` for smooth textures.
+    pub fn with_filter_mode(mut self, filter_mode: FilterMode) -> Self {
+        self.filter_mode = filter_mode;
+        self
+    }
+
+    #[inline]
+    pub fn filter_mode(&self) -> FilterMode {
+        self.filter_mode
+    }
+
+    /// Generate mipmaps for the textures when they are loaded, and sample them using
+    /// trilinear filtering. This removes the shimmering when the camera zooms far out.
+    ///
+    /// Every tile is downsampled on its own, so colors won't bleed between adjacent tiles.
+    /// The mip chain stops when the tile size is no longer divisible by 2, so use
+    /// power of two tile sizes to get the full chain.
+    /// This is also why the `Array` texture mode is recommended with mipmaps:
+    /// each tile lives in its own layer, so sampling won't read the neighbouring tiles
+    /// like it does in a packed atlas.
+    pub fn with_mipmaps(mut self, enabled: bool) -> Self {
+        self.mipmaps = enabled;
+        self
+    }
+
+    #[inline]
+    pub fn mipmaps(&self) -> bool {
+        self.mipmaps
+    }
+
+    /// The number of mip levels of the texture array, `1` if mipmaps are disabled.
+    pub fn mip_level_count(&self) -> u32 {
+        if !self.mipmaps {
+            return 1;
         }
+
+        self.textures
+            .iter()
+            .map(|t| {
+                let size = t.desc.tile_size;
+                size.x.trailing_zeros().min(size.y.trailing_zeros()) + 1
+            })
+            .min()
+            .unwrap_or(1)
     }
 
     pub fn assert_uniform_tile_size(&self) {
@@ -300,10 +352,11 @@ impl TilemapTexture {
     }
 
     pub fn as_atlas_layout(&self) -> TextureAtlasLayout {
+        let tile_count = self.desc.size / self.desc.tile_size;
         TextureAtlasLayout::from_grid(
             self.desc.tile_size.as_vec2(),
-            self.desc.size.x as usize,
-            self.desc.size.y as usize,
+            tile_count.x as usize,
+            tile_count.y as usize,
             Some(Vec2::ZERO),
             Some(Vec2::ZERO),
         )
@@ -329,6 +382,21 @@ impl TilemapTexture {
             max: (tile_index + 1) * self.desc.tile_size - 1,
         }
     }
+
+    /// Get the index of the tile whose pixel rect is `src`.
+    /// This is the inverse of `get_atlas_urect()`.
+    ///
+    /// Returns `None` if `src` is not exactly one tile of this texture.
+    pub fn get_atlas_index(&self, src: UAabb2d) -> Option<u32> {
+        let tile_count = self.desc.size / self.desc.tile_size;
+        let tile_index = src.min / self.desc.tile_size;
+        if tile_index.cmpge(tile_count).any() {
+            return None;
+        }
+
+        let index = tile_index.y * tile_count.x + tile_index.x;
+        (self.get_atlas_urect(index) == src).then_some(index)
+    }
 }
 
 #[derive(Component, Debug, Default, Clone)]
@@ -406,6 +474,24 @@ pub struct TilemapSlotSize(pub Vec2);
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TilePivot(pub Vec2);
 
+/// Where tiles larger than their slots are anchored inside the slot.
+///
+/// The point `anchor` of the tile mesh is placed at the point `anchor` of the slot,
+/// both relative to their own size. For example, a 16x32 tree over a 16x16 slot
+/// with `TileAnchor::BOTTOM` is centered horizontally and extends upward.
+///
+/// This has no effect if `TileRenderSize` equals `TilemapSlotSize`.
+/// Without this component the tiles are anchored at the bottom left.
+#[derive(Component, Default, Debug, Clone, Copy, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileAnchor(pub Vec2);
+
+impl TileAnchor {
+    pub const BOTTOM_LEFT: Self = Self(Vec2::ZERO);
+    pub const BOTTOM: Self = Self(Vec2::new(0.5, 0.));
+    pub const CENTER: Self = Self(Vec2::splat(0.5));
+}
+
 /// The opacity of each tile layer.
 #[derive(Component, Debug, Clone, Copy, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
@@ -417,6 +503,42 @@ impl Default for TilemapLayerOpacities {
     }
 }
 
+/// Fade or hide the whole tilemap.
+///
+/// `alpha` fades the whole tilemap, on top of `TilemapLayerOpacities` and the tints of tiles.
+/// Hidden tilemaps are not extracted for rendering at all, just like
+/// the ones hidden using `Visibility`.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapVisibility {
+    pub visible: bool,
+    pub alpha: f32,
+}
+
+impl Default for TilemapVisibility {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            alpha: 1.,
+        }
+    }
+}
+
+/// Only draw the part of the tilemap inside this rect, which is useful for tilemaps
+/// shown in UI panels. Unlike culling, tiles crossing the edges are clipped.
+///
+/// The rect is in physical pixels relative to the top left corner of the camera viewport,
+/// and `max` is excluded. `None` draws the whole tilemap.
+#[derive(Component, Default, Debug, Clone, Copy, Reflect)]
+pub struct TilemapScissor(pub Option<UAabb2d>);
+
+/// Only draw the tiles whose indices are inside this aabb, `max` included.
+///
+/// Unlike `TilemapScissor`, this is in tile indices so the clipped area moves with the tilemap,
+/// and the tiles outside produce no geometry at all. `None` draws the whole tilemap.
+#[derive(Component, Default, Debug, Clone, Copy, Reflect)]
+pub struct TilemapClip(pub Option<IAabb2d>);
+
 /// The tilemap's aabb.
 #[derive(Component, Default, Debug, Clone, Copy, Reflect)]
 pub struct TilemapAabbs {
@@ -444,6 +566,9 @@ pub struct TilemapStorage {
     pub(crate) storage: EntityChunkedStorage,
     pub(crate) reserved: HashMap<IVec2, Aabb2d>,
     pub(crate) calc_queue: HashSet<IVec2>,
+    /// Chunks that are modified since the last save.
+    #[cfg_attr(feature = "serializing", serde(skip))]
+    pub(crate) dirty_chunks: HashSet<IVec2>,
 }
 
 impl TilemapStorage {
@@ -454,6 +579,39 @@ impl TilemapStorage {
             ..Default::default()
         }
     }
+
+    /// The world space aabb of the tiles that are set, for example to fit the camera to a level.
+    ///
+    /// Returns `None` if the tilemap is empty.
+    pub fn world_bounds(
+        &self,
+        ty: TilemapType,
+        tile_pivot: Vec2,
+        axis_flip: TilemapAxisFlip,
+        slot_size: Vec2,
+        transform: TilemapTransform,
+    ) -> Option<Aabb2d> {
+        self.storage
+            .occupied_aabb()
+            .map(|tiles| Aabb2d::from_tiles(tiles, ty, tile_pivot, axis_flip, slot_size, transform))
+    }
+
+    /// The world space aabb of all the allocated chunks, which is cheaper than `world_bounds`
+    /// as the tiles are not iterated, but may cover empty space.
+    ///
+    /// Returns `None` if the tilemap is empty.
+    pub fn grid_bounds(
+        &self,
+        ty: TilemapType,
+        tile_pivot: Vec2,
+        axis_flip: TilemapAxisFlip,
+        slot_size: Vec2,
+        transform: TilemapTransform,
+    ) -> Option<Aabb2d> {
+        self.storage
+            .allocated_aabb()
+            .map(|tiles| Aabb2d::from_tiles(tiles, ty, tile_pivot, axis_flip, slot_size, transform))
+    }
 }
 
 impl Default for TilemapStorage {
@@ -463,6 +621,7 @@ impl Default for TilemapStorage {
             storage: Default::default(),
             reserved: Default::default(),
             calc_queue: Default::default(),
+            dirty_chunks: Default::default(),
         }
     }
 }
@@ -474,6 +633,51 @@ impl TilemapStorage {
         self.storage.get_elem(index).cloned()
     }
 
+    /// Get the 4 or 8 neighbours of a tile, see `ChunkedStorage::neighbours()`.
+    #[inline]
+    pub fn neighbours<const N: usize>(&self, index: IVec2) -> [Option<Entity>; N] {
+        self.storage.neighbours(index).map(|e| e.cloned())
+    }
+
+    /// Iterate over all the tiles along with their indices, skipping the empty slots.
+    ///
+    /// The order is unspecified.
+    #[inline]
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (IVec2, Entity)> + '_ {
+        self.storage
+            .indexed_iter_some()
+            .map(|(index, e)| (index, *e))
+    }
+
+    /// Iterate over every index between `min` and `max` (both inclusive) along with
+    /// the tile there, including the empty slots. See `ChunkedStorage::iter_region()`.
+    #[inline]
+    pub fn iter_region(
+        &self,
+        min: IVec2,
+        max: IVec2,
+    ) -> impl Iterator<Item = (IVec2, Option<Entity>)> + '_ {
+        self.storage
+            .iter_region(min, max)
+            .map(|(index, e)| (index, e.cloned()))
+    }
+
+    /// Get the tile at the world position, for example under the cursor.
+    ///
+    /// Returns `None` if there's no tile at that position.
+    pub fn get_at_world<'a>(
+        &self,
+        tiles_query: &'a Query<&Tile>,
+        world_pos: Vec2,
+        ty: TilemapType,
+        transform: &TilemapTransform,
+        pivot: Vec2,
+        slot_size: Vec2,
+    ) -> Option<&'a Tile> {
+        let index = world_to_index(world_pos, ty, transform, pivot, slot_size);
+        self.get(index).and_then(|e| tiles_query.get(e).ok())
+    }
+
     /// Get a chunk.
     #[inline]
     pub fn get_chunk(&self, index: IVec2) -> Option<&Vec<Option<Entity>>> {
@@ -500,11 +704,13 @@ impl TilemapStorage {
         let mut tile_entity = commands.spawn_empty();
         self.storage.set_elem(index, tile_entity.id());
         self.reserve(new_tile.chunk_index);
+        self.dirty_chunks.insert(new_tile.chunk_index);
         tile_entity.insert(new_tile);
     }
 
     #[inline]
     pub(crate) fn set_entity(&mut self, index: IVec2, entity: Option<Entity>) {
+        self.mark_dirty(index);
         if let Some(e) = entity {
             let (chunk_index, in_chunk_index) = self.storage.transform_index(index);
             self.storage
@@ -527,9 +733,25 @@ impl TilemapStorage {
     pub fn update(&mut self, commands: &mut Commands, index: IVec2, updater: TileUpdater) {
         if let Some(entity) = self.get(index) {
             commands.entity(entity).insert(updater);
+            self.mark_dirty(index);
         }
     }
 
+    /// Set the tint of a tile, which is multiplied with the tile color in the shader.
+    ///
+    /// Use `Color::WHITE` to clear the tint.
+    #[inline]
+    pub fn set_tile_color(&mut self, commands: &mut Commands, index: IVec2, color: Color) {
+        self.update(
+            commands,
+            index,
+            TileUpdater {
+                tint: Some(color),
+                ..Default::default()
+            },
+        );
+    }
+
     /// Remove a tile.
     #[inline]
     pub fn remove(&mut self, commands: &mut Commands, index: IVec2) {
@@ -560,6 +782,28 @@ impl TilemapStorage {
             });
     }
 
+    /// Remove all the tiles but keep the tilemap with its settings and textures,
+    /// for example before regenerating a procedural level. Use `despawn()` to get rid
+    /// of the tilemap as well.
+    ///
+    /// All the chunks are marked dirty. If `shrink` is true, the chunks are freed
+    /// to reclaim memory, otherwise they are kept empty to be filled again.
+    pub fn clear(&mut self, commands: &mut Commands, shrink: bool) {
+        self.dirty_chunks.extend(self.storage.chunks.keys());
+
+        if shrink {
+            self.remove_all(commands);
+            self.storage.chunks.shrink_to_fit();
+        } else {
+            self.storage
+                .iter_mut()
+                .filter_map(|e| e.take())
+                .for_each(|entity| {
+                    commands.entity(entity).insert(DespawnMe);
+                });
+        }
+    }
+
     /// Declare that a chunk is existent.
     ///
     /// Use `reserve_with_aabb` if you can provide the aabb.
@@ -624,20 +868,102 @@ impl TilemapStorage {
 
         for y in area.origin.y..=area.dest.y {
             for x in area.origin.x..=area.dest.x {
-                let index = IVec2 { x, y };
-                let tile = tile_builder.build_component(index, &self, self.tilemap);
-                let entity = self.get(index).unwrap_or_else(|| {
-                    let e = commands.spawn_empty().id();
-                    self.set_entity(index, Some(e));
-                    e
-                });
-                tile_batch.push((entity, tile));
+                tile_batch.push(self.prepare_batched(commands, IVec2 { x, y }, &tile_builder));
+            }
+        }
+
+        commands.insert_or_spawn_batch(tile_batch);
+    }
+
+    /// Fill the rectangle between two corners with the same tile.
+    ///
+    /// The corners can be given in any order. Tiles out of `bounds` are skipped,
+    /// so out-of-range coordinates are clipped instead of causing a panic.
+    ///
+    /// The changed tiles only mark their render chunks dirty, each of them
+    /// rebuilds and uploads its mesh once in the next frame.
+    pub fn fill_rect_clipped(
+        &mut self,
+        commands: &mut Commands,
+        corner_a: IVec2,
+        corner_b: IVec2,
+        bounds: Option<IAabb2d>,
+        tile_builder: TileBuilder,
+    ) {
+        let mut aabb = IAabb2d {
+            min: corner_a.min(corner_b),
+            max: corner_a.max(corner_b),
+        };
+        if let Some(bounds) = bounds {
+            aabb = aabb.intersection(bounds);
+        }
+        if aabb.min.cmpgt(aabb.max).any() {
+            return;
+        }
+
+        self.fill_rect(
+            commands,
+            TileArea::from_min_max(aabb.min, aabb.max),
+            tile_builder,
+        );
+    }
+
+    /// Draw a line from `start` to `end` (both inclusive) using Bresenham's algorithm.
+    ///
+    /// Tiles out of `bounds` are skipped. All the tiles are inserted in a single batch.
+    pub fn draw_line(
+        &mut self,
+        commands: &mut Commands,
+        start: IVec2,
+        end: IVec2,
+        bounds: Option<IAabb2d>,
+        tile_builder: TileBuilder,
+    ) {
+        let delta = (end - start).abs();
+        let step = (end - start).signum();
+        let mut err = delta.x - delta.y;
+        let mut cur = start;
+        let mut tile_batch = Vec::with_capacity(delta.max_element() as usize + 1);
+
+        loop {
+            if bounds.is_none_or(|b| b.contains(cur)) {
+                tile_batch.push(self.prepare_batched(commands, cur, &tile_builder));
+            }
+            if cur == end {
+                break;
+            }
+
+            let e2 = err * 2;
+            if e2 > -delta.y {
+                err -= delta.y;
+                cur.x += step.x;
+            }
+            if e2 < delta.x {
+                err += delta.x;
+                cur.y += step.y;
             }
         }
 
         commands.insert_or_spawn_batch(tile_batch);
     }
 
+    /// Get or reserve the entity at `index` and build the tile for batch insertion.
+    fn prepare_batched(
+        &mut self,
+        commands: &mut Commands,
+        index: IVec2,
+        tile_builder: &TileBuilder,
+    ) -> (Entity, Tile) {
+        let tile = tile_builder.build_component(index, self, self.tilemap);
+        let entity = self.get(index).unwrap_or_else(|| {
+            let e = commands.spawn_empty().id();
+            self.set_entity(index, Some(e));
+            e
+        });
+        self.mark_dirty(index);
+        (entity, tile)
+    }
+
     /// Fill a rectangle area with tiles returned by `tile_builder`.
     ///
     /// Set `relative_index` to true if your function takes index relative to the area origin.
@@ -669,6 +995,7 @@ impl TilemapStorage {
                     self.set_entity(index, Some(e));
                     e
                 });
+                self.mark_dirty(index);
                 tile_batch.push((entity, tile));
             }
         }
@@ -690,6 +1017,7 @@ impl TilemapStorage {
                 let tile = b.build_component(i + origin, &self, self.tilemap);
 
                 if let Some(e) = self.get(tile.index) {
+                    self.mark_dirty(tile.index);
                     (e, tile)
                 } else {
                     let e = commands.spawn_empty().id();
@@ -702,6 +1030,186 @@ impl TilemapStorage {
         commands.insert_or_spawn_batch(batch);
     }
 
+    /// Copy the tiles in `area` into a detached clip.
+    ///
+    /// Cells without tiles are empty in the clip. See `dump_region()`.
+    pub fn copy_region(&self, tiles_query: &Query<&Tile>, area: TileArea) -> TileClip {
+        TileClip {
+            size: area.extent,
+            anchor: IVec2::ZERO,
+            tiles: self.dump_region(tiles_query, area.origin, area.dest),
+        }
+    }
+
+    /// Find all the tiles showing the tile at `src` (in pixels) of the texture at `texture_index`.
+    /// This is useful when you want to replace all the tiles with the same sprite.
+    ///
+    /// Any layer of a static tile counts. The result is sorted by y then x.
+    pub fn tiles_using_src(
+        &self,
+        tiles_query: &Query<&Tile>,
+        textures: &TilemapTextures,
+        texture_index: u32,
+        src: UAabb2d,
+    ) -> Vec<IVec2> {
+        let Some(local_index) = textures
+            .textures
+            .get(texture_index as usize)
+            .and_then(|t| t.get_atlas_index(src))
+        else {
+            return Vec::new();
+        };
+
+        #[cfg(feature = "atlas")]
+        let is_target = |layer: &TileLayer| {
+            layer.texture_index == texture_index as i32 && layer.atlas_index == local_index as i32
+        };
+        #[cfg(not(feature = "atlas"))]
+        let is_target = {
+            let atlas_index = (textures.start_index[texture_index as usize] + local_index) as i32;
+            move |layer: &TileLayer| layer.atlas_index == atlas_index
+        };
+
+        let mut result = Vec::new();
+        for (chunk_index, chunk) in self.storage.chunks.iter() {
+            for (in_chunk_index, entity) in chunk.iter().enumerate() {
+                let Some(tile) = entity.and_then(|e| tiles_query.get(e).ok()) else {
+                    continue;
+                };
+                let TileTexture::Static(layers) = &tile.texture else {
+                    continue;
+                };
+                if layers.iter().any(is_target) {
+                    result.push(
+                        self.storage
+                            .inverse_transform_index(*chunk_index, in_chunk_index),
+                    );
+                }
+            }
+        }
+
+        result.sort_by_key(|index| (index.y, index.x));
+        result
+    }
+
+    /// Paste the clip so its anchor lands on `dest`.
+    ///
+    /// Empty cells in the clip remove the tiles below them unless `skip_empty` is true.
+    /// Cells out of `bounds` are skipped.
+    pub fn paste(
+        &mut self,
+        commands: &mut Commands,
+        clip: &TileClip,
+        dest: IVec2,
+        bounds: Option<IAabb2d>,
+        skip_empty: bool,
+    ) {
+        let origin = dest - clip.anchor;
+        self.apply_tiles(
+            commands,
+            clip.iter()
+                .map(|(offset, tile)| (origin + offset, tile))
+                .filter(|(index, _)| bounds.is_none_or(|b| b.contains(*index))),
+            skip_empty,
+        );
+    }
+
+    /// Snapshot the tiles between `min` and `max` (both inclusive) into a dense row-major buffer,
+    /// for example to export, hash or diff a map. Empty cells are `None`.
+    ///
+    /// This is the same layout as the tiles of a `TileClip`, see `copy_region()`.
+    /// Unallocated chunks are skipped without looking up every cell.
+    pub fn dump_region(
+        &self,
+        tiles_query: &Query<&Tile>,
+        min: IVec2,
+        max: IVec2,
+    ) -> Vec<Option<TileBuilder>> {
+        if min.cmpgt(max).any() {
+            return Vec::new();
+        }
+
+        let width = (max.x - min.x + 1) as usize;
+        let mut data = vec![None; width * (max.y - min.y + 1) as usize];
+        for (index, entity) in self.iter_region(min, max) {
+            let Some(tile) = entity.and_then(|e| tiles_query.get(e).ok()) else {
+                continue;
+            };
+            let offset = index - min;
+            data[offset.y as usize * width + offset.x as usize] = Some(tile.clone().into());
+        }
+
+        data
+    }
+
+    /// Apply a buffer from `dump_region()` back to the tiles between `min` and `max`.
+    ///
+    /// `None` cells remove the tiles there, so the region ends up exactly as dumped.
+    /// This is `paste()` without an anchor, bounds or skipping the empty cells.
+    ///
+    /// **Notice**: This panics if the length of `data` doesn't match the size of the region.
+    pub fn load_region(
+        &mut self,
+        commands: &mut Commands,
+        min: IVec2,
+        max: IVec2,
+        data: &[Option<TileBuilder>],
+    ) {
+        let width = (max.x - min.x + 1).max(0) as usize;
+        let height = (max.y - min.y + 1).max(0) as usize;
+        assert_eq!(
+            data.len(),
+            width * height,
+            "The buffer doesn't match the size of the region!"
+        );
+
+        self.apply_tiles(
+            commands,
+            data.iter().enumerate().map(|(i, tile)| {
+                (
+                    min + IVec2::new((i % width) as i32, (i / width) as i32),
+                    tile.as_ref(),
+                )
+            }),
+            false,
+        );
+    }
+
+    /// Set or remove the tiles in a single batch. Used by `paste()` and `load_region()`.
+    fn apply_tiles<'a>(
+        &mut self,
+        commands: &mut Commands,
+        tiles: impl Iterator<Item = (IVec2, Option<&'a TileBuilder>)>,
+        skip_empty: bool,
+    ) {
+        let mut tile_batch = Vec::with_capacity(tiles.size_hint().0);
+
+        for (index, tile) in tiles {
+            match tile {
+                Some(builder) => {
+                    tile_batch.push(self.prepare_batched(commands, index, builder));
+                }
+                None if !skip_empty => self.remove(commands, index),
+                None => {}
+            }
+        }
+
+        commands.insert_or_spawn_batch(tile_batch);
+    }
+
+    /// Stamp the brush so its anchor lands on `at`.
+    ///
+    /// The empty cells of the brush leave the tiles below them untouched.
+    pub fn stamp(
+        &mut self,
+        commands: &mut Commands,
+        brush: &Brush,
+        at: IVec2,
+        transform: BrushTransform,
+    ) {
+        self.paste(commands, &brush.transformed(transform), at, None, true);
+    }
+
     /// Simlar to `TilemapStorage::fill_rect()`.
     pub fn update_rect(&mut self, commands: &mut Commands, area: TileArea, updater: TileUpdater) {
         let mut batch = Vec::with_capacity(area.size());
@@ -710,6 +1218,7 @@ impl TilemapStorage {
             for x in area.origin.x..=area.dest.x {
                 if let Some(entity) = self.get(IVec2 { x, y }) {
                     batch.push((entity, updater.clone()));
+                    self.mark_dirty(IVec2 { x, y });
                 }
             }
         }
@@ -730,6 +1239,7 @@ impl TilemapStorage {
         for y in area.origin.y..=area.dest.y {
             for x in area.origin.x..=area.dest.x {
                 if let Some(entity) = self.get(IVec2 { x, y }) {
+                    self.mark_dirty(IVec2 { x, y });
                     batch.push((
                         entity,
                         updater(if relative_index {
@@ -744,6 +1254,228 @@ impl TilemapStorage {
 
         commands.insert_or_spawn_batch(batch);
     }
+
+    /// Replace the 4-connected region of tiles that match the tile at `start` with `new_tile`,
+    /// just like the paint bucket.
+    ///
+    /// `match_fn` receives the start tile and a neighbour, and returns whether the neighbour
+    /// belongs to the region. Empty slots are never filled, and nothing happens if the start tile
+    /// already matches the new tile.
+    ///
+    /// Returns the indices of the changed tiles.
+    pub fn flood_fill(
+        &mut self,
+        commands: &mut Commands,
+        tiles_query: &Query<&Tile>,
+        start: IVec2,
+        new_tile: TileBuilder,
+        match_fn: impl Fn(&Tile, &Tile) -> bool,
+    ) -> HashSet<IVec2> {
+        let mut region = HashSet::new();
+        let Some(start_tile) = self.get(start).and_then(|e| tiles_query.get(e).ok()) else {
+            return region;
+        };
+        if match_fn(
+            start_tile,
+            &new_tile.build_component(start, self, self.tilemap),
+        ) {
+            return region;
+        }
+
+        let mut stack = vec![start];
+        region.insert(start);
+        while let Some(index) = stack.pop() {
+            for offset in [IVec2::Y, IVec2::X, IVec2::NEG_X, IVec2::NEG_Y] {
+                let neighbour = index + offset;
+                if region.contains(&neighbour) {
+                    continue;
+                }
+
+                let Some(tile) = self.get(neighbour).and_then(|e| tiles_query.get(e).ok()) else {
+                    continue;
+                };
+                if match_fn(start_tile, tile) {
+                    region.insert(neighbour);
+                    stack.push(neighbour);
+                }
+            }
+        }
+
+        region.iter().for_each(|index| {
+            self.set(commands, *index, new_tile.clone());
+        });
+        region
+    }
+
+    #[inline]
+    fn mark_dirty(&mut self, index: IVec2) {
+        self.dirty_chunks
+            .insert(self.storage.transform_index(index).0);
+    }
+
+    /// Mark a chunk as modified so it will be saved by `ChunkSaveCache::save_dirty_chunks()`.
+    #[inline]
+    pub fn mark_chunk_dirty(&mut self, chunk_index: IVec2) {
+        self.dirty_chunks.insert(chunk_index);
+    }
+
+    /// Check if a chunk is modified since the last save.
+    #[inline]
+    pub fn is_chunk_dirty(&self, chunk_index: IVec2) -> bool {
+        self.dirty_chunks.contains(&chunk_index)
+    }
+
+    /// Get all the chunks that are modified since the last save.
+    #[inline]
+    pub fn dirty_chunks(&self) -> impl Iterator<Item = &IVec2> {
+        self.dirty_chunks.iter()
+    }
+
+    /// Take all the dirty chunks and mark them as clean.
+    #[inline]
+    pub fn take_dirty_chunks(&mut self) -> Vec<IVec2> {
+        self.dirty_chunks.drain().collect()
+    }
+
+    /// Pack the tiles into a compact read-only view.
+    ///
+    /// Tiles that aren't found in `tiles_query` are left empty.
+    pub fn to_dense(&self, tiles_query: &Query<&Tile>) -> DenseTilemapStorage {
+        let mut palette = Vec::new();
+        let mut ids = HashMap::new();
+        let storage = DenseChunkedStorage::from_mapped(&self.storage, |entity| {
+            let tile: TileBuilder = tiles_query.get(*entity).ok()?.clone().into();
+            let id = *ids.entry(dense_tile_key(&tile)).or_insert_with(|| {
+                palette.push(tile);
+                DenseTileId(NonZeroU32::new(palette.len() as u32).unwrap())
+            });
+            Some(id)
+        });
+
+        DenseTilemapStorage {
+            tilemap: self.tilemap,
+            storage,
+            palette,
+        }
+    }
+}
+
+/// Marks a tilemap as immutable.
+///
+/// The tiles of tilemaps with this component are moved into a `DenseTilemapStorage`,
+/// which packs the tiles without spawning an entity for each of them, and is what gets rendered.
+/// The tile entities are despawned and the `TilemapStorage` is left empty,
+/// so tiles can't be changed, picked or queried through it anymore.
+///
+/// Tiles that are set after this component is added won't be in the dense storage.
+#[derive(Component, Default, Debug, Clone, Copy, Reflect)]
+pub struct ImmutableTilemap;
+
+/// The id of a tile in a `DenseTilemapStorage`, which is the index into its palette plus one.
+///
+/// It's only 4 bytes, and so is `Option<DenseTileId>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DenseTileId(NonZeroU32);
+
+impl DenseTileId {
+    #[inline]
+    pub fn palette_index(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+/// The read-only dense version of `TilemapStorage`.
+///
+/// Each slot holds a `DenseTileId` pointing into a palette of the unique tiles,
+/// so the identical tiles of a map are only stored once.
+#[derive(Component, Debug, Clone)]
+pub struct DenseTilemapStorage {
+    pub(crate) tilemap: Entity,
+    pub(crate) storage: DenseTileChunkedStorage,
+    pub(crate) palette: Vec<TileBuilder>,
+}
+
+impl DenseTilemapStorage {
+    /// Get a tile.
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<&TileBuilder> {
+        self.get_id(index)
+            .map(|id| &self.palette[id.palette_index()])
+    }
+
+    /// Get the id of a tile.
+    #[inline]
+    pub fn get_id(&self, index: IVec2) -> Option<DenseTileId> {
+        self.storage.get_elem(index).copied()
+    }
+
+    /// Get the 4 or 8 neighbours of a tile, see `ChunkedStorage::neighbours()`.
+    #[inline]
+    pub fn neighbours<const N: usize>(&self, index: IVec2) -> [Option<&TileBuilder>; N] {
+        self.storage
+            .neighbours(index)
+            .map(|id| id.map(|id| &self.palette[id.palette_index()]))
+    }
+
+    /// Get a chunk.
+    #[inline]
+    pub fn get_chunk(&self, index: IVec2) -> Option<&[Option<DenseTileId>]> {
+        self.storage.get_chunk(index)
+    }
+
+    /// The unique tiles of this tilemap, indexed by `DenseTileId::palette_index()`.
+    #[inline]
+    pub fn palette(&self) -> &[TileBuilder] {
+        &self.palette
+    }
+
+    /// The tilemap this storage belongs to.
+    #[inline]
+    pub fn tilemap(&self) -> Entity {
+        self.tilemap
+    }
+
+    /// Iterate over all the tiles along with their indices.
+    ///
+    /// The order is unspecified.
+    #[inline]
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (IVec2, &TileBuilder)> + '_ {
+        self.storage
+            .indexed_iter_some()
+            .map(|(index, id)| (index, &self.palette[id.palette_index()]))
+    }
+
+    /// Get the underlying storage.
+    #[inline]
+    pub fn get_storage_raw(&self) -> &DenseTileChunkedStorage {
+        &self.storage
+    }
+}
+
+/// `TileBuilder` can't be hashed because of the floats in it,
+/// so the palette is deduplicated by the bits of its fields.
+fn dense_tile_key(tile: &TileBuilder) -> Vec<u32> {
+    let tint = tile.tint.as_rgba_f32().map(f32::to_bits);
+    match &tile.texture {
+        TileTexture::Static(layers) => {
+            let mut key = [0].into_iter().chain(tint).collect::<Vec<_>>();
+            layers.iter().for_each(|layer| {
+                #[cfg(feature = "atlas")]
+                key.push(layer.texture_index as u32);
+                key.extend([
+                    layer.atlas_index as u32,
+                    layer.flip.bits(),
+                    layer.sublayer as u32,
+                ]);
+            });
+            key
+        }
+        TileTexture::Animated(anim) => [1]
+            .into_iter()
+            .chain(tint)
+            .chain([anim.start, anim.length, anim.fps])
+            .collect(),
+    }
 }
 
 /// The tilemap's animation buffer.
@@ -779,6 +1511,29 @@ impl TilemapAnimations {
             fps: anim.fps,
         }
     }
+
+    /// Get the frame of the animation at the given time in seconds.
+    ///
+    /// This is the same as what the shader does, so it can be used to
+    /// find out what's displayed on the screen.
+    pub fn frame_at(&self, animation: &TileAnimation, time: f32) -> TileLayer {
+        let frame = (time * animation.fps as f32) as u32 % animation.length.max(1);
+
+        #[cfg(not(feature = "atlas"))]
+        return TileLayer {
+            atlas_index: self.0[(animation.start + frame) as usize],
+            ..Default::default()
+        };
+        #[cfg(feature = "atlas")]
+        {
+            let index = (animation.start + frame * 2) as usize;
+            TileLayer {
+                texture_index: self.0[index],
+                atlas_index: self.0[index + 1],
+                ..Default::default()
+            }
+        }
+    }
 }
 
 pub fn transform_syncer(
@@ -794,6 +1549,24 @@ pub fn transform_syncer(
         });
 }
 
+pub fn dense_storage_builder(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(Entity, &mut TilemapStorage), Added<ImmutableTilemap>>,
+    tiles_query: Query<&Tile>,
+) {
+    tilemaps_query.iter_mut().for_each(|(entity, mut storage)| {
+        let dense = storage.to_dense(&tiles_query);
+
+        // The render chunks keep the tiles until the dense storage is extracted,
+        // so the tiles are despawned without announcing it.
+        storage.storage.iter_some().for_each(|tile| {
+            commands.entity(*tile).despawn();
+        });
+        storage.storage.clear();
+        commands.entity(entity).insert(dense);
+    });
+}
+
 pub fn queued_chunk_aabb_calculator(
     mut tilemaps_query: Query<(
         &mut TilemapStorage,
@@ -840,20 +1613,26 @@ pub fn tilemap_aabb_calculator(
             &TilemapAxisFlip,
             &TilemapSlotSize,
             &TilemapTransform,
+            Option<&DenseTilemapStorage>,
         ),
-        Changed<TilemapStorage>,
+        Or<(Changed<TilemapStorage>, Added<DenseTilemapStorage>)>,
     >,
 ) {
     tilemaps_query.par_iter_mut().for_each(
-        |(mut aabbs, storage, ty, tile_pivot, axis_direction, slot_size, transform)| {
+        |(mut aabbs, storage, ty, tile_pivot, axis_direction, slot_size, transform, dense)| {
             let mut chunk_aabb: Option<IAabb2d> = None;
-            storage.storage.chunks.keys().for_each(|chunk_index| {
-                if let Some(aabb) = &mut chunk_aabb {
-                    aabb.expand_to_contain(*chunk_index);
-                } else {
-                    chunk_aabb = Some(IAabb2d::splat(*chunk_index));
-                }
-            });
+            if let Some(dense) = dense {
+                // The tiles of immutable tilemaps are in the dense storage.
+                chunk_aabb = (dense.storage.chunk_count() > 0).then(|| dense.storage.chunk_aabb());
+            } else {
+                storage.storage.chunks.keys().for_each(|chunk_index| {
+                    if let Some(aabb) = &mut chunk_aabb {
+                        aabb.expand_to_contain(*chunk_index);
+                    } else {
+                        chunk_aabb = Some(IAabb2d::splat(*chunk_index));
+                    }
+                });
+            }
 
             let Some(chunk_aabb) = chunk_aabb else {
                 return;
@@ -886,3 +1665,733 @@ pub fn tilemap_aabb_calculator(
         },
     );
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            system::{CommandQueue, SystemState},
+            world::World,
+        },
+        render::color::Color,
+    };
+
+    use crate::tilemap::tile::TileFlip;
+
+    use super::*;
+
+    fn tile(atlas_index: i32, flip: TileFlip) -> TileBuilder {
+        TileBuilder::new().with_layer(
+            0,
+            TileLayer {
+                atlas_index,
+                flip,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// A world to apply the commands to and query the tiles from.
+    #[derive(Default)]
+    struct TestWorld {
+        world: World,
+        queue: CommandQueue,
+    }
+
+    impl TestWorld {
+        /// Run `f` with the commands and the tiles, then apply the commands.
+        fn run<R>(&mut self, f: impl FnOnce(&mut Commands, &Query<&Tile>) -> R) -> R {
+            let mut state = SystemState::<Query<&Tile>>::new(&mut self.world);
+            let result = {
+                let tiles_query = state.get(&self.world);
+                let mut commands = Commands::new(&mut self.queue, &self.world);
+                f(&mut commands, &tiles_query)
+            };
+            self.queue.apply(&mut self.world);
+            result
+        }
+    }
+
+    #[test]
+    fn test_dirty_chunks() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut storage = TilemapStorage::new(16, Entity::PLACEHOLDER);
+
+        storage.set(&mut commands, IVec2::new(17, 3), TileBuilder::new());
+        storage.set(&mut commands, IVec2::new(-1, 0), TileBuilder::new());
+        assert!(storage.is_chunk_dirty(IVec2::new(1, 0)));
+        assert!(storage.is_chunk_dirty(IVec2::new(-1, 0)));
+
+        let mut dirty = storage.take_dirty_chunks();
+        dirty.sort_by_key(|c| c.x);
+        assert_eq!(dirty, vec![IVec2::new(-1, 0), IVec2::new(1, 0)]);
+        assert_eq!(storage.dirty_chunks().count(), 0);
+
+        storage.remove(&mut commands, IVec2::new(17, 3));
+        storage.update(&mut commands, IVec2::new(100, 100), TileUpdater::default());
+        assert_eq!(storage.take_dirty_chunks(), vec![IVec2::new(1, 0)]);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut storage = TilemapStorage::new(16, Entity::PLACEHOLDER);
+
+        let indices = [IVec2::new(-20, 7), IVec2::new(3, 3), IVec2::new(40, -1)];
+        let mut commands = Commands::new(&mut queue, &world);
+        indices.iter().for_each(|index| {
+            storage.set(&mut commands, *index, TileBuilder::new());
+        });
+        let entities = indices.map(|index| storage.get(index).unwrap());
+        storage.take_dirty_chunks();
+
+        storage.clear(&mut commands, false);
+        assert!(indices.iter().all(|index| storage.get(*index).is_none()));
+        assert_eq!(storage.iter_tiles().count(), 0);
+        assert_eq!(storage.storage.chunks.len(), 3);
+        assert_eq!(storage.dirty_chunks().count(), 3);
+
+        queue.apply(&mut world);
+        assert!(entities
+            .iter()
+            .all(|e| world.get::<DespawnMe>(*e).is_some()));
+
+        let mut commands = Commands::new(&mut queue, &world);
+        storage.set(&mut commands, IVec2::new(3, 3), TileBuilder::new());
+        storage.clear(&mut commands, true);
+        assert!(storage.get(IVec2::new(3, 3)).is_none());
+        assert!(storage.storage.chunks.is_empty());
+    }
+
+    #[test]
+    fn test_immutable_tilemap() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let tilemap = world.spawn_empty().id();
+        let mut storage = TilemapStorage::new(16, tilemap);
+        let grass = TileBuilder::new().with_layer(
+            0,
+            TileLayer {
+                atlas_index: 1,
+                ..Default::default()
+            },
+        );
+        let tiles = [
+            (IVec2::new(-20, 7), grass.clone()),
+            (IVec2::new(3, 3), grass.clone().with_tint(Color::RED)),
+            (IVec2::new(40, -1), grass.clone()),
+            (
+                IVec2::new(41, -1),
+                TileBuilder::new().with_animation(TileAnimation {
+                    start: 1,
+                    length: 2,
+                    fps: 3,
+                }),
+            ),
+        ];
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            tiles.iter().for_each(|(index, tile)| {
+                storage.set(&mut commands, *index, tile.clone());
+            });
+        }
+        queue.apply(&mut world);
+        // What the hashmap-backed storage returns.
+        let expected = (-30..30)
+            .flat_map(|y| (-30..60).map(move |x| IVec2::new(x, y)))
+            .map(|index| {
+                let tile: Option<TileBuilder> = storage
+                    .get(index)
+                    .map(|e| world.get::<Tile>(e).unwrap().clone().into());
+                (index, tile)
+            })
+            .collect::<Vec<_>>();
+        let entities = tiles.clone().map(|(index, _)| storage.get(index).unwrap());
+        world
+            .entity_mut(tilemap)
+            .insert((storage, ImmutableTilemap));
+
+        world.run_system_once(dense_storage_builder);
+        let dense = world.get::<DenseTilemapStorage>(tilemap).unwrap();
+        // The identical tiles share one palette entry.
+        assert_eq!(dense.palette().len(), 3);
+        assert_eq!(
+            dense.get_id(IVec2::new(-20, 7)),
+            dense.get_id(IVec2::new(40, -1))
+        );
+        assert_eq!(dense.storage.iter_some().count(), 4);
+        assert_eq!(dense.iter_tiles().count(), 4);
+
+        // The dense view returns the same tiles as the hashmap-backed one.
+        expected.iter().for_each(|(index, tile)| {
+            assert_eq!(dense.get(*index), tile.as_ref());
+        });
+        assert_eq!(dense.get(IVec2::new(3, 3)), Some(&tiles[1].1));
+
+        // The tiles are moved out of the `TilemapStorage` and their entities are despawned.
+        let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+        assert!(storage.storage.chunks.is_empty());
+        assert!(entities.iter().all(|e| world.get_entity(*e).is_none()));
+    }
+
+    #[test]
+    fn test_iter_tiles() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut storage = TilemapStorage::new(16, Entity::PLACEHOLDER);
+
+        let indices = [IVec2::new(-20, 7), IVec2::new(3, 3), IVec2::new(40, -1)];
+        indices.iter().for_each(|index| {
+            storage.set(&mut commands, *index, TileBuilder::new());
+        });
+        storage.set(&mut commands, IVec2::new(4, 3), TileBuilder::new());
+        storage.remove(&mut commands, IVec2::new(4, 3));
+
+        let mut tiles = storage.iter_tiles().collect::<Vec<_>>();
+        tiles.sort_by_key(|(index, _)| (index.x, index.y));
+        assert_eq!(
+            tiles,
+            indices
+                .iter()
+                .map(|index| (*index, storage.get(*index).unwrap()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_batch_setters() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut storage = TilemapStorage::new(16, Entity::PLACEHOLDER);
+        let bounds = IAabb2d {
+            min: IVec2::ZERO,
+            max: IVec2::splat(9),
+        };
+
+        storage.fill_rect_clipped(
+            &mut commands,
+            IVec2::new(5, 12),
+            IVec2::new(-3, 8),
+            Some(bounds),
+            TileBuilder::new(),
+        );
+        for y in 0..16 {
+            for x in -4..8 {
+                let inside = (0..=5).contains(&x) && (8..=9).contains(&y);
+                assert_eq!(storage.get(IVec2::new(x, y)).is_some(), inside);
+            }
+        }
+
+        // Completely out of bounds.
+        storage.fill_rect_clipped(
+            &mut commands,
+            IVec2::new(20, 20),
+            IVec2::new(30, 30),
+            Some(bounds),
+            TileBuilder::new(),
+        );
+        assert!(storage.get(IVec2::new(20, 20)).is_none());
+
+        let mut storage = TilemapStorage::new(16, Entity::PLACEHOLDER);
+        storage.draw_line(
+            &mut commands,
+            IVec2::new(-2, -1),
+            IVec2::new(4, 2),
+            Some(bounds),
+            TileBuilder::new(),
+        );
+        let line = bounds
+            .into_iter()
+            .filter(|i| storage.get(*i).is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(storage.storage.chunks.len(), 1);
+        assert_eq!(
+            line,
+            vec![
+                IVec2::new(0, 0),
+                IVec2::new(1, 0),
+                IVec2::new(2, 1),
+                IVec2::new(3, 1),
+                IVec2::new(4, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bounds() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut storage = TilemapStorage::new(16, Entity::PLACEHOLDER);
+        let transform = TilemapTransform::from_translation(Vec2::new(100., -50.));
+        let bounds = |storage: &TilemapStorage, grid: bool| {
+            let f = if grid {
+                TilemapStorage::grid_bounds
+            } else {
+                TilemapStorage::world_bounds
+            };
+            f(
+                storage,
+                TilemapType::Square,
+                Vec2::ZERO,
+                TilemapAxisFlip::NONE,
+                Vec2::splat(16.),
+                transform,
+            )
+        };
+        assert_eq!(bounds(&storage, false), None);
+        assert_eq!(bounds(&storage, true), None);
+
+        storage.fill_rect(
+            &mut commands,
+            TileArea::new(IVec2::new(2, 1), UVec2::new(3, 2)),
+            TileBuilder::new(),
+        );
+        assert_eq!(
+            bounds(&storage, false),
+            Some(Aabb2d::new(132., -34., 180., -2.))
+        );
+        assert_eq!(
+            bounds(&storage, true),
+            Some(Aabb2d::new(100., -50., 356., 206.))
+        );
+
+        // Removing tiles doesn't deallocate the chunk.
+        storage.remove(&mut commands, IVec2::new(4, 1));
+        storage.remove(&mut commands, IVec2::new(4, 2));
+        assert_eq!(
+            bounds(&storage, false),
+            Some(Aabb2d::new(132., -34., 164., -2.))
+        );
+        assert_eq!(
+            bounds(&storage, true),
+            Some(Aabb2d::new(100., -50., 356., 206.))
+        );
+    }
+
+    #[test]
+    fn test_copy_paste() {
+        let mut t = TestWorld::default();
+        let mut storage = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        let tinted = |atlas_index, flip| {
+            tile(atlas_index, flip).with_tint(Color::rgba(1., 1., 1., atlas_index as f32 / 10.))
+        };
+        t.run(|commands, _| {
+            // 1 _ 3
+            // _ 2 _
+            // 0 _ 4
+            storage.set(commands, IVec2::new(0, 0), tinted(0, TileFlip::NONE));
+            storage.set(commands, IVec2::new(1, 1), tinted(2, TileFlip::HORIZONTAL));
+            storage.set(commands, IVec2::new(0, 2), tinted(1, TileFlip::VERTICAL));
+            storage.set(commands, IVec2::new(2, 2), tinted(3, TileFlip::BOTH));
+            storage.set(commands, IVec2::new(2, 0), tinted(4, TileFlip::NONE));
+        });
+
+        let area = TileArea::new(IVec2::ZERO, UVec2::splat(3));
+        let clip = t.run(|_, tiles_query| storage.copy_region(tiles_query, area));
+        assert_eq!(
+            clip.get(IVec2::new(1, 1)),
+            Some(&tinted(2, TileFlip::HORIZONTAL))
+        );
+        assert_eq!(clip.get(IVec2::new(1, 0)), None);
+
+        // Pasting to the same spot changes nothing.
+        let entities = area
+            .aabb()
+            .into_iter()
+            .map(|i| storage.get(i))
+            .collect::<Vec<_>>();
+        t.run(|commands, _| storage.paste(commands, &clip, IVec2::ZERO, None, false));
+        assert_eq!(
+            area.aabb()
+                .into_iter()
+                .map(|i| storage.get(i))
+                .collect::<Vec<_>>(),
+            entities
+        );
+        assert_eq!(
+            t.run(|_, tiles_query| storage.copy_region(tiles_query, area)),
+            clip
+        );
+
+        // Paste with the center anchored at the corner of the map,
+        // so only the top right 2x2 cells are inside.
+        let bounds = IAabb2d {
+            min: IVec2::ZERO,
+            max: IVec2::splat(9),
+        };
+        let mut target = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        t.run(|commands, _| {
+            target.fill_rect(
+                commands,
+                TileArea::new(IVec2::ZERO, UVec2::splat(2)),
+                tinted(9, TileFlip::NONE),
+            );
+            target.paste(
+                commands,
+                &clip.clone().with_anchor(IVec2::ONE),
+                IVec2::ZERO,
+                Some(bounds),
+                true,
+            );
+        });
+
+        let pasted = t.run(|_, tiles_query| {
+            target.copy_region(tiles_query, TileArea::new(IVec2::ZERO, UVec2::splat(2)))
+        });
+        assert_eq!(
+            pasted.get(IVec2::new(0, 0)),
+            Some(&tinted(2, TileFlip::HORIZONTAL))
+        );
+        assert_eq!(
+            pasted.get(IVec2::new(1, 1)),
+            Some(&tinted(3, TileFlip::BOTH))
+        );
+        // Empty cells are skipped, so the background is kept.
+        assert_eq!(
+            pasted.get(IVec2::new(1, 0)),
+            Some(&tinted(9, TileFlip::NONE))
+        );
+        assert_eq!(
+            pasted.get(IVec2::new(0, 1)),
+            Some(&tinted(9, TileFlip::NONE))
+        );
+        assert!(target.get(IVec2::new(-1, -1)).is_none());
+        assert_eq!(target.storage.chunks.len(), 1);
+
+        // Empty cells erase the background when not skipped.
+        t.run(|commands, _| target.paste(commands, &clip, IVec2::ZERO, Some(bounds), false));
+        assert!(target.get(IVec2::new(1, 0)).is_none());
+        assert!(target.get(IVec2::new(0, 0)).is_some());
+    }
+
+    #[test]
+    fn test_dump_region() {
+        let mut t = TestWorld::default();
+        let mut storage = TilemapStorage::new(2, Entity::PLACEHOLDER);
+        t.run(|commands, _| {
+            storage.set(commands, IVec2::new(-1, 0), tile(0, TileFlip::NONE));
+            storage.set(commands, IVec2::new(1, 1), tile(1, TileFlip::NONE));
+            storage.set(commands, IVec2::new(2, 3), tile(2, TileFlip::NONE));
+        });
+
+        // The region covers a few unallocated chunks as well.
+        let (min, max) = (IVec2::new(-2, 0), IVec2::new(3, 5));
+        let dump = t.run(|_, tiles_query| storage.dump_region(tiles_query, min, max));
+        assert_eq!(dump.len(), 6 * 6);
+        assert_eq!(dump[1], Some(tile(0, TileFlip::NONE)));
+        assert_eq!(dump[6 + 3], Some(tile(1, TileFlip::NONE)));
+        assert_eq!(dump[3 * 6 + 4], Some(tile(2, TileFlip::NONE)));
+        assert_eq!(dump.iter().filter(|t| t.is_some()).count(), 3);
+
+        // Scribble over the region, then load the snapshot back.
+        t.run(|commands, _| {
+            storage.remove(commands, IVec2::new(1, 1));
+            storage.fill_rect(
+                commands,
+                TileArea::new(IVec2::new(0, 2), UVec2::splat(3)),
+                tile(9, TileFlip::NONE),
+            );
+            storage.load_region(commands, min, max, &dump);
+        });
+
+        t.run(|_, tiles_query| {
+            assert_eq!(storage.dump_region(tiles_query, min, max), dump);
+            assert!(storage.dump_region(tiles_query, max, min).is_empty());
+        });
+        assert!(storage.get(IVec2::new(0, 2)).is_none());
+    }
+
+    #[test]
+    fn test_paste_rotated() {
+        // 0 _ _
+        // 1 2 _
+        let mut clip = TileClip::new(UVec2::new(3, 2));
+        clip.set(IVec2::new(0, 1), Some(tile(0, TileFlip::NONE)));
+        clip.set(IVec2::new(0, 0), Some(tile(1, TileFlip::HORIZONTAL)));
+        clip.set(IVec2::new(1, 0), Some(tile(2, TileFlip::NONE)));
+
+        let rotated = clip.rotated(TilemapRotation::Cw90);
+        assert_eq!(rotated.size(), UVec2::new(2, 3));
+        assert_eq!(rotated.anchor(), IVec2::new(0, 2));
+        // Rotating a flipped tile makes it flipped along the anti-diagonal.
+        assert_eq!(
+            rotated.get(IVec2::new(0, 2)),
+            Some(&tile(1, TileFlip::DIAGONAL | TileFlip::BOTH))
+        );
+
+        let mut t = TestWorld::default();
+        let mut storage = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        t.run(|commands, _| storage.paste(commands, &rotated, IVec2::splat(5), None, false));
+
+        // 1 0
+        // 2 _
+        // _ _
+        let pasted = t.run(|_, tiles_query| {
+            storage.copy_region(
+                tiles_query,
+                TileArea::new(IVec2::new(5, 3), UVec2::new(2, 3)),
+            )
+        });
+        assert_eq!(
+            pasted.get(IVec2::new(1, 2)),
+            Some(&tile(0, TileFlip::DIAGONAL | TileFlip::VERTICAL))
+        );
+        assert_eq!(
+            pasted.get(IVec2::new(0, 2)),
+            Some(&tile(1, TileFlip::DIAGONAL | TileFlip::BOTH))
+        );
+        assert_eq!(
+            pasted.get(IVec2::new(0, 1)),
+            Some(&tile(2, TileFlip::DIAGONAL | TileFlip::VERTICAL))
+        );
+        assert_eq!(pasted.iter().filter(|(_, t)| t.is_some()).count(), 3);
+
+        // Rotations and flips compose.
+        assert_eq!(
+            clip.rotated(TilemapRotation::Cw180),
+            clip.flipped(TileFlip::BOTH)
+        );
+        assert_eq!(
+            rotated
+                .rotated(TilemapRotation::Cw270)
+                .rotated(TilemapRotation::None),
+            clip
+        );
+        assert_eq!(
+            rotated
+                .rotated(TilemapRotation::Cw90)
+                .rotated(TilemapRotation::Cw180),
+            clip
+        );
+        assert_eq!(
+            clip.flipped(TileFlip::HORIZONTAL)
+                .flipped(TileFlip::HORIZONTAL),
+            clip
+        );
+    }
+
+    #[test]
+    fn test_stamp() {
+        let mut t = TestWorld::default();
+        let mut source = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        let mut storage = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        t.run(|commands, _| {
+            // _ _ 2
+            // _ 1 _
+            // 0 _ _
+            for i in 0..3 {
+                source.set(commands, IVec2::splat(i), tile(i, TileFlip::NONE));
+            }
+            storage.fill_rect(
+                commands,
+                TileArea::new(IVec2::splat(10), UVec2::splat(3)),
+                tile(9, TileFlip::NONE),
+            );
+        });
+
+        let brush = t.run(|_, tiles_query| {
+            Brush::from_tilemap(
+                &source,
+                tiles_query,
+                TileArea::new(IVec2::ZERO, UVec2::splat(3)),
+            )
+        });
+        t.run(|commands, _| {
+            storage.stamp(commands, &brush, IVec2::splat(10), Default::default());
+            storage.stamp(
+                commands,
+                &brush,
+                IVec2::splat(20),
+                BrushTransform {
+                    flip: TileFlip::HORIZONTAL,
+                    ..Default::default()
+                },
+            );
+        });
+
+        let stamped = t.run(|_, tiles_query| {
+            storage.copy_region(
+                tiles_query,
+                TileArea::new(IVec2::splat(10), UVec2::splat(3)),
+            )
+        });
+        for (index, t) in stamped.iter() {
+            let expected = if index.x == index.y { index.x } else { 9 };
+            assert_eq!(t, Some(&tile(expected, TileFlip::NONE)));
+        }
+
+        // Flipped horizontally around the anchor, which is the bottom left corner:
+        // 2 _ _
+        // _ 1 _
+        // _ _ 0
+        assert!(storage.get(IVec2::new(20, 20)).is_some());
+        assert!(storage.get(IVec2::new(19, 21)).is_some());
+        assert!(storage.get(IVec2::new(18, 22)).is_some());
+        assert!(storage.get(IVec2::new(18, 20)).is_none());
+        assert!(storage.get(IVec2::new(20, 22)).is_none());
+        assert_eq!(t.world.query::<&Tile>().iter(&t.world).count(), 3 + 9 + 3);
+    }
+
+    #[test]
+    fn test_animation_frames() {
+        let mut animations = TilemapAnimations::default();
+        animations.register(RawTileAnimation {
+            #[cfg(not(feature = "atlas"))]
+            sequence: vec![0, 1],
+            #[cfg(feature = "atlas")]
+            sequence: vec![(0, 0), (0, 1)],
+            fps: 1,
+        });
+        let anim = animations.register(RawTileAnimation {
+            #[cfg(not(feature = "atlas"))]
+            sequence: vec![5, 6, 7],
+            #[cfg(feature = "atlas")]
+            sequence: vec![(1, 5), (1, 6), (1, 7)],
+            fps: 4,
+        });
+
+        let frames = [0., 0.3, 0.5, 0.76, 1.]
+            .into_iter()
+            .map(|t| animations.frame_at(&anim, t).atlas_index)
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![5, 6, 7, 5, 6]);
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        // 0 0 1 0
+        // 0 1 0 0
+        // 0 1 0 0
+        let mut t = TestWorld::default();
+        let mut storage = TilemapStorage::new(2, Entity::PLACEHOLDER);
+        t.run(|commands, _| {
+            for y in 0..3 {
+                for x in 0..4 {
+                    let wall = (x == 1 && y < 2) || (x == 2 && y == 2);
+                    storage.set(
+                        commands,
+                        IVec2::new(x, y),
+                        tile(wall as i32, TileFlip::NONE),
+                    );
+                }
+            }
+        });
+
+        let same = |a: &Tile, b: &Tile| match (&a.texture, &b.texture) {
+            (TileTexture::Static(a), TileTexture::Static(b)) => {
+                a[0].atlas_index == b[0].atlas_index
+            }
+            _ => false,
+        };
+        let mut fill = |start, atlas_index| {
+            t.run(|commands, tiles_query| {
+                let target = tile(atlas_index, TileFlip::NONE);
+                storage.flood_fill(commands, tiles_query, start, target, same)
+            })
+        };
+
+        // The left column is separated by the wall.
+        let region = fill(IVec2::new(0, 1), 2);
+        assert_eq!(
+            region,
+            HashSet::from([
+                IVec2::new(0, 0),
+                IVec2::new(0, 1),
+                IVec2::new(0, 2),
+                IVec2::new(1, 2)
+            ])
+        );
+
+        // Nothing changes if the tile is already the target.
+        assert!(fill(IVec2::new(0, 0), 2).is_empty());
+
+        // Fill the rest of the floor across the chunks.
+        assert_eq!(fill(IVec2::new(3, 0), 2).len(), 5);
+        // Walls only connected by corners are different regions.
+        assert_eq!(fill(IVec2::new(2, 2), 2).len(), 1);
+        assert_eq!(fill(IVec2::new(1, 0), 2).len(), 2);
+        // Fill the whole map.
+        assert_eq!(fill(IVec2::new(3, 2), 3).len(), 12);
+    }
+
+    #[test]
+    fn test_tiles_using_src() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut storage = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        let texture = TilemapTexture::new(
+            Handle::default(),
+            TilemapTextureDescriptor::new(UVec2::new(32, 32), UVec2::new(16, 16)),
+        );
+        let textures = TilemapTextures::single(texture.clone(), FilterMode::Nearest);
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            storage.fill_rect_custom(
+                &mut commands,
+                TileArea::new(IVec2::ZERO, UVec2::new(6, 2)),
+                |index| {
+                    Some(TileBuilder::new().with_layer(
+                        0,
+                        TileLayer {
+                            #[cfg(feature = "atlas")]
+                            texture_index: 0,
+                            atlas_index: if index.x % 2 == 0 { 3 } else { 1 },
+                            ..Default::default()
+                        },
+                    ))
+                },
+                false,
+            );
+        }
+        queue.apply(&mut world);
+
+        let src = UAabb2d {
+            min: UVec2::new(16, 16),
+            max: UVec2::new(31, 31),
+        };
+        assert_eq!(texture.get_atlas_index(src), Some(3));
+        assert_eq!(
+            texture.get_atlas_index(UAabb2d {
+                min: UVec2::new(8, 0),
+                max: UVec2::new(23, 15),
+            }),
+            None
+        );
+
+        let mut state = SystemState::<Query<&Tile>>::new(&mut world);
+        let tiles_query = state.get(&world);
+        let found = storage.tiles_using_src(&tiles_query, &textures, 0, src);
+        assert_eq!(
+            found,
+            (0..2)
+                .flat_map(|y| (0..6).step_by(2).map(move |x| IVec2::new(x, y)))
+                .collect::<Vec<_>>()
+        );
+        assert!(storage
+            .tiles_using_src(&tiles_query, &textures, 1, src)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_atlas_layout() {
+        let texture = TilemapTexture::new(
+            Handle::default(),
+            TilemapTextureDescriptor::new(UVec2::new(64, 32), UVec2::new(16, 16)),
+        );
+        let layout = texture.as_atlas_layout();
+
+        assert_eq!(layout.len(), 8);
+        assert_eq!(
+            layout.textures[5],
+            bevy::math::Rect::new(16., 16., 32., 32.)
+        );
+    }
+}
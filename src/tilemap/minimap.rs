@@ -0,0 +1,435 @@
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityHashMap},
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Query, Res, ResMut},
+    },
+    math::{IVec2, UVec2, Vec4},
+    reflect::Reflect,
+    render::{
+        color::Color,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{Image, ImageSampler},
+    },
+    utils::HashMap,
+};
+
+use crate::math::TileArea;
+
+use super::{
+    map::{TilemapStorage, TilemapTextures},
+    tile::{Tile, TileLayer, TileTexture},
+};
+
+/// How the color of a tile on the minimap is picked.
+#[derive(Debug, Clone, Reflect)]
+pub enum MinimapColors {
+    /// The average color of the top layer of the tile, multiplied by the tint.
+    /// Animated tiles and tiles without textures only use the tint.
+    Average,
+    /// Look up the atlas index of the top layer of the tile.
+    /// Tiles that are not in the table use `default`.
+    Table {
+        colors: HashMap<i32, Color>,
+        default: Color,
+    },
+}
+
+pub struct MinimapBuilder {
+    area: TileArea,
+    scale: u32,
+    colors: MinimapColors,
+    background: Color,
+}
+
+impl MinimapBuilder {
+    /// Draw the tiles inside `area` on the minimap.
+    pub fn new(area: TileArea) -> Self {
+        Self {
+            area,
+            scale: 1,
+            colors: MinimapColors::Average,
+            background: Color::NONE,
+        }
+    }
+
+    /// Draw each tile as a `scale` x `scale` block. Default is 1.
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        assert_ne!(scale, 0, "The minimap scale can't be 0!");
+        self.scale = scale;
+        self
+    }
+
+    /// Use explicit colors for the atlas indices instead of the average texture colors.
+    pub fn with_color_table(mut self, colors: HashMap<i32, Color>, default: Color) -> Self {
+        self.colors = MinimapColors::Table { colors, default };
+        self
+    }
+
+    /// The color of the empty slots. Default is transparent.
+    pub fn with_background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Create the minimap image, add the returned component to the tilemap.
+    pub fn build(self, image_assets: &mut Assets<Image>) -> Minimap {
+        let size = self.area.extent * self.scale;
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &self.background.as_rgba_u8(),
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::all(),
+        );
+        image.sampler = ImageSampler::nearest();
+
+        Minimap {
+            image: image_assets.add(image),
+            area: self.area,
+            scale: self.scale,
+            colors: self.colors,
+            background: self.background,
+            drawn: Default::default(),
+            average_colors: Default::default(),
+            redraw: true,
+        }
+    }
+}
+
+/// A downscaled image of a tilemap, one pixel (or block) per tile.
+///
+/// Only the modified tiles are redrawn. Use `MinimapBuilder` to create this.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+    pub(crate) area: TileArea,
+    pub(crate) scale: u32,
+    pub(crate) colors: MinimapColors,
+    pub(crate) background: Color,
+    /// The tile entities on the minimap and where they are drawn,
+    /// to find the pixels to clear when they are despawned.
+    #[reflect(ignore)]
+    pub(crate) drawn: EntityHashMap<IVec2>,
+    /// The average colors of the tiles in the textures, keyed by the texture and the tile in it.
+    #[reflect(ignore)]
+    pub(crate) average_colors: HashMap<(usize, u32), Color>,
+    pub(crate) redraw: bool,
+}
+
+impl Minimap {
+    #[inline]
+    pub fn area(&self) -> TileArea {
+        self.area
+    }
+
+    #[inline]
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Redraw the whole minimap next frame.
+    #[inline]
+    pub fn redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    fn tile_color(
+        &mut self,
+        tile: Option<&Tile>,
+        textures: Option<&TilemapTextures>,
+        image_assets: &Assets<Image>,
+    ) -> Color {
+        let Some(tile) = tile else {
+            return self.background;
+        };
+
+        let top = match &tile.texture {
+            TileTexture::Static(layers) => layers
+                .iter()
+                .filter(|l| l.atlas_index >= 0)
+                .max_by_key(|l| l.sublayer),
+            TileTexture::Animated(_) => None,
+        };
+
+        let color = match &self.colors {
+            MinimapColors::Table { colors, default } => top
+                .and_then(|l| colors.get(&l.atlas_index))
+                .copied()
+                .unwrap_or(*default),
+            MinimapColors::Average => top
+                .zip(textures)
+                .and_then(|(layer, textures)| {
+                    let key = locate_layer(textures, layer)?;
+                    if let Some(color) = self.average_colors.get(&key) {
+                        return Some(*color);
+                    }
+
+                    let texture = &textures.textures[key.0];
+                    let Some(image) = image_assets.get(&texture.texture) else {
+                        // Try again once the texture is loaded.
+                        self.redraw = true;
+                        return None;
+                    };
+                    let color = average_color(image, texture.desc.tile_size, key.1)?;
+                    self.average_colors.insert(key, color);
+                    Some(color)
+                })
+                .unwrap_or(Color::WHITE),
+        };
+
+        Color::rgba_from_array(
+            Vec4::from(color.as_rgba_f32()) * Vec4::from(tile.tint.as_rgba_f32()),
+        )
+    }
+
+    fn draw(&self, image: &mut Image, index: IVec2, color: Color) {
+        let rel = index - self.area.origin;
+        if rel.x < 0
+            || rel.y < 0
+            || rel.x >= self.area.extent.x as i32
+            || rel.y >= self.area.extent.y as i32
+        {
+            return;
+        }
+
+        // The image is y down.
+        let min = UVec2::new(rel.x as u32, self.area.extent.y - 1 - rel.y as u32) * self.scale;
+        let width = image.width();
+        let bytes = color.as_rgba_u8();
+        for y in min.y..min.y + self.scale {
+            for x in min.x..min.x + self.scale {
+                let i = ((y * width + x) * 4) as usize;
+                image.data[i..i + 4].copy_from_slice(&bytes);
+            }
+        }
+    }
+}
+
+/// The texture and the atlas index in that texture of a tile layer.
+#[cfg(feature = "atlas")]
+fn locate_layer(textures: &TilemapTextures, layer: &TileLayer) -> Option<(usize, u32)> {
+    (layer.texture_index >= 0 && (layer.texture_index as usize) < textures.textures.len())
+        .then_some((layer.texture_index as usize, layer.atlas_index as u32))
+}
+
+/// The texture and the atlas index in that texture of a tile layer.
+#[cfg(not(feature = "atlas"))]
+fn locate_layer(textures: &TilemapTextures, layer: &TileLayer) -> Option<(usize, u32)> {
+    let index = layer.atlas_index as u32;
+    let texture = textures.start_index.iter().rposition(|s| *s <= index)?;
+    Some((texture, index - textures.start_index[texture]))
+}
+
+/// The average color of a tile in a `Rgba8` texture.
+fn average_color(image: &Image, tile_size: UVec2, atlas_index: u32) -> Option<Color> {
+    if !matches!(
+        image.texture_descriptor.format,
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm
+    ) {
+        return None;
+    }
+
+    let width = image.width();
+    let tiles_per_row = width / tile_size.x;
+    if tiles_per_row == 0 {
+        return None;
+    }
+    let min = UVec2::new(atlas_index % tiles_per_row, atlas_index / tiles_per_row) * tile_size;
+    if min.y + tile_size.y > image.height() {
+        return None;
+    }
+
+    let mut sum = [0u64; 4];
+    for y in min.y..min.y + tile_size.y {
+        for x in min.x..min.x + tile_size.x {
+            let i = ((y * width + x) * 4) as usize;
+            sum.iter_mut()
+                .zip(&image.data[i..i + 4])
+                .for_each(|(s, c)| *s += *c as u64);
+        }
+    }
+
+    let count = (tile_size.x * tile_size.y) as u64;
+    Some(Color::rgba_u8(
+        (sum[0] / count) as u8,
+        (sum[1] / count) as u8,
+        (sum[2] / count) as u8,
+        (sum[3] / count) as u8,
+    ))
+}
+
+pub fn minimap_updater(
+    mut minimaps_query: Query<(
+        Entity,
+        &mut Minimap,
+        &TilemapStorage,
+        Option<&Handle<TilemapTextures>>,
+    )>,
+    changed_tiles_query: Query<&Tile, Changed<Tile>>,
+    mut removed_tiles: RemovedComponents<Tile>,
+    tiles_query: Query<&Tile>,
+    textures_assets: Res<Assets<TilemapTextures>>,
+    mut image_assets: ResMut<Assets<Image>>,
+) {
+    let removed_tiles = removed_tiles.read().collect::<Vec<_>>();
+
+    minimaps_query
+        .iter_mut()
+        .for_each(|(entity, mut minimap, storage, textures)| {
+            let textures = textures.and_then(|t| textures_assets.get(t));
+            let area = minimap.area.aabb();
+
+            let mut dirty = Vec::new();
+            if minimap.redraw {
+                minimap.redraw = false;
+                minimap.drawn.clear();
+                let (origin, extent) = (minimap.area.origin, minimap.area.extent.as_ivec2());
+                dirty.extend(
+                    (0..extent.y).flat_map(|y| (0..extent.x).map(move |x| origin + IVec2 { x, y })),
+                );
+            } else {
+                // The tiles that are despawned, whether they are removed or replaced.
+                dirty.extend(
+                    removed_tiles
+                        .iter()
+                        .filter_map(|tile| minimap.drawn.remove(tile)),
+                );
+                dirty.extend(
+                    changed_tiles_query
+                        .iter()
+                        .filter(|t| t.tilemap_id == entity && area.contains(t.index))
+                        .map(|t| t.index),
+                );
+            }
+            if dirty.is_empty() {
+                return;
+            }
+
+            let colors = dirty
+                .into_iter()
+                .filter(|index| area.contains(*index))
+                .map(|index| {
+                    let tile = storage
+                        .get(index)
+                        .and_then(|e| tiles_query.get(e).ok().map(|t| (e, t)));
+                    if let Some((tile_entity, _)) = tile {
+                        minimap.drawn.insert(tile_entity, index);
+                    }
+                    let color = minimap.tile_color(tile.map(|(_, t)| t), textures, &image_assets);
+                    (index, color)
+                })
+                .collect::<Vec<_>>();
+
+            let Some(image) = image_assets.get_mut(&minimap.image) else {
+                return;
+            };
+            colors.into_iter().for_each(|(index, color)| {
+                minimap.draw(image, index, color);
+            });
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::{App, PreUpdate, Update},
+        ecs::system::{CommandQueue, Commands},
+    };
+
+    use crate::tilemap::{despawn::despawn_applier, tile::TileBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_minimap() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<Assets<TilemapTextures>>()
+            .add_systems(PreUpdate, despawn_applier)
+            .add_systems(Update, minimap_updater);
+
+        let tilemap = app.world.spawn_empty().id();
+        let mut storage = TilemapStorage::new(4, tilemap);
+        let mut queue = CommandQueue::default();
+        {
+            let mut commands = Commands::new(&mut queue, &app.world);
+            storage.fill_rect(
+                &mut commands,
+                TileArea::new(IVec2::ZERO, UVec2::new(3, 2)),
+                TileBuilder::new().with_tint(Color::RED),
+            );
+        }
+        queue.apply(&mut app.world);
+
+        let minimap = MinimapBuilder::new(TileArea::new(IVec2::ZERO, UVec2::new(6, 4)))
+            .with_scale(2)
+            .build(&mut app.world.resource_mut::<Assets<Image>>());
+        let image = minimap.image.clone();
+        app.world.entity_mut(tilemap).insert((storage, minimap));
+        app.update();
+
+        // The pixel at the top left corner of the tile.
+        let offset = |index: IVec2| ((index.x * 2 + (3 - index.y) * 2 * 12) * 4) as usize;
+        let pixel = |app: &App, index: IVec2| {
+            let image = app.world.resource::<Assets<Image>>().get(&image).unwrap();
+            let i = offset(index);
+            [
+                image.data[i],
+                image.data[i + 1],
+                image.data[i + 2],
+                image.data[i + 3],
+            ]
+        };
+        assert_eq!(pixel(&app, IVec2::new(2, 1)), [255, 0, 0, 255]);
+        assert_eq!(pixel(&app, IVec2::new(3, 1)), [0, 0, 0, 0]);
+        assert_eq!(pixel(&app, IVec2::new(0, 3)), [0, 0, 0, 0]);
+
+        // Mark a pixel to make sure the untouched tiles are not redrawn.
+        {
+            let mut images = app.world.resource_mut::<Assets<Image>>();
+            images.get_mut(&image).unwrap().data[offset(IVec2::new(0, 1))] = 7;
+        }
+
+        let mut queue = CommandQueue::default();
+        {
+            let mut storage = app
+                .world
+                .get_mut::<TilemapStorage>(tilemap)
+                .unwrap()
+                .clone();
+            let mut commands = Commands::new(&mut queue, &app.world);
+            storage.set(
+                &mut commands,
+                IVec2::new(5, 3),
+                TileBuilder::new().with_tint(Color::BLUE),
+            );
+            storage.set(
+                &mut commands,
+                IVec2::new(0, 0),
+                TileBuilder::new().with_tint(Color::GREEN),
+            );
+            storage.remove(&mut commands, IVec2::new(2, 1));
+            app.world.entity_mut(tilemap).insert(storage);
+        }
+        queue.apply(&mut app.world);
+        app.update();
+
+        assert_eq!(pixel(&app, IVec2::new(5, 3)), [0, 0, 255, 255]);
+        assert_eq!(pixel(&app, IVec2::new(0, 0)), [0, 255, 0, 255]);
+        assert_eq!(pixel(&app, IVec2::new(2, 1)), [0, 0, 0, 0]);
+        assert_eq!(pixel(&app, IVec2::new(0, 1))[0], 7);
+
+        // The despawned tiles are forgotten.
+        let minimap = app.world.get::<Minimap>(tilemap).unwrap();
+        assert_eq!(minimap.drawn.len(), 6);
+    }
+}
@@ -4,25 +4,36 @@ use bevy::{
 };
 
 use self::{
+    autotile::AutoTiler,
     chunking::camera::{CameraChunkUpdater, CameraChunkUpdation},
+    crossfade::TilemapCrossfade,
     map::{
-        TilePivot, TileRenderSize, TilemapAabbs, TilemapAnimations, TilemapLayerOpacities,
-        TilemapName, TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTextureDescriptor,
-        TilemapTextures, TilemapTransform, TilemapType,
+        ImmutableTilemap, TileAnchor, TilePivot, TileRenderSize, TilemapAabbs, TilemapAnimations,
+        TilemapClip, TilemapLayerOpacities, TilemapName, TilemapScissor, TilemapSlotSize,
+        TilemapStorage, TilemapTexture, TilemapTextureDescriptor, TilemapTextures,
+        TilemapTransform, TilemapType, TilemapVisibility,
     },
+    minimap::Minimap,
     tile::{LayerUpdater, Tile, TileLayer, TileTexture, TileUpdater},
 };
 
 #[cfg(feature = "algorithm")]
 pub mod algorithm;
+pub mod autotile;
 pub mod buffers;
+pub mod builder;
 pub mod bundles;
 pub mod chunking;
 pub mod coordinates;
+pub mod crossfade;
 pub mod despawn;
+pub mod distance;
 pub mod map;
+pub mod minimap;
+pub mod occlusion;
 #[cfg(feature = "physics")]
 pub mod physics;
+pub mod picking;
 pub mod tile;
 
 pub struct EntiTilesTilemapPlugin;
@@ -34,9 +45,13 @@ impl Plugin for EntiTilesTilemapPlugin {
                 Update,
                 (
                     map::transform_syncer,
+                    map::dense_storage_builder,
                     map::queued_chunk_aabb_calculator,
                     map::tilemap_aabb_calculator,
+                    autotile::autotiler,
                     tile::tile_updater,
+                    crossfade::tilemap_crossfader,
+                    minimap::minimap_updater,
                     chunking::camera::camera_chunk_update,
                 ),
             )
@@ -59,8 +74,16 @@ impl Plugin for EntiTilesTilemapPlugin {
             .register_type::<TilemapSlotSize>()
             .register_type::<TilemapType>()
             .register_type::<TilePivot>()
+            .register_type::<TileAnchor>()
             .register_type::<TilemapLayerOpacities>()
+            .register_type::<TilemapVisibility>()
+            .register_type::<TilemapScissor>()
+            .register_type::<TilemapClip>()
+            .register_type::<TilemapCrossfade>()
+            .register_type::<AutoTiler>()
+            .register_type::<Minimap>()
             .register_type::<TilemapStorage>()
+            .register_type::<ImmutableTilemap>()
             .register_type::<TilemapAabbs>()
             .register_type::<TilemapTransform>()
             .register_type::<TilemapTexture>()
@@ -0,0 +1,217 @@
+use bevy::{
+    ecs::{entity::Entity, system::Query},
+    math::{IVec2, Vec2},
+};
+
+use super::{map::TilemapStorage, tile::Tile};
+
+/// The cells a segment passes through in order, found with a DDA traversal.
+///
+/// The points are in index space, where the tile `(x, y)` covers `[x, x + 1) x [y, y + 1)`,
+/// so the center of a tile is `index + 0.5`.
+#[derive(Debug, Clone)]
+pub struct GridRay {
+    cur: IVec2,
+    end: IVec2,
+    step: IVec2,
+    t_max: Vec2,
+    t_delta: Vec2,
+    finished: bool,
+}
+
+impl GridRay {
+    pub fn new(from: Vec2, to: Vec2) -> Self {
+        let cur = from.floor().as_ivec2();
+        let dir = to - from;
+        let step = IVec2::new(
+            (dir.x > 0.) as i32 - (dir.x < 0.) as i32,
+            (dir.y > 0.) as i32 - (dir.y < 0.) as i32,
+        );
+        // How far along the segment (from 0 to 1) the next cell boundary is.
+        let boundary = |from: f32, cur: i32, dir: f32| {
+            if dir > 0. {
+                (cur as f32 + 1. - from) / dir
+            } else if dir < 0. {
+                (from - cur as f32) / -dir
+            } else {
+                f32::INFINITY
+            }
+        };
+
+        Self {
+            cur,
+            end: to.floor().as_ivec2(),
+            step,
+            t_max: Vec2::new(
+                boundary(from.x, cur.x, dir.x),
+                boundary(from.y, cur.y, dir.y),
+            ),
+            t_delta: Vec2::new(1. / dir.x.abs(), 1. / dir.y.abs()),
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for GridRay {
+    type Item = IVec2;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let cell = self.cur;
+        // Also stop if the segment ends before reaching `end` due to float errors.
+        if cell == self.end || self.t_max.min_element() > 1. {
+            self.finished = true;
+        } else if self.t_max.x < self.t_max.y {
+            self.cur.x += self.step.x;
+            self.t_max.x += self.t_delta.x;
+        } else {
+            self.cur.y += self.step.y;
+            self.t_max.y += self.t_delta.y;
+        }
+        Some(cell)
+    }
+}
+
+impl TilemapStorage {
+    /// Returns true if any tile between `from` and `to` blocks the line of sight.
+    ///
+    /// The points are in index space, see `GridRay`. The tiles containing
+    /// `from` and `to` are ignored, so things don't occlude themselves.
+    pub fn is_occluded(
+        &self,
+        tiles_query: &Query<&Tile>,
+        from: Vec2,
+        to: Vec2,
+        blocks: impl Fn(&Tile) -> bool,
+    ) -> bool {
+        self.tiles_between(from, to)
+            .filter_map(|e| tiles_query.get(e).ok())
+            .any(blocks)
+    }
+
+    /// Sum up the partial occlusion of the tiles between `from` and `to`.
+    /// For example, how much a sound is muffled by the walls it goes through.
+    ///
+    /// Like `is_occluded`, the tiles containing `from` and `to` are ignored.
+    pub fn occlusion(
+        &self,
+        tiles_query: &Query<&Tile>,
+        from: Vec2,
+        to: Vec2,
+        block: impl Fn(&Tile) -> f32,
+    ) -> f32 {
+        self.tiles_between(from, to)
+            .filter_map(|e| tiles_query.get(e).ok())
+            .map(block)
+            .sum()
+    }
+
+    fn tiles_between(&self, from: Vec2, to: Vec2) -> impl Iterator<Item = Entity> + '_ {
+        let start = from.floor().as_ivec2();
+        let end = to.floor().as_ivec2();
+        GridRay::new(from, to)
+            .filter(move |index| *index != start && *index != end)
+            .filter_map(|index| self.get(index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            system::{CommandQueue, Commands, SystemState},
+            world::World,
+        },
+        math::UVec2,
+    };
+
+    use crate::{
+        math::TileArea,
+        tilemap::tile::{TileBuilder, TileLayer, TileTexture},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_grid_ray() {
+        let cells = GridRay::new(Vec2::new(0.5, 0.5), Vec2::new(3.5, 1.5)).collect::<Vec<_>>();
+        assert_eq!(cells.first(), Some(&IVec2::new(0, 0)));
+        assert_eq!(cells.last(), Some(&IVec2::new(3, 1)));
+        assert_eq!(cells.len(), 5);
+        assert!(cells
+            .windows(2)
+            .all(|w| [IVec2::X, IVec2::Y].contains(&(w[1] - w[0]).abs())));
+
+        let cells = GridRay::new(Vec2::new(2.5, 0.5), Vec2::new(-0.5, 0.5)).collect::<Vec<_>>();
+        assert_eq!(
+            cells,
+            vec![
+                IVec2::new(2, 0),
+                IVec2::new(1, 0),
+                IVec2::new(0, 0),
+                IVec2::new(-1, 0)
+            ]
+        );
+
+        let cells = GridRay::new(Vec2::new(1.2, 1.7), Vec2::new(1.8, 1.1)).collect::<Vec<_>>();
+        assert_eq!(cells, vec![IVec2::ONE]);
+    }
+
+    #[test]
+    fn test_occlusion() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut storage = TilemapStorage::new(4, Entity::PLACEHOLDER);
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            storage.fill_rect_custom(
+                &mut commands,
+                TileArea::new(IVec2::ZERO, UVec2::new(6, 2)),
+                |index| {
+                    // A wall at (3, 1), and curtains at (1, 0) and (2, 0).
+                    let atlas_index = match (index.x, index.y) {
+                        (3, 1) => 1,
+                        (1, 0) | (2, 0) => 2,
+                        _ => 0,
+                    };
+                    Some(TileBuilder::new().with_layer(
+                        0,
+                        TileLayer {
+                            atlas_index,
+                            ..Default::default()
+                        },
+                    ))
+                },
+                false,
+            );
+        }
+        queue.apply(&mut world);
+
+        let mut state = SystemState::<Query<&Tile>>::new(&mut world);
+        let tiles_query = state.get(&world);
+        let atlas_index = |tile: &Tile| match &tile.texture {
+            TileTexture::Static(layers) => layers[0].atlas_index,
+            TileTexture::Animated(_) => 0,
+        };
+        let is_wall = |tile: &Tile| atlas_index(tile) == 1;
+        let muffle = |tile: &Tile| match atlas_index(tile) {
+            1 => 1.,
+            2 => 0.25,
+            _ => 0.,
+        };
+
+        let (from, to) = (Vec2::new(0.5, 1.5), Vec2::new(5.5, 1.5));
+        assert!(storage.is_occluded(&tiles_query, from, to, is_wall));
+        assert_eq!(storage.occlusion(&tiles_query, from, to, muffle), 1.);
+
+        let (from, to) = (Vec2::new(0.5, 0.5), Vec2::new(5.5, 0.5));
+        assert!(!storage.is_occluded(&tiles_query, from, to, is_wall));
+        assert_eq!(storage.occlusion(&tiles_query, from, to, muffle), 0.5);
+
+        // The wall itself is the target.
+        assert!(!storage.is_occluded(&tiles_query, from, Vec2::new(3.5, 1.5), is_wall));
+    }
+}
@@ -34,7 +34,8 @@ impl Plugin for EntiTilesPhysicsTilemapPlugin {
         app.register_type::<PhysicsTileSpawn>()
             .register_type::<PhysicsTilemap>()
             .register_type::<DataPhysicsTilemap>()
-            .register_type::<PhysicsTile>();
+            .register_type::<PhysicsTile>()
+            .register_type::<TileSlope>();
 
         app.add_event::<PhysicsTileSpawn>();
     }
@@ -135,6 +136,65 @@ impl Default for PhysicsTile {
 
 impl Tiles for PhysicsTile {}
 
+/// The shape of a 45° sloped physics tile. The collider of a sloped tile
+/// is a right triangle that covers half of the tile.
+///
+/// The variants are named after the direction the slope rises/falls to
+/// when walking from left to right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileSlope {
+    /// Rises from the bottom left to the top right.
+    /// The right angle is at the bottom right corner.
+    UpRight,
+    /// Rises from the bottom right to the top left.
+    /// The right angle is at the bottom left corner.
+    UpLeft,
+    /// A ceiling slope from the top left to the bottom right.
+    /// The right angle is at the top right corner.
+    DownRight,
+    /// A ceiling slope from the top right to the bottom left.
+    /// The right angle is at the top left corner.
+    DownLeft,
+}
+
+impl TileSlope {
+    /// Mirror the slope horizontally.
+    pub fn flip_x(self) -> Self {
+        match self {
+            TileSlope::UpRight => TileSlope::UpLeft,
+            TileSlope::UpLeft => TileSlope::UpRight,
+            TileSlope::DownRight => TileSlope::DownLeft,
+            TileSlope::DownLeft => TileSlope::DownRight,
+        }
+    }
+
+    /// Mirror the slope vertically.
+    pub fn flip_y(self) -> Self {
+        match self {
+            TileSlope::UpRight => TileSlope::DownRight,
+            TileSlope::UpLeft => TileSlope::DownLeft,
+            TileSlope::DownRight => TileSlope::UpRight,
+            TileSlope::DownLeft => TileSlope::UpLeft,
+        }
+    }
+
+    /// Pick the triangle vertices out of the corners of a tile.
+    ///
+    /// The corners should be in the order of `[bottom_left, bottom_right, top_right, top_left]`.
+    /// As every triangle spans the whole tile, the hypotenuses of adjacent slopes
+    /// with the same direction will line up.
+    pub fn get_triangle(self, corners: [Vec2; 4]) -> Vec<Vec2> {
+        let [bl, br, tr, tl] = corners;
+        match self {
+            TileSlope::UpRight => vec![bl, br, tr],
+            TileSlope::UpLeft => vec![bl, br, tl],
+            TileSlope::DownRight => vec![br, tr, tl],
+            TileSlope::DownLeft => vec![bl, tr, tl],
+        }
+    }
+}
+
 /// This can used to spawn a optimized physics tilemap.
 ///
 /// Once the component is added, the crate will figure out the least amount of colliders
@@ -147,6 +207,9 @@ pub struct DataPhysicsTilemap {
     pub(crate) size: UVec2,
     pub(crate) air: i32,
     pub(crate) tiles: HashMap<i32, PhysicsTile>,
+    /// Sloped tiles, indexed in the flipped space. These won't be concated.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) slopes: HashMap<UVec2, TileSlope>,
 }
 
 impl DataPhysicsTilemap {
@@ -180,6 +243,7 @@ impl DataPhysicsTilemap {
             size,
             air,
             tiles,
+            slopes: HashMap::default(),
         }
     }
 
@@ -203,6 +267,7 @@ impl DataPhysicsTilemap {
             size,
             air,
             tiles,
+            slopes: HashMap::default(),
         }
     }
 
@@ -228,13 +293,33 @@ impl DataPhysicsTilemap {
     pub fn set(&mut self, index: UVec2, value: i32) {
         self.data[(index.x + index.y * self.size.x) as usize] = value;
     }
+
+    /// Mark the tiles as slopes. The indices are in the flipped space,
+    /// which is the same as `get_or_air` and `set`.
+    pub fn with_slopes(mut self, slopes: HashMap<UVec2, TileSlope>) -> Self {
+        self.slopes = slopes;
+        self
+    }
+
+    #[inline]
+    pub fn get_slope(&self, index: UVec2) -> Option<TileSlope> {
+        self.slopes.get(&index).cloned()
+    }
+
+    #[inline]
+    pub fn set_slope(&mut self, index: UVec2, slope: Option<TileSlope>) {
+        match slope {
+            Some(slope) => self.slopes.insert(index, slope),
+            None => self.slopes.remove(&index),
+        };
+    }
 }
 
 /// A tilemap with physics tiles.
 #[derive(Component, Debug, Clone, Reflect)]
 pub struct PhysicsTilemap {
     pub(crate) storage: EntityChunkedStorage,
-    pub(crate) spawn_queue: Vec<(IAabb2d, PhysicsTile, Option<i32>)>,
+    pub(crate) spawn_queue: Vec<(IAabb2d, PhysicsTile, Option<i32>, Option<TileSlope>)>,
     pub(crate) data: PackedPhysicsTileChunkedStorage,
 }
 
@@ -268,7 +353,17 @@ impl PhysicsTilemap {
     /// Set a tile. This actually queues the tile and it will be spawned later.
     #[inline]
     pub fn set(&mut self, index: IVec2, tile: PhysicsTile) {
-        self.spawn_queue.push((IAabb2d::splat(index), tile, None));
+        self.spawn_queue
+            .push((IAabb2d::splat(index), tile, None, None));
+    }
+
+    /// Set a sloped tile. This actually queues the tile and it will be spawned later.
+    ///
+    /// Only square and isometric tilemaps support slopes.
+    #[inline]
+    pub fn set_slope(&mut self, index: IVec2, tile: PhysicsTile, slope: TileSlope) {
+        self.spawn_queue
+            .push((IAabb2d::splat(index), tile, None, Some(slope)));
     }
 
     /// Remove a tile.
@@ -303,12 +398,12 @@ impl PhysicsTilemap {
     /// Set `concat` to true if you want to concat the adjacent tiles.
     pub fn fill_rect(&mut self, area: TileArea, tile: PhysicsTile, concat: bool) {
         if concat {
-            self.spawn_queue.push((area.into(), tile, None));
+            self.spawn_queue.push((area.into(), tile, None, None));
         } else {
             self.spawn_queue.extend(
                 (area.origin.y..=area.dest.y)
                     .flat_map(|y| (area.origin.x..=area.dest.x).map(move |x| IVec2 { x, y }))
-                    .map(|index| (IAabb2d::splat(index), tile.clone(), None)),
+                    .map(|index| (IAabb2d::splat(index), tile.clone(), None, None)),
             );
         }
     }
@@ -332,7 +427,8 @@ impl PhysicsTilemap {
                 } else {
                     index
                 }) {
-                    self.spawn_queue.push((IAabb2d::splat(index), tile, None));
+                    self.spawn_queue
+                        .push((IAabb2d::splat(index), tile, None, None));
                 }
             }
         }
@@ -344,7 +440,7 @@ impl PhysicsTilemap {
             buffer
                 .tiles
                 .into_iter()
-                .map(|(index, tile)| (IAabb2d::splat(index + origin), tile, None)),
+                .map(|(index, tile)| (IAabb2d::splat(index + origin), tile, None, None)),
         );
     }
 
@@ -353,7 +449,71 @@ impl PhysicsTilemap {
             buffer
                 .tiles
                 .into_iter()
-                .map(|(index, tile)| (IAabb2d::splat(index + origin), tile.into(), None)),
+                .map(|(index, tile)| (IAabb2d::splat(index + origin), tile.into(), None, None)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tilemap::{
+        coordinates,
+        map::{TilemapTransform, TilemapType},
+    };
+
+    #[test]
+    fn test_slope_collider() {
+        let data = DataPhysicsTilemap::new(
+            IVec2::ZERO,
+            vec![1, 1],
+            UVec2::new(2, 1),
+            0,
+            HashMap::default(),
+        )
+        .with_slopes([(UVec2::ZERO, TileSlope::UpRight)].into_iter().collect());
+        assert_eq!(data.get_slope(UVec2::X), None);
+
+        let slope = data.get_slope(UVec2::ZERO).unwrap();
+        let quad = coordinates::get_tile_collider_world(
+            IVec2::ZERO,
+            TilemapType::Square,
+            UVec2::ONE,
+            &TilemapTransform::default(),
+            Vec2::ZERO,
+            Vec2::splat(16.),
+        );
+        let tri = systems::get_slope_collider(&quad, TilemapType::Square, slope).unwrap();
+
+        assert_eq!(
+            tri,
+            vec![Vec2::ZERO, Vec2::new(16., 0.), Vec2::new(16., 16.)]
+        );
+        // The right angle is at the bottom right corner.
+        assert_eq!((tri[0] - tri[1]).dot(tri[2] - tri[1]), 0.);
+        // The hypotenuse goes from the bottom left to the top right.
+        assert_eq!((tri[2] - tri[0]).normalize(), Vec2::ONE.normalize());
+
+        let mirrored =
+            systems::get_slope_collider(&quad, TilemapType::Square, slope.flip_x()).unwrap();
+        assert_eq!(
+            mirrored,
+            vec![Vec2::ZERO, Vec2::new(16., 0.), Vec2::new(0., 16.)]
+        );
+
+        // Mirrored vertically, the right angle moves to the top right corner.
+        let mirrored =
+            systems::get_slope_collider(&quad, TilemapType::Square, slope.flip_y()).unwrap();
+        assert_eq!(
+            mirrored,
+            vec![Vec2::new(16., 0.), Vec2::new(16., 16.), Vec2::new(0., 16.)]
+        );
+        assert_eq!(slope.flip_x().flip_y(), slope.flip_y().flip_x());
+        assert_eq!(slope.flip_y().flip_y(), slope);
+
+        assert_eq!(
+            systems::get_slope_collider(&quad, TilemapType::Hexagonal(4), slope),
+            None
         );
     }
 }
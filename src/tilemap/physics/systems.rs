@@ -4,7 +4,8 @@ use bevy::{
         event::EventWriter,
         system::{ParallelCommands, Query},
     },
-    math::UVec2,
+    log::warn,
+    math::{UVec2, Vec2},
 };
 
 use crate::{
@@ -18,6 +19,7 @@ use crate::{
 
 use super::{
     DataPhysicsTilemap, PackedPhysicsTile, PhysicsCollider, PhysicsTileSpawn, PhysicsTilemap,
+    TileSlope,
 };
 
 pub fn spawn_colliders(
@@ -38,7 +40,7 @@ pub fn spawn_colliders(
         let physics_tiles = physics_tilemap.spawn_queue.drain(..).collect::<Vec<_>>();
         physics_tiles
             .into_iter()
-            .for_each(|(aabb, physics_tile, maybe_int_repr)| {
+            .for_each(|(aabb, physics_tile, maybe_int_repr, maybe_slope)| {
                 commands.command_scope(|mut c| {
                     let vertices = coordinates::get_tile_collider_world(
                         aabb.min,
@@ -51,18 +53,23 @@ pub fn spawn_colliders(
 
                     let packed_tile = PackedPhysicsTile {
                         parent: aabb.min,
-                        collider: match ty {
-                            TilemapType::Square | TilemapType::Isometric => {
-                                PhysicsCollider::Convex(vertices.clone())
-                            }
-                            TilemapType::Hexagonal(_) => {
+                        collider: match (ty, maybe_slope) {
+                            (TilemapType::Hexagonal(_), _) => {
+                                if maybe_slope.is_some() {
+                                    warn!("Slopes are not supported on hexagonal tilemaps!");
+                                }
                                 PhysicsCollider::Polyline(vertices.clone())
                             }
+                            (_, Some(slope)) => PhysicsCollider::Convex(
+                                get_slope_collider(&vertices, *ty, slope)
+                                    .unwrap_or_else(|| vertices.clone()),
+                            ),
+                            (_, None) => PhysicsCollider::Convex(vertices.clone()),
                         },
                         physics_tile,
                     };
                     let tile_entity = packed_tile.spawn(&mut c);
-                    
+
                     spawn_event.send(PhysicsTileSpawn {
                         tilemap: tilemap_entity,
                         tile: tile_entity,
@@ -99,6 +106,23 @@ pub fn data_physics_tilemap_analyzer(
                         i
                     };
 
+                    if let Some(slope) = data_tilemap.get_slope(cur) {
+                        data_tilemap.set(cur, air);
+                        aabbs.push((
+                            IAabb2d::splat(cur.as_ivec2() + data_tilemap.origin),
+                            data_tilemap.get_tile(cur_i).unwrap_or_default(),
+                            Some(cur_i),
+                            Some(slope),
+                        ));
+                        continue;
+                    }
+
+                    // Sloped tiles are excluded from concating.
+                    let is_same = |index: UVec2| {
+                        data_tilemap.get_or_air(index) == cur_i
+                            && data_tilemap.get_slope(index).is_none()
+                    };
+
                     let mut d = UVec2 {
                         x: if x == size.x - 1 { 0 } else { 1 },
                         y: if y == size.y - 1 { 0 } else { 1 },
@@ -106,22 +130,20 @@ pub fn data_physics_tilemap_analyzer(
                     let mut dst = cur;
                     while d.x != 0 || d.y != 0 {
                         for t_x in cur.x..=dst.x {
-                            if data_tilemap.get_or_air(UVec2::new(t_x, dst.y + d.y)) != cur_i {
+                            if !is_same(UVec2::new(t_x, dst.y + d.y)) {
                                 d.y = 0;
                                 break;
                             }
                         }
 
                         for t_y in cur.y..=dst.y {
-                            if data_tilemap.get_or_air(UVec2::new(dst.x + d.x, t_y)) != cur_i {
+                            if !is_same(UVec2::new(dst.x + d.x, t_y)) {
                                 d.x = 0;
                                 break;
                             }
                         }
 
-                        if d == UVec2::ONE
-                            && data_tilemap.get_or_air(UVec2::new(dst.x + 1, dst.y + 1)) != cur_i
-                        {
+                        if d == UVec2::ONE && !is_same(UVec2::new(dst.x + 1, dst.y + 1)) {
                             d.y = 0;
                         }
 
@@ -141,6 +163,7 @@ pub fn data_physics_tilemap_analyzer(
                         },
                         data_tilemap.get_tile(cur_i).unwrap_or_default(),
                         Some(cur_i),
+                        None,
                     ));
                 }
             }
@@ -160,3 +183,24 @@ pub fn data_physics_tilemap_analyzer(
             });
         });
 }
+
+/// Get the triangle collider of a sloped tile from the vertices
+/// returned by `coordinates::get_tile_collider_world`.
+///
+/// Returns `None` for hexagonal tilemaps as slopes are not supported there.
+pub fn get_slope_collider(
+    vertices: &[Vec2],
+    ty: TilemapType,
+    slope: TileSlope,
+) -> Option<Vec<Vec2>> {
+    match ty {
+        TilemapType::Square => {
+            Some(slope.get_triangle([vertices[0], vertices[1], vertices[2], vertices[3]]))
+        }
+        // [down, up, right, left]
+        TilemapType::Isometric => {
+            Some(slope.get_triangle([vertices[0], vertices[2], vertices[1], vertices[3]]))
+        }
+        TilemapType::Hexagonal(_) => None,
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::{
+    ecs::{
+        entity::Entity,
+        system::{Query, SystemParam},
+    },
+    math::{IVec2, Vec2},
+    render::{camera::Camera, view::Visibility},
+    transform::components::GlobalTransform,
+};
+
+use super::{
+    coordinates::world_to_index,
+    map::{
+        TilePivot, TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType,
+        TilemapVisibility,
+    },
+};
+
+/// A tile found by `TilemapPicker`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickedTile {
+    pub tilemap: Entity,
+    pub tile: Entity,
+    pub index: IVec2,
+    /// The z index of the tilemap.
+    pub z_index: f32,
+}
+
+/// Find the tile under a world position or the cursor, like for click selection.
+///
+/// Only the tilemaps drawn on top are considered, according to `TilemapTransform::z_index`.
+/// Tilemaps with the same z index are drawn in an unspecified order, so avoid picking those.
+#[derive(SystemParam)]
+pub struct TilemapPicker<'w, 's> {
+    tilemaps_query: Query<'w, 's, PickableTilemap>,
+}
+
+type PickableTilemap = (
+    Entity,
+    &'static TilemapStorage,
+    &'static TilemapType,
+    &'static TilemapTransform,
+    &'static TilePivot,
+    &'static TilemapSlotSize,
+    Option<&'static Visibility>,
+    Option<&'static TilemapVisibility>,
+);
+
+impl<'w, 's> TilemapPicker<'w, 's> {
+    /// The topmost tile at the world position among all the visible tilemaps.
+    ///
+    /// Returns `None` if none of them has a tile there.
+    #[inline]
+    pub fn pick(&self, world_pos: Vec2) -> Option<PickedTile> {
+        self.pick_filtered(world_pos, |_| true)
+    }
+
+    /// Same as `pick()`, but only the tilemaps that `filter` returns true for are considered.
+    pub fn pick_filtered(
+        &self,
+        world_pos: Vec2,
+        filter: impl Fn(Entity) -> bool,
+    ) -> Option<PickedTile> {
+        self.tilemaps_query
+            .iter()
+            .filter(|(entity, _, _, _, _, _, visibility, tilemap_visibility)| {
+                visibility != &Some(&Visibility::Hidden)
+                    && tilemap_visibility.is_none_or(|v| v.visible)
+                    && filter(*entity)
+            })
+            .filter_map(|(entity, storage, ty, transform, pivot, slot_size, ..)| {
+                let index = world_to_index(world_pos, *ty, transform, pivot.0, slot_size.0);
+                storage.get(index).map(|tile| PickedTile {
+                    tilemap: entity,
+                    tile,
+                    index,
+                    z_index: transform.z_index,
+                })
+            })
+            .max_by(|a, b| a.z_index.total_cmp(&b.z_index))
+    }
+
+    /// The topmost tile under a position in the viewport of the camera, like the cursor.
+    pub fn pick_viewport(
+        &self,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        viewport_pos: Vec2,
+    ) -> Option<PickedTile> {
+        camera
+            .viewport_to_world_2d(camera_transform, viewport_pos)
+            .and_then(|world_pos| self.pick(world_pos))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            system::{CommandQueue, Commands, RunSystemOnce},
+            world::World,
+        },
+        math::UVec2,
+    };
+
+    use crate::{math::TileArea, tilemap::tile::TileBuilder};
+
+    use super::*;
+
+    fn spawn_tilemap(world: &mut World, area: TileArea, z_index: f32) -> Entity {
+        let entity = world.spawn_empty().id();
+        let mut storage = TilemapStorage::new(4, entity);
+        let mut queue = CommandQueue::default();
+        storage.fill_rect(
+            &mut Commands::new(&mut queue, world),
+            area,
+            TileBuilder::new(),
+        );
+        queue.apply(world);
+
+        world.entity_mut(entity).insert((
+            storage,
+            TilemapType::Square,
+            TilemapTransform::from_z_index(z_index),
+            TilePivot::default(),
+            TilemapSlotSize(Vec2::splat(16.)),
+        ));
+        entity
+    }
+
+    #[test]
+    fn test_pick() {
+        let mut world = World::new();
+        let bottom = spawn_tilemap(&mut world, TileArea::new(IVec2::ZERO, UVec2::splat(8)), 1.);
+        let top = spawn_tilemap(&mut world, TileArea::new(IVec2::ZERO, UVec2::splat(2)), 2.);
+
+        let mut pick = |world_pos: Vec2| {
+            world.run_system_once(move |picker: TilemapPicker| picker.pick(world_pos))
+        };
+
+        // Empty cell.
+        assert_eq!(pick(Vec2::new(-8., 8.)), None);
+
+        // Both tilemaps have a tile here.
+        let picked = pick(Vec2::new(8., 24.)).unwrap();
+        assert_eq!((picked.tilemap, picked.index), (top, IVec2::new(0, 1)));
+
+        // Only the bottom one.
+        let picked = pick(Vec2::new(40., 8.)).unwrap();
+        assert_eq!((picked.tilemap, picked.index), (bottom, IVec2::new(2, 0)));
+
+        world.entity_mut(top).insert(Visibility::Hidden);
+        let picked = world
+            .run_system_once(|picker: TilemapPicker| picker.pick(Vec2::new(8., 24.)))
+            .unwrap();
+        assert_eq!(picked.tilemap, bottom);
+    }
+}
@@ -1,17 +1,20 @@
 use bevy::{
     ecs::system::{ParallelCommands, Query},
-    math::IVec2,
+    math::{IVec2, Mat2, Vec2},
     prelude::{Component, Entity},
     reflect::Reflect,
     render::{color::Color, render_resource::ShaderType},
 };
 
-use super::{buffers::Tiles, map::TilemapStorage};
+use super::{
+    buffers::Tiles,
+    map::{TilemapRotation, TilemapStorage},
+};
 
 /// A tile layer. This is the logical representation of a tile layer.
 /// Not all the layers you added to a tile will be taken into consideration
 /// when rendering. Only the top 4 layers will be rendered.
-#[derive(Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileLayer {
     #[cfg(feature = "atlas")]
@@ -19,6 +22,11 @@ pub struct TileLayer {
     pub atlas_index: i32,
     #[reflect(ignore)]
     pub flip: TileFlip,
+    /// The render order of this layer inside the tile.
+    /// Layers with higher sublayers will be drawn on top of the lower ones.
+    /// Layers with the same sublayer are drawn in the order they are added.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub sublayer: i32,
 }
 
 impl Default for TileLayer {
@@ -29,10 +37,30 @@ impl Default for TileLayer {
             texture_index: -1,
             atlas_index: -1,
             flip: Default::default(),
+            sublayer: 0,
         }
     }
 }
 
+impl TileLayer {
+    /// Set the sublayer of this layer.
+    #[inline]
+    pub fn with_sublayer(mut self, sublayer: i32) -> Self {
+        self.sublayer = sublayer;
+        self
+    }
+
+    /// Rotate this layer clockwise, on top of its current flip.
+    ///
+    /// The rotation is stored in the flip bits, as `DIAGONAL` together with
+    /// `HORIZONTAL` and `VERTICAL` can express all the rotations of a tile.
+    #[inline]
+    pub fn with_rotation(mut self, rotation: TilemapRotation) -> Self {
+        self.flip = self.flip.rotated(rotation);
+        self
+    }
+}
+
 #[cfg(not(feature = "atlas"))]
 impl TileLayer {
     #[inline]
@@ -40,6 +68,7 @@ impl TileLayer {
         Self {
             atlas_index,
             flip: TileFlip::NONE,
+            sublayer: 0,
         }
     }
 
@@ -48,6 +77,7 @@ impl TileLayer {
         Self {
             atlas_index,
             flip: TileFlip::HORIZONTAL,
+            sublayer: 0,
         }
     }
 
@@ -56,6 +86,7 @@ impl TileLayer {
         Self {
             atlas_index,
             flip: TileFlip::VERTICAL,
+            sublayer: 0,
         }
     }
 
@@ -64,6 +95,7 @@ impl TileLayer {
         Self {
             atlas_index,
             flip: TileFlip::BOTH,
+            sublayer: 0,
         }
     }
 }
@@ -76,6 +108,7 @@ impl TileLayer {
             texture_index,
             atlas_index,
             flip: TileFlip::NONE,
+            sublayer: 0,
         }
     }
 
@@ -85,6 +118,7 @@ impl TileLayer {
             texture_index,
             atlas_index,
             flip: TileFlip::HORIZONTAL,
+            sublayer: 0,
         }
     }
 
@@ -94,6 +128,7 @@ impl TileLayer {
             texture_index,
             atlas_index,
             flip: TileFlip::VERTICAL,
+            sublayer: 0,
         }
     }
 
@@ -103,6 +138,7 @@ impl TileLayer {
             texture_index,
             atlas_index,
             flip: TileFlip::BOTH,
+            sublayer: 0,
         }
     }
 }
@@ -131,13 +167,16 @@ pub struct TileUpdater {
 
 bitflags::bitflags! {
     /// The flip of a tile.
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
     pub struct TileFlip: u32 {
         const NONE = 0b00;
         const HORIZONTAL = 0b10;
         const VERTICAL = 0b01;
         const BOTH = 0b11;
+        /// Swap the x and y axes of the texture, which is applied before the other flips.
+        /// Combined with them, this can rotate the tile by 90 degrees.
+        const DIAGONAL = 0b100;
     }
 }
 
@@ -147,8 +186,121 @@ impl Default for TileFlip {
     }
 }
 
+impl TileFlip {
+    /// The flip that shows the tile rotated clockwise by `rotation`.
+    ///
+    /// Assumes the texture of the tile is square.
+    pub fn rotated(self, rotation: TilemapRotation) -> Self {
+        let rotation = match rotation {
+            TilemapRotation::None => Mat2::IDENTITY,
+            TilemapRotation::Cw90 => Mat2::from_cols(Vec2::NEG_Y, Vec2::X),
+            TilemapRotation::Cw180 => -Mat2::IDENTITY,
+            TilemapRotation::Cw270 => Mat2::from_cols(Vec2::Y, Vec2::NEG_X),
+        };
+        Self::from_uv_mat(self.uv_mat() * rotation)
+    }
+
+    /// The flip that shows the tile mirrored by `flip` on top of the current one.
+    ///
+    /// Only `HORIZONTAL` and `VERTICAL` of `flip` are taken into account.
+    pub fn flipped(self, flip: TileFlip) -> Self {
+        let mirror = Mat2::from_diagonal(Vec2::new(
+            if flip.contains(Self::HORIZONTAL) {
+                -1.
+            } else {
+                1.
+            },
+            if flip.contains(Self::VERTICAL) {
+                -1.
+            } else {
+                1.
+            },
+        ));
+        Self::from_uv_mat(self.uv_mat() * mirror)
+    }
+
+    /// Convert the `f` of LDtk tiles, where bit 0 is the x flip and bit 1 is the y flip.
+    pub fn from_ldtk(f: i32) -> Self {
+        let mut flip = Self::NONE;
+        if f & 0b01 != 0 {
+            flip |= Self::HORIZONTAL;
+        }
+        if f & 0b10 != 0 {
+            flip |= Self::VERTICAL;
+        }
+        flip
+    }
+
+    /// Convert back to the `f` of LDtk tiles. `DIAGONAL` is dropped as LDtk can't rotate tiles.
+    pub fn to_ldtk(self) -> i32 {
+        self.contains(Self::HORIZONTAL) as i32 | (self.contains(Self::VERTICAL) as i32) << 1
+    }
+
+    /// Convert the flags in the highest bits of a Tiled global tile id.
+    /// The id itself and the hexagonal rotation flag are ignored.
+    pub fn from_tiled(gid: u32) -> Self {
+        let mut flip = Self::NONE;
+        if gid & 0x8000_0000 != 0 {
+            flip |= Self::HORIZONTAL;
+        }
+        if gid & 0x4000_0000 != 0 {
+            flip |= Self::VERTICAL;
+        }
+        if gid & 0x2000_0000 != 0 {
+            flip |= Self::DIAGONAL;
+        }
+        flip
+    }
+
+    /// The bits read by the tilemap shader to transform the uv,
+    /// to be packed into the atlas index of the layer.
+    ///
+    /// The horizontal and vertical flips take bit 30 and 29, as the sign bit marks empty layers,
+    /// and the diagonal flip takes bit 28.
+    #[inline]
+    pub fn to_uv_transform(self) -> i32 {
+        let bits = self.bits() as i32;
+        ((bits & 0b11) << 29) | ((bits >> 2 & 1) << 28)
+    }
+
+    /// The matrix that maps the uv of the tile (centered) to the uv of the texture.
+    fn uv_mat(self) -> Mat2 {
+        let mirror = Mat2::from_diagonal(Vec2::new(
+            if self.contains(Self::HORIZONTAL) {
+                -1.
+            } else {
+                1.
+            },
+            if self.contains(Self::VERTICAL) {
+                -1.
+            } else {
+                1.
+            },
+        ));
+        if self.contains(Self::DIAGONAL) {
+            mirror * Mat2::from_cols(Vec2::Y, Vec2::X)
+        } else {
+            mirror
+        }
+    }
+
+    fn from_uv_mat(mat: Mat2) -> Self {
+        let (diagonal, x, y) = if mat.x_axis.x != 0. {
+            (false, mat.x_axis.x, mat.y_axis.y)
+        } else {
+            (true, mat.y_axis.x, mat.x_axis.y)
+        };
+
+        let mut flip = Self::NONE;
+        flip.set(Self::HORIZONTAL, x < 0.);
+        flip.set(Self::VERTICAL, y < 0.);
+        flip.set(Self::DIAGONAL, diagonal);
+        flip
+    }
+}
+
 /// A tile builder. This is used to create a tile.
-#[derive(Debug, Clone, Reflect)]
+#[derive(Debug, Clone, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileBuilder {
     pub(crate) texture: TileTexture,
@@ -216,7 +368,7 @@ impl TileBuilder {
 
 /// A tile animation. This is actually information about the position of the animation
 /// in the tilemap animation buffer. So it's cheap to clone.
-#[derive(ShaderType, Debug, Clone, Copy, Reflect)]
+#[derive(ShaderType, Debug, Clone, Copy, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileAnimation {
     pub(crate) start: u32,
@@ -237,7 +389,7 @@ pub struct RawTileAnimation {
 }
 
 /// A tile texture. This is either a static texture or an animation.
-#[derive(Debug, Clone, Reflect)]
+#[derive(Debug, Clone, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub enum TileTexture {
     Static(Vec<TileLayer>),
@@ -299,3 +451,98 @@ pub fn tile_updater(
             });
         });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The texture uv sampled at `uv` of the tile, the same as `tilemap_fragment`.
+    fn sample_uv(flip: TileFlip, uv: Vec2) -> Vec2 {
+        let mut uv = uv;
+        if flip.contains(TileFlip::DIAGONAL) {
+            uv = Vec2::new(uv.y, uv.x);
+        }
+        if flip.contains(TileFlip::HORIZONTAL) {
+            uv.x = 1. - uv.x;
+        }
+        if flip.contains(TileFlip::VERTICAL) {
+            uv.y = 1. - uv.y;
+        }
+        uv
+    }
+
+    #[test]
+    fn test_rotated_layer() {
+        let corners = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+        let rotations = [
+            TilemapRotation::None,
+            TilemapRotation::Cw90,
+            TilemapRotation::Cw180,
+            TilemapRotation::Cw270,
+        ];
+
+        for bits in 0..8 {
+            let flip = TileFlip::from_bits(bits).unwrap();
+            for (turns, rotation) in rotations.into_iter().enumerate() {
+                let layer = TileLayer {
+                    flip,
+                    ..Default::default()
+                }
+                .with_rotation(rotation);
+
+                for corner in corners {
+                    // The corner of the flipped tile that ends up at `corner`
+                    // after rotating clockwise, with uv's y pointing down.
+                    let mut source = corner - 0.5;
+                    for _ in 0..turns {
+                        source = Vec2::new(source.y, -source.x);
+                    }
+                    assert_eq!(
+                        sample_uv(layer.flip, corner),
+                        sample_uv(flip, source + 0.5),
+                        "flip: {:?}, rotation: {:?}, corner: {}",
+                        flip,
+                        rotation,
+                        corner
+                    );
+                }
+            }
+        }
+
+        // The top left corner shows the bottom left of the texture after rotating 90 degrees,
+        let layer = TileLayer::default().with_rotation(TilemapRotation::Cw90);
+        assert_eq!(sample_uv(layer.flip, Vec2::ZERO), Vec2::Y);
+        // or the bottom right if the texture is flipped horizontally first.
+        let layer = TileLayer {
+            flip: TileFlip::HORIZONTAL,
+            ..Default::default()
+        }
+        .with_rotation(TilemapRotation::Cw90);
+        assert_eq!(sample_uv(layer.flip, Vec2::ZERO), Vec2::ONE);
+    }
+
+    #[test]
+    fn test_flip_conversions() {
+        assert_eq!(TileFlip::from_ldtk(0), TileFlip::NONE);
+        assert_eq!(TileFlip::from_ldtk(1), TileFlip::HORIZONTAL);
+        assert_eq!(TileFlip::from_ldtk(2), TileFlip::VERTICAL);
+        assert_eq!(TileFlip::from_ldtk(3), TileFlip::BOTH);
+
+        assert_eq!(TileFlip::from_tiled(5), TileFlip::NONE);
+        assert_eq!(TileFlip::from_tiled(0x8000_0005), TileFlip::HORIZONTAL);
+        assert_eq!(TileFlip::from_tiled(0x4000_0005), TileFlip::VERTICAL);
+        assert_eq!(TileFlip::from_tiled(0x2000_0005), TileFlip::DIAGONAL);
+        assert_eq!(
+            TileFlip::from_tiled(0xE000_0005),
+            TileFlip::BOTH | TileFlip::DIAGONAL
+        );
+        // The hexagonal rotation flag.
+        assert_eq!(TileFlip::from_tiled(0x1000_0005), TileFlip::NONE);
+
+        // Bit 1 is the x flip and bit 0 the y flip in the shader, like the bits of `TileFlip`.
+        assert_eq!(TileFlip::HORIZONTAL.to_uv_transform(), 0b10 << 29);
+        assert_eq!(TileFlip::VERTICAL.to_uv_transform(), 0b01 << 29);
+        assert_eq!(TileFlip::DIAGONAL.to_uv_transform(), 1 << 28);
+        assert!((TileFlip::BOTH | TileFlip::DIAGONAL).to_uv_transform() > 0);
+    }
+}